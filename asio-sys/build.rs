@@ -174,6 +174,7 @@ fn create_bindings(cpal_asio_dir: &PathBuf) {
         .whitelist_type("ASIOChannelInfo")
         .whitelist_type("AsioTimeInfoFlags")
         .whitelist_type("ASIOTimeCodeFlags")
+        .whitelist_type("ASIOInputMonitor")
         .whitelist_var("kAsioSelectorSupported")
         .whitelist_var("kAsioEngineVersion")
         .whitelist_var("kAsioResetRequest")
@@ -189,10 +190,12 @@ fn create_bindings(cpal_asio_dir: &PathBuf) {
         .whitelist_var("kAsioSupportsOutputGain")
         .whitelist_var("kAsioSupportsOutputMeter")
         .whitelist_var("kAsioOverload")
+        .whitelist_var("kAsioSetInputMonitor")
         .whitelist_function("ASIOGetChannels")
         .whitelist_function("ASIOGetChannelInfo")
         .whitelist_function("ASIOGetBufferSize")
         .whitelist_function("ASIOGetSamplePosition")
+        .whitelist_function("ASIOFuture")
         .whitelist_function("get_sample_rate")
         .whitelist_function("set_sample_rate")
         .whitelist_function("can_sample_rate")
@@ -52,6 +52,8 @@ struct DriverInner {
     state: Mutex<DriverState>,
     // The unique name associated with this driver.
     name: String,
+    // The version of the driver, as reported by `ASIOInit`.
+    version: c_long,
     // Track whether or not the driver has been destroyed.
     //
     // This allows for the user to manually destroy the driver and handle any errors if they wish.
@@ -339,12 +341,13 @@ impl Asio {
                 true => {
                     // Initialize ASIO.
                     asio_result!(ai::ASIOInit(driver_info.as_mut_ptr()))?;
-                    let _driver_info = driver_info.assume_init();
+                    let driver_info = driver_info.assume_init();
                     let state = Mutex::new(DriverState::Initialized);
                     let name = driver_name.to_string();
                     let destroyed = false;
                     let inner = Arc::new(DriverInner {
                         name,
+                        version: driver_info.driverVersion,
                         state,
                         destroyed,
                     });
@@ -374,6 +377,11 @@ impl Driver {
         &self.inner.name
     }
 
+    /// The version of the driver, in a format of the driver's vendor's choosing.
+    pub fn version(&self) -> c_long {
+        self.inner.version
+    }
+
     /// Returns the number of input and output channels available on the driver.
     pub fn channels(&self) -> Result<Channels, AsioError> {
         let mut ins: c_long = 0;
@@ -385,6 +393,22 @@ impl Driver {
         Ok(channel)
     }
 
+    /// Returns the names of the input or output channels of the driver, in channel order.
+    pub fn channel_names(&self, is_input: bool) -> Result<Vec<String>, AsioError> {
+        let channels = self.channels()?;
+        let count = if is_input {
+            channels.ins
+        } else {
+            channels.outs
+        };
+        (0..count)
+            .map(|channel| {
+                let channel_info = asio_channel_info(channel, is_input)?;
+                Ok(driver_name_to_utf8(&channel_info.name).into_owned())
+            })
+            .collect()
+    }
+
     /// Get the min and max supported buffersize of the driver.
     pub fn buffersize_range(&self) -> Result<(c_long, c_long), AsioError> {
         let buffer_sizes = asio_get_buffer_sizes()?;
@@ -421,6 +445,33 @@ impl Driver {
         Ok(())
     }
 
+    /// Route an input channel to an output channel in hardware, or stop routing it.
+    ///
+    /// An `input` of -1 selects all input channels. Returns `AsioError::NoDrivers` if the driver
+    /// does not support input monitoring.
+    pub fn set_input_monitor(
+        &self,
+        input: c_long,
+        output: c_long,
+        enabled: bool,
+    ) -> Result<(), AsioError> {
+        // Unity gain and a centred pan, as defined by the ASIO SDK.
+        let mut monitor = ai::ASIOInputMonitor {
+            input,
+            output,
+            gain: 0x2000_0000,
+            state: enabled as ai::ASIOBool,
+            pan: 0x3fff_ffff,
+        };
+        unsafe {
+            asio_result!(ai::ASIOFuture(
+                ai::kAsioSetInputMonitor as c_long,
+                &mut monitor as *mut _ as *mut c_void,
+            ))?;
+        }
+        Ok(())
+    }
+
     /// Get the current data type of the driver's input stream.
     ///
     /// This queries a single channel's type assuming all channels have the same sample type.
@@ -19,6 +19,7 @@ fn main() {
         cpal::SampleFormat::F32 => run::<f32>(&device, &config.into()).unwrap(),
         cpal::SampleFormat::I16 => run::<i16>(&device, &config.into()).unwrap(),
         cpal::SampleFormat::U16 => run::<u16>(&device, &config.into()).unwrap(),
+        sample_format => panic!("Unsupported sample format '{:?}'", sample_format),
     }
 }
 
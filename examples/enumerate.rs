@@ -9,8 +9,15 @@ fn main() -> Result<(), anyhow::Error> {
     println!("Available hosts:\n  {:?}", available_hosts);
 
     for host_id in available_hosts {
-        println!("{}", host_id.name());
         let host = cpal::host_from_id(host_id)?;
+        print!("{}", host.name());
+        if host.is_default() {
+            print!(" (default)");
+        }
+        match host.backend_version() {
+            Some(version) => println!(", {}", version),
+            None => println!(),
+        }
 
         let default_in = host.default_input_device().map(|e| e.name().unwrap());
         let default_out = host.default_output_device().map(|e| e.name().unwrap());
@@ -97,7 +97,12 @@ fn main() -> Result<(), anyhow::Error> {
 
     // The WAV file we're recording to.
     const PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/recorded.wav");
-    let spec = wav_spec_from_config(&config);
+    let spec = wav_spec_from_config(&config).ok_or_else(|| {
+        anyhow::Error::msg(format!(
+            "Unsupported sample format '{:?}'",
+            config.sample_format()
+        ))
+    })?;
     let writer = hound::WavWriter::create(PATH, spec)?;
     let writer = Arc::new(Mutex::new(Some(writer)));
 
@@ -127,6 +132,12 @@ fn main() -> Result<(), anyhow::Error> {
             move |data, _: &_| write_input_data::<u16, i16>(data, &writer_2),
             err_fn,
         )?,
+        sample_format => {
+            return Err(anyhow::Error::msg(format!(
+                "Unsupported sample format '{:?}'",
+                sample_format
+            )))
+        }
     };
 
     stream.play()?;
@@ -139,21 +150,23 @@ fn main() -> Result<(), anyhow::Error> {
     Ok(())
 }
 
-fn sample_format(format: cpal::SampleFormat) -> hound::SampleFormat {
+// The WAV sample format the samples of `format` are written in, if they can be.
+fn sample_format(format: cpal::SampleFormat) -> Option<hound::SampleFormat> {
     match format {
-        cpal::SampleFormat::U16 => hound::SampleFormat::Int,
-        cpal::SampleFormat::I16 => hound::SampleFormat::Int,
-        cpal::SampleFormat::F32 => hound::SampleFormat::Float,
+        cpal::SampleFormat::U16 => Some(hound::SampleFormat::Int),
+        cpal::SampleFormat::I16 => Some(hound::SampleFormat::Int),
+        cpal::SampleFormat::F32 => Some(hound::SampleFormat::Float),
+        _ => None,
     }
 }
 
-fn wav_spec_from_config(config: &cpal::SupportedStreamConfig) -> hound::WavSpec {
-    hound::WavSpec {
+fn wav_spec_from_config(config: &cpal::SupportedStreamConfig) -> Option<hound::WavSpec> {
+    Some(hound::WavSpec {
         channels: config.channels() as _,
         sample_rate: config.sample_rate().0 as _,
         bits_per_sample: (config.sample_format().sample_size() * 8) as _,
-        sample_format: sample_format(config.sample_format()),
-    }
+        sample_format: sample_format(config.sample_format())?,
+    })
 }
 
 type WavWriterHandle = Arc<Mutex<Option<hound::WavWriter<BufWriter<File>>>>>;
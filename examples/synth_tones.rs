@@ -42,11 +42,12 @@ where
 {
     let (_host, device, config) = host_device_setup()?;
 
-    match config.sample_format() {
-        cpal::SampleFormat::F32 => stream_make::<f32, _>(&device, &config.into(), on_sample),
-        cpal::SampleFormat::I16 => stream_make::<i16, _>(&device, &config.into(), on_sample),
-        cpal::SampleFormat::U16 => stream_make::<u16, _>(&device, &config.into(), on_sample),
-    }
+    cpal::for_each_sample_format!(config.sample_format() => |T| {
+        stream_make::<T, _>(&device, &config.into(), on_sample)
+    }, sample_format => Err(anyhow::Error::msg(format!(
+        "Unsupported sample format '{:?}'",
+        sample_format
+    ))))
 }
 
 pub fn host_device_setup(
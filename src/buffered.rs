@@ -0,0 +1,572 @@
+//! Streams that exchange samples through a ring buffer instead of a callback.
+//!
+//! The data callback of the stream moves samples between the device and a lock-free ring buffer,
+//! and [`BufferedInput`] and [`BufferedOutput`] read and write the other end. Reading waits for
+//! captured samples, writing waits for room in the buffer, so a program producing audio is paced
+//! by the device. An input stream can also feed several [`BufferedInput`]s at once, see
+//! [`build_input_stream_tee`](crate::traits::DeviceTrait::build_input_stream_tee).
+//!
+//! Both ends can be used from blocking code with `read` and `write`, or from async code with
+//! `recv` and `send` when the `async` feature is enabled. The futures only rely on `std::task`,
+//! so they can be awaited on any executor. The feature also makes [`BufferedInput`] a
+//! `futures_core::Stream` and [`BufferedOutput`] a `futures_sink::Sink`, for use with the
+//! combinators of the futures ecosystem.
+//!
+//! This module is only available with the `buffered` feature, which the `async` feature enables.
+
+use crate::{Sample, StreamConfig};
+use ringbuf::{Consumer, Producer, RingBuffer};
+#[cfg(feature = "async")]
+use std::convert::Infallible;
+use std::future::{poll_fn, Future};
+#[cfg(feature = "async")]
+use std::mem;
+use std::pin::pin;
+#[cfg(feature = "async")]
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread::{self, Thread};
+use std::time::Duration;
+
+// The state shared between the data callback and the reading or writing end.
+#[derive(Default)]
+struct Shared {
+    // The task waiting on the ring buffer.
+    waker: Mutex<Option<Waker>>,
+    // Set once the data callback is dropped along with its stream.
+    closed: AtomicBool,
+    // Set by the first write to an output buffer.
+    started: AtomicBool,
+    // The frames dropped by input streams, or filled with silence by output streams.
+    lost_frames: AtomicU64,
+}
+
+impl Shared {
+    // Registers the task to be woken on the next change to the ring buffer. The caller must check
+    // the buffer after registering, as changes made meanwhile wake the previous task.
+    fn register(&self, waker: &Waker) {
+        let mut registered = self.waker.lock().unwrap();
+        if !registered.as_ref().is_some_and(|w| w.will_wake(waker)) {
+            *registered = Some(waker.clone());
+        }
+    }
+
+    // Wakes the waiting task, without blocking the audio thread. If the lock is taken, the task
+    // is registering and checks the buffer afterwards.
+    fn wake(&self) {
+        if let Ok(mut waker) = self.waker.try_lock() {
+            if let Some(waker) = waker.take() {
+                waker.wake();
+            }
+        }
+    }
+}
+
+// Closes the buffer when the data callback owning it is dropped.
+struct CloseOnDrop(Arc<Shared>);
+
+impl Drop for CloseOnDrop {
+    fn drop(&mut self) {
+        self.0.closed.store(true, Ordering::Release);
+        self.0.wake();
+    }
+}
+
+// Wakes a thread blocked in `block_on`.
+struct ThreadWaker(Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+// Polls a future to completion on the current thread, parking it while the future is pending.
+fn block_on<F: Future>(future: F) -> F::Output {
+    let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    let mut future = pin!(future);
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+        thread::park();
+    }
+}
+
+// The capacity of the ring buffer in samples, rounded down to whole frames.
+fn capacity(config: &StreamConfig, latency: Duration) -> usize {
+    let channels = (config.channels as usize).max(1);
+    let frames = (latency.as_secs_f64() * config.sample_rate.0 as f64) as usize;
+    frames.max(1) * channels
+}
+
+/// The reading end of an input stream built with
+/// [`build_input_stream_buffered`](crate::traits::DeviceTrait::build_input_stream_buffered).
+pub struct BufferedInput<T> {
+    consumer: Consumer<T>,
+    shared: Arc<Shared>,
+}
+
+impl<T: Sample> BufferedInput<T> {
+    /// Blocks until `buffer` is filled with captured interleaved samples.
+    ///
+    /// Returns the number of samples read, which is less than `buffer.len()` only if the stream
+    /// was dropped.
+    pub fn read(&mut self, buffer: &mut [T]) -> usize {
+        let mut read = 0;
+        block_on(poll_fn(|cx| self.poll_read(cx, buffer, &mut read)));
+        read
+    }
+
+    fn poll_read(&mut self, cx: &mut Context, buffer: &mut [T], read: &mut usize) -> Poll<()> {
+        self.shared.register(cx.waker());
+        *read += self.consumer.pop_slice(&mut buffer[*read..]);
+        if *read == buffer.len() || self.shared.closed.load(Ordering::Acquire) {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+
+    /// Waits for captured samples and returns all interleaved frames buffered so far.
+    ///
+    /// Returns `None` once the stream was dropped and every buffered frame was read.
+    #[cfg(feature = "async")]
+    pub async fn recv(&mut self) -> Option<Vec<T>> {
+        poll_fn(|cx| self.poll_recv(cx)).await
+    }
+
+    #[cfg(feature = "async")]
+    fn poll_recv(&mut self, cx: &mut Context) -> Poll<Option<Vec<T>>> {
+        self.shared.register(cx.waker());
+        if !self.consumer.is_empty() {
+            let mut samples = vec![T::from(&0.0f32); self.consumer.len()];
+            self.consumer.pop_slice(&mut samples);
+            Poll::Ready(Some(samples))
+        } else if self.shared.closed.load(Ordering::Acquire) {
+            Poll::Ready(None)
+        } else {
+            Poll::Pending
+        }
+    }
+
+    /// The number of captured frames dropped because the buffer was full, as it was not read
+    /// quickly enough.
+    pub fn dropped_frames(&self) -> u64 {
+        self.shared.lost_frames.load(Ordering::Relaxed)
+    }
+}
+
+/// The writing end of an output stream built with
+/// [`build_output_stream_buffered`](crate::traits::DeviceTrait::build_output_stream_buffered).
+pub struct BufferedOutput<T> {
+    producer: Producer<T>,
+    shared: Arc<Shared>,
+    // The samples passed to `Sink::start_send` that are not all in the ring buffer yet, and the
+    // number of them that are.
+    #[cfg(feature = "async")]
+    pending: Vec<T>,
+    #[cfg(feature = "async")]
+    pending_sent: usize,
+}
+
+impl<T: Sample> BufferedOutput<T> {
+    /// Queues interleaved samples for playback, blocking until there is room in the buffer for
+    /// all of them.
+    ///
+    /// Returns the number of samples queued, which is less than `samples.len()` only if the
+    /// stream was dropped.
+    pub fn write(&mut self, samples: &[T]) -> usize {
+        self.shared.started.store(true, Ordering::Relaxed);
+        let mut sent = 0;
+        block_on(poll_fn(|cx| self.poll_write(cx, samples, &mut sent)));
+        sent
+    }
+
+    /// Queues interleaved samples for playback, waiting for room in the buffer as needed.
+    ///
+    /// Returns the number of samples queued, which is less than `samples.len()` only if the
+    /// stream was dropped.
+    #[cfg(feature = "async")]
+    pub async fn send(&mut self, samples: &[T]) -> usize {
+        self.shared.started.store(true, Ordering::Relaxed);
+        let mut sent = 0;
+        poll_fn(|cx| self.poll_write(cx, samples, &mut sent)).await;
+        sent
+    }
+
+    fn poll_write(&mut self, cx: &mut Context, samples: &[T], sent: &mut usize) -> Poll<()> {
+        self.shared.register(cx.waker());
+        *sent += self.producer.push_slice(&samples[*sent..]);
+        if *sent == samples.len() || self.shared.closed.load(Ordering::Acquire) {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+
+    /// Blocks until the stream has taken every queued sample from the buffer.
+    ///
+    /// The samples may still be on their way to the speakers; follow up with
+    /// [`StreamTrait::drain`](crate::traits::StreamTrait::drain) to wait for them to be played.
+    pub fn flush(&mut self) {
+        block_on(poll_fn(|cx| self.poll_empty(cx)));
+    }
+
+    /// Waits until the stream has taken every queued sample from the buffer.
+    #[cfg(feature = "async")]
+    pub async fn flushed(&mut self) {
+        poll_fn(|cx| self.poll_empty(cx)).await;
+    }
+
+    fn poll_empty(&mut self, cx: &mut Context) -> Poll<()> {
+        self.shared.register(cx.waker());
+        if self.producer.is_empty() || self.shared.closed.load(Ordering::Acquire) {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+
+    /// The number of frames played as silence because the buffer ran empty, counted from the
+    /// first write.
+    pub fn underrun_frames(&self) -> u64 {
+        self.shared.lost_frames.load(Ordering::Relaxed)
+    }
+
+    // Queues the samples left over from `Sink::start_send`.
+    #[cfg(feature = "async")]
+    fn poll_pending(&mut self, cx: &mut Context) -> Poll<()> {
+        let pending = mem::take(&mut self.pending);
+        let mut sent = self.pending_sent;
+        let poll = self.poll_write(cx, &pending, &mut sent);
+        self.pending = pending;
+        if poll.is_ready() {
+            self.pending.clear();
+            self.pending_sent = 0;
+        } else {
+            self.pending_sent = sent;
+        }
+        poll
+    }
+}
+
+// Nothing in the output is pinned in place, whatever the sample type.
+#[cfg(feature = "async")]
+impl<T> Unpin for BufferedOutput<T> {}
+
+/// Yields the interleaved frames captured since the last item, as
+/// [`recv`](BufferedInput::recv) does, and ends once the stream was dropped.
+#[cfg(feature = "async")]
+impl<T: Sample> futures_core::Stream for BufferedInput<T> {
+    type Item = Vec<T>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Vec<T>>> {
+        self.get_mut().poll_recv(cx)
+    }
+}
+
+/// Queues buffers of interleaved samples for playback, as [`send`](BufferedOutput::send) does.
+///
+/// The sink is ready for the next buffer once the previous one is in the ring buffer, and flushed
+/// once the stream took every sample from it, as with [`flushed`](BufferedOutput::flushed).
+/// Samples sent after the stream was dropped are discarded.
+#[cfg(feature = "async")]
+impl<T: Sample> futures_sink::Sink<Vec<T>> for BufferedOutput<T> {
+    type Error = Infallible;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Infallible>> {
+        self.get_mut().poll_pending(cx).map(Ok)
+    }
+
+    fn start_send(self: Pin<&mut Self>, samples: Vec<T>) -> Result<(), Infallible> {
+        let this = self.get_mut();
+        this.shared.started.store(true, Ordering::Relaxed);
+        if this.pending.is_empty() {
+            this.pending = samples;
+        } else {
+            this.pending.extend(samples);
+        }
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Infallible>> {
+        let this = self.get_mut();
+        if this.poll_pending(cx).is_pending() {
+            return Poll::Pending;
+        }
+        this.poll_empty(cx).map(Ok)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Infallible>> {
+        self.poll_flush(cx)
+    }
+}
+
+/// Creates an input buffer holding `latency` worth of frames, along with the data callback to
+/// build the stream with.
+pub(crate) fn input<T>(
+    config: &StreamConfig,
+    latency: Duration,
+) -> (impl FnMut(&[T]) + Send + 'static, BufferedInput<T>)
+where
+    T: Sample + Send + 'static,
+{
+    let channels = (config.channels as usize).max(1);
+    let (mut producer, consumer) = RingBuffer::new(capacity(config, latency)).split();
+    let shared = Arc::new(Shared::default());
+    let guard = CloseOnDrop(shared.clone());
+    let callback = move |data: &[T]| {
+        let shared = &guard.0;
+        let room = producer.remaining() / channels * channels;
+        let pushed = producer.push_slice(&data[..data.len().min(room)]);
+        let dropped = (data.len() - pushed) / channels;
+        if dropped > 0 {
+            shared
+                .lost_frames
+                .fetch_add(dropped as u64, Ordering::Relaxed);
+        }
+        shared.wake();
+    };
+    (callback, BufferedInput { consumer, shared })
+}
+
+/// Creates `consumers` input buffers holding `latency` worth of frames each, along with the data
+/// callback to build the stream with. Every buffer receives all captured frames, and drops
+/// frames on its own when it is not read quickly enough.
+pub(crate) fn tee<T>(
+    config: &StreamConfig,
+    latency: Duration,
+    consumers: usize,
+) -> (impl FnMut(&[T]) + Send + 'static, Vec<BufferedInput<T>>)
+where
+    T: Sample + Send + 'static,
+{
+    let (mut callbacks, buffers): (Vec<_>, Vec<_>) =
+        (0..consumers).map(|_| input(config, latency)).unzip();
+    let callback = move |data: &[T]| {
+        for callback in &mut callbacks {
+            callback(data);
+        }
+    };
+    (callback, buffers)
+}
+
+/// Creates an output buffer holding `latency` worth of frames, along with the data callback to
+/// build the stream with.
+pub(crate) fn output<T>(
+    config: &StreamConfig,
+    latency: Duration,
+) -> (impl FnMut(&mut [T]) + Send + 'static, BufferedOutput<T>)
+where
+    T: Sample + Send + 'static,
+{
+    let channels = (config.channels as usize).max(1);
+    let (producer, mut consumer) = RingBuffer::new(capacity(config, latency)).split();
+    let shared = Arc::new(Shared::default());
+    let guard = CloseOnDrop(shared.clone());
+    let callback = move |data: &mut [T]| {
+        let shared = &guard.0;
+        let popped = consumer.pop_slice(data);
+        data[popped..].fill(T::from(&0.0f32));
+        if popped < data.len() && shared.started.load(Ordering::Relaxed) {
+            let missing = (data.len() - popped) / channels;
+            shared
+                .lost_frames
+                .fetch_add(missing as u64, Ordering::Relaxed);
+        }
+        shared.wake();
+    };
+    let buffered = BufferedOutput {
+        producer,
+        shared,
+        #[cfg(feature = "async")]
+        pending: Vec::new(),
+        #[cfg(feature = "async")]
+        pending_sent: 0,
+    };
+    (callback, buffered)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{input, output, tee};
+    use crate::{BufferSize, SampleRate, StreamConfig};
+    use std::thread;
+    use std::time::Duration;
+    #[cfg(feature = "async")]
+    use std::{
+        future::Future,
+        pin::{pin, Pin},
+        sync::Arc,
+        task::{Context, Poll, Wake, Waker},
+    };
+
+    #[cfg(feature = "async")]
+    struct NoopWaker;
+
+    #[cfg(feature = "async")]
+    impl Wake for NoopWaker {
+        fn wake(self: Arc<Self>) {}
+    }
+
+    #[cfg(feature = "async")]
+    fn poll_once<F: Future>(future: F) -> Poll<F::Output> {
+        let waker = Waker::from(Arc::new(NoopWaker));
+        pin!(future).poll(&mut Context::from_waker(&waker))
+    }
+
+    // Two channels at 1 kHz with a 4 ms buffer hold 4 frames.
+    fn config() -> StreamConfig {
+        StreamConfig {
+            channels: 2,
+            sample_rate: SampleRate(1000),
+            buffer_size: BufferSize::Default,
+        }
+    }
+
+    #[test]
+    fn reads_captured_frames() {
+        let (mut callback, mut buffered) = input::<i16>(&config(), Duration::from_millis(10));
+        let capture = thread::spawn(move || {
+            for frame in 0..6 {
+                callback(&[frame, -frame]);
+                thread::sleep(Duration::from_millis(1));
+            }
+        });
+        let mut buffer = [0; 6];
+        assert_eq!(buffered.read(&mut buffer), 6);
+        assert_eq!(buffer, [0, 0, 1, -1, 2, -2]);
+        capture.join().unwrap();
+        let mut buffer = [0; 8];
+        assert_eq!(buffered.read(&mut buffer), 6);
+        assert_eq!(buffer[..6], [3, -3, 4, -4, 5, -5]);
+    }
+
+    #[test]
+    fn tees_captured_frames() {
+        let (mut callback, buffers) = tee::<i16>(&config(), Duration::from_millis(4), 2);
+        let [mut fast, mut slow]: [_; 2] = buffers.try_into().ok().unwrap();
+        callback(&[1, 2, 3, 4]);
+        let mut buffer = [0; 4];
+        assert_eq!(fast.read(&mut buffer), 4);
+        callback(&[5, 6, 7, 8, 9, 10]);
+        assert_eq!(fast.read(&mut buffer), 4);
+        assert_eq!(buffer, [5, 6, 7, 8]);
+        assert_eq!(fast.dropped_frames(), 0);
+
+        // The slow consumer drops frames without affecting the fast one.
+        let mut buffer = [0; 8];
+        assert_eq!(slow.read(&mut buffer), 8);
+        assert_eq!(buffer, [1, 2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(slow.dropped_frames(), 1);
+    }
+
+    #[test]
+    fn writes_frames_for_playback() {
+        let (mut callback, mut buffered) = output::<i16>(&config(), Duration::from_millis(4));
+        let playback = thread::spawn(move || {
+            // Silence is played whenever the buffer runs empty, so only collect the samples.
+            let mut played: Vec<i16> = vec![];
+            while played.len() < 12 {
+                let mut data = [0; 2];
+                callback(&mut data);
+                played.extend(data.iter().filter(|&&s| s != 0));
+                thread::sleep(Duration::from_millis(1));
+            }
+            played
+        });
+        let samples: Vec<i16> = (1..=12).collect();
+        assert_eq!(buffered.write(&samples), 12);
+        buffered.flush();
+        assert_eq!(playback.join().unwrap(), samples);
+    }
+
+    #[test]
+    #[cfg(feature = "async")]
+    fn receives_captured_frames() {
+        let (mut callback, mut buffered) = input::<i16>(&config(), Duration::from_millis(4));
+        assert_eq!(poll_once(buffered.recv()), Poll::Pending);
+        callback(&[1, 2, 3, 4]);
+        callback(&[5, 6, 7, 8, 9, 10]);
+        assert_eq!(
+            poll_once(buffered.recv()),
+            Poll::Ready(Some(vec![1, 2, 3, 4, 5, 6, 7, 8]))
+        );
+        assert_eq!(buffered.dropped_frames(), 1);
+        drop(callback);
+        assert_eq!(poll_once(buffered.recv()), Poll::Ready(None));
+    }
+
+    #[test]
+    #[cfg(feature = "async")]
+    fn sends_frames_for_playback() {
+        let (mut callback, mut buffered) = output::<i16>(&config(), Duration::from_millis(4));
+        let mut data = [9; 4];
+        callback(&mut data);
+        assert_eq!(data, [0; 4]);
+        assert_eq!(buffered.underrun_frames(), 0);
+
+        let samples = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10];
+        let mut send = Box::pin(buffered.send(&samples));
+        assert_eq!(poll_once(send.as_mut()), Poll::Pending);
+        callback(&mut data);
+        assert_eq!(data, [1, 2, 3, 4]);
+        assert_eq!(poll_once(send.as_mut()), Poll::Ready(10));
+        drop(send);
+
+        let mut data = [9; 8];
+        callback(&mut data);
+        assert_eq!(data, [5, 6, 7, 8, 9, 10, 0, 0]);
+        assert_eq!(buffered.underrun_frames(), 1);
+    }
+
+    #[test]
+    #[cfg(feature = "async")]
+    fn streams_captured_frames() {
+        use futures_core::Stream;
+
+        let (mut callback, mut buffered) = input::<i16>(&config(), Duration::from_millis(4));
+        let waker = Waker::from(Arc::new(NoopWaker));
+        let mut cx = Context::from_waker(&waker);
+        assert_eq!(Pin::new(&mut buffered).poll_next(&mut cx), Poll::Pending);
+        callback(&[1, 2, 3, 4]);
+        assert_eq!(
+            Pin::new(&mut buffered).poll_next(&mut cx),
+            Poll::Ready(Some(vec![1, 2, 3, 4]))
+        );
+        drop(callback);
+        assert_eq!(
+            Pin::new(&mut buffered).poll_next(&mut cx),
+            Poll::Ready(None)
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "async")]
+    fn sinks_frames_for_playback() {
+        use futures_sink::Sink;
+
+        let (mut callback, mut buffered) = output::<i16>(&config(), Duration::from_millis(4));
+        let waker = Waker::from(Arc::new(NoopWaker));
+        let mut cx = Context::from_waker(&waker);
+        let mut sink = Pin::new(&mut buffered);
+        assert_eq!(sink.as_mut().poll_ready(&mut cx), Poll::Ready(Ok(())));
+        sink.as_mut().start_send((1..=10).collect()).unwrap();
+        assert_eq!(sink.as_mut().poll_ready(&mut cx), Poll::Pending);
+
+        let mut data = [0; 4];
+        callback(&mut data);
+        assert_eq!(data, [1, 2, 3, 4]);
+        assert_eq!(sink.as_mut().poll_ready(&mut cx), Poll::Ready(Ok(())));
+        assert_eq!(sink.as_mut().poll_flush(&mut cx), Poll::Pending);
+
+        let mut data = [0; 8];
+        callback(&mut data);
+        assert_eq!(data, [5, 6, 7, 8, 9, 10, 0, 0]);
+        assert_eq!(sink.as_mut().poll_close(&mut cx), Poll::Ready(Ok(())));
+    }
+}
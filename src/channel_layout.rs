@@ -0,0 +1,310 @@
+use crate::ChannelCount;
+
+/// The speaker a channel of a stream is intended for.
+///
+/// The variants follow the speaker positions of the WAVE channel mask, in the order of their bits.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ChannelPosition {
+    FrontLeft,
+    FrontRight,
+    FrontCenter,
+    /// The low-frequency effects channel, usually routed to a subwoofer.
+    LowFrequency,
+    BackLeft,
+    BackRight,
+    FrontLeftOfCenter,
+    FrontRightOfCenter,
+    BackCenter,
+    SideLeft,
+    SideRight,
+    TopCenter,
+    TopFrontLeft,
+    TopFrontCenter,
+    TopFrontRight,
+    TopBackLeft,
+    TopBackCenter,
+    TopBackRight,
+    /// A channel without a known speaker position.
+    Unknown,
+}
+
+impl ChannelPosition {
+    // The positions with a bit in the WAVE channel mask, in bit order.
+    pub(crate) const WAVE_MASK_ORDER: [ChannelPosition; 18] = [
+        ChannelPosition::FrontLeft,
+        ChannelPosition::FrontRight,
+        ChannelPosition::FrontCenter,
+        ChannelPosition::LowFrequency,
+        ChannelPosition::BackLeft,
+        ChannelPosition::BackRight,
+        ChannelPosition::FrontLeftOfCenter,
+        ChannelPosition::FrontRightOfCenter,
+        ChannelPosition::BackCenter,
+        ChannelPosition::SideLeft,
+        ChannelPosition::SideRight,
+        ChannelPosition::TopCenter,
+        ChannelPosition::TopFrontLeft,
+        ChannelPosition::TopFrontCenter,
+        ChannelPosition::TopFrontRight,
+        ChannelPosition::TopBackLeft,
+        ChannelPosition::TopBackCenter,
+        ChannelPosition::TopBackRight,
+    ];
+
+    /// The bit of this position in a WAVE channel mask, or `None` for `Unknown`.
+    pub fn wave_channel_mask_bit(self) -> Option<u32> {
+        match self {
+            ChannelPosition::Unknown => None,
+            position => Some(1 << position as u32),
+        }
+    }
+}
+
+/// The order in which the components of an ambisonic signal appear in each frame.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AmbisonicOrdering {
+    /// Ambisonic Channel Number ordering, as used by AmbiX: W, Y, Z, X, ...
+    Acn,
+    /// Furse-Malham ordering: W, X, Y, Z, ... Only defined up to third order.
+    FuMa,
+}
+
+/// The normalization of the spherical harmonic components of an ambisonic signal.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AmbisonicNormalization {
+    /// Schmidt semi-normalization, as used by AmbiX.
+    Sn3d,
+    /// Full three-dimensional normalization.
+    N3d,
+    /// The maxN normalization of Furse-Malham, with W attenuated by 3 dB.
+    FuMa,
+}
+
+/// Describes the channels of a stream carrying a full-sphere ambisonic signal.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Ambisonics {
+    /// The ambisonic order, 1 for first-order B-format.
+    pub order: u32,
+    /// The order of the components within each frame.
+    pub ordering: AmbisonicOrdering,
+    /// The normalization of the components.
+    pub normalization: AmbisonicNormalization,
+}
+
+impl Ambisonics {
+    /// The AmbiX convention: ACN ordering with SN3D normalization.
+    pub fn ambix(order: u32) -> Self {
+        Ambisonics {
+            order,
+            ordering: AmbisonicOrdering::Acn,
+            normalization: AmbisonicNormalization::Sn3d,
+        }
+    }
+
+    /// The number of channels carrying the signal, `(order + 1)²`.
+    pub fn channels(&self) -> ChannelCount {
+        ((self.order + 1) * (self.order + 1)) as ChannelCount
+    }
+
+    /// The index within each frame of the component of the given degree `l` and index `m`, where
+    /// `-l <= m <= l`.
+    ///
+    /// Returns `None` if the component is beyond the order of the signal, or cannot be expressed in
+    /// the channel ordering.
+    pub fn component_index(&self, l: u32, m: i32) -> Option<usize> {
+        if l > self.order || m.unsigned_abs() > l {
+            return None;
+        }
+        let acn = (l * (l + 1)) as usize;
+        let acn = if m < 0 {
+            acn - m.unsigned_abs() as usize
+        } else {
+            acn + m as usize
+        };
+        match self.ordering {
+            AmbisonicOrdering::Acn => Some(acn),
+            AmbisonicOrdering::FuMa => {
+                // The FuMa channel letters W, XYZ, RSTUV and KLMNOPQ, by ACN index.
+                const FUMA_FROM_ACN: [usize; 16] =
+                    [0, 2, 3, 1, 8, 6, 4, 5, 7, 15, 13, 11, 9, 10, 12, 14];
+                FUMA_FROM_ACN.get(acn).copied()
+            }
+        }
+    }
+}
+
+/// The speaker positions of the channels of a stream, in the order they appear in each frame.
+///
+/// A layout may instead describe an ambisonic signal, whose channels have no speaker positions.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ChannelLayout {
+    positions: Vec<ChannelPosition>,
+    ambisonics: Option<Ambisonics>,
+}
+
+impl ChannelLayout {
+    /// Creates a layout with the given position for each channel.
+    pub fn new(positions: Vec<ChannelPosition>) -> Self {
+        ChannelLayout {
+            positions,
+            ambisonics: None,
+        }
+    }
+
+    /// Creates a layout for a stream carrying the given ambisonic signal.
+    ///
+    /// Its channels have `Unknown` speaker positions.
+    pub fn ambisonic(ambisonics: Ambisonics) -> Self {
+        ChannelLayout {
+            positions: vec![ChannelPosition::Unknown; ambisonics.channels() as usize],
+            ambisonics: Some(ambisonics),
+        }
+    }
+
+    /// The conventional layout for the given number of channels, as assumed by WAVE files without
+    /// a channel mask.
+    ///
+    /// Counts without a convention are given `Unknown` positions.
+    pub fn default_for(channels: ChannelCount) -> Self {
+        use self::ChannelPosition::*;
+        let positions = match channels {
+            1 => vec![FrontCenter],
+            2 => vec![FrontLeft, FrontRight],
+            3 => vec![FrontLeft, FrontRight, FrontCenter],
+            4 => vec![FrontLeft, FrontRight, BackLeft, BackRight],
+            5 => vec![FrontLeft, FrontRight, FrontCenter, BackLeft, BackRight],
+            6 => vec![
+                FrontLeft,
+                FrontRight,
+                FrontCenter,
+                LowFrequency,
+                BackLeft,
+                BackRight,
+            ],
+            7 => vec![
+                FrontLeft,
+                FrontRight,
+                FrontCenter,
+                LowFrequency,
+                BackCenter,
+                SideLeft,
+                SideRight,
+            ],
+            8 => vec![
+                FrontLeft,
+                FrontRight,
+                FrontCenter,
+                LowFrequency,
+                BackLeft,
+                BackRight,
+                SideLeft,
+                SideRight,
+            ],
+            n => vec![Unknown; n as usize],
+        };
+        ChannelLayout::new(positions)
+    }
+
+    /// Creates the layout described by a WAVE channel mask, as found in `WAVEFORMATEXTENSIBLE`.
+    ///
+    /// The channels are assigned the positions of the set bits in ascending order. Channels beyond
+    /// the set bits have no position.
+    pub fn from_wave_channel_mask(mask: u32, channels: ChannelCount) -> Self {
+        let masked = ChannelPosition::WAVE_MASK_ORDER
+            .iter()
+            .copied()
+            .filter(|position| position.wave_channel_mask_bit().unwrap() & mask != 0);
+        let positions = masked
+            .chain(std::iter::repeat(ChannelPosition::Unknown))
+            .take(channels as usize)
+            .collect();
+        ChannelLayout::new(positions)
+    }
+
+    /// The WAVE channel mask describing this layout.
+    ///
+    /// Returns `None` if the layout cannot be described by a mask, which requires every position
+    /// to be known and the positions to be in mask order.
+    pub fn wave_channel_mask(&self) -> Option<u32> {
+        let mut mask = 0;
+        for position in &self.positions {
+            let bit = position.wave_channel_mask_bit()?;
+            if bit <= mask {
+                return None;
+            }
+            mask |= bit;
+        }
+        Some(mask)
+    }
+
+    /// The number of channels.
+    pub fn channels(&self) -> ChannelCount {
+        self.positions.len() as ChannelCount
+    }
+
+    /// The position of each channel.
+    pub fn positions(&self) -> &[ChannelPosition] {
+        &self.positions
+    }
+
+    /// The ambisonic signal carried by the channels, if the layout describes one.
+    pub fn ambisonics(&self) -> Option<Ambisonics> {
+        self.ambisonics
+    }
+
+    /// The index of the channel at `position` within each frame, if the layout has one.
+    pub fn index_of(&self, position: ChannelPosition) -> Option<usize> {
+        self.positions.iter().position(|&p| p == position)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{AmbisonicOrdering, Ambisonics, ChannelLayout, ChannelPosition};
+
+    #[test]
+    fn wave_channel_mask() {
+        // KSAUDIO_SPEAKER_5POINT1
+        let layout = ChannelLayout::from_wave_channel_mask(0x3f, 6);
+        assert_eq!(layout, ChannelLayout::default_for(6));
+        assert_eq!(layout.index_of(ChannelPosition::LowFrequency), Some(3));
+        assert_eq!(layout.wave_channel_mask(), Some(0x3f));
+
+        let layout = ChannelLayout::from_wave_channel_mask(0x3, 3);
+        assert_eq!(layout.positions()[2], ChannelPosition::Unknown);
+        assert_eq!(layout.wave_channel_mask(), None);
+
+        let swapped = ChannelLayout::new(vec![
+            ChannelPosition::FrontRight,
+            ChannelPosition::FrontLeft,
+        ]);
+        assert_eq!(swapped.wave_channel_mask(), None);
+    }
+
+    #[test]
+    fn ambisonic_components() {
+        let layout = ChannelLayout::ambisonic(Ambisonics::ambix(3));
+        assert_eq!(layout.channels(), 16);
+        let ambix = layout.ambisonics().unwrap();
+        // W, Y, Z, X
+        assert_eq!(ambix.component_index(0, 0), Some(0));
+        assert_eq!(ambix.component_index(1, -1), Some(1));
+        assert_eq!(ambix.component_index(1, 1), Some(3));
+        assert_eq!(ambix.component_index(3, 3), Some(15));
+        assert_eq!(ambix.component_index(4, 0), None);
+
+        let fuma = Ambisonics {
+            ordering: AmbisonicOrdering::FuMa,
+            ..ambix
+        };
+        // W, X, Y, Z
+        assert_eq!(fuma.component_index(1, 1), Some(1));
+        assert_eq!(fuma.component_index(1, -1), Some(2));
+        assert_eq!(fuma.component_index(1, 0), Some(3));
+    }
+}
@@ -0,0 +1,1799 @@
+//! Streams whose callback buffers are processed on their way from or to the device.
+//!
+//! Backs `DeviceTrait::build_input_stream_with_options` and
+//! `DeviceTrait::build_output_stream_with_options`.
+
+use crate::processing::channels::adapt_channels;
+use crate::processing::dc::DcBlocker;
+use crate::processing::dither::Dither;
+use crate::processing::fade::{ChannelGains, Ramp};
+use crate::processing::limiter::ClipMode;
+use crate::processing::loudness::LoudnessMeter;
+use crate::processing::{SharedStage, StageInfo};
+use crate::traits::{DeviceTrait, StreamTrait};
+use crate::{
+    BufferSize, BuildStreamError, ChannelCount, Data, DefaultStreamConfigError, InputCallbackInfo,
+    NegotiatedConfig, OutputCallbackInfo, PauseStreamError, PlayStreamError, RealtimePriority,
+    Sample, SampleFormat, SampleRate, StreamConfig, StreamError, StreamInstant, StreamOptions,
+    SupportedBufferSize, SupportedStreamConfig, SupportedStreamConfigRange,
+};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicU8, Ordering};
+use std::sync::{Arc, Mutex, TryLockError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// How much longer than the fade itself `StreamControls::pause` waits for the fade to reach the
+// device, to allow for the buffering on the way.
+const FADE_OUT_MARGIN: Duration = Duration::from_secs(1);
+
+// The time taken by gain changes made through `StreamControls` to take full effect.
+const GAIN_RAMP: Duration = Duration::from_millis(10);
+
+// The formats a device may be opened in for conversion, most preferred first.
+const CONVERTIBLE_FORMATS: &[SampleFormat] = &[
+    SampleFormat::F32,
+    SampleFormat::I16,
+    SampleFormat::U16,
+    SampleFormat::F16,
+    SampleFormat::MuLaw,
+];
+
+pub(crate) fn build_input_stream<Dev, T, D, E>(
+    device: &Dev,
+    config: &StreamConfig,
+    options: &StreamOptions,
+    mut data_callback: D,
+    error_callback: E,
+) -> Result<(Dev::Stream, NegotiatedConfig), BuildStreamError>
+where
+    Dev: DeviceTrait + ?Sized,
+    T: Sample + Send + 'static,
+    D: FnMut(&[T], &InputCallbackInfo) + Send + 'static,
+    E: FnMut(StreamError) + Send + 'static,
+{
+    let config = &native_config(config, options, || device.default_input_config())?;
+    let supported: Vec<_> = device
+        .supported_input_configs()
+        .map(|configs| configs.collect())
+        .unwrap_or_default();
+    if options.bit_perfect {
+        check_bit_perfect(&supported, config, T::FORMAT, options, false)?;
+    }
+    let device_config = choose_config(supported, config, T::FORMAT, options);
+    let sample_format = device_config.sample_format();
+    let mut promotion = Promotion::new(options);
+    let mut data_callback = move |data: &[T], info: &InputCallbackInfo| {
+        promotion.check();
+        data_callback(data, info)
+    };
+    if sample_format == T::FORMAT
+        && device_config.channels() == config.channels
+        && !options.remove_dc
+        && options.stages.is_empty()
+        && !options.meter_loudness
+        && options.fade.is_zero()
+        && options.controls.is_none()
+    {
+        let stream = build_input(device, config, options, data_callback, error_callback)?;
+        let negotiated = NegotiatedConfig::new(&stream, device_config, options.share_mode);
+        return Ok((stream, negotiated));
+    }
+
+    let mut converter = Converter::<T>::input(config, device_config.channels(), options);
+    let config = StreamConfig {
+        channels: device_config.channels(),
+        ..config.clone()
+    };
+    let stream = crate::for_each_sample_format!(sample_format => |U| {
+        build_input(
+            device,
+            &config,
+            options,
+            move |data: &[U], info: &InputCallbackInfo| {
+                converter.read(data, |samples| data_callback(samples, info))
+            },
+            error_callback,
+        )
+    }, _ => unreachable!("`choose_config` only returns convertible formats"))?;
+    let negotiated = NegotiatedConfig::new(&stream, device_config, options.share_mode);
+    Ok((stream, negotiated))
+}
+
+pub(crate) fn build_output_stream<Dev, T, D, E>(
+    device: &Dev,
+    config: &StreamConfig,
+    options: &StreamOptions,
+    mut data_callback: D,
+    error_callback: E,
+) -> Result<(Dev::Stream, NegotiatedConfig), BuildStreamError>
+where
+    Dev: DeviceTrait + ?Sized,
+    T: Sample + Send + 'static,
+    D: FnMut(&mut [T], &OutputCallbackInfo) + Send + 'static,
+    E: FnMut(StreamError) + Send + 'static,
+{
+    let config = &native_config(config, options, || device.default_output_config())?;
+    let supported: Vec<_> = device
+        .supported_output_configs()
+        .map(|configs| configs.collect())
+        .unwrap_or_default();
+    if options.bit_perfect {
+        check_bit_perfect(&supported, config, T::FORMAT, options, true)?;
+    }
+    let device_config = choose_config(supported, config, T::FORMAT, options);
+    let sample_format = device_config.sample_format();
+    let mut promotion = Promotion::new(options);
+    let mut data_callback = move |data: &mut [T], info: &OutputCallbackInfo| {
+        promotion.check();
+        data_callback(data, info)
+    };
+    if sample_format == T::FORMAT
+        && device_config.channels() == config.channels
+        && options.clip == ClipMode::Off
+        && options.stages.is_empty()
+        && !options.meter_loudness
+        && options.fade.is_zero()
+        && options.controls.is_none()
+    {
+        let stream = build_output(device, config, options, data_callback, error_callback)?;
+        let negotiated = NegotiatedConfig::new(&stream, device_config, options.share_mode);
+        return Ok((stream, negotiated));
+    }
+
+    let mut converter = Converter::<T>::output(config, device_config.channels(), options);
+    let config = StreamConfig {
+        channels: device_config.channels(),
+        ..config.clone()
+    };
+    let stream = crate::for_each_sample_format!(sample_format => |U| {
+        build_output(
+            device,
+            &config,
+            options,
+            move |data: &mut [U], info: &OutputCallbackInfo| {
+                converter.write(data, |samples| data_callback(samples, info))
+            },
+            error_callback,
+        )
+    }, _ => unreachable!("`choose_config` only returns convertible formats"))?;
+    let negotiated = NegotiatedConfig::new(&stream, device_config, options.share_mode);
+    Ok((stream, negotiated))
+}
+
+// The requested config, at the current rate of the device with `StreamOptions::native_sample_rate`.
+fn native_config(
+    config: &StreamConfig,
+    options: &StreamOptions,
+    default_config: impl FnOnce() -> Result<SupportedStreamConfig, DefaultStreamConfigError>,
+) -> Result<StreamConfig, BuildStreamError> {
+    if !options.native_sample_rate {
+        return Ok(config.clone());
+    }
+    let sample_rate = default_config()
+        .map_err(|err| match err {
+            DefaultStreamConfigError::DeviceNotAvailable => BuildStreamError::DeviceNotAvailable,
+            DefaultStreamConfigError::StreamTypeNotSupported => BuildStreamError::InvalidArgument,
+            DefaultStreamConfigError::BackendSpecific { err } => err.into(),
+        })?
+        .sample_rate();
+    Ok(StreamConfig {
+        sample_rate,
+        ..config.clone()
+    })
+}
+
+// `DeviceTrait::build_input_stream`, opening the device in `StreamOptions::share_mode` with
+// `StreamOptions::usage`.
+fn build_input<Dev, T, D, E>(
+    device: &Dev,
+    config: &StreamConfig,
+    options: &StreamOptions,
+    mut data_callback: D,
+    error_callback: E,
+) -> Result<Dev::Stream, BuildStreamError>
+where
+    Dev: DeviceTrait + ?Sized,
+    T: Sample,
+    D: FnMut(&[T], &InputCallbackInfo) + Send + 'static,
+    E: FnMut(StreamError) + Send + 'static,
+{
+    let mut timer = CallbackTimer::new(config, options);
+    let error_callback = Arc::new(Mutex::new(count_xruns(options, error_callback)));
+    let watchdog = Watchdog::spawn(config, options, error_callback.clone());
+    let data_callback = move |data: &Data, info: &InputCallbackInfo| {
+        if let Some(watchdog) = &watchdog {
+            watchdog.feed(data.len());
+        }
+        timer.measure(info.callback_instant(), data.len(), || {
+            data_callback(
+                data.as_slice()
+                    .expect("host supplied incorrect sample type"),
+                info,
+            )
+        })
+    };
+    let error_callback = move |err| report_error(&error_callback, err);
+    match options.usage {
+        Some(usage) => device.build_input_stream_raw_with_usage(
+            config,
+            T::FORMAT,
+            options.share_mode,
+            usage,
+            data_callback,
+            error_callback,
+        ),
+        None => device.build_input_stream_raw_with_share_mode(
+            config,
+            T::FORMAT,
+            options.share_mode,
+            data_callback,
+            error_callback,
+        ),
+    }
+}
+
+// `DeviceTrait::build_output_stream`, opening the device in `StreamOptions::share_mode` with
+// `StreamOptions::usage`.
+fn build_output<Dev, T, D, E>(
+    device: &Dev,
+    config: &StreamConfig,
+    options: &StreamOptions,
+    mut data_callback: D,
+    error_callback: E,
+) -> Result<Dev::Stream, BuildStreamError>
+where
+    Dev: DeviceTrait + ?Sized,
+    T: Sample,
+    D: FnMut(&mut [T], &OutputCallbackInfo) + Send + 'static,
+    E: FnMut(StreamError) + Send + 'static,
+{
+    let mut timer = CallbackTimer::new(config, options);
+    let mut pre_roll = PreRoll::new(config, options);
+    let mut scheduled = ScheduledStart::new(config, options);
+    let error_callback = Arc::new(Mutex::new(count_xruns(options, error_callback)));
+    let watchdog = Watchdog::spawn(config, options, error_callback.clone());
+    let data_callback = move |data: &mut Data, info: &OutputCallbackInfo| {
+        let len = data.len();
+        if let Some(watchdog) = &watchdog {
+            watchdog.feed(len);
+        }
+        let data = data
+            .as_slice_mut()
+            .expect("host supplied incorrect sample type");
+        timer.measure(info.callback_instant(), len, || {
+            if !pre_roll.fill(data) {
+                scheduled.process(data, info, &mut data_callback)
+            }
+        })
+    };
+    let error_callback = move |err| report_error(&error_callback, err);
+    match options.usage {
+        Some(usage) => device.build_output_stream_raw_with_usage(
+            config,
+            T::FORMAT,
+            options.share_mode,
+            usage,
+            data_callback,
+            error_callback,
+        ),
+        None => device.build_output_stream_raw_with_share_mode(
+            config,
+            T::FORMAT,
+            options.share_mode,
+            data_callback,
+            error_callback,
+        ),
+    }
+}
+
+/// Picks the device config to open for a stream with the given config and callback sample format.
+///
+/// The requested format and channel count are preferred whenever the device supports them. If it
+/// does not, the options decide whether to fall back to another sample format, another channel
+/// count, or both, with changing the sample format preferred over changing the channel count.
+/// Without a match the requested config is returned unchanged, leaving it to the host to accept or
+/// reject it.
+fn choose_config(
+    supported: Vec<SupportedStreamConfigRange>,
+    config: &StreamConfig,
+    sample_format: SampleFormat,
+    options: &StreamOptions,
+) -> SupportedStreamConfig {
+    let mut formats = vec![sample_format];
+    if options.convert_sample_format {
+        formats.extend(
+            CONVERTIBLE_FORMATS
+                .iter()
+                .filter(|&&format| format != sample_format && is_convertible(format)),
+        );
+    }
+    let candidates = |format: SampleFormat| {
+        supported.iter().filter(move |range| {
+            range.sample_format == format
+                && range.min_sample_rate <= config.sample_rate
+                && config.sample_rate <= range.max_sample_rate
+        })
+    };
+    let exact = formats
+        .iter()
+        .find_map(|&format| candidates(format).find(|range| range.channels == config.channels));
+    let adapted = || {
+        formats.iter().find_map(|&format| {
+            candidates(format).min_by_key(|range| channel_distance(config.channels, range.channels))
+        })
+    };
+    let range = match exact {
+        Some(range) => Some(range),
+        None if options.adapt_channels => adapted(),
+        None => None,
+    };
+    match range {
+        Some(range) => range.clone().with_sample_rate(config.sample_rate),
+        None => SupportedStreamConfig::new(
+            config.channels,
+            config.sample_rate,
+            SupportedBufferSize::Unknown,
+            sample_format,
+        ),
+    }
+}
+
+// Fails unless the device supports the config exactly and the options ask for no processing, so
+// that the stream passes its buffers between the callback and the device untouched.
+fn check_bit_perfect(
+    supported: &[SupportedStreamConfigRange],
+    config: &StreamConfig,
+    sample_format: SampleFormat,
+    options: &StreamOptions,
+    output: bool,
+) -> Result<(), BuildStreamError> {
+    let mut reasons = Vec::new();
+    let native = supported.iter().any(|range| {
+        range.sample_format == sample_format
+            && range.channels == config.channels
+            && range.min_sample_rate <= config.sample_rate
+            && config.sample_rate <= range.max_sample_rate
+    });
+    if !native {
+        reasons.push(format!(
+            "the device does not support {} channels of {} at {} Hz natively",
+            config.channels, sample_format, config.sample_rate.0
+        ));
+    }
+    if output && options.clip != ClipMode::Off {
+        reasons.push("clip protection is enabled".to_string());
+    }
+    if !output && options.remove_dc {
+        reasons.push("DC offset removal is enabled".to_string());
+    }
+    if !options.fade.is_zero() {
+        reasons.push("fading is enabled".to_string());
+    }
+    if !options.stages.is_empty() {
+        reasons.push("processing stages are inserted".to_string());
+    }
+    if options.meter_loudness {
+        reasons.push("loudness metering is enabled".to_string());
+    }
+    if options.controls.is_some() {
+        reasons.push("stream controls are attached".to_string());
+    }
+    if reasons.is_empty() {
+        Ok(())
+    } else {
+        let reason = reasons.join("; ");
+        Err(BuildStreamError::NotBitPerfect { reason })
+    }
+}
+
+// Orders channel counts by how well they suit a stream with `requested` channels: the smallest
+// count that fits all requested channels, then the largest of those that do not.
+fn channel_distance(requested: ChannelCount, available: ChannelCount) -> (bool, u16) {
+    (available < requested, requested.abs_diff(available))
+}
+
+/// A handle to the processing of a stream built with options, usable from any thread.
+///
+/// Pass a clone in `StreamOptions::controls` when building the stream, and keep another to
+/// monitor and control it. All clones refer to the same stream.
+#[derive(Clone, Debug, Default)]
+pub struct StreamControls {
+    shared: Arc<SharedControls>,
+}
+
+#[derive(Debug, Default)]
+struct SharedControls {
+    clipped_samples: AtomicU64,
+    // The length of the stream's fade in nanoseconds, zero if it does not fade.
+    fade_nanos: AtomicU64,
+    // Set by `pause` and cleared by `play`.
+    fading_out: AtomicBool,
+    // Set by the stream once it has processed a whole buffer after fading out.
+    faded_out: AtomicBool,
+    // The bits of the `f32` balance.
+    balance: AtomicU32,
+    // The gains set by `set_channel_gains`, and whether they changed since the stream last
+    // picked them up.
+    channel_gains: Mutex<Vec<f32>>,
+    channel_gains_changed: AtomicBool,
+    muted: AtomicBool,
+    // The volume set by `set_volume`, `None` for unity gain.
+    volume: AtomicOptionF32,
+    // The `RealtimePriority` reported by `realtime_priority`, offset by one to store `None` as 0.
+    realtime_priority: AtomicU8,
+    momentary_loudness: AtomicOptionF32,
+    short_term_loudness: AtomicOptionF32,
+    stats: StatsCounters,
+    // The duration of the silence played by `StreamOptions::pre_roll` so far, in nanoseconds.
+    pre_roll_nanos: AtomicU64,
+    // The start scheduled by `play_at`, and whether it changed since the stream last picked it
+    // up.
+    start_at: Mutex<Option<StreamInstant>>,
+    start_at_changed: AtomicBool,
+}
+
+/// Statistics about the data callback of a stream, as returned by `StreamControls::stats`.
+///
+/// The durations are zero until the callback has been called, the jitter until it has been
+/// called twice.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct StreamStats {
+    /// The number of times the data callback was called.
+    pub callbacks: u64,
+    /// The number of frames exchanged with the device.
+    pub frames: u64,
+    /// The shortest time a callback took, including the processing of its buffer.
+    pub min_callback_duration: Duration,
+    /// The average time a callback took.
+    pub avg_callback_duration: Duration,
+    /// The longest time a callback took.
+    pub max_callback_duration: Duration,
+    /// The average difference between the time from one callback to the next and the duration
+    /// of the audio exchanged in the first of them. Callbacks resumed after a pause are not
+    /// counted.
+    pub avg_jitter: Duration,
+    /// The largest such difference.
+    pub max_jitter: Duration,
+    /// The number of underruns and overruns reported to the error callback.
+    pub xruns: u64,
+}
+
+// The counters behind `StreamStats`, in nanoseconds where they hold durations.
+#[derive(Debug)]
+struct StatsCounters {
+    callbacks: AtomicU64,
+    frames: AtomicU64,
+    min_nanos: AtomicU64,
+    total_nanos: AtomicU64,
+    max_nanos: AtomicU64,
+    intervals: AtomicU64,
+    total_jitter_nanos: AtomicU64,
+    max_jitter_nanos: AtomicU64,
+    xruns: AtomicU64,
+}
+
+impl Default for StatsCounters {
+    fn default() -> Self {
+        StatsCounters {
+            callbacks: AtomicU64::new(0),
+            frames: AtomicU64::new(0),
+            min_nanos: AtomicU64::new(u64::MAX),
+            total_nanos: AtomicU64::new(0),
+            max_nanos: AtomicU64::new(0),
+            intervals: AtomicU64::new(0),
+            total_jitter_nanos: AtomicU64::new(0),
+            max_jitter_nanos: AtomicU64::new(0),
+            xruns: AtomicU64::new(0),
+        }
+    }
+}
+
+// An `Option<f32>` that can be shared between threads, storing `None` as NaN.
+#[derive(Debug)]
+struct AtomicOptionF32(AtomicU32);
+
+impl AtomicOptionF32 {
+    fn load(&self) -> Option<f32> {
+        let value = f32::from_bits(self.0.load(Ordering::Relaxed));
+        (!value.is_nan()).then_some(value)
+    }
+
+    fn store(&self, value: Option<f32>) {
+        let value = value.unwrap_or(f32::NAN);
+        self.0.store(value.to_bits(), Ordering::Relaxed);
+    }
+}
+
+impl Default for AtomicOptionF32 {
+    fn default() -> Self {
+        AtomicOptionF32(AtomicU32::new(f32::NAN.to_bits()))
+    }
+}
+
+impl StreamControls {
+    /// Creates a handle that is not attached to a stream yet.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The number of samples that exceeded full scale and were clipped according to
+    /// `StreamOptions::clip` since the stream was built.
+    pub fn clipped_samples(&self) -> u64 {
+        self.shared.clipped_samples.load(Ordering::Relaxed)
+    }
+
+    /// Statistics about the stream's data callback since the stream was built, for diagnosing
+    /// dropouts.
+    pub fn stats(&self) -> StreamStats {
+        let stats = &self.shared.stats;
+        let callbacks = stats.callbacks.load(Ordering::Relaxed);
+        let intervals = stats.intervals.load(Ordering::Relaxed);
+        let nanos = |counter: &AtomicU64| Duration::from_nanos(counter.load(Ordering::Relaxed));
+        let average = |counter: &AtomicU64, count: u64| {
+            Duration::from_nanos(counter.load(Ordering::Relaxed) / count.max(1))
+        };
+        StreamStats {
+            callbacks,
+            frames: stats.frames.load(Ordering::Relaxed),
+            min_callback_duration: match callbacks {
+                0 => Duration::ZERO,
+                _ => nanos(&stats.min_nanos),
+            },
+            avg_callback_duration: average(&stats.total_nanos, callbacks),
+            max_callback_duration: nanos(&stats.max_nanos),
+            avg_jitter: average(&stats.total_jitter_nanos, intervals),
+            max_jitter: nanos(&stats.max_jitter_nanos),
+            xruns: stats.xruns.load(Ordering::Relaxed),
+        }
+    }
+
+    /// The duration of the silence played before the first buffer of the data callback, as
+    /// requested by `StreamOptions::pre_roll`.
+    ///
+    /// Grows with every period of the pre-roll, and is final once the data callback has been
+    /// called. Add it to the latency of the stream when relating the first buffer to the time it
+    /// is heard.
+    pub fn pre_roll(&self) -> Duration {
+        Duration::from_nanos(self.shared.pre_roll_nanos.load(Ordering::Acquire))
+    }
+
+    /// Whether the thread calling the data callback was promoted to realtime scheduling, as
+    /// requested by `StreamOptions::realtime_priority`.
+    ///
+    /// Returns `None` if it was not requested, or before the callback has first been called.
+    pub fn realtime_priority(&self) -> Option<RealtimePriority> {
+        match self.shared.realtime_priority.load(Ordering::Acquire) {
+            1 => Some(RealtimePriority::Promoted),
+            2 => Some(RealtimePriority::AlreadyRealtime),
+            3 => Some(RealtimePriority::Denied),
+            4 => Some(RealtimePriority::Unsupported),
+            _ => None,
+        }
+    }
+
+    /// Sets the balance between the left and right channel of a stereo stream, from -1.0 for
+    /// left only to 1.0 for right only.
+    ///
+    /// The opposite channel is attenuated, while the favoured one stays at unity gain. The change
+    /// is ramped in over a few milliseconds. Streams with other channel counts are unaffected.
+    pub fn set_balance(&self, balance: f32) {
+        let balance = balance.clamp(-1.0, 1.0);
+        self.shared
+            .balance
+            .store(balance.to_bits(), Ordering::Relaxed);
+    }
+
+    /// The balance set by `set_balance`.
+    pub fn balance(&self) -> f32 {
+        f32::from_bits(self.shared.balance.load(Ordering::Relaxed))
+    }
+
+    /// Sets a linear gain for each channel of the stream, as seen by the callback.
+    ///
+    /// Channels without a gain in `gains` are left at unity gain, and extra gains are ignored.
+    /// The gains combine with the balance, and are ramped in over a few milliseconds.
+    pub fn set_channel_gains(&self, gains: &[f32]) {
+        let mut channel_gains = self
+            .shared
+            .channel_gains
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        channel_gains.clear();
+        channel_gains.extend_from_slice(gains);
+        self.shared
+            .channel_gains_changed
+            .store(true, Ordering::Release);
+    }
+
+    /// The gains set by `set_channel_gains`.
+    pub fn channel_gains(&self) -> Vec<f32> {
+        self.shared
+            .channel_gains
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+
+    /// The loudness of the last 400 ms of the stream in LUFS.
+    ///
+    /// Returns `None` unless the stream was built with `StreamOptions::meter_loudness`, or before
+    /// it has processed that much audio.
+    pub fn momentary_loudness(&self) -> Option<f32> {
+        self.shared.momentary_loudness.load()
+    }
+
+    /// The loudness of the last 3 s of the stream in LUFS.
+    ///
+    /// Returns `None` unless the stream was built with `StreamOptions::meter_loudness`, or before
+    /// it has processed that much audio.
+    pub fn short_term_loudness(&self) -> Option<f32> {
+        self.shared.short_term_loudness.load()
+    }
+
+    /// Sets a linear gain for all channels of the stream, as seen by the callback.
+    ///
+    /// The volume combines with the channel gains and the balance, and is ramped in over a few
+    /// milliseconds.
+    pub fn set_volume(&self, volume: f32) {
+        self.shared.volume.store(Some(volume));
+    }
+
+    /// The volume set by `set_volume`, 1.0 by default.
+    pub fn volume(&self) -> f32 {
+        self.shared.volume.load().unwrap_or(1.0)
+    }
+
+    /// Mutes or unmutes the stream, ramping to or from silence over a few milliseconds.
+    ///
+    /// Muting leaves the balance and channel gains untouched. Input streams deliver silence to
+    /// their callback while muted.
+    pub fn set_muted(&self, muted: bool) {
+        self.shared.muted.store(muted, Ordering::Relaxed);
+    }
+
+    /// Whether the stream was muted by `set_muted`.
+    pub fn is_muted(&self) -> bool {
+        self.shared.muted.load(Ordering::Relaxed)
+    }
+
+    /// Fades the stream out over `StreamOptions::fade`, then pauses it.
+    ///
+    /// Blocks until the fade has been processed. Without a fade, or if the stream stops
+    /// processing buffers, the stream is paused anyway. Pausing before dropping the stream keeps
+    /// it from stopping with a click.
+    pub fn pause<S>(&self, stream: &S) -> Result<(), PauseStreamError>
+    where
+        S: StreamTrait + ?Sized,
+    {
+        let shared = &self.shared;
+        let fade = Duration::from_nanos(shared.fade_nanos.load(Ordering::Relaxed));
+        shared.faded_out.store(false, Ordering::Relaxed);
+        shared.fading_out.store(true, Ordering::Relaxed);
+        if !fade.is_zero() {
+            let deadline = Instant::now() + fade + FADE_OUT_MARGIN;
+            while !shared.faded_out.load(Ordering::Relaxed) && Instant::now() < deadline {
+                thread::sleep(Duration::from_millis(1));
+            }
+        }
+        stream.pause()
+    }
+
+    /// Resumes the stream, fading it in over `StreamOptions::fade`.
+    pub fn play<S>(&self, stream: &S) -> Result<(), PlayStreamError>
+    where
+        S: StreamTrait + ?Sized,
+    {
+        self.shared.fading_out.store(false, Ordering::Relaxed);
+        self.shared.faded_out.store(false, Ordering::Relaxed);
+        stream.play()
+    }
+
+    /// Plays an output stream such that the first frame of its data callback is played at
+    /// `instant`, for synchronizing with external events such as video frames or a MIDI clock.
+    ///
+    /// The instant is on the clock of `OutputCallbackInfo::timestamp`. The stream plays silence
+    /// until then, and the buffer it starts in is passed to the data callback from the scheduled
+    /// frame on. If `instant` has already passed when the stream gets to it, it starts right
+    /// away. Input streams are played without delay.
+    pub fn play_at<S>(&self, stream: &S, instant: StreamInstant) -> Result<(), PlayStreamError>
+    where
+        S: StreamTrait + ?Sized,
+    {
+        *self
+            .shared
+            .start_at
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(instant);
+        self.shared.start_at_changed.store(true, Ordering::Release);
+        self.play(stream)
+    }
+}
+
+// Promotes the callback thread on the first callback of a stream built with
+// `StreamOptions::realtime_priority`.
+struct Promotion {
+    pending: bool,
+    controls: Option<StreamControls>,
+}
+
+impl Promotion {
+    fn new(options: &StreamOptions) -> Self {
+        Promotion {
+            pending: options.realtime_priority,
+            controls: options.controls.clone(),
+        }
+    }
+
+    #[inline]
+    fn check(&mut self) {
+        if !self.pending {
+            return;
+        }
+        self.pending = false;
+        let priority = crate::realtime::promote_current_thread();
+        if let Some(controls) = &self.controls {
+            let value = match priority {
+                RealtimePriority::Promoted => 1,
+                RealtimePriority::AlreadyRealtime => 2,
+                RealtimePriority::Denied => 3,
+                RealtimePriority::Unsupported => 4,
+            };
+            controls
+                .shared
+                .realtime_priority
+                .store(value, Ordering::Release);
+        }
+    }
+}
+
+// Measures the data callback of a stream built with `StreamOptions::controls`, for
+// `StreamControls::stats`. Only atomic counters are updated, so that the callback is not blocked.
+struct CallbackTimer {
+    controls: Option<StreamControls>,
+    channels: usize,
+    sample_rate: u32,
+    // When the previous callback started, and the duration of the audio it exchanged.
+    previous: Option<(Instant, Duration)>,
+}
+
+impl CallbackTimer {
+    // Intervals between callbacks longer than this many times the duration of their audio are
+    // taken to be a pause rather than jitter.
+    const MAX_INTERVAL_RATIO: u32 = 4;
+
+    fn new(config: &StreamConfig, options: &StreamOptions) -> Self {
+        CallbackTimer {
+            controls: options.controls.clone(),
+            channels: config.channels as usize,
+            sample_rate: config.sample_rate.0,
+            previous: None,
+        }
+    }
+
+    // Calls `callback`, which started at `start` and exchanges `samples` samples with the device.
+    #[inline]
+    fn measure<R>(&mut self, start: Instant, samples: usize, callback: impl FnOnce() -> R) -> R {
+        let controls = match &self.controls {
+            Some(controls) => controls,
+            None => return callback(),
+        };
+        let result = callback();
+        let nanos = start.elapsed().as_nanos() as u64;
+        let stats = &controls.shared.stats;
+        let frames = samples / self.channels.max(1);
+        stats.callbacks.fetch_add(1, Ordering::Relaxed);
+        stats.frames.fetch_add(frames as u64, Ordering::Relaxed);
+        stats.min_nanos.fetch_min(nanos, Ordering::Relaxed);
+        stats.total_nanos.fetch_add(nanos, Ordering::Relaxed);
+        stats.max_nanos.fetch_max(nanos, Ordering::Relaxed);
+
+        if let Some((previous_start, previous_audio)) = self.previous {
+            let interval = start.saturating_duration_since(previous_start);
+            if interval <= previous_audio * Self::MAX_INTERVAL_RATIO {
+                let jitter = interval.abs_diff(previous_audio).as_nanos() as u64;
+                stats.intervals.fetch_add(1, Ordering::Relaxed);
+                stats
+                    .total_jitter_nanos
+                    .fetch_add(jitter, Ordering::Relaxed);
+                stats.max_jitter_nanos.fetch_max(jitter, Ordering::Relaxed);
+            }
+        }
+        let audio =
+            Duration::from_nanos(frames as u64 * 1_000_000_000 / self.sample_rate.max(1) as u64);
+        self.previous = Some((start, audio));
+        result
+    }
+}
+
+// Plays the silence requested by `StreamOptions::pre_roll` in place of the first buffers of the
+// data callback.
+struct PreRoll {
+    periods: u32,
+    controls: Option<StreamControls>,
+    channels: usize,
+    sample_rate: u32,
+    frames: u64,
+}
+
+impl PreRoll {
+    fn new(config: &StreamConfig, options: &StreamOptions) -> Self {
+        PreRoll {
+            periods: options.pre_roll,
+            controls: options.controls.clone(),
+            channels: config.channels as usize,
+            sample_rate: config.sample_rate.0,
+            frames: 0,
+        }
+    }
+
+    // Fills `data` with silence while the pre-roll lasts, returning whether it did.
+    #[inline]
+    fn fill<T: Sample>(&mut self, data: &mut [T]) -> bool {
+        if self.periods == 0 {
+            return false;
+        }
+        self.periods -= 1;
+        data.fill(T::from(&0.0f32));
+        self.frames += (data.len() / self.channels.max(1)) as u64;
+        if let Some(controls) = &self.controls {
+            let nanos = self.frames * 1_000_000_000 / self.sample_rate.max(1) as u64;
+            controls
+                .shared
+                .pre_roll_nanos
+                .store(nanos, Ordering::Release);
+        }
+        true
+    }
+}
+
+// Plays silence until the start scheduled by `StreamControls::play_at`.
+struct ScheduledStart {
+    controls: Option<StreamControls>,
+    channels: usize,
+    sample_rate: SampleRate,
+    start: Option<StreamInstant>,
+}
+
+impl ScheduledStart {
+    fn new(config: &StreamConfig, options: &StreamOptions) -> Self {
+        ScheduledStart {
+            controls: options.controls.clone(),
+            channels: (config.channels as usize).max(1),
+            sample_rate: config.sample_rate,
+            start: None,
+        }
+    }
+
+    // Calls `callback` with the part of `data` at or after the scheduled start, filling the rest
+    // with silence.
+    #[inline]
+    fn process<T, F>(&mut self, data: &mut [T], info: &OutputCallbackInfo, callback: F)
+    where
+        T: Sample,
+        F: FnOnce(&mut [T], &OutputCallbackInfo),
+    {
+        if let Some(controls) = &self.controls {
+            let shared = &controls.shared;
+            // Pick up a new start without blocking on `play_at`, trying again on the next buffer
+            // if it holds the lock.
+            if shared.start_at_changed.load(Ordering::Acquire) {
+                if let Ok(start_at) = shared.start_at.try_lock() {
+                    shared.start_at_changed.store(false, Ordering::Relaxed);
+                    self.start = *start_at;
+                }
+            }
+        }
+        let start = match self.start {
+            Some(start) => start,
+            None => return callback(data, info),
+        };
+        let delay = start
+            .duration_since(&info.timestamp().playback)
+            .unwrap_or_default();
+        let frames = (delay.as_nanos() * self.sample_rate.0 as u128 / 1_000_000_000) as usize;
+        let silent = frames.saturating_mul(self.channels).min(data.len());
+        data[..silent].fill(T::from(&0.0f32));
+        if silent < data.len() {
+            self.start = None;
+            let info = info.skip_frames(frames as u64, self.sample_rate);
+            callback(&mut data[silent..], &info);
+        }
+    }
+}
+
+// Counts the underruns and overruns reported to the error callback for `StreamControls::stats`.
+fn count_xruns<E>(
+    options: &StreamOptions,
+    mut error_callback: E,
+) -> impl FnMut(StreamError) + Send + 'static
+where
+    E: FnMut(StreamError) + Send + 'static,
+{
+    let controls = options.controls.clone();
+    move |err| {
+        if let (Some(controls), StreamError::Underrun { .. } | StreamError::Overrun { .. }) =
+            (&controls, &err)
+        {
+            controls.shared.stats.xruns.fetch_add(1, Ordering::Relaxed);
+        }
+        error_callback(err)
+    }
+}
+
+// Calls an error callback shared between the host and the watchdog.
+fn report_error<E: FnMut(StreamError)>(error_callback: &Mutex<E>, err: StreamError) {
+    let mut error_callback = error_callback
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    (*error_callback)(err);
+}
+
+// Reports a data callback that stopped being called, for `StreamOptions::watchdog`. A thread
+// checks on the callback until the stream, owning the `Watchdog`, is dropped.
+struct Watchdog {
+    shared: Arc<WatchdogShared>,
+    channels: usize,
+    sample_rate: u32,
+}
+
+#[derive(Default)]
+struct WatchdogShared {
+    callbacks: AtomicU64,
+    // The duration of the audio of the last callback.
+    period_nanos: AtomicU64,
+    closed: AtomicBool,
+}
+
+impl Watchdog {
+    // Bounds on how often the thread checks on the callback.
+    const MIN_INTERVAL: Duration = Duration::from_millis(5);
+    const MAX_INTERVAL: Duration = Duration::from_millis(100);
+
+    fn spawn<E>(
+        config: &StreamConfig,
+        options: &StreamOptions,
+        error_callback: Arc<Mutex<E>>,
+    ) -> Option<Self>
+    where
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        let periods = options.watchdog?;
+        let shared = Arc::<WatchdogShared>::default();
+        let watched = shared.clone();
+        let controls = options.controls.clone();
+        thread::Builder::new()
+            .name("cpal_watchdog".to_owned())
+            .spawn(move || watch(&watched, periods, controls, &error_callback))
+            .ok()?;
+        Some(Watchdog {
+            shared,
+            channels: config.channels as usize,
+            sample_rate: config.sample_rate.0,
+        })
+    }
+
+    // Records a callback exchanging `samples` samples with the device.
+    #[inline]
+    fn feed(&self, samples: usize) {
+        let frames = (samples / self.channels.max(1)) as u64;
+        let nanos = frames * 1_000_000_000 / self.sample_rate.max(1) as u64;
+        self.shared.period_nanos.store(nanos, Ordering::Relaxed);
+        self.shared.callbacks.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+impl Drop for Watchdog {
+    fn drop(&mut self) {
+        self.shared.closed.store(true, Ordering::Relaxed);
+    }
+}
+
+// The watchdog thread, reporting a stall once until the callback is called again.
+fn watch<E: FnMut(StreamError)>(
+    shared: &WatchdogShared,
+    periods: u32,
+    controls: Option<StreamControls>,
+    error_callback: &Mutex<E>,
+) {
+    let mut seen = 0;
+    let mut progress = Instant::now();
+    let mut stalled = false;
+    while !shared.closed.load(Ordering::Relaxed) {
+        let period = Duration::from_nanos(shared.period_nanos.load(Ordering::Relaxed));
+        let timeout = period * periods;
+        thread::sleep((timeout / 4).clamp(Watchdog::MIN_INTERVAL, Watchdog::MAX_INTERVAL));
+
+        let callbacks = shared.callbacks.load(Ordering::Relaxed);
+        let paused = controls
+            .as_ref()
+            .is_some_and(|controls| controls.shared.fading_out.load(Ordering::Relaxed));
+        if callbacks != seen || callbacks == 0 || paused {
+            seen = callbacks;
+            progress = Instant::now();
+            stalled = false;
+            continue;
+        }
+        let elapsed = progress.elapsed();
+        if !stalled && elapsed >= timeout {
+            stalled = true;
+            report_error(error_callback, StreamError::Stalled { elapsed });
+        }
+    }
+}
+
+fn is_convertible(sample_format: SampleFormat) -> bool {
+    crate::for_each_sample_format!(sample_format => |T| T::FORMAT == sample_format, _ => false)
+}
+
+/// The buffers and state used to convert between the callback and device buffers.
+struct Converter<T> {
+    channels: usize,
+    device_channels: usize,
+    // The callback's buffer.
+    samples: Vec<T>,
+    // The samples as `f32`, with the callback's and the device's channel count.
+    floats: Vec<f32>,
+    device_floats: Vec<f32>,
+    dither: Dither,
+    clip: ClipMode,
+    dc_blocker: Option<DcBlocker>,
+    gains: GainStage,
+    stages: Vec<SharedStage>,
+    stage_info: StageInfo,
+    fade: Ramp,
+    // Measures the callback's buffers for input streams, and the device's for output streams.
+    loudness: Option<LoudnessMeter>,
+    controls: Option<StreamControls>,
+}
+
+impl<T> Converter<T>
+where
+    T: Sample,
+{
+    // Input streams dither the converted samples before passing them to the callback.
+    fn input(
+        config: &StreamConfig,
+        device_channels: ChannelCount,
+        options: &StreamOptions,
+    ) -> Self {
+        let mut converter = Self::new(config, device_channels, options, config.channels);
+        let channels = config.channels as usize;
+        if options.remove_dc {
+            converter.dc_blocker = Some(DcBlocker::new(channels, config.sample_rate));
+        }
+        if options.meter_loudness {
+            converter.loudness = Some(LoudnessMeter::new(channels, config.sample_rate));
+        }
+        converter
+    }
+
+    // Output streams dither the samples written by the callback on their way to the device.
+    fn output(
+        config: &StreamConfig,
+        device_channels: ChannelCount,
+        options: &StreamOptions,
+    ) -> Self {
+        let mut converter = Self::new(config, device_channels, options, device_channels);
+        if options.meter_loudness {
+            let channels = device_channels as usize;
+            converter.loudness = Some(LoudnessMeter::new(channels, config.sample_rate));
+        }
+        converter
+    }
+
+    fn new(
+        config: &StreamConfig,
+        device_channels: ChannelCount,
+        options: &StreamOptions,
+        dither_channels: ChannelCount,
+    ) -> Self {
+        let channels = config.channels as usize;
+        let device_channels = device_channels as usize;
+        // Allocate up front where the buffer size is known, so that the audio thread does not have
+        // to. Otherwise the buffers grow on the first callback.
+        let frames = match config.buffer_size {
+            BufferSize::Fixed(frames) => frames as usize,
+            BufferSize::Preferred { max, .. } => max as usize,
+            BufferSize::Default => 0,
+        };
+        // Streams with a fade start silent and fade in.
+        let fade_frames = duration_frames(options.fade, config);
+        let mut fade = Ramp::new(if fade_frames == 0 { 1.0 } else { 0.0 }, fade_frames);
+        fade.set_target(1.0);
+        if let Some(controls) = &options.controls {
+            let fade_nanos = if fade_frames == 0 {
+                0
+            } else {
+                options.fade.as_nanos() as u64
+            };
+            controls
+                .shared
+                .fade_nanos
+                .store(fade_nanos, Ordering::Relaxed);
+        }
+        Converter {
+            channels,
+            device_channels,
+            samples: Vec::with_capacity(frames * channels),
+            floats: Vec::with_capacity(frames * channels),
+            device_floats: Vec::with_capacity(frames * device_channels),
+            dither: Dither::new(options.dither, dither_channels as usize),
+            clip: options.clip,
+            dc_blocker: None,
+            gains: GainStage::new(channels, config),
+            stages: options.stages.clone(),
+            stage_info: StageInfo {
+                channels,
+                sample_rate: config.sample_rate,
+            },
+            fade,
+            loudness: None,
+            controls: options.controls.clone(),
+        }
+    }
+
+    // Converts an input buffer from the device and passes it to `callback`.
+    fn read<U, F>(&mut self, data: &[U], callback: F)
+    where
+        U: Sample,
+        F: FnOnce(&[T]),
+    {
+        let frames = data.len() / self.device_channels;
+        self.device_floats.clear();
+        self.device_floats.extend(data.iter().map(Sample::to_f32));
+        let floats = if self.channels == self.device_channels {
+            &mut self.device_floats
+        } else {
+            self.floats.resize(frames * self.channels, 0.0);
+            adapt_channels(
+                &self.device_floats,
+                self.device_channels,
+                &mut self.floats,
+                self.channels,
+            );
+            &mut self.floats
+        };
+        if let Some(dc_blocker) = &mut self.dc_blocker {
+            dc_blocker.process(floats);
+        }
+        self.gains.process(self.controls.as_ref(), floats);
+        run_stages(&self.stages, floats, &self.stage_info);
+        fade(
+            &mut self.fade,
+            self.controls.as_ref(),
+            floats,
+            self.channels,
+        );
+        measure_loudness(self.loudness.as_mut(), self.controls.as_ref(), floats);
+        self.samples.resize(floats.len(), T::from(&0.0f32));
+        self.dither.convert(floats, &mut self.samples);
+        callback(&self.samples);
+    }
+
+    // Fills an output buffer for the device from the samples written by `callback`.
+    fn write<U, F>(&mut self, data: &mut [U], callback: F)
+    where
+        U: Sample,
+        F: FnOnce(&mut [T]),
+    {
+        let frames = data.len() / self.device_channels;
+        self.samples
+            .resize(frames * self.channels, T::from(&0.0f32));
+        callback(&mut self.samples);
+        self.floats.clear();
+        self.floats.extend(self.samples.iter().map(Sample::to_f32));
+        self.gains.process(self.controls.as_ref(), &mut self.floats);
+        run_stages(&self.stages, &mut self.floats, &self.stage_info);
+        let device_floats = if self.channels == self.device_channels {
+            &mut self.floats
+        } else {
+            self.device_floats.resize(data.len(), 0.0);
+            adapt_channels(
+                &self.floats,
+                self.channels,
+                &mut self.device_floats,
+                self.device_channels,
+            );
+            &mut self.device_floats
+        };
+        fade(
+            &mut self.fade,
+            self.controls.as_ref(),
+            device_floats,
+            self.device_channels,
+        );
+        let clipped = self.clip.process(device_floats);
+        if let (Some(controls), true) = (&self.controls, clipped > 0) {
+            controls
+                .shared
+                .clipped_samples
+                .fetch_add(clipped as u64, Ordering::Relaxed);
+        }
+        measure_loudness(
+            self.loudness.as_mut(),
+            self.controls.as_ref(),
+            device_floats,
+        );
+        self.dither.convert(device_floats, data);
+    }
+}
+
+// The number of frames played in `duration` by a stream with the given config.
+fn duration_frames(duration: Duration, config: &StreamConfig) -> usize {
+    (duration.as_secs_f64() * config.sample_rate.0 as f64) as usize
+}
+
+// Applies the gains set through the controls, to buffers with the callback's channel count.
+struct GainStage {
+    ramps: ChannelGains,
+    // The stream's copy of the channel gains of the controls.
+    channel_gains: Vec<f32>,
+}
+
+impl GainStage {
+    fn new(channels: usize, config: &StreamConfig) -> Self {
+        GainStage {
+            ramps: ChannelGains::new(channels, duration_frames(GAIN_RAMP, config)),
+            channel_gains: vec![1.0; channels],
+        }
+    }
+
+    fn process(&mut self, controls: Option<&StreamControls>, samples: &mut [f32]) {
+        if let Some(controls) = controls {
+            let shared = &controls.shared;
+            // Pick up new channel gains without blocking on `set_channel_gains`, trying again on
+            // the next buffer if it holds the lock.
+            if shared.channel_gains_changed.load(Ordering::Acquire) {
+                if let Ok(channel_gains) = shared.channel_gains.try_lock() {
+                    shared.channel_gains_changed.store(false, Ordering::Relaxed);
+                    for (channel, gain) in self.channel_gains.iter_mut().enumerate() {
+                        *gain = channel_gains.get(channel).copied().unwrap_or(1.0);
+                    }
+                }
+            }
+            let balance = controls.balance();
+            let mute_gain = if controls.is_muted() { 0.0 } else { 1.0 };
+            let volume = controls.volume();
+            let stereo = self.channel_gains.len() == 2;
+            for (channel, &gain) in self.channel_gains.iter().enumerate() {
+                let balance_gain = match (stereo, channel) {
+                    (true, 0) => (1.0 - balance).min(1.0),
+                    (true, _) => (1.0 + balance).min(1.0),
+                    (false, _) => 1.0,
+                };
+                self.ramps
+                    .set_target(channel, gain * balance_gain * mute_gain * volume);
+            }
+        }
+        self.ramps.apply(samples);
+    }
+}
+
+// Runs the samples through each stage that isn't locked by the code adjusting it, without blocking.
+fn run_stages(stages: &[SharedStage], samples: &mut [f32], info: &StageInfo) {
+    for stage in stages {
+        let mut stage = match stage.try_lock() {
+            Ok(stage) => stage,
+            Err(TryLockError::Poisoned(poisoned)) => poisoned.into_inner(),
+            Err(TryLockError::WouldBlock) => continue,
+        };
+        stage.process(samples, info);
+    }
+}
+
+// Measures the samples and publishes the loudness through the controls.
+fn measure_loudness(
+    meter: Option<&mut LoudnessMeter>,
+    controls: Option<&StreamControls>,
+    samples: &[f32],
+) {
+    if let Some(meter) = meter {
+        meter.process(samples);
+        if let Some(controls) = controls {
+            let shared = &controls.shared;
+            shared.momentary_loudness.store(meter.momentary());
+            shared.short_term_loudness.store(meter.short_term());
+        }
+    }
+}
+
+// Fades the samples as requested through the controls, and reports back once a whole buffer has
+// been silenced by a fade out.
+fn fade(ramp: &mut Ramp, controls: Option<&StreamControls>, samples: &mut [f32], channels: usize) {
+    if let Some(controls) = controls {
+        let fading_out = controls.shared.fading_out.load(Ordering::Relaxed);
+        ramp.set_target(if fading_out { 0.0 } else { 1.0 });
+        if fading_out && ramp.is_settled() {
+            controls.shared.faded_out.store(true, Ordering::Relaxed);
+        }
+    }
+    ramp.apply(samples, channels);
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        check_bit_perfect, choose_config, count_xruns, native_config, CallbackTimer, Converter,
+        PreRoll, Promotion, ScheduledStart, StreamControls, Watchdog,
+    };
+    use crate::processing::dither::DitherMode;
+    use crate::processing::limiter::ClipMode;
+    use crate::processing::StageInfo;
+    use crate::{
+        BufferSize, BuildStreamError, DefaultStreamConfigError, OutputCallbackInfo,
+        OutputStreamTimestamp, SampleFormat, SampleRate, StreamConfig, StreamError, StreamInstant,
+        StreamOptions, SupportedBufferSize, SupportedStreamConfigRange,
+    };
+    use std::sync::atomic::Ordering;
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+
+    fn range(channels: u16, sample_format: SampleFormat) -> SupportedStreamConfigRange {
+        SupportedStreamConfigRange::new(
+            channels,
+            SampleRate(8_000),
+            SampleRate(96_000),
+            SupportedBufferSize::Unknown,
+            sample_format,
+        )
+    }
+
+    const CONFIG: StreamConfig = StreamConfig {
+        channels: 2,
+        sample_rate: SampleRate(48_000),
+        buffer_size: BufferSize::Default,
+    };
+
+    #[test]
+    fn choose_config_falls_back_to_convertible_format() {
+        let supported = vec![
+            range(2, SampleFormat::Iec61937),
+            range(1, SampleFormat::F32),
+            range(2, SampleFormat::U16),
+            range(2, SampleFormat::I16),
+        ];
+        let convert = StreamOptions {
+            convert_sample_format: true,
+            ..Default::default()
+        };
+
+        let chosen = choose_config(supported.clone(), &CONFIG, SampleFormat::U16, &convert);
+        assert_eq!(chosen.sample_format(), SampleFormat::U16);
+        let chosen = choose_config(supported.clone(), &CONFIG, SampleFormat::F32, &convert);
+        assert_eq!(chosen.sample_format(), SampleFormat::I16);
+        assert_eq!(chosen.sample_rate(), SampleRate(48_000));
+        let chosen = choose_config(supported, &CONFIG, SampleFormat::F32, &Default::default());
+        assert_eq!(chosen.sample_format(), SampleFormat::F32);
+    }
+
+    #[test]
+    fn native_config_uses_device_rate() {
+        let native = StreamOptions {
+            native_sample_rate: true,
+            ..Default::default()
+        };
+        let default_config =
+            || Ok(range(2, SampleFormat::I16).with_sample_rate(SampleRate(44_100)));
+
+        let config = native_config(&CONFIG, &Default::default(), default_config).unwrap();
+        assert_eq!(config.sample_rate, SampleRate(48_000));
+        let config = native_config(&CONFIG, &native, default_config).unwrap();
+        assert_eq!(config.sample_rate, SampleRate(44_100));
+        assert_eq!(config.channels, CONFIG.channels);
+        let err = native_config(&CONFIG, &native, || {
+            Err(DefaultStreamConfigError::DeviceNotAvailable)
+        });
+        assert!(matches!(err, Err(BuildStreamError::DeviceNotAvailable)));
+    }
+
+    #[test]
+    fn bit_perfect_requires_native_config() {
+        let supported = [range(2, SampleFormat::I16)];
+        let options = StreamOptions {
+            bit_perfect: true,
+            ..Default::default()
+        };
+        assert!(check_bit_perfect(&supported, &CONFIG, SampleFormat::I16, &options, true).is_ok());
+
+        let options = StreamOptions {
+            clip: ClipMode::Soft,
+            ..options
+        };
+        let err = check_bit_perfect(&supported, &CONFIG, SampleFormat::F32, &options, true);
+        match err {
+            Err(BuildStreamError::NotBitPerfect { reason }) => assert_eq!(
+                reason,
+                "the device does not support 2 channels of f32 at 48000 Hz natively; \
+                 clip protection is enabled"
+            ),
+            _ => panic!("expected `NotBitPerfect`"),
+        }
+    }
+
+    #[test]
+    fn converter_round_trips() {
+        let options = StreamOptions {
+            convert_sample_format: true,
+            dither: DitherMode::Off,
+            ..Default::default()
+        };
+        let mut converter = Converter::<f32>::output(&CONFIG, 2, &options);
+        let mut data = [0i16; 4];
+        converter.write(&mut data, |samples| {
+            samples.copy_from_slice(&[0.0, 0.5, -0.5, -1.0]);
+        });
+        assert_eq!(data, [0, 16384, -16384, -32768]);
+
+        let mut read = Vec::new();
+        converter.read(&data, |samples| read.extend_from_slice(samples));
+        for (read, expected) in read.iter().zip(&[0.0, 0.5, -0.5, -1.0]) {
+            assert!((read - expected).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn choose_config_adapts_channels() {
+        let supported = vec![
+            range(1, SampleFormat::I16),
+            range(6, SampleFormat::F32),
+            range(8, SampleFormat::F32),
+        ];
+        let adapt = StreamOptions {
+            adapt_channels: true,
+            ..Default::default()
+        };
+        let chosen = choose_config(supported.clone(), &CONFIG, SampleFormat::F32, &adapt);
+        assert_eq!(chosen.channels(), 6);
+        let chosen = choose_config(supported.clone(), &CONFIG, SampleFormat::I16, &adapt);
+        assert_eq!(chosen.channels(), 1);
+        // Converting the sample format is preferred over adapting the channels.
+        let supported = vec![range(1, SampleFormat::F32), range(2, SampleFormat::I16)];
+        let both = StreamOptions {
+            convert_sample_format: true,
+            ..adapt
+        };
+        let chosen = choose_config(supported, &CONFIG, SampleFormat::F32, &both);
+        assert_eq!(chosen.channels(), 2);
+        assert_eq!(chosen.sample_format(), SampleFormat::I16);
+    }
+
+    #[test]
+    fn converter_adapts_channels() {
+        let config = StreamConfig {
+            channels: 1,
+            ..CONFIG
+        };
+        let mut converter = Converter::<f32>::output(&config, 4, &Default::default());
+        let mut data = [0.0f32; 8];
+        converter.write(&mut data, |samples| {
+            samples.copy_from_slice(&[0.5, -0.5]);
+        });
+        assert_eq!(data, [0.5, 0.5, 0.5, 0.5, -0.5, -0.5, -0.5, -0.5]);
+    }
+
+    #[test]
+    fn converter_counts_clipped_samples() {
+        let controls = StreamControls::new();
+        let options = StreamOptions {
+            clip: ClipMode::Hard,
+            controls: Some(controls.clone()),
+            ..Default::default()
+        };
+        let mut converter = Converter::<f32>::output(&CONFIG, 2, &options);
+        let mut data = [0i16; 4];
+        converter.write(&mut data, |samples| {
+            samples.copy_from_slice(&[0.5, 1.5, -3.0, 1.0]);
+        });
+        assert_eq!(data[1], i16::MAX);
+        assert_eq!(data[2], i16::MIN);
+        assert_eq!(controls.clipped_samples(), 2);
+    }
+
+    #[test]
+    fn converter_applies_balance() {
+        let controls = StreamControls::new();
+        let options = StreamOptions {
+            controls: Some(controls.clone()),
+            ..Default::default()
+        };
+        let config = StreamConfig {
+            sample_rate: SampleRate(400),
+            ..CONFIG
+        };
+        let mut converter = Converter::<f32>::output(&config, 2, &options);
+        controls.set_balance(0.5);
+        let mut data = [0.0f32; 4];
+        converter.write(&mut data, |samples| samples.fill(1.0));
+        // The left channel is ramped down over four frames.
+        assert_eq!(data, [0.75, 1.0, 0.5, 1.0]);
+        converter.write(&mut data, |samples| samples.fill(1.0));
+        assert_eq!(data, [0.5, 1.0, 0.5, 1.0]);
+    }
+
+    #[test]
+    fn converter_applies_channel_gains() {
+        let controls = StreamControls::new();
+        let options = StreamOptions {
+            controls: Some(controls.clone()),
+            ..Default::default()
+        };
+        let config = StreamConfig {
+            channels: 3,
+            sample_rate: SampleRate(100),
+            ..CONFIG
+        };
+        let mut converter = Converter::<f32>::output(&config, 3, &options);
+        controls.set_channel_gains(&[0.5, 2.0]);
+        let mut data = [0.0f32; 3];
+        converter.write(&mut data, |samples| samples.fill(0.25));
+        assert_eq!(data, [0.125, 0.5, 0.25]);
+        assert_eq!(controls.channel_gains(), [0.5, 2.0]);
+
+        controls.set_volume(0.5);
+        converter.write(&mut data, |samples| samples.fill(0.25));
+        assert_eq!(data, [0.0625, 0.25, 0.125]);
+    }
+
+    #[test]
+    fn converter_mutes() {
+        let controls = StreamControls::new();
+        let options = StreamOptions {
+            controls: Some(controls.clone()),
+            ..Default::default()
+        };
+        let config = StreamConfig {
+            channels: 1,
+            sample_rate: SampleRate(400),
+            ..CONFIG
+        };
+        let mut converter = Converter::<f32>::input(&config, 1, &options);
+        controls.set_muted(true);
+        let mut read = Vec::new();
+        converter.read(&[1.0; 6], |samples| read.extend_from_slice(samples));
+        assert_eq!(read, [0.75, 0.5, 0.25, 0.0, 0.0, 0.0]);
+
+        controls.set_muted(false);
+        read.clear();
+        converter.read(&[1.0; 2], |samples| read.extend_from_slice(samples));
+        assert_eq!(read, [0.25, 0.5]);
+    }
+
+    #[test]
+    fn converter_runs_stages() {
+        let invert = |samples: &mut [f32], info: &StageInfo| {
+            assert_eq!(info.channels, 2);
+            samples.iter_mut().for_each(|sample| *sample = -*sample);
+        };
+        let options = StreamOptions {
+            stages: vec![
+                Arc::new(Mutex::new(invert)),
+                Arc::new(Mutex::new(ClipMode::Hard)),
+            ],
+            ..Default::default()
+        };
+        let mut converter = Converter::<f32>::output(&CONFIG, 2, &options);
+        let mut data = [0.0f32; 2];
+        converter.write(&mut data, |samples| {
+            samples.copy_from_slice(&[0.5, -2.0]);
+        });
+        assert_eq!(data, [-0.5, 1.0]);
+    }
+
+    #[test]
+    fn converter_skips_locked_stages() {
+        let invert = |samples: &mut [f32], _: &StageInfo| {
+            samples.iter_mut().for_each(|sample| *sample = -*sample);
+        };
+        let stage: crate::processing::SharedStage = Arc::new(Mutex::new(invert));
+        let options = StreamOptions {
+            stages: vec![stage.clone()],
+            ..Default::default()
+        };
+        let mut converter = Converter::<f32>::output(&CONFIG, 2, &options);
+        let mut data = [0.0f32; 2];
+        let guard = stage.lock().unwrap();
+        converter.write(&mut data, |samples| samples.copy_from_slice(&[0.5, -0.25]));
+        assert_eq!(data, [0.5, -0.25]);
+        drop(guard);
+        converter.write(&mut data, |samples| samples.copy_from_slice(&[0.5, -0.25]));
+        assert_eq!(data, [-0.5, 0.25]);
+    }
+
+    #[test]
+    fn converter_meters_loudness() {
+        let controls = StreamControls::new();
+        let options = StreamOptions {
+            meter_loudness: true,
+            controls: Some(controls.clone()),
+            ..Default::default()
+        };
+        let mut converter = Converter::<f32>::input(&CONFIG, 2, &options);
+        // A quarter of a second of stereo silence.
+        let data = vec![0.0f32; 24_000];
+        converter.read(&data, |_| ());
+        assert_eq!(controls.momentary_loudness(), None);
+        converter.read(&data, |_| ());
+        assert_eq!(controls.momentary_loudness(), Some(f32::NEG_INFINITY));
+        assert_eq!(controls.short_term_loudness(), None);
+    }
+
+    #[test]
+    fn converter_fades_in_and_out() {
+        let controls = StreamControls::new();
+        let config = StreamConfig {
+            channels: 1,
+            sample_rate: SampleRate(4),
+            ..CONFIG
+        };
+        let options = StreamOptions {
+            fade: Duration::from_secs(1),
+            controls: Some(controls.clone()),
+            ..Default::default()
+        };
+        let mut converter = Converter::<f32>::output(&config, 1, &options);
+        let mut data = [0.0f32; 6];
+        converter.write(&mut data, |samples| samples.fill(1.0));
+        assert_eq!(data, [0.25, 0.5, 0.75, 1.0, 1.0, 1.0]);
+
+        controls.shared.fading_out.store(true, Ordering::Relaxed);
+        converter.write(&mut data, |samples| samples.fill(1.0));
+        assert_eq!(data, [0.75, 0.5, 0.25, 0.0, 0.0, 0.0]);
+        assert!(!controls.shared.faded_out.load(Ordering::Relaxed));
+        converter.write(&mut data, |samples| samples.fill(1.0));
+        assert!(controls.shared.faded_out.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn promotes_on_first_callback() {
+        let controls = StreamControls::new();
+        let options = StreamOptions {
+            realtime_priority: true,
+            controls: Some(controls.clone()),
+            ..Default::default()
+        };
+        let mut promotion = Promotion::new(&options);
+        assert_eq!(controls.realtime_priority(), None);
+        // Promote a thread of its own, so as not to affect other tests.
+        std::thread::spawn(move || promotion.check())
+            .join()
+            .unwrap();
+        assert!(controls.realtime_priority().is_some());
+    }
+
+    #[test]
+    fn collects_callback_stats() {
+        let controls = StreamControls::new();
+        let options = StreamOptions {
+            controls: Some(controls.clone()),
+            ..Default::default()
+        };
+        let config = StreamConfig {
+            channels: 2,
+            sample_rate: SampleRate(1000),
+            buffer_size: BufferSize::Default,
+        };
+        assert_eq!(controls.stats(), Default::default());
+
+        // Buffers of 10 ms, called back after 10 ms and then after 15 ms.
+        let mut timer = CallbackTimer::new(&config, &options);
+        let start = Instant::now() - Duration::from_millis(25);
+        for offset in [0, 10, 25] {
+            timer.measure(start + Duration::from_millis(offset), 20, || ());
+        }
+        let mut error_callback = count_xruns(&options, |_| ());
+        error_callback(StreamError::Underrun { frames_lost: None });
+        error_callback(StreamError::DeviceNotAvailable);
+
+        let stats = controls.stats();
+        assert_eq!(stats.callbacks, 3);
+        assert_eq!(stats.frames, 30);
+        assert!(stats.min_callback_duration <= stats.avg_callback_duration);
+        assert!(stats.avg_callback_duration <= stats.max_callback_duration);
+        assert!(stats.max_callback_duration >= Duration::from_millis(25));
+        assert_eq!(stats.avg_jitter, Duration::from_micros(2500));
+        assert_eq!(stats.max_jitter, Duration::from_millis(5));
+        assert_eq!(stats.xruns, 1);
+    }
+
+    #[test]
+    fn pre_roll_plays_silence_first() {
+        let controls = StreamControls::new();
+        let options = StreamOptions {
+            pre_roll: 2,
+            controls: Some(controls.clone()),
+            ..Default::default()
+        };
+        let config = StreamConfig {
+            channels: 2,
+            sample_rate: SampleRate(1000),
+            buffer_size: BufferSize::Default,
+        };
+        let mut pre_roll = PreRoll::new(&config, &options);
+
+        // Two periods of 5 ms are silenced, the third is left to the data callback.
+        let mut data = [0.5f32; 10];
+        assert!(pre_roll.fill(&mut data));
+        assert_eq!(data, [0.0; 10]);
+        data.fill(0.5);
+        assert!(pre_roll.fill(&mut data));
+        data.fill(0.5);
+        assert!(!pre_roll.fill(&mut data));
+        assert_eq!(data, [0.5; 10]);
+        assert_eq!(controls.pre_roll(), Duration::from_millis(10));
+    }
+
+    #[test]
+    fn scheduled_start_pads_silence() {
+        let controls = StreamControls::new();
+        let options = StreamOptions {
+            controls: Some(controls.clone()),
+            ..Default::default()
+        };
+        let config = StreamConfig {
+            channels: 1,
+            sample_rate: SampleRate(1000),
+            buffer_size: BufferSize::Default,
+        };
+        let mut scheduled = ScheduledStart::new(&config, &options);
+        *controls.shared.start_at.lock().unwrap() = Some(StreamInstant::new(10, 6_000_000));
+        controls
+            .shared
+            .start_at_changed
+            .store(true, Ordering::Release);
+
+        // Buffers of 4 ms, the start falls in the second one.
+        let mut calls = vec![];
+        for (position, millis) in [(0, 0), (4, 4), (8, 8)] {
+            let instant = StreamInstant::new(10, millis * 1_000_000);
+            let timestamp = OutputStreamTimestamp {
+                callback: instant,
+                playback: instant,
+            };
+            let info = OutputCallbackInfo::new(timestamp, position);
+            let mut data = [0.5f32; 4];
+            scheduled.process(&mut data, &info, |samples: &mut [f32], info| {
+                calls.push((samples.len(), info.frame_position()));
+                samples.fill(1.0);
+            });
+            match position {
+                0 => assert_eq!(data, [0.0; 4]),
+                4 => assert_eq!(data, [0.0, 0.0, 1.0, 1.0]),
+                _ => assert_eq!(data, [1.0; 4]),
+            }
+        }
+        assert_eq!(calls, [(2, 6), (4, 8)]);
+    }
+
+    #[test]
+    fn watchdog_reports_stall_once() {
+        let options = StreamOptions {
+            watchdog: Some(2),
+            ..Default::default()
+        };
+        let config = StreamConfig {
+            channels: 1,
+            sample_rate: SampleRate(1000),
+            buffer_size: BufferSize::Default,
+        };
+        let errors = Arc::new(Mutex::new(vec![]));
+        let error_callback = {
+            let errors = errors.clone();
+            Arc::new(Mutex::new(move |err| errors.lock().unwrap().push(err)))
+        };
+        let watchdog = Watchdog::spawn(&config, &options, error_callback).unwrap();
+
+        // Not armed before the first callback.
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(errors.lock().unwrap().is_empty());
+
+        // Callbacks of 5 ms stall after 10 ms.
+        watchdog.feed(5);
+        std::thread::sleep(Duration::from_millis(100));
+        let errors = errors.lock().unwrap();
+        assert_eq!(errors.len(), 1);
+        match errors[0] {
+            StreamError::Stalled { elapsed } => assert!(elapsed >= Duration::from_millis(10)),
+            ref err => panic!("unexpected error: {}", err),
+        }
+    }
+}
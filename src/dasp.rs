@@ -0,0 +1,99 @@
+//! Interoperability with the dasp digital audio signal processing crates.
+//!
+//! Enable the `dasp` feature to use this module. It lets a `dasp_signal::Signal` render straight
+//! into an output stream, and reads interleaved stream buffers as `dasp_frame::Frame`s.
+
+use crate::{OutputCallbackInfo, Sample};
+use dasp_frame::Frame;
+use dasp_sample::ToSample;
+use dasp_signal::Signal;
+
+/// Fills an interleaved stream buffer with the frames of a signal, converting each sample to `T`.
+///
+/// `dst` is expected to hold as many channels as the frames of the signal. Only whole frames are
+/// written; any trailing samples are silenced. Returns the number of frames written.
+pub fn fill_from_signal<S, T>(signal: &mut S, dst: &mut [T]) -> usize
+where
+    S: Signal,
+    <S::Frame as Frame>::Sample: ToSample<f32>,
+    T: Sample,
+{
+    let channels = S::Frame::CHANNELS;
+    if channels == 0 {
+        return 0;
+    }
+    let mut frames = 0;
+    for dst_frame in dst.chunks_exact_mut(channels) {
+        let frame = signal.next();
+        for (dst_sample, sample) in dst_frame.iter_mut().zip(frame.channels()) {
+            *dst_sample = T::from(&sample.to_sample_());
+        }
+        frames += 1;
+    }
+    dst[frames * channels..].fill(T::from(&0.0f32));
+    frames
+}
+
+/// Turns a signal into a data callback for
+/// [`build_output_stream`](crate::traits::DeviceTrait::build_output_stream).
+///
+/// The stream must be configured with as many channels as the frames of the signal.
+pub fn signal_callback<S, T>(mut signal: S) -> impl FnMut(&mut [T], &OutputCallbackInfo)
+where
+    S: Signal,
+    <S::Frame as Frame>::Sample: ToSample<f32>,
+    T: Sample,
+{
+    move |data, _| {
+        fill_from_signal(&mut signal, data);
+    }
+}
+
+/// Reads an interleaved stream buffer as frames of a fixed channel count.
+///
+/// Trailing samples that do not make up a whole frame are skipped.
+pub fn frames<F>(src: &[F::Sample]) -> impl Iterator<Item = F> + '_
+where
+    F: Frame,
+{
+    src.chunks_exact(F::CHANNELS)
+        .filter_map(|frame| F::from_samples(&mut frame.iter().copied()))
+}
+
+/// Writes frames of a fixed channel count into an interleaved stream buffer.
+///
+/// Returns the number of frames written, which is limited by the length of `dst`.
+pub fn write_frames<F, I>(frames: I, dst: &mut [F::Sample]) -> usize
+where
+    F: Frame,
+    I: IntoIterator<Item = F>,
+{
+    let mut written = 0;
+    for (dst_frame, frame) in dst.chunks_exact_mut(F::CHANNELS).zip(frames) {
+        for (dst_sample, sample) in dst_frame.iter_mut().zip(frame.channels()) {
+            *dst_sample = sample;
+        }
+        written += 1;
+    }
+    written
+}
+
+#[cfg(test)]
+mod test {
+    use super::{fill_from_signal, frames, write_frames};
+
+    #[test]
+    fn signals_and_frames() {
+        let mut signal = dasp_signal::from_iter([[0.5f32, -0.5], [1.0, 0.0]].iter().copied());
+        let mut data = [1i16; 5];
+        assert_eq!(fill_from_signal(&mut signal, &mut data), 2);
+        assert_eq!(data, [16383, -16384, 32767, 0, 0]);
+
+        let read: Vec<[i16; 2]> = frames(&data).collect();
+        assert_eq!(read, [[16383, -16384], [32767, 0]]);
+
+        let mut data = [0i16; 3];
+        assert_eq!(write_frames(read, &mut data), 1);
+        assert_eq!(data, [16383, -16384, 0]);
+    }
+}
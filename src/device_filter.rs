@@ -0,0 +1,111 @@
+//! Enumerating only the devices an application can use.
+//!
+//! Backs `HostTrait::devices_filtered`.
+
+use crate::traits::DeviceTrait;
+use crate::{ChannelCount, DeviceDirection, SampleFormat, SampleRate, SupportedStreamConfigRange};
+
+/// What the devices returned by `HostTrait::devices_filtered` have to support.
+///
+/// The default filter lets every device through. Each field narrows the devices down further.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DeviceFilter {
+    /// Only devices supporting streams in this direction.
+    pub direction: Option<DeviceDirection>,
+    /// Only devices supporting at least this many channels.
+    pub min_channels: Option<ChannelCount>,
+    /// Only devices supporting this sample rate.
+    pub sample_rate: Option<SampleRate>,
+    /// Only devices supporting this sample format.
+    pub sample_format: Option<SampleFormat>,
+    /// Only devices whose output input streams can record, see `DeviceCapabilities::loopback`.
+    ///
+    /// A `direction` of `Input` is then met by the output configs, which loopback streams record
+    /// in.
+    pub loopback: bool,
+    /// Skips the virtual devices of hosts that tell them apart from the hardware, such as the
+    /// plugins of ALSA. Has no effect on other hosts.
+    pub physical_only: bool,
+}
+
+impl DeviceFilter {
+    /// Whether `device` passes the filter.
+    ///
+    /// The device is only probed for what the filter asks for. The channel count, sample rate
+    /// and sample format have to be supported together by one of its configs in `direction`, or
+    /// in either direction if there is none.
+    pub fn matches<D: DeviceTrait + ?Sized>(&self, device: &D) -> bool {
+        if self.loopback && !device.capabilities().is_ok_and(|c| c.loopback) {
+            return false;
+        }
+        let probes_configs = self.direction.is_some()
+            || self.min_channels.is_some()
+            || self.sample_rate.is_some()
+            || self.sample_format.is_some();
+        if !probes_configs {
+            return true;
+        }
+        // Loopback streams record in the formats of the output.
+        let (input, output) = match self.direction {
+            Some(DeviceDirection::Input) if self.loopback => (false, true),
+            Some(DeviceDirection::Input) => (true, false),
+            Some(DeviceDirection::Output) => (false, true),
+            None => (true, true),
+        };
+        let input = input
+            && device
+                .supported_input_configs()
+                .is_ok_and(|mut configs| configs.any(|c| self.matches_config(&c)));
+        input
+            || output
+                && device
+                    .supported_output_configs()
+                    .is_ok_and(|mut configs| configs.any(|c| self.matches_config(&c)))
+    }
+
+    fn matches_config(&self, config: &SupportedStreamConfigRange) -> bool {
+        self.min_channels
+            .is_none_or(|channels| config.channels >= channels)
+            && self.sample_rate.is_none_or(|rate| {
+                (config.min_sample_rate..=config.max_sample_rate).contains(&rate)
+            })
+            && self
+                .sample_format
+                .is_none_or(|format| config.sample_format == format)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::DeviceFilter;
+    use crate::{SampleFormat, SampleRate, SupportedBufferSize, SupportedStreamConfigRange};
+
+    #[test]
+    fn configs_match_all_criteria() {
+        let config = SupportedStreamConfigRange::new(
+            2,
+            SampleRate(44100),
+            SampleRate(48000),
+            SupportedBufferSize::Unknown,
+            SampleFormat::I16,
+        );
+        let filter = DeviceFilter {
+            min_channels: Some(2),
+            sample_rate: Some(SampleRate(48000)),
+            sample_format: Some(SampleFormat::I16),
+            ..Default::default()
+        };
+        assert!(filter.matches_config(&config));
+        assert!(DeviceFilter::default().matches_config(&config));
+        let surround = DeviceFilter {
+            min_channels: Some(6),
+            ..filter.clone()
+        };
+        assert!(!surround.matches_config(&config));
+        let float = DeviceFilter {
+            sample_format: Some(SampleFormat::F32),
+            ..filter
+        };
+        assert!(!float.matches_config(&config));
+    }
+}
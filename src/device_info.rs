@@ -0,0 +1,255 @@
+use crate::traits::DeviceTrait;
+use crate::{
+    ChannelLayout, DeviceNameError, HostId, SampleRate, SupportedBufferSize,
+    SupportedStreamConfigRange, SupportedStreamConfigsError,
+};
+use std::time::Duration;
+
+/// Describes a device in more detail than its name, as returned by `DeviceTrait::info`.
+///
+/// Hosts fill in what they know about their devices, leaving the rest `None`, `false`,
+/// `FormFactor::Unknown` or `Transport::Unknown`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DeviceInfo {
+    /// The name to show the user, the same as `DeviceTrait::name`.
+    pub name: String,
+    /// Identifies the device across runs of the application, for saving it in settings. The name
+    /// where the host has no such id.
+    pub id: String,
+    /// Whether the device captures audio.
+    pub input: bool,
+    /// Whether the device plays audio.
+    pub output: bool,
+    /// Whether the device is the default input device of its host.
+    pub default_input: bool,
+    /// Whether the device is the default output device of its host.
+    pub default_output: bool,
+    /// The manufacturer of the device.
+    pub manufacturer: Option<String>,
+    /// The driver of the device.
+    pub driver: Option<String>,
+    /// The host of the device, filled in by the platform `Device`.
+    pub host: Option<HostId>,
+    /// What kind of device it is.
+    pub form_factor: FormFactor,
+    /// How the device is connected to the computer.
+    pub transport: Transport,
+    /// The profile a Bluetooth device is running in, where the host tells.
+    pub bluetooth_profile: Option<BluetoothProfile>,
+    /// The codec of a Bluetooth device, such as `SBC`, `AAC` or `aptX`, where the host tells.
+    pub bluetooth_codec: Option<String>,
+}
+
+/// The profile a Bluetooth device is running in, see `DeviceInfo::bluetooth_profile`.
+///
+/// Headsets switch to a hands-free profile when an application records from their microphone,
+/// which drops the sample rate of their playback to telephone quality as well.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BluetoothProfile {
+    /// A2DP: playback at high quality, without a microphone.
+    A2dp,
+    /// HFP or HSP: playback and recording at 8 or 16 kHz, for calls.
+    HandsFree,
+    /// LE Audio, which carries both at high quality.
+    LeAudio,
+}
+
+impl BluetoothProfile {
+    /// The profile of a classic Bluetooth device running at `sample_rate`, for hosts that only
+    /// tell the rate: hands-free profiles run at 16 kHz at most, A2DP above.
+    #[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+    pub(crate) fn from_sample_rate(sample_rate: SampleRate) -> Self {
+        if sample_rate.0 <= 16_000 {
+            BluetoothProfile::HandsFree
+        } else {
+            BluetoothProfile::A2dp
+        }
+    }
+}
+
+/// How the device a `DeviceInfo` describes is connected to the computer.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Transport {
+    /// Built into the computer, such as its sound card or speakers.
+    BuiltIn,
+    /// Connected through USB.
+    Usb,
+    /// The audio of an HDMI display.
+    Hdmi,
+    /// The audio of a DisplayPort display.
+    DisplayPort,
+    /// Connected through Bluetooth, which adds latency.
+    Bluetooth,
+    /// A device of software, such as a sound server or a loopback device.
+    Virtual,
+    /// The host does not tell.
+    #[default]
+    Unknown,
+}
+
+/// What kind of device a `DeviceInfo` describes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FormFactor {
+    /// Loudspeakers, built into the computer or connected to it.
+    Speakers,
+    /// Headphones without a microphone.
+    Headphones,
+    /// Headphones with a microphone.
+    Headset,
+    /// A microphone.
+    Microphone,
+    /// An analog line input or output.
+    LineLevel,
+    /// A digital connection such as S/PDIF or HDMI.
+    Digital,
+    /// The host does not tell.
+    #[default]
+    Unknown,
+}
+
+/// What a device can do, as returned by `DeviceTrait::capabilities`, for choosing how to open it.
+///
+/// Hosts fill in what they know, leaving the rest `false`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DeviceCapabilities {
+    /// Whether streams can open the device with `ShareMode::Exclusive`.
+    pub exclusive_mode: bool,
+    /// Whether input streams can record what the device plays.
+    pub loopback: bool,
+    /// The duration of the smallest buffer the device supports, at the highest sample rate it
+    /// supports it at, or `None` if the host does not report buffer sizes.
+    pub min_latency: Option<Duration>,
+    /// The channel layouts of the supported configs, with `ChannelLayout::default_for` standing
+    /// in for the ones the host does not report.
+    pub channel_layouts: Vec<ChannelLayout>,
+    /// Whether the device has a volume control of its own.
+    pub hardware_volume: bool,
+    /// Whether the device is the default input device of its host.
+    pub default_input: bool,
+    /// Whether the device is the default output device of its host.
+    pub default_output: bool,
+}
+
+/// `DeviceTrait::info` for hosts that only know the name of a device.
+pub(crate) fn from_name<D: DeviceTrait + ?Sized>(
+    device: &D,
+) -> Result<DeviceInfo, DeviceNameError> {
+    let name = device.name()?;
+    Ok(DeviceInfo {
+        id: name.clone(),
+        name,
+        input: device
+            .supported_input_configs()
+            .is_ok_and(|mut configs| configs.next().is_some()),
+        output: device
+            .supported_output_configs()
+            .is_ok_and(|mut configs| configs.next().is_some()),
+        ..Default::default()
+    })
+}
+
+/// `DeviceTrait::capabilities` for what the supported configs and the info of a device tell.
+pub(crate) fn capabilities_from_configs<D: DeviceTrait + ?Sized>(
+    device: &D,
+) -> Result<DeviceCapabilities, SupportedStreamConfigsError> {
+    let configs: Vec<_> = match (
+        device.supported_input_configs(),
+        device.supported_output_configs(),
+    ) {
+        (Err(err), Err(_)) => return Err(err),
+        (input, output) => input
+            .into_iter()
+            .flatten()
+            .chain(output.into_iter().flatten())
+            .collect(),
+    };
+    let info = device.info().unwrap_or_default();
+    Ok(DeviceCapabilities {
+        min_latency: min_latency(&configs),
+        channel_layouts: channel_layouts(&configs),
+        default_input: info.default_input,
+        default_output: info.default_output,
+        ..Default::default()
+    })
+}
+
+fn min_latency(configs: &[SupportedStreamConfigRange]) -> Option<Duration> {
+    configs
+        .iter()
+        .filter_map(|config| match config.buffer_size {
+            // Hosts without a lower limit report a minimum of zero.
+            SupportedBufferSize::Range { min, .. } if min > 0 => {
+                let nanos = min as u64 * 1_000_000_000 / config.max_sample_rate.0.max(1) as u64;
+                Some(Duration::from_nanos(nanos))
+            }
+            _ => None,
+        })
+        .min()
+}
+
+fn channel_layouts(configs: &[SupportedStreamConfigRange]) -> Vec<ChannelLayout> {
+    let mut layouts = Vec::new();
+    for config in configs {
+        let layout = config
+            .channel_layout
+            .clone()
+            .unwrap_or_else(|| ChannelLayout::default_for(config.channels));
+        if !layouts.contains(&layout) {
+            layouts.push(layout);
+        }
+    }
+    layouts
+}
+
+#[cfg(test)]
+mod test {
+    use super::{channel_layouts, min_latency, BluetoothProfile};
+    use crate::{
+        BufferLayout, ChannelLayout, SampleFormat, SampleRate, SupportedBufferSize,
+        SupportedStreamConfigRange,
+    };
+    use std::time::Duration;
+
+    fn config(
+        channels: u16,
+        rate: u32,
+        buffer_size: SupportedBufferSize,
+    ) -> SupportedStreamConfigRange {
+        SupportedStreamConfigRange {
+            channels,
+            min_sample_rate: SampleRate(8000),
+            max_sample_rate: SampleRate(rate),
+            buffer_size,
+            sample_format: SampleFormat::F32,
+            layout: BufferLayout::Interleaved,
+            channel_layout: None,
+        }
+    }
+
+    #[test]
+    fn bluetooth_profile_from_sample_rate() {
+        let profile = |rate| BluetoothProfile::from_sample_rate(SampleRate(rate));
+        assert_eq!(profile(8000), BluetoothProfile::HandsFree);
+        assert_eq!(profile(16000), BluetoothProfile::HandsFree);
+        assert_eq!(profile(44100), BluetoothProfile::A2dp);
+    }
+
+    #[test]
+    fn capabilities_summarize_configs() {
+        let configs = [
+            config(2, 48000, SupportedBufferSize::Range { min: 96, max: 4096 }),
+            config(2, 44100, SupportedBufferSize::Range { min: 0, max: 4096 }),
+            config(1, 96000, SupportedBufferSize::Range { min: 96, max: 4096 }),
+            config(6, 48000, SupportedBufferSize::Unknown),
+        ];
+        assert_eq!(min_latency(&configs), Some(Duration::from_millis(1)));
+        assert_eq!(min_latency(&configs[3..]), None);
+        assert_eq!(
+            channel_layouts(&configs),
+            [2, 1, 6].map(ChannelLayout::default_for)
+        );
+    }
+}
@@ -0,0 +1,174 @@
+//! Watching the properties of a device.
+//!
+//! Backs `DevicePropertyWatcher`. As with the default devices, the hosts are not asked to report
+//! changes; the default configs of the device are polled and compared instead.
+
+use crate::follow::POLL_INTERVAL;
+use crate::traits::{DeviceTrait, HostTrait};
+use crate::{
+    host_from_id, BackendSpecificError, Device, DeviceDirection, HostId, SupportedStreamConfig,
+};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::thread::{self, JoinHandle};
+
+/// A change of the sample rate, sample format or channel count of a device, as reported by
+/// `DevicePropertyWatcher`.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct DevicePropertyChange {
+    /// Whether the input or output side of the device changed.
+    pub direction: DeviceDirection,
+    /// The default config of the device from now on, or `None` if it has none in this direction
+    /// anymore, such as when it was unplugged.
+    pub config: Option<SupportedStreamConfig>,
+}
+
+/// Reports changes of the default input and output configs of a device, such as when the user
+/// switches its sample rate in the system settings, or another application switches its format.
+///
+/// Streams on the device may stop working, or be resampled by the system, after such a change.
+/// The configs are polled twice a second on a thread of the watcher, which is stopped when the
+/// watcher is dropped.
+#[derive(Debug)]
+pub struct DevicePropertyWatcher {
+    stop: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl DevicePropertyWatcher {
+    /// Starts watching the device of `host` named `device`, calling `callback` with every change.
+    pub fn spawn<F>(
+        host: HostId,
+        device: &str,
+        mut callback: F,
+    ) -> Result<Self, BackendSpecificError>
+    where
+        F: FnMut(DevicePropertyChange) + Send + 'static,
+    {
+        let name = device.to_owned();
+        let (stop, stopped) = mpsc::channel();
+        let (started_tx, started_rx) = mpsc::channel();
+        let thread = thread::Builder::new()
+            .name("cpal_device_properties".to_owned())
+            .spawn(move || {
+                // Hosts and devices are not `Send` everywhere, so the thread looks up its own.
+                let device = match find_device(host, &name) {
+                    Ok(device) => device,
+                    Err(err) => {
+                        let _ = started_tx.send(Err(err));
+                        return;
+                    }
+                };
+                let _ = started_tx.send(Ok(()));
+                let mut configs = DefaultConfigs::of(&device);
+                while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(POLL_INTERVAL) {
+                    let current = DefaultConfigs::of(&device);
+                    configs.changes(&current).for_each(&mut callback);
+                    configs = current;
+                }
+            })
+            .map_err(|err| BackendSpecificError {
+                description: format!("failed to spawn the watcher thread: {}", err),
+            })?;
+        match started_rx.recv() {
+            Ok(Ok(())) => Ok(DevicePropertyWatcher {
+                stop: Some(stop),
+                thread: Some(thread),
+            }),
+            Ok(Err(err)) => Err(err),
+            Err(_) => Err(BackendSpecificError {
+                description: "the watcher thread panicked".to_owned(),
+            }),
+        }
+    }
+}
+
+impl Drop for DevicePropertyWatcher {
+    fn drop(&mut self) {
+        // Disconnecting the channel wakes the thread up.
+        self.stop.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn find_device(host: HostId, name: &str) -> Result<Device, BackendSpecificError> {
+    let host = host_from_id(host).map_err(|err| BackendSpecificError {
+        description: err.to_string(),
+    })?;
+    let mut devices = host.devices().map_err(|err| BackendSpecificError {
+        description: err.to_string(),
+    })?;
+    devices
+        .find(|device| device.name().is_ok_and(|n| n == name))
+        .ok_or_else(|| BackendSpecificError {
+            description: format!("no device named {:?}", name),
+        })
+}
+
+// The default configs of a device at one time.
+#[derive(Debug, Default, PartialEq)]
+struct DefaultConfigs {
+    input: Option<SupportedStreamConfig>,
+    output: Option<SupportedStreamConfig>,
+}
+
+impl DefaultConfigs {
+    fn of(device: &Device) -> Self {
+        DefaultConfigs {
+            input: device.default_input_config().ok(),
+            output: device.default_output_config().ok(),
+        }
+    }
+
+    // The changes from these configs to `current`.
+    fn changes(&self, current: &Self) -> impl Iterator<Item = DevicePropertyChange> {
+        let input = (self.input != current.input).then(|| DevicePropertyChange {
+            direction: DeviceDirection::Input,
+            config: current.input.clone(),
+        });
+        let output = (self.output != current.output).then(|| DevicePropertyChange {
+            direction: DeviceDirection::Output,
+            config: current.output.clone(),
+        });
+        input.into_iter().chain(output)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{DefaultConfigs, DevicePropertyChange};
+    use crate::{
+        DeviceDirection, SampleFormat, SampleRate, SupportedBufferSize, SupportedStreamConfig,
+    };
+
+    #[test]
+    fn reports_changed_configs() {
+        let config = |rate| {
+            SupportedStreamConfig::new(
+                2,
+                SampleRate(rate),
+                SupportedBufferSize::Unknown,
+                SampleFormat::F32,
+            )
+        };
+        let before = DefaultConfigs {
+            input: None,
+            output: Some(config(44100)),
+        };
+        let after = DefaultConfigs {
+            input: None,
+            output: Some(config(48000)),
+        };
+        assert_eq!(before.changes(&before).count(), 0);
+        let changes: Vec<_> = before.changes(&after).collect();
+        assert_eq!(
+            changes,
+            [DevicePropertyChange {
+                direction: DeviceDirection::Output,
+                config: Some(config(48000)),
+            }]
+        );
+        assert_eq!(after.changes(&DefaultConfigs::default()).count(), 1);
+    }
+}
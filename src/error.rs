@@ -1,3 +1,5 @@
+use crate::{BluetoothProfile, FrameCount, HostId, SampleRate, SupportedStreamConfig};
+use std::time::Duration;
 use thiserror::Error;
 
 /// The requested host, although supported on this platform, is unavailable.
@@ -5,6 +7,52 @@ use thiserror::Error;
 #[error("the requested host is unavailable")]
 pub struct HostUnavailable;
 
+/// Why `Host::preferred` skipped a host.
+#[derive(Debug, Error)]
+pub enum HostSkipReason {
+    /// The host is not available on this system, see `available_hosts`.
+    #[error("the host is not available on this system")]
+    NotAvailable,
+    /// The host could not be initialised.
+    #[error("{err}")]
+    Unavailable {
+        #[from]
+        err: HostUnavailable,
+    },
+    /// The host failed to enumerate its devices.
+    #[error("{err}")]
+    Devices {
+        #[from]
+        err: DevicesError,
+    },
+    /// The host has no devices, such as JACK without a running server.
+    #[error("the host has no devices")]
+    NoDevices,
+}
+
+/// None of the hosts passed to `Host::preferred` is usable.
+#[derive(Debug, Error)]
+#[error("none of the preferred hosts is usable{}", describe_skipped(.skipped))]
+pub struct NoPreferredHost {
+    /// The hosts that were tried, in order, with why each was skipped.
+    pub skipped: Vec<(HostId, HostSkipReason)>,
+}
+
+/// A string passed to `HostId::from_str` names none of the hosts of this platform.
+#[derive(Clone, Debug, Error)]
+#[error("no host named {name:?} on this platform")]
+pub struct HostIdParseError {
+    /// The string that failed to parse.
+    pub name: String,
+}
+
+fn describe_skipped(skipped: &[(HostId, HostSkipReason)]) -> String {
+    skipped
+        .iter()
+        .map(|(id, reason)| format!("; {}: {}", id.name(), reason))
+        .collect()
+}
+
 /// Some error has occurred that is specific to the backend from which it was produced.
 ///
 /// This error is often used as a catch-all in cases where:
@@ -102,6 +150,23 @@ pub enum BuildStreamError {
     /// Occurs if adding a new Stream ID would cause an integer overflow.
     #[error("Adding a new stream ID would cause an overflow")]
     StreamIdOverflow,
+    /// The stream was requested with `StreamOptions::bit_perfect`, but its samples would be
+    /// altered on their way between the callback and the device.
+    #[error("The stream cannot be bit-perfect: {reason}")]
+    NotBitPerfect {
+        /// Everything that would alter the samples, separated by semicolons.
+        reason: String,
+    },
+    /// The host or device does not support opening streams with the requested `ShareMode`.
+    #[error("The requested share mode is not supported by the device.")]
+    ShareModeNotSupported,
+    /// The application is not allowed to record, see `HostTrait::input_permission`.
+    #[error("The application is not allowed to record from input devices.")]
+    PermissionDenied,
+    /// The device is held exclusively by another application or stream, such as one opened with
+    /// `ShareMode::Exclusive`. Opening it may succeed again once that one is closed.
+    #[error("The requested device is in use by another application.")]
+    DeviceBusy,
     /// See the `BackendSpecificError` docs for more information about this error variant.
     #[error("{err}")]
     BackendSpecific {
@@ -146,13 +211,182 @@ pub enum PauseStreamError {
     },
 }
 
+/// Errors that might occur when changing the configuration of a running stream, such as with
+/// `set_buffer_size` or `set_sample_rate`.
+#[derive(Debug, Error)]
+pub enum ReconfigureStreamError {
+    /// The host cannot change this part of the configuration while the stream exists.
+    #[error("The host does not support changing this configuration of a running stream.")]
+    NotSupported,
+    /// The requested value is not supported by the device. The stream keeps its previous
+    /// configuration.
+    #[error("The requested stream configuration is not supported by the device.")]
+    StreamConfigNotSupported,
+    /// The device associated with the stream is no longer available.
+    #[error("the device associated with the stream is no longer available")]
+    DeviceNotAvailable,
+    /// See the `BackendSpecificError` docs for more information about this error variant.
+    #[error("{err}")]
+    BackendSpecific {
+        #[from]
+        err: BackendSpecificError,
+    },
+}
+
+/// Errors that might occur when enabling or disabling direct monitoring with
+/// `DeviceTrait::set_direct_monitoring`.
+#[derive(Debug, Error)]
+pub enum DirectMonitoringError {
+    /// The host or device does not offer direct monitoring.
+    #[error("The device does not support direct monitoring.")]
+    NotSupported,
+    /// The device no longer exists. This can happen if the device is disconnected while the
+    /// program is running.
+    #[error("The requested device is no longer available. For example, it has been unplugged.")]
+    DeviceNotAvailable,
+    /// See the `BackendSpecificError` docs for more information about this error variant.
+    #[error("{err}")]
+    BackendSpecific {
+        #[from]
+        err: BackendSpecificError,
+    },
+}
+
+/// Errors that might occur when switching the sample rate of a device with
+/// `DeviceTrait::set_default_sample_rate`.
+#[derive(Debug, Error)]
+pub enum SetSampleRateError {
+    /// The host cannot switch the sample rate of its devices.
+    #[error("The host does not support switching the sample rate of a device.")]
+    NotSupported,
+    /// The device does not support the requested sample rate.
+    #[error("The requested sample rate is not supported by the device.")]
+    SampleRateNotSupported,
+    /// The device no longer exists. This can happen if the device is disconnected while the
+    /// program is running.
+    #[error("The requested device is no longer available. For example, it has been unplugged.")]
+    DeviceNotAvailable,
+    /// See the `BackendSpecificError` docs for more information about this error variant.
+    #[error("{err}")]
+    BackendSpecific {
+        #[from]
+        err: BackendSpecificError,
+    },
+}
+
+/// How to get a stream running again after a `StreamError`, as returned by
+/// `StreamError::recovery`.
+///
+/// These are hints based on the kind of error; the host may still fail in other ways.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum StreamRecovery {
+    /// The stream recovers on its own, nothing needs to be done.
+    None,
+    /// Building the same stream again on the same device is likely to succeed.
+    Rebuild,
+    /// Building the same stream again is unlikely to succeed, but a stream with another config or
+    /// share mode on the same device is likely to.
+    ChangeConfig,
+    /// The device is gone. Build the stream on another device, or wait for it to come back.
+    ChangeDevice,
+    /// The error is backend-specific, whether rebuilding helps is unknown.
+    Unknown,
+}
+
 /// Errors that might occur while a stream is running.
+///
+/// `recovery` tells how a supervisor can get the stream running again.
 #[derive(Debug, Error)]
 pub enum StreamError {
     /// The device no longer exists. This can happen if the device is disconnected while the
     /// program is running.
     #[error("The requested device is no longer available. For example, it has been unplugged.")]
     DeviceNotAvailable,
+    /// The device no longer supports the configuration of the stream, as when its format was
+    /// changed in the system settings or the JACK server changed its sample rate. The stream
+    /// stopped.
+    #[error("The device no longer supports the stream configuration.")]
+    StreamConfigNotSupported,
+    /// Another application took exclusive access to the device, or exclusive access of this
+    /// stream was revoked. The stream stopped.
+    ///
+    /// Reported by WASAPI.
+    #[error("Exclusive access to the device was lost.")]
+    ExclusiveAccessLost,
+    /// The audio service, server or driver was shut down or restarted. The stream stopped.
+    ///
+    /// Reported by WASAPI and JACK.
+    #[error("The audio service or driver was restarted.")]
+    DriverRestarted,
+    /// The output device ran out of frames to play because the stream did not provide them in
+    /// time, causing an audible gap. The stream recovers on its own.
+    ///
+    /// Reported by ALSA, WASAPI, AAudio and, when the device misses an IO cycle, CoreAudio on
+    /// macOS.
+    #[error("The output stream underran, the device played a gap.")]
+    Underrun {
+        /// The number of frames that were not played, if the host reports it.
+        frames_lost: Option<FrameCount>,
+    },
+    /// The input device captured frames faster than the stream read them and dropped some. The
+    /// stream recovers on its own.
+    ///
+    /// Reported by ALSA, WASAPI, AAudio and, when the device misses an IO cycle, CoreAudio on
+    /// macOS.
+    #[error("The input stream overran, captured frames were dropped.")]
+    Overrun {
+        /// The number of frames that were dropped, if the host reports it.
+        frames_lost: Option<FrameCount>,
+    },
+    /// The sample rate of the stream was changed with `StreamTrait::set_sample_rate`. Reported
+    /// before the first data callback at the new rate.
+    #[error("The sample rate of the stream changed to {} Hz.", .sample_rate.0)]
+    SampleRateChanged {
+        /// The sample rate of the data passed to the callback from now on.
+        sample_rate: SampleRate,
+    },
+    /// The format of the device was changed by the system or another application while the
+    /// stream was running, as when its sample rate is changed in the system settings.
+    ///
+    /// Pass `config` to `Stream::rebuild_with` to carry on at the new format with the same
+    /// callbacks. CoreAudio keeps the stream running meanwhile, converting to the stream's rate;
+    /// JACK stops it.
+    #[error("The device format changed to {} Hz.", .config.sample_rate().0)]
+    DeviceFormatChanged {
+        /// The configuration of the stream at the new format of the device.
+        config: SupportedStreamConfig,
+    },
+    /// A Bluetooth device switched profiles, such as to a hands-free profile when another
+    /// application started recording from it. Its sample rate and quality change with it.
+    ///
+    /// Only reported by CoreAudio on macOS, which tells the profile by the new sample rate.
+    /// WASAPI has an endpoint per profile, so streams on the old one fail with
+    /// `DeviceNotAvailable` instead.
+    #[error("The Bluetooth device switched to the {profile:?} profile.")]
+    BluetoothProfileChanged {
+        /// The profile the device runs in from now on.
+        profile: BluetoothProfile,
+    },
+    /// The data callback panicked. The panic was caught rather than unwinding into the host. The
+    /// callback is not called again, and output streams play silence from then on.
+    ///
+    /// Reported by the streams of the platform `Device`, not those built from a host's device
+    /// type directly.
+    #[error(
+        "The data callback panicked: {}",
+        .message.as_deref().unwrap_or("no message")
+    )]
+    CallbackPanicked {
+        /// The message the callback panicked with, if it was a string.
+        message: Option<String>,
+    },
+    /// The data callback stopped being called, as detected by `StreamOptions::watchdog`. The
+    /// stream may resume on its own, otherwise it has to be built again.
+    #[error("The data callback has not been called for {elapsed:?}.")]
+    Stalled {
+        /// The time since the data callback was last called.
+        elapsed: Duration,
+    },
     /// See the `BackendSpecificError` docs for more information about this error variant.
     #[error("{err}")]
     BackendSpecific {
@@ -160,3 +394,24 @@ pub enum StreamError {
         err: BackendSpecificError,
     },
 }
+
+impl StreamError {
+    /// How the stream can be got running again after this error.
+    pub fn recovery(&self) -> StreamRecovery {
+        match self {
+            StreamError::Underrun { .. }
+            | StreamError::Overrun { .. }
+            | StreamError::SampleRateChanged { .. } => StreamRecovery::None,
+            // A stalled stream may resume on its own, rebuilding is the fallback.
+            StreamError::Stalled { .. }
+            | StreamError::DriverRestarted
+            | StreamError::CallbackPanicked { .. } => StreamRecovery::Rebuild,
+            StreamError::StreamConfigNotSupported
+            | StreamError::ExclusiveAccessLost
+            | StreamError::DeviceFormatChanged { .. }
+            | StreamError::BluetoothProfileChanged { .. } => StreamRecovery::ChangeConfig,
+            StreamError::DeviceNotAvailable => StreamRecovery::ChangeDevice,
+            StreamError::BackendSpecific { .. } => StreamRecovery::Unknown,
+        }
+    }
+}
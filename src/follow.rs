@@ -0,0 +1,282 @@
+//! Following the default devices of a host.
+//!
+//! Backs `DefaultDeviceWatcher`, `DefaultDevicePoller` and `StreamHandle::spawn_following_default`.
+//! The hosts do not report when their default devices change, so the defaults are polled and
+//! compared by name.
+
+use crate::handle::Command;
+use crate::traits::{DeviceTrait, HostTrait, StreamTrait};
+use crate::{
+    host_from_id, BackendSpecificError, BuildStreamError, Device, Host, HostId, HostUnavailable,
+    StreamControls, StreamState,
+};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// How often the default devices are polled.
+pub(crate) const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Whether a device captures or plays audio.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum DeviceDirection {
+    /// The device captures audio.
+    Input,
+    /// The device plays audio.
+    Output,
+}
+
+/// A change of a default device of a host, as reported by `DefaultDeviceWatcher`.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct DefaultDeviceChange {
+    /// Whether the default input or output device changed.
+    pub direction: DeviceDirection,
+    /// The name of the new default device, or `None` if there is none.
+    pub name: Option<String>,
+}
+
+/// Reports changes of the default input and output devices of a host, such as when headphones are
+/// plugged in or the user picks another device in the system settings.
+///
+/// The defaults are polled twice a second on a thread of the watcher, which is stopped when the
+/// watcher is dropped. Applications that keep their threads to themselves can poll a
+/// `DefaultDevicePoller` instead.
+#[derive(Debug)]
+pub struct DefaultDeviceWatcher {
+    stop: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl DefaultDeviceWatcher {
+    /// Starts watching the default devices of `host`, calling `callback` with every change.
+    pub fn spawn<F>(host: HostId, mut callback: F) -> Result<Self, BackendSpecificError>
+    where
+        F: FnMut(DefaultDeviceChange) + Send + 'static,
+    {
+        let (stop, stopped) = mpsc::channel();
+        let (started_tx, started_rx) = mpsc::channel();
+        let thread = thread::Builder::new()
+            .name("cpal_default_devices".to_owned())
+            .spawn(move || {
+                // Hosts are not `Send` everywhere, so the thread opens its own.
+                let host = match host_from_id(host) {
+                    Ok(host) => host,
+                    Err(err) => {
+                        let _ = started_tx.send(Err(err));
+                        return;
+                    }
+                };
+                let _ = started_tx.send(Ok(()));
+                let mut poller = DefaultDevicePoller::with_host(host);
+                while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(POLL_INTERVAL) {
+                    poller.poll().into_iter().for_each(&mut callback);
+                }
+            })
+            .map_err(|err| BackendSpecificError {
+                description: format!("failed to spawn the watcher thread: {}", err),
+            })?;
+        match started_rx.recv() {
+            Ok(Ok(())) => Ok(DefaultDeviceWatcher {
+                stop: Some(stop),
+                thread: Some(thread),
+            }),
+            Ok(Err(err)) => Err(BackendSpecificError {
+                description: err.to_string(),
+            }),
+            Err(_) => Err(BackendSpecificError {
+                description: "the watcher thread panicked".to_owned(),
+            }),
+        }
+    }
+}
+
+impl Drop for DefaultDeviceWatcher {
+    fn drop(&mut self) {
+        // Disconnecting the channel wakes the thread up.
+        self.stop.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Reports changes of the default input and output devices of a host whenever it is polled.
+///
+/// This is `DefaultDeviceWatcher` without a thread of its own, for applications that poll from a
+/// thread or executor they control, such as once per frame of a game. Hosts are not `Send` on
+/// every platform, so the poller has to be created on the thread polling it.
+pub struct DefaultDevicePoller {
+    host: Host,
+    defaults: DefaultNames,
+}
+
+impl DefaultDevicePoller {
+    /// Starts watching the default devices of `host`, as they are now.
+    pub fn new(host: HostId) -> Result<Self, HostUnavailable> {
+        Ok(Self::with_host(host_from_id(host)?))
+    }
+
+    fn with_host(host: Host) -> Self {
+        let defaults = DefaultNames::of(&host);
+        DefaultDevicePoller { host, defaults }
+    }
+
+    /// The changes of the default devices since the last poll, or since the poller was created.
+    ///
+    /// This queries the host for its defaults, which may block for a moment, so it is best not
+    /// called from an audio callback.
+    pub fn poll(&mut self) -> Vec<DefaultDeviceChange> {
+        let current = DefaultNames::of(&self.host);
+        let changes = self.defaults.changes(&current).collect();
+        self.defaults = current;
+        changes
+    }
+}
+
+impl std::fmt::Debug for DefaultDevicePoller {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("DefaultDevicePoller")
+            .field("host", &self.host.id())
+            .field("defaults", &self.defaults)
+            .finish()
+    }
+}
+
+// The names of the default devices of a host at one time.
+#[derive(Debug, Default, PartialEq)]
+struct DefaultNames {
+    input: Option<String>,
+    output: Option<String>,
+}
+
+impl DefaultNames {
+    fn of(host: &Host) -> Self {
+        DefaultNames {
+            input: default_device(host, DeviceDirection::Input).and_then(|d| d.name().ok()),
+            output: default_device(host, DeviceDirection::Output).and_then(|d| d.name().ok()),
+        }
+    }
+
+    // The changes from these defaults to `current`.
+    fn changes(&self, current: &Self) -> impl Iterator<Item = DefaultDeviceChange> {
+        let input = (self.input != current.input).then(|| DefaultDeviceChange {
+            direction: DeviceDirection::Input,
+            name: current.input.clone(),
+        });
+        let output = (self.output != current.output).then(|| DefaultDeviceChange {
+            direction: DeviceDirection::Output,
+            name: current.output.clone(),
+        });
+        input.into_iter().chain(output)
+    }
+}
+
+fn default_device(host: &Host, direction: DeviceDirection) -> Option<Device> {
+    match direction {
+        DeviceDirection::Input => host.default_input_device(),
+        DeviceDirection::Output => host.default_output_device(),
+    }
+}
+
+/// The body of the thread of `StreamHandle::spawn_following_default`: builds the stream on the
+/// default device, carries out the commands, and builds it again whenever the default changes.
+pub(crate) fn run<S, F>(
+    host: HostId,
+    direction: DeviceDirection,
+    mut build: F,
+    controls: StreamControls,
+    commands: Receiver<Command>,
+    built: Sender<Result<(), BuildStreamError>>,
+) where
+    S: StreamTrait,
+    F: FnMut(&Device, StreamControls) -> Result<S, BuildStreamError>,
+{
+    let host = match host_from_id(host) {
+        Ok(host) => host,
+        Err(_) => {
+            let _ = built.send(Err(BuildStreamError::DeviceNotAvailable));
+            return;
+        }
+    };
+    // The device is only needed to build the first stream.
+    let (mut name, mut stream) = {
+        let device = match default_device(&host, direction) {
+            Some(device) => device,
+            None => {
+                let _ = built.send(Err(BuildStreamError::DeviceNotAvailable));
+                return;
+            }
+        };
+        match build(&device, controls.clone()) {
+            Ok(stream) => (device.name().ok(), Some(stream)),
+            Err(err) => {
+                let _ = built.send(Err(err));
+                return;
+            }
+        }
+    };
+    let _ = built.send(Ok(()));
+
+    let mut state = stream.as_ref().map_or(StreamState::Playing, |s| s.state());
+    loop {
+        match commands.recv_timeout(POLL_INTERVAL) {
+            Ok(Command::Play(reply)) => {
+                state = StreamState::Playing;
+                let _ = reply.send(stream.as_ref().map_or(Ok(()), |s| controls.play(s)));
+            }
+            Ok(Command::Pause(reply)) => {
+                state = StreamState::Paused;
+                let _ = reply.send(stream.as_ref().map_or(Ok(()), |s| controls.pause(s)));
+            }
+            Ok(Command::Stop(reply)) => {
+                drop(stream.take());
+                let _ = reply.send(());
+                return;
+            }
+            Err(RecvTimeoutError::Disconnected) => return,
+            Err(RecvTimeoutError::Timeout) => {
+                let device = default_device(&host, direction);
+                let current = device.as_ref().and_then(|device| device.name().ok());
+                // A stream that failed to build is retried on the same device.
+                if current == name && stream.is_some() {
+                    continue;
+                }
+                name = current;
+                // The old stream may hold the device the new one needs.
+                drop(stream.take());
+                stream = device.and_then(|device| build(&device, controls.clone()).ok());
+                if let (Some(stream), StreamState::Paused) = (&stream, state) {
+                    let _ = stream.pause();
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{DefaultDeviceChange, DefaultNames, DeviceDirection};
+
+    #[test]
+    fn reports_changed_defaults() {
+        let before = DefaultNames {
+            input: Some("mic".to_owned()),
+            output: Some("speakers".to_owned()),
+        };
+        let after = DefaultNames {
+            input: Some("mic".to_owned()),
+            output: Some("headphones".to_owned()),
+        };
+        assert_eq!(before.changes(&before).count(), 0);
+        let changes: Vec<_> = before.changes(&after).collect();
+        assert_eq!(
+            changes,
+            [DefaultDeviceChange {
+                direction: DeviceDirection::Output,
+                name: Some("headphones".to_owned()),
+            }]
+        );
+        let unplugged = DefaultNames::default();
+        assert_eq!(after.changes(&unplugged).count(), 2);
+    }
+}
@@ -0,0 +1,313 @@
+//! Test signal generators.
+//!
+//! Each generator is an iterator of mono `f32` samples at full scale, producing one sample per
+//! frame at the sample rate it was created with. They never allocate, so they can run inside a
+//! data callback. Use [`fill_interleaved`] to write a generator to every channel of a stream
+//! buffer, and `Iterator::map` to scale it down.
+
+use crate::{Sample, SampleRate};
+use std::f32::consts::TAU;
+
+/// Writes the samples of `source` to every channel of the interleaved frames of `data`.
+///
+/// Frames after the end of `source`, and trailing samples that do not make up a whole frame, are
+/// silenced. Returns the number of frames taken from `source`.
+pub fn fill_interleaved<I, T>(source: &mut I, data: &mut [T], channels: usize) -> usize
+where
+    I: Iterator<Item = f32>,
+    T: Sample,
+{
+    if channels == 0 {
+        return 0;
+    }
+    let mut frames = 0;
+    let mut chunks = data.chunks_exact_mut(channels);
+    for frame in &mut chunks {
+        let value = match source.next() {
+            Some(sample) => {
+                frames += 1;
+                sample
+            }
+            None => 0.0,
+        };
+        frame.fill(T::from(&value));
+    }
+    chunks.into_remainder().fill(T::from(&0.0f32));
+    frames
+}
+
+// The phase of an oscillator, as a fraction of a period in [0.0, 1.0).
+#[derive(Clone, Debug)]
+struct Phase {
+    phase: f32,
+    increment: f32,
+}
+
+impl Phase {
+    fn new(frequency: f32, sample_rate: SampleRate) -> Self {
+        Phase {
+            phase: 0.0,
+            increment: frequency / sample_rate.0 as f32,
+        }
+    }
+
+    #[inline]
+    fn advance(&mut self) -> f32 {
+        let phase = self.phase;
+        self.phase = (self.phase + self.increment).fract();
+        phase
+    }
+}
+
+/// A sine wave.
+#[derive(Clone, Debug)]
+pub struct Sine {
+    phase: Phase,
+}
+
+impl Sine {
+    /// Creates a sine wave of `frequency` Hz, starting at a zero crossing.
+    pub fn new(frequency: f32, sample_rate: SampleRate) -> Self {
+        Sine {
+            phase: Phase::new(frequency, sample_rate),
+        }
+    }
+}
+
+impl Iterator for Sine {
+    type Item = f32;
+
+    #[inline]
+    fn next(&mut self) -> Option<f32> {
+        Some((self.phase.advance() * TAU).sin())
+    }
+}
+
+/// A square wave with a 50% duty cycle.
+///
+/// The wave is not band-limited, so high frequencies alias.
+#[derive(Clone, Debug)]
+pub struct Square {
+    phase: Phase,
+}
+
+impl Square {
+    /// Creates a square wave of `frequency` Hz, starting with the high half of its period.
+    pub fn new(frequency: f32, sample_rate: SampleRate) -> Self {
+        Square {
+            phase: Phase::new(frequency, sample_rate),
+        }
+    }
+}
+
+impl Iterator for Square {
+    type Item = f32;
+
+    #[inline]
+    fn next(&mut self) -> Option<f32> {
+        Some(if self.phase.advance() < 0.5 {
+            1.0
+        } else {
+            -1.0
+        })
+    }
+}
+
+/// A rising sawtooth wave.
+///
+/// The wave is not band-limited, so high frequencies alias.
+#[derive(Clone, Debug)]
+pub struct Saw {
+    phase: Phase,
+}
+
+impl Saw {
+    /// Creates a sawtooth wave of `frequency` Hz, starting at its minimum.
+    pub fn new(frequency: f32, sample_rate: SampleRate) -> Self {
+        Saw {
+            phase: Phase::new(frequency, sample_rate),
+        }
+    }
+}
+
+impl Iterator for Saw {
+    type Item = f32;
+
+    #[inline]
+    fn next(&mut self) -> Option<f32> {
+        Some(self.phase.advance() * 2.0 - 1.0)
+    }
+}
+
+/// Uniformly distributed white noise.
+#[derive(Clone, Debug)]
+pub struct WhiteNoise {
+    state: u32,
+}
+
+impl WhiteNoise {
+    /// Creates a noise generator with a fixed default seed.
+    pub fn new() -> Self {
+        Self::with_seed(0x2545_F491)
+    }
+
+    /// Creates a noise generator from the given seed.
+    pub fn with_seed(seed: u32) -> Self {
+        // The xorshift generator is stuck at zero.
+        let state = if seed == 0 { 0x2545_F491 } else { seed };
+        WhiteNoise { state }
+    }
+}
+
+impl Default for WhiteNoise {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Iterator for WhiteNoise {
+    type Item = f32;
+
+    #[inline]
+    fn next(&mut self) -> Option<f32> {
+        // Xorshift32, scaled to [-1.0, 1.0).
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 17;
+        self.state ^= self.state << 5;
+        Some((self.state >> 8) as f32 / (1 << 23) as f32 - 1.0)
+    }
+}
+
+/// Pink noise, whose power falls by 3 dB per octave.
+///
+/// White noise is shaped by Paul Kellet's economy filter, which is accurate to within 0.5 dB
+/// above 10 Hz at 44.1 kHz.
+#[derive(Clone, Debug, Default)]
+pub struct PinkNoise {
+    white: WhiteNoise,
+    state: [f32; 3],
+}
+
+impl PinkNoise {
+    // Brings the filter gain back to roughly unity, leaving headroom for its peaks.
+    const SCALE: f32 = 0.25;
+
+    /// Creates a noise generator with a fixed default seed.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a noise generator from the given seed.
+    pub fn with_seed(seed: u32) -> Self {
+        PinkNoise {
+            white: WhiteNoise::with_seed(seed),
+            state: [0.0; 3],
+        }
+    }
+}
+
+impl Iterator for PinkNoise {
+    type Item = f32;
+
+    #[inline]
+    fn next(&mut self) -> Option<f32> {
+        let white = self.white.next()?;
+        let [b0, b1, b2] = &mut self.state;
+        *b0 = 0.99765 * *b0 + white * 0.0990460;
+        *b1 = 0.96300 * *b1 + white * 0.2965164;
+        *b2 = 0.57000 * *b2 + white * 1.0526913;
+        let pink = *b0 + *b1 + *b2 + white * 0.1848;
+        Some((pink * Self::SCALE).clamp(-1.0, 1.0))
+    }
+}
+
+/// A sine wave sweeping exponentially from one frequency to another.
+///
+/// Each octave takes the same time, which suits measuring frequency responses. The iterator ends
+/// after the sweep.
+#[derive(Clone, Debug)]
+pub struct LogSweep {
+    phase: f32,
+    increment: f32,
+    growth: f32,
+    remaining: u64,
+}
+
+impl LogSweep {
+    /// Creates a sweep from `start` to `end` Hz lasting `seconds` seconds.
+    pub fn new(start: f32, end: f32, seconds: f32, sample_rate: SampleRate) -> Self {
+        let frames = (seconds * sample_rate.0 as f32).max(1.0);
+        LogSweep {
+            phase: 0.0,
+            increment: start / sample_rate.0 as f32,
+            growth: (end / start).powf(1.0 / frames),
+            remaining: frames as u64,
+        }
+    }
+}
+
+impl Iterator for LogSweep {
+    type Item = f32;
+
+    #[inline]
+    fn next(&mut self) -> Option<f32> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        let sample = (self.phase * TAU).sin();
+        self.phase = (self.phase + self.increment).fract();
+        self.increment *= self.growth;
+        Some(sample)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.remaining as usize;
+        (remaining, Some(remaining))
+    }
+}
+
+impl ExactSizeIterator for LogSweep {}
+
+#[cfg(test)]
+mod test {
+    use super::{fill_interleaved, LogSweep, PinkNoise, Sine, Square, WhiteNoise};
+    use crate::SampleRate;
+
+    #[test]
+    fn periodic_waves() {
+        let rate = SampleRate(48000);
+        let sine: Vec<f32> = Sine::new(12000.0, rate).take(5).collect();
+        let expected = [0.0, 1.0, 0.0, -1.0, 0.0];
+        for (sample, expected) in sine.iter().zip(expected) {
+            assert!((sample - expected).abs() < 1e-6);
+        }
+
+        let square: Vec<f32> = Square::new(12000.0, rate).take(4).collect();
+        assert_eq!(square, [1.0, 1.0, -1.0, -1.0]);
+
+        let mut data = [0.5f32; 5];
+        assert_eq!(
+            fill_interleaved(&mut Square::new(12000.0, rate), &mut data, 2),
+            2
+        );
+        assert_eq!(data, [1.0, 1.0, 1.0, 1.0, 0.0]);
+    }
+
+    #[test]
+    fn noise_and_sweeps() {
+        assert!(WhiteNoise::new()
+            .take(48000)
+            .all(|sample| (-1.0..1.0).contains(&sample)));
+        let pink: Vec<f32> = PinkNoise::new().take(48000).collect();
+        let rms = (pink.iter().map(|s| s * s).sum::<f32>() / pink.len() as f32).sqrt();
+        assert!(rms > 0.05 && rms < 0.5);
+
+        let mut sweep = LogSweep::new(20.0, 20000.0, 1.0, SampleRate(48000));
+        assert_eq!(sweep.len(), 48000);
+        let mut data = [1i16; 4];
+        sweep.nth(47997);
+        assert_eq!(fill_interleaved(&mut sweep, &mut data, 1), 2);
+        assert_eq!(data[2..], [0, 0]);
+    }
+}
@@ -0,0 +1,309 @@
+//! Controlling a stream from other threads.
+//!
+//! Backs `StreamHandle`.
+
+use crate::traits::StreamTrait;
+use crate::{
+    BackendSpecificError, BuildStreamError, Device, DeviceDirection, HostId, PauseStreamError,
+    PlayStreamError, StreamControls,
+};
+#[cfg(feature = "async")]
+use std::future::Future;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+
+/// A handle to play, pause and stop a stream from any thread.
+///
+/// Streams are not `Send` on some hosts, so they have to stay on the thread that built them.
+/// `StreamHandle::spawn` builds the stream on a thread of its own, which owns it and carries out
+/// the commands sent through the handle. The handle is `Send` and `Sync`, and cheap to clone.
+///
+/// The stream is stopped by `stop`, by a `StopGuard` or cancellation future tied to it, or once
+/// all clones of the handle have been dropped.
+#[derive(Clone, Debug)]
+pub struct StreamHandle {
+    commands: Sender<Command>,
+    controls: StreamControls,
+}
+
+pub(crate) enum Command {
+    Play(Sender<Result<(), PlayStreamError>>),
+    Pause(Sender<Result<(), PauseStreamError>>),
+    Stop(Sender<()>),
+}
+
+impl StreamHandle {
+    /// Builds a stream on a new thread, returning a handle to control it.
+    ///
+    /// `build` is passed the `StreamControls` behind `set_volume` and `controls`. Pass them in
+    /// `StreamOptions::controls` when building the stream, or the volume has no effect.
+    pub fn spawn<S, F>(build: F) -> Result<Self, BuildStreamError>
+    where
+        S: StreamTrait + 'static,
+        F: FnOnce(StreamControls) -> Result<S, BuildStreamError> + Send + 'static,
+    {
+        Self::spawn_thread(
+            move |controls, commands, built| match build(controls.clone()) {
+                Ok(stream) => {
+                    let _ = built.send(Ok(()));
+                    run(stream, &controls, commands);
+                }
+                Err(err) => {
+                    let _ = built.send(Err(err));
+                }
+            },
+        )
+    }
+
+    /// Builds a stream on the default input or output device of `host` on a new thread, and
+    /// builds it again on the new default device whenever the default changes, like browsers do.
+    ///
+    /// `build` is called with the device whenever the stream is built, and is passed the
+    /// `StreamControls` like for `spawn`. It should pick a config supported by the device, such
+    /// as its default one, as the devices may differ in their formats. A stream that was paused
+    /// is paused again after moving.
+    ///
+    /// Only the first build is reported. When building on a new default device fails, or there
+    /// is none, the handle has no stream until the default changes again.
+    pub fn spawn_following_default<S, F>(
+        host: HostId,
+        direction: DeviceDirection,
+        build: F,
+    ) -> Result<Self, BuildStreamError>
+    where
+        S: StreamTrait + 'static,
+        F: FnMut(&Device, StreamControls) -> Result<S, BuildStreamError> + Send + 'static,
+    {
+        Self::spawn_thread(move |controls, commands, built| {
+            crate::follow::run(host, direction, build, controls, commands, built)
+        })
+    }
+
+    // Spawns the thread owning the stream. `body` builds it, reports whether that succeeded to
+    // the sender, and carries out the commands.
+    pub(crate) fn spawn_thread<F>(body: F) -> Result<Self, BuildStreamError>
+    where
+        F: FnOnce(StreamControls, Receiver<Command>, Sender<Result<(), BuildStreamError>>)
+            + Send
+            + 'static,
+    {
+        let controls = StreamControls::new();
+        let (commands, receiver) = mpsc::channel();
+        let (built_tx, built_rx) = mpsc::channel();
+        let stream_controls = controls.clone();
+        thread::Builder::new()
+            .name("cpal_stream".to_owned())
+            .spawn(move || body(stream_controls, receiver, built_tx))
+            .map_err(|err| BackendSpecificError {
+                description: format!("failed to spawn the stream thread: {}", err),
+            })?;
+        let built = built_rx.recv().unwrap_or_else(|_| {
+            Err(BackendSpecificError {
+                description: "the stream thread panicked while building the stream".to_owned(),
+            }
+            .into())
+        });
+        built.map(|()| StreamHandle { commands, controls })
+    }
+
+    /// Plays the stream, fading it in over `StreamOptions::fade`. See `StreamControls::play`.
+    pub fn play(&self) -> Result<(), PlayStreamError> {
+        let (reply, result) = mpsc::channel();
+        self.send(Command::Play(reply))?;
+        result.recv().unwrap_or_else(|_| Err(stopped().into()))
+    }
+
+    /// Pauses the stream, fading it out over `StreamOptions::fade`. See `StreamControls::pause`.
+    pub fn pause(&self) -> Result<(), PauseStreamError> {
+        let (reply, result) = mpsc::channel();
+        self.send(Command::Pause(reply))?;
+        result.recv().unwrap_or_else(|_| Err(stopped().into()))
+    }
+
+    /// Stops the stream and drops it, releasing the device. Blocks until it has been dropped.
+    ///
+    /// Stopping affects all clones of the handle, which fail to play or pause the stream from
+    /// then on.
+    pub fn stop(&self) {
+        let (reply, result) = mpsc::channel();
+        if self.commands.send(Command::Stop(reply)).is_ok() {
+            let _ = result.recv();
+        }
+    }
+
+    /// Returns a guard that stops the stream when dropped, on whichever thread drops it.
+    ///
+    /// This ties the stream to a scope, such as a task of a structured-concurrency runtime that
+    /// may be cancelled.
+    pub fn stop_guard(&self) -> StopGuard {
+        StopGuard {
+            handle: Some(self.clone()),
+        }
+    }
+
+    /// Stops the stream once `cancelled` completes, such as the `cancelled()` future of a
+    /// cancellation token.
+    ///
+    /// Stopping blocks the executor while the stream is dropped, which is usually brief.
+    #[cfg(feature = "async")]
+    pub async fn stop_when<F: Future<Output = ()>>(&self, cancelled: F) {
+        cancelled.await;
+        self.stop();
+    }
+
+    /// Sets the volume of the stream. See `StreamControls::set_volume`.
+    pub fn set_volume(&self, volume: f32) {
+        self.controls.set_volume(volume);
+    }
+
+    /// The controls the stream was built with, for everything beyond playing, pausing and
+    /// stopping.
+    pub fn controls(&self) -> &StreamControls {
+        &self.controls
+    }
+
+    fn send(&self, command: Command) -> Result<(), BackendSpecificError> {
+        self.commands.send(command).map_err(|_| stopped())
+    }
+}
+
+/// Stops a stream when dropped, as returned by `StreamHandle::stop_guard`.
+#[derive(Debug)]
+#[must_use = "the stream is stopped as soon as the guard is dropped"]
+pub struct StopGuard {
+    handle: Option<StreamHandle>,
+}
+
+impl StopGuard {
+    /// Releases the stream from the guard without stopping it.
+    pub fn disarm(mut self) -> StreamHandle {
+        self.handle.take().expect("the guard is only disarmed once")
+    }
+}
+
+impl Drop for StopGuard {
+    fn drop(&mut self) {
+        if let Some(handle) = self.handle.take() {
+            handle.stop();
+        }
+    }
+}
+
+// Carries out the commands for the stream until it is stopped or all handles are dropped.
+fn run<S: StreamTrait>(stream: S, controls: &StreamControls, commands: Receiver<Command>) {
+    for command in commands {
+        match command {
+            Command::Play(reply) => {
+                let _ = reply.send(controls.play(&stream));
+            }
+            Command::Pause(reply) => {
+                let _ = reply.send(controls.pause(&stream));
+            }
+            Command::Stop(reply) => {
+                drop(stream);
+                let _ = reply.send(());
+                return;
+            }
+        }
+    }
+}
+
+fn stopped() -> BackendSpecificError {
+    BackendSpecificError {
+        description: "the stream has been stopped".to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::StreamHandle;
+    use crate::traits::StreamTrait;
+    use crate::{PauseStreamError, PlayStreamError, StreamState};
+    use std::cell::Cell;
+    use std::rc::Rc;
+    use std::sync::{Arc, Mutex};
+
+    // A stream that cannot be sent to another thread, recording the calls made to it.
+    struct LocalStream {
+        state: Rc<Cell<StreamState>>,
+        calls: Arc<Mutex<Vec<&'static str>>>,
+    }
+
+    impl StreamTrait for LocalStream {
+        fn play(&self) -> Result<(), PlayStreamError> {
+            self.state.set(StreamState::Playing);
+            self.calls.lock().unwrap().push("play");
+            Ok(())
+        }
+
+        fn pause(&self) -> Result<(), PauseStreamError> {
+            self.state.set(StreamState::Paused);
+            self.calls.lock().unwrap().push("pause");
+            Ok(())
+        }
+
+        fn state(&self) -> StreamState {
+            self.state.get()
+        }
+    }
+
+    impl Drop for LocalStream {
+        fn drop(&mut self) {
+            self.calls.lock().unwrap().push("drop");
+        }
+    }
+
+    #[test]
+    fn controls_stream_from_other_threads() {
+        fn assert_send_sync<T: Send + Sync>(_: &T) {}
+
+        let calls = Arc::new(Mutex::new(vec![]));
+        let handle = {
+            let calls = calls.clone();
+            StreamHandle::spawn(move |_| {
+                Ok(LocalStream {
+                    state: Rc::new(Cell::new(StreamState::Playing)),
+                    calls,
+                })
+            })
+            .unwrap()
+        };
+        assert_send_sync(&handle);
+
+        let remote = handle.clone();
+        std::thread::spawn(move || {
+            remote.pause().unwrap();
+            remote.set_volume(0.5);
+            remote.play().unwrap();
+        })
+        .join()
+        .unwrap();
+        assert_eq!(handle.controls().volume(), 0.5);
+
+        handle.stop();
+        assert_eq!(*calls.lock().unwrap(), ["pause", "play", "drop"]);
+        assert!(handle.play().is_err());
+    }
+
+    #[test]
+    fn stop_guard_stops_stream() {
+        let calls = Arc::new(Mutex::new(vec![]));
+        let handle = {
+            let calls = calls.clone();
+            StreamHandle::spawn(move |_| {
+                Ok(LocalStream {
+                    state: Rc::new(Cell::new(StreamState::Playing)),
+                    calls,
+                })
+            })
+            .unwrap()
+        };
+
+        let handle = handle.stop_guard().disarm();
+        assert!(handle.pause().is_ok());
+        let guard = handle.stop_guard();
+        std::thread::spawn(move || drop(guard)).join().unwrap();
+        assert_eq!(*calls.lock().unwrap(), ["pause", "drop"]);
+        assert!(handle.play().is_err());
+    }
+}
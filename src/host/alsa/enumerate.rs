@@ -6,12 +6,27 @@ use crate::{BackendSpecificError, DevicesError};
 /// ALSA's implementation for `Devices`.
 pub struct Devices {
     hint_iter: alsa::device_name::HintIter,
+    // Skips the devices of the other direction without opening them.
+    direction: Option<alsa::Direction>,
+    // Skips the plugin devices, leaving the `hw` ones.
+    physical_only: bool,
 }
 
 impl Devices {
     pub fn new() -> Result<Self, DevicesError> {
+        Self::matching(None, false)
+    }
+
+    /// The devices supporting `direction`, or all if it is `None`, and only the hardware devices
+    /// if `physical_only` is set.
+    pub fn matching(
+        direction: Option<alsa::Direction>,
+        physical_only: bool,
+    ) -> Result<Self, DevicesError> {
         Ok(Devices {
             hint_iter: alsa::device_name::HintIter::new_str(None, "pcm")?,
+            direction,
+            physical_only,
         })
     }
 }
@@ -27,17 +42,28 @@ impl Iterator for Devices {
             match self.hint_iter.next() {
                 None => return None,
                 Some(hint) => {
+                    // Hints without a direction support both.
+                    if hint.direction.is_some()
+                        && self.direction.is_some()
+                        && hint.direction != self.direction
+                    {
+                        continue;
+                    }
                     let name = match hint.name {
                         None => continue,
                         // Ignoring the `null` device.
                         Some(name) if name == "null" => continue,
                         Some(name) => name,
                     };
+                    if self.physical_only && !name.starts_with("hw:") {
+                        continue;
+                    }
 
                     if let Ok(handles) = DeviceHandles::open(&name) {
                         return Some(Device {
                             name,
                             handles: Mutex::new(handles),
+                            default_sample_rate: Mutex::new(None),
                         });
                     }
                 }
@@ -51,6 +77,7 @@ pub fn default_input_device() -> Option<Device> {
     Some(Device {
         name: "default".to_owned(),
         handles: Mutex::new(Default::default()),
+        default_sample_rate: Mutex::new(None),
     })
 }
 
@@ -59,6 +86,7 @@ pub fn default_output_device() -> Option<Device> {
     Some(Device {
         name: "default".to_owned(),
         handles: Mutex::new(Default::default()),
+        default_sample_rate: Mutex::new(None),
     })
 }
 
@@ -3,17 +3,22 @@ extern crate libc;
 extern crate parking_lot;
 
 use self::alsa::poll::Descriptors;
-use self::parking_lot::Mutex;
+use self::parking_lot::{Mutex, MutexGuard};
 use crate::traits::{DeviceTrait, HostTrait, StreamTrait};
 use crate::{
-    BackendSpecificError, BufferSize, BuildStreamError, ChannelCount, Data,
-    DefaultStreamConfigError, DeviceNameError, DevicesError, InputCallbackInfo, OutputCallbackInfo,
-    PauseStreamError, PlayStreamError, SampleFormat, SampleRate, StreamConfig, StreamError,
-    SupportedBufferSize, SupportedStreamConfig, SupportedStreamConfigRange,
-    SupportedStreamConfigsError,
+    BackendSpecificError, BluetoothProfile, BufferLayout, BufferSize, BuildStreamError,
+    ChannelCount, ChannelLayout, ChannelPosition, Data, DefaultStreamConfigError,
+    DeviceCapabilities, DeviceDirection, DeviceFilter, DeviceInfo, DeviceNameError, DevicesError,
+    DirectMonitoringError, FormFactor, FrameCount, InputCallbackInfo, OutputCallbackInfo,
+    PauseStreamError, PlayStreamError, ReconfigureStreamError, SampleFormat, SampleRate,
+    SetSampleRateError, StreamConfig, StreamError, StreamLatency, StreamState, SupportedBufferSize,
+    SupportedStreamConfig, SupportedStreamConfigRange, SupportedStreamConfigsError, Transport,
 };
 use std::cmp;
 use std::convert::TryInto;
+use std::fmt::Write;
+use std::os::unix::io::RawFd;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::thread::{self, JoinHandle};
 use std::vec::IntoIter as VecIntoIter;
@@ -25,6 +30,11 @@ pub type SupportedOutputConfigs = VecIntoIter<SupportedStreamConfigRange>;
 
 mod enumerate;
 
+// Not wrapped by the `alsa` crate, but linked through it.
+extern "C" {
+    fn snd_asoundlib_version() -> *const libc::c_char;
+}
+
 /// The default linux, dragonfly and freebsd host type.
 #[derive(Debug)]
 pub struct Host;
@@ -33,8 +43,63 @@ impl Host {
     pub fn new() -> Result<Self, crate::HostUnavailable> {
         Ok(Host)
     }
+
+    /// Drives the callbacks of `streams` on the calling thread until `stop` returns `true`, for
+    /// applications without threads of their own.
+    ///
+    /// The streams must have been built with `Device::build_driven_input_stream_raw` or
+    /// `Device::build_driven_output_stream_raw`. `stop` is checked between callbacks, and at least
+    /// every 10 milliseconds while the devices have nothing to process. Paused streams are skipped
+    /// until they are played again.
+    ///
+    /// Errors of the streams go to their error callbacks. An error is only returned if one of the
+    /// streams is not driven, or the devices cannot be polled.
+    pub fn run<F>(&self, streams: &[&Stream], mut stop: F) -> Result<(), BackendSpecificError>
+    where
+        F: FnMut() -> bool,
+    {
+        if streams.iter().any(|stream| stream.driven.is_none()) {
+            let description = "`Host::run` was passed a stream that is not driven".to_string();
+            return Err(BackendSpecificError { description });
+        }
+        let mut descriptors = vec![];
+        let mut polled = vec![];
+        while !stop() {
+            descriptors.clear();
+            polled.clear();
+            for stream in streams {
+                if stream.inner.paused.load(Ordering::Acquire) {
+                    continue;
+                }
+                let start = descriptors.len();
+                descriptors.resize(
+                    start + stream.inner.num_descriptors,
+                    libc::pollfd {
+                        fd: 0,
+                        events: 0,
+                        revents: 0,
+                    },
+                );
+                stream.inner.channel.fill(&mut descriptors[start..])?;
+                polled.push((stream, start..descriptors.len()));
+            }
+            if alsa::poll::poll(&mut descriptors, RUN_POLL_INTERVAL_MS)? == 0 {
+                continue;
+            }
+            for (stream, range) in &polled {
+                let descriptors = &descriptors[range.clone()];
+                if descriptors.iter().any(|descriptor| descriptor.revents != 0) {
+                    stream.process_ready(descriptors);
+                }
+            }
+        }
+        Ok(())
+    }
 }
 
+// How long `Host::run` waits on the devices before checking its stop condition again.
+const RUN_POLL_INTERVAL_MS: i32 = 10;
+
 impl HostTrait for Host {
     type Devices = Devices;
     type Device = Device;
@@ -55,6 +120,22 @@ impl HostTrait for Host {
     fn default_output_device(&self) -> Option<Self::Device> {
         default_output_device()
     }
+
+    fn backend_version(&self) -> Option<String> {
+        let version = unsafe { std::ffi::CStr::from_ptr(snd_asoundlib_version()) };
+        Some(format!("alsa-lib {}", version.to_string_lossy()))
+    }
+
+    // Leaves the devices of the other direction and the plugins out before opening them.
+    fn devices_filtered(&self, filter: DeviceFilter) -> Result<Vec<Self::Device>, DevicesError> {
+        let direction = filter.direction.map(|direction| match direction {
+            DeviceDirection::Input => alsa::Direction::Capture,
+            DeviceDirection::Output => alsa::Direction::Playback,
+        });
+        Ok(Devices::matching(direction, filter.physical_only)?
+            .filter(|device| filter.matches(device))
+            .collect())
+    }
 }
 
 impl DeviceTrait for Device {
@@ -66,6 +147,14 @@ impl DeviceTrait for Device {
         Device::name(self)
     }
 
+    fn info(&self) -> Result<DeviceInfo, DeviceNameError> {
+        Device::info(self)
+    }
+
+    fn capabilities(&self) -> Result<DeviceCapabilities, SupportedStreamConfigsError> {
+        Device::capabilities(self)
+    }
+
     fn supported_input_configs(
         &self,
     ) -> Result<Self::SupportedInputConfigs, SupportedStreamConfigsError> {
@@ -78,6 +167,14 @@ impl DeviceTrait for Device {
         Device::supported_output_configs(self)
     }
 
+    fn supports_input_config(&self, config: &StreamConfig, sample_format: SampleFormat) -> bool {
+        Device::supports_config(self, alsa::Direction::Capture, config, sample_format)
+    }
+
+    fn supports_output_config(&self, config: &StreamConfig, sample_format: SampleFormat) -> bool {
+        Device::supports_config(self, alsa::Direction::Playback, config, sample_format)
+    }
+
     fn default_input_config(&self) -> Result<SupportedStreamConfig, DefaultStreamConfigError> {
         Device::default_input_config(self)
     }
@@ -86,6 +183,23 @@ impl DeviceTrait for Device {
         Device::default_output_config(self)
     }
 
+    fn set_direct_monitoring(&self, enabled: bool) -> Result<(), DirectMonitoringError> {
+        Device::set_direct_monitoring(self, enabled)
+    }
+
+    // Makes `sample_rate` the rate of the default configs, which streams open the hardware at.
+    fn set_default_sample_rate(&self, sample_rate: SampleRate) -> Result<(), SetSampleRateError> {
+        let filter = DeviceFilter {
+            sample_rate: Some(sample_rate),
+            ..Default::default()
+        };
+        if !filter.matches(self) {
+            return Err(SetSampleRateError::SampleRateNotSupported);
+        }
+        *self.default_sample_rate.lock() = Some(sample_rate);
+        Ok(())
+    }
+
     fn build_input_stream_raw<D, E>(
         &self,
         conf: &StreamConfig,
@@ -226,6 +340,8 @@ impl DeviceHandles {
 pub struct Device {
     name: String,
     handles: Mutex<DeviceHandles>,
+    // The rate of the default configs, as set with `set_default_sample_rate`.
+    default_sample_rate: Mutex<Option<SampleRate>>,
 }
 
 impl Device {
@@ -235,19 +351,30 @@ impl Device {
         sample_format: SampleFormat,
         stream_type: alsa::Direction,
     ) -> Result<StreamInner, BuildStreamError> {
-        let handle_result = self
-            .handles
-            .lock()
-            .take(&self.name, stream_type)
-            .map_err(|e| (e, e.errno()));
+        let handle_result = if sample_format == SampleFormat::Iec61937 {
+            if stream_type != alsa::Direction::Playback {
+                return Err(BuildStreamError::StreamConfigNotSupported);
+            }
+            let name = iec958_passthrough_name(&self.name, conf.sample_rate)
+                .ok_or(BuildStreamError::StreamConfigNotSupported)?;
+            // The channel status bits can only be set when opening the PCM, so release the cached
+            // handle before reopening the device flagged as non-audio.
+            self.handles.lock().playback.take();
+            alsa::pcm::PCM::new(&name, stream_type, true).map_err(|e| (e, e.errno()))
+        } else {
+            self.handles
+                .lock()
+                .take(&self.name, stream_type)
+                .map_err(|e| (e, e.errno()))
+        };
 
         let handle = match handle_result {
-            Err((_, nix::errno::Errno::EBUSY)) => return Err(BuildStreamError::DeviceNotAvailable),
+            Err((_, nix::errno::Errno::EBUSY)) => return Err(BuildStreamError::DeviceBusy),
             Err((_, nix::errno::Errno::EINVAL)) => return Err(BuildStreamError::InvalidArgument),
             Err((e, _)) => return Err(e.into()),
             Ok(handle) => handle,
         };
-        let can_pause = set_hw_params_from_format(&handle, conf, sample_format)?;
+        let can_pause = set_hw_params_from_format(&handle, conf, sample_format, DEFAULT_PERIODS)?;
         let period_len = set_sw_params_from_format(&handle, conf, stream_type)?;
 
         handle.prepare()?;
@@ -276,19 +403,151 @@ impl Device {
             sample_format,
             num_descriptors,
             conf: conf.clone(),
-            period_len,
+            sample_rate: AtomicU32::new(conf.sample_rate.0),
+            period_len: AtomicUsize::new(period_len),
+            io: Mutex::new(()),
             can_pause,
+            paused: AtomicBool::new(false),
+            stopped: AtomicBool::new(false),
+            frame_position: AtomicU64::new(0),
             creation_instant,
         };
 
         Ok(stream_inner)
     }
 
+    /// Builds an input stream like `build_input_stream_raw`, without a thread of its own. Its
+    /// callbacks are called by `Host::run` on the thread calling it, or by `Stream::on_ready`.
+    pub fn build_driven_input_stream_raw<D, E>(
+        &self,
+        conf: &StreamConfig,
+        sample_format: SampleFormat,
+        data_callback: D,
+        error_callback: E,
+    ) -> Result<Stream, BuildStreamError>
+    where
+        D: FnMut(&Data, &InputCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        let stream_inner =
+            self.build_stream_inner(conf, sample_format, alsa::Direction::Capture)?;
+        let callback = DrivenCallback::Input(Box::new(data_callback));
+        Ok(Stream::new_driven(
+            Arc::new(stream_inner),
+            callback,
+            Box::new(error_callback),
+        ))
+    }
+
+    /// Builds an output stream like `build_output_stream_raw`, without a thread of its own. Its
+    /// callbacks are called by `Host::run` on the thread calling it, or by `Stream::on_ready`.
+    pub fn build_driven_output_stream_raw<D, E>(
+        &self,
+        conf: &StreamConfig,
+        sample_format: SampleFormat,
+        data_callback: D,
+        error_callback: E,
+    ) -> Result<Stream, BuildStreamError>
+    where
+        D: FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        let stream_inner =
+            self.build_stream_inner(conf, sample_format, alsa::Direction::Playback)?;
+        let callback = DrivenCallback::Output(Box::new(data_callback));
+        Ok(Stream::new_driven(
+            Arc::new(stream_inner),
+            callback,
+            Box::new(error_callback),
+        ))
+    }
+
     #[inline]
     fn name(&self) -> Result<String, DeviceNameError> {
         Ok(self.name.clone())
     }
 
+    // Adds the driver of the device's card, and what the name of the device tells about it.
+    fn info(&self) -> Result<DeviceInfo, DeviceNameError> {
+        let mut info = crate::device_info::from_name(self)?;
+        // The "default" device is the one ALSA picks when an application names none.
+        let is_default = self.name == "default";
+        info.default_input = is_default && info.input;
+        info.default_output = is_default && info.output;
+        info.driver = alsa::ctl::Ctl::new(&mixer_name(&self.name), false)
+            .and_then(|ctl| ctl.card_info())
+            .ok()
+            .and_then(|card| card.get_driver().ok().map(str::to_owned));
+        let plugin = self.name.split(':').next().unwrap_or_default();
+        info.form_factor = match plugin {
+            "hdmi" | "iec958" | "spdif" => FormFactor::Digital,
+            _ => FormFactor::Unknown,
+        };
+        info.transport = transport(plugin, info.driver.as_deref());
+        if plugin == "bluealsa" {
+            let (profile, codec) = bluealsa_profile(&self.name);
+            info.bluetooth_profile = Some(profile);
+            info.bluetooth_codec = codec;
+        }
+        Ok(info)
+    }
+
+    // Adds whether the mixer of the device's card has a volume control for its directions.
+    fn capabilities(&self) -> Result<DeviceCapabilities, SupportedStreamConfigsError> {
+        let mut capabilities = crate::device_info::capabilities_from_configs(self)?;
+        let (input, output) = (
+            self.supported_input_configs()
+                .is_ok_and(|mut c| c.next().is_some()),
+            self.supported_output_configs()
+                .is_ok_and(|mut c| c.next().is_some()),
+        );
+        capabilities.hardware_volume = alsa::mixer::Mixer::new(&mixer_name(&self.name), false)
+            .map(|mixer| {
+                mixer
+                    .iter()
+                    .filter_map(alsa::mixer::Selem::new)
+                    .any(|selem| {
+                        (output && selem.has_playback_volume())
+                            || (input && selem.has_capture_volume())
+                    })
+            })
+            .unwrap_or(false);
+        Ok(capabilities)
+    }
+
+    // Switches the "Direct Monitor" controls of the mixer of the device's card.
+    fn set_direct_monitoring(&self, enabled: bool) -> Result<(), DirectMonitoringError> {
+        let mixer = alsa::mixer::Mixer::new(&mixer_name(&self.name), false)
+            .map_err(|_| DirectMonitoringError::DeviceNotAvailable)?;
+        let mut found = false;
+        for elem in mixer.iter() {
+            let selem = match alsa::mixer::Selem::new(elem) {
+                Some(selem) => selem,
+                None => continue,
+            };
+            let id = selem.get_id();
+            if !id
+                .get_name()
+                .is_ok_and(|name| name.contains("Direct Monitor"))
+            {
+                continue;
+            }
+            if selem.has_playback_switch() {
+                selem.set_playback_switch_all(enabled as i32)?;
+                found = true;
+            }
+            if selem.has_capture_switch() {
+                selem.set_capture_switch_all(enabled as i32)?;
+                found = true;
+            }
+        }
+        if found {
+            Ok(())
+        } else {
+            Err(DirectMonitoringError::NotSupported)
+        }
+    }
+
     fn supported_configs(
         &self,
         stream_t: alsa::Direction,
@@ -312,7 +571,7 @@ impl Device {
         let hw_params = alsa::pcm::HwParams::any(handle)?;
 
         // TODO: check endianness
-        const FORMATS: [(SampleFormat, alsa::pcm::Format); 3] = [
+        const FORMATS: [(SampleFormat, alsa::pcm::Format); 6] = [
             //SND_PCM_FORMAT_S8,
             //SND_PCM_FORMAT_U8,
             (SampleFormat::I16, alsa::pcm::Format::S16LE),
@@ -333,7 +592,7 @@ impl Device {
             //SND_PCM_FORMAT_FLOAT64_BE,
             //SND_PCM_FORMAT_IEC958_SUBFRAME_LE,
             //SND_PCM_FORMAT_IEC958_SUBFRAME_BE,
-            //SND_PCM_FORMAT_MU_LAW,
+            (SampleFormat::MuLaw, alsa::pcm::Format::MuLaw),
             //SND_PCM_FORMAT_A_LAW,
             //SND_PCM_FORMAT_IMA_ADPCM,
             //SND_PCM_FORMAT_MPEG,
@@ -351,6 +610,11 @@ impl Device {
             //SND_PCM_FORMAT_S18_3BE,
             //SND_PCM_FORMAT_U18_3LE,
             //SND_PCM_FORMAT_U18_3BE,
+            (SampleFormat::DsdU8, alsa::pcm::Format::DSDU8),
+            //SND_PCM_FORMAT_DSD_U16_LE,
+            (SampleFormat::DsdU32, alsa::pcm::Format::DSDU32LE),
+            //SND_PCM_FORMAT_DSD_U16_BE,
+            //SND_PCM_FORMAT_DSD_U32_BE,
         ];
 
         let mut supported_formats = Vec::new();
@@ -360,6 +624,14 @@ impl Device {
             }
         }
 
+        // Compressed passthrough is carried in 16-bit frames on S/PDIF and HDMI outputs.
+        if stream_t == alsa::Direction::Playback
+            && is_iec958_device(&self.name)
+            && supported_formats.contains(&SampleFormat::I16)
+        {
+            supported_formats.push(SampleFormat::Iec61937);
+        }
+
         let min_rate = hw_params.get_rate_min()?;
         let max_rate = hw_params.get_rate_max()?;
 
@@ -399,13 +671,9 @@ impl Device {
             })
             .collect::<Vec<_>>();
 
-        let min_buffer_size = hw_params.get_buffer_size_min()?;
-        let max_buffer_size = hw_params.get_buffer_size_max()?;
+        let channel_layouts = query_channel_layouts(handle);
 
-        let buffer_size_range = SupportedBufferSize::Range {
-            min: min_buffer_size as u32,
-            max: max_buffer_size as u32,
-        };
+        let buffer_size_range = buffer_size_range(&hw_params, DEFAULT_PERIODS)?;
 
         let mut output = Vec::with_capacity(
             supported_formats.len() * supported_channels.len() * sample_rates.len(),
@@ -419,6 +687,11 @@ impl Device {
                         max_sample_rate: SampleRate(max_rate as u32),
                         buffer_size: buffer_size_range.clone(),
                         sample_format,
+                        layout: BufferLayout::Interleaved,
+                        channel_layout: channel_layouts
+                            .iter()
+                            .find(|layout| layout.channels() == channels)
+                            .cloned(),
                     });
                 }
             }
@@ -439,6 +712,44 @@ impl Device {
         self.supported_configs(alsa::Direction::Playback)
     }
 
+    // Narrows the hardware parameters of the device down to the config, instead of enumerating
+    // every combination.
+    fn supports_config(
+        &self,
+        stream_t: alsa::Direction,
+        config: &StreamConfig,
+        sample_format: SampleFormat,
+    ) -> bool {
+        let alsa_format = match sample_format {
+            // Which devices carry passthrough depends on more than their ALSA format. DoP is
+            // opened as plain S32 PCM, so it is checked as such below.
+            SampleFormat::Iec61937 => {
+                return self
+                    .supported_configs(stream_t)
+                    .is_ok_and(|mut configs| configs.any(|c| c.supports(config, sample_format)))
+            }
+            _ => match alsa_format_from_sample_format(sample_format) {
+                Some(alsa_format) => alsa_format,
+                None => return false,
+            },
+        };
+        let mut guard = self.handles.lock();
+        let handle = match guard.get_mut(&self.name, stream_t) {
+            Ok(handle) => handle,
+            Err(_) => return false,
+        };
+        let narrowed = || -> Result<SupportedBufferSize, alsa::Error> {
+            let hw_params = alsa::pcm::HwParams::any(handle)?;
+            hw_params.set_access(alsa::pcm::Access::RWInterleaved)?;
+            hw_params.set_format(alsa_format)?;
+            hw_params.set_channels(config.channels as u32)?;
+            hw_params.test_rate(config.sample_rate.0)?;
+            hw_params.set_rate(config.sample_rate.0, alsa::ValueOr::Nearest)?;
+            buffer_size_range(&hw_params, DEFAULT_PERIODS)
+        };
+        narrowed().is_ok_and(|supported| config.buffer_size.fits(&supported))
+    }
+
     // ALSA does not offer default stream formats, so instead we compare all supported formats by
     // the `SupportedStreamConfigRange::cmp_default_heuristics` order and select the greatest.
     fn default_config(
@@ -470,8 +781,13 @@ impl Device {
                 let max_r = f.max_sample_rate;
                 let mut format = f.with_max_sample_rate();
                 const HZ_44100: SampleRate = SampleRate(44_100);
-                if min_r <= HZ_44100 && HZ_44100 <= max_r {
-                    format.sample_rate = HZ_44100;
+                let preferred = *self.default_sample_rate.lock();
+                if let Some(rate) = [preferred, Some(HZ_44100)]
+                    .into_iter()
+                    .flatten()
+                    .find(|rate| (min_r..=max_r).contains(rate))
+                {
+                    format.sample_rate = rate;
                 }
                 Ok(format)
             }
@@ -502,14 +818,32 @@ struct StreamInner {
     // The configuration used to open this stream.
     conf: StreamConfig,
 
+    // The sample rate the device runs at, which differs from the one in `conf` once changed by
+    // `set_sample_rate`.
+    sample_rate: AtomicU32,
+
     // Minimum number of samples to put in the buffer.
-    period_len: usize,
+    period_len: AtomicUsize,
+
+    // Held by the worker while it uses the device, except while it waits for the device to be
+    // ready, so that the device can be set up again with a new buffer size.
+    io: Mutex<()>,
 
-    #[allow(dead_code)]
-    // Whether or not the hardware supports pausing the stream.
-    // TODO: We need an API to expose this. See #197, #284.
+    // Whether or not the hardware supports pausing the stream. If it does not, pausing stops the
+    // stream instead.
     can_pause: bool,
 
+    // Whether the stream was paused by `Stream::pause`. The worker does not poll the device while
+    // it is set.
+    paused: AtomicBool,
+
+    // Whether the paused stream was stopped rather than paused in hardware, and has to be prepared
+    // again to resume.
+    stopped: AtomicBool,
+
+    // The number of frames passed to or from the callback so far, reported in the callback info.
+    frame_position: AtomicU64,
+
     // In the case that the device does not return valid timestamps via `get_htstamp`, this field
     // will be `Some` and will contain an `Instant` representing the moment the stream was created.
     //
@@ -521,6 +855,12 @@ struct StreamInner {
     creation_instant: Option<std::time::Instant>,
 }
 
+impl StreamInner {
+    fn sample_rate(&self) -> SampleRate {
+        SampleRate(self.sample_rate.load(Ordering::Relaxed))
+    }
+}
+
 // Assume that the ALSA library is built with thread safe option.
 unsafe impl Sync for StreamInner {}
 
@@ -532,14 +872,45 @@ enum StreamType {
 
 pub struct Stream {
     /// The high-priority audio processing thread calling callbacks.
-    /// Option used for moving out in destructor.
+    /// Option used for moving out in destructor, and `None` for driven streams.
     thread: Option<JoinHandle<()>>,
 
     /// Handle to the underlying stream for playback controls.
     inner: Arc<StreamInner>,
 
-    /// Used to signal to stop processing.
-    trigger: TriggerSender,
+    /// Used to signal to stop processing. `None` for driven streams.
+    trigger: Option<TriggerSender>,
+
+    /// The callbacks of a stream driven by `Host::run` rather than a thread of its own.
+    driven: Option<Mutex<Driven>>,
+}
+
+/// A file descriptor of a driven stream, to be watched by an application's own event loop, as
+/// returned by `Stream::poll_descriptors`.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct PollDescriptor {
+    /// The file descriptor.
+    pub fd: RawFd,
+    /// Whether to wait for the descriptor to become readable.
+    pub readable: bool,
+    /// Whether to wait for the descriptor to become writable.
+    pub writable: bool,
+}
+
+// The state of a driven stream, kept between the calls to `Host::run` or `Stream::on_ready`.
+struct Driven {
+    callback: DrivenCallback,
+    error_callback: Box<dyn FnMut(StreamError) + Send + 'static>,
+    buffer: Vec<u8>,
+    sample_rate: SampleRate,
+}
+
+type InputDataCallback = Box<dyn FnMut(&Data, &InputCallbackInfo) + Send + 'static>;
+type OutputDataCallback = Box<dyn FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static>;
+
+enum DrivenCallback {
+    Input(InputDataCallback),
+    Output(OutputDataCallback),
 }
 
 #[derive(Default)]
@@ -555,44 +926,58 @@ fn input_stream_worker(
     error_callback: &mut (dyn FnMut(StreamError) + Send + 'static),
 ) {
     let mut ctxt = StreamWorkerContext::default();
+    let mut sample_rate = stream.sample_rate();
+    let mut io = stream.io.lock();
     loop {
-        let flow =
-            poll_descriptors_and_prepare_buffer(&rx, stream, &mut ctxt).unwrap_or_else(|err| {
+        let flow = poll_descriptors_and_prepare_buffer(&rx, stream, &mut io, &mut ctxt)
+            .unwrap_or_else(|err| {
                 error_callback(err.into());
                 PollDescriptorsFlow::Continue
             });
+        if let PollDescriptorsFlow::Return = flow {
+            return;
+        }
+        handle_input_flow(
+            stream,
+            flow,
+            &mut ctxt.buffer,
+            &mut sample_rate,
+            data_callback,
+            error_callback,
+        );
+    }
+}
 
-        match flow {
-            PollDescriptorsFlow::Continue => {
-                continue;
-            }
-            PollDescriptorsFlow::XRun => {
-                if let Err(err) = stream.channel.prepare() {
-                    error_callback(err.into());
-                }
-                continue;
+fn handle_input_flow(
+    stream: &StreamInner,
+    flow: PollDescriptorsFlow,
+    buffer: &mut [u8],
+    sample_rate: &mut SampleRate,
+    data_callback: &mut (dyn FnMut(&Data, &InputCallbackInfo) + Send + 'static),
+    error_callback: &mut (dyn FnMut(StreamError) + Send + 'static),
+) {
+    match flow {
+        PollDescriptorsFlow::Continue | PollDescriptorsFlow::Return => (),
+        PollDescriptorsFlow::XRun => {
+            error_callback(StreamError::Overrun { frames_lost: None });
+            if let Err(err) = stream.channel.prepare() {
+                error_callback(err.into());
             }
-            PollDescriptorsFlow::Return => return,
-            PollDescriptorsFlow::Ready {
-                status,
-                avail_frames: _,
-                delay_frames,
+        }
+        PollDescriptorsFlow::Ready {
+            status,
+            avail_frames: _,
+            delay_frames,
+            stream_type,
+        } => {
+            assert_eq!(
                 stream_type,
-            } => {
-                assert_eq!(
-                    stream_type,
-                    StreamType::Input,
-                    "expected input stream, but polling descriptors indicated output",
-                );
-                if let Err(err) = process_input(
-                    stream,
-                    &mut ctxt.buffer,
-                    status,
-                    delay_frames,
-                    data_callback,
-                ) {
-                    error_callback(err.into());
-                }
+                StreamType::Input,
+                "expected input stream, but polling descriptors indicated output",
+            );
+            report_sample_rate(stream, sample_rate, error_callback);
+            if let Err(err) = process_input(stream, buffer, status, delay_frames, data_callback) {
+                error_callback(err.into());
             }
         }
     }
@@ -605,49 +990,84 @@ fn output_stream_worker(
     error_callback: &mut (dyn FnMut(StreamError) + Send + 'static),
 ) {
     let mut ctxt = StreamWorkerContext::default();
+    let mut sample_rate = stream.sample_rate();
+    let mut io = stream.io.lock();
     loop {
-        let flow =
-            poll_descriptors_and_prepare_buffer(&rx, stream, &mut ctxt).unwrap_or_else(|err| {
+        let flow = poll_descriptors_and_prepare_buffer(&rx, stream, &mut io, &mut ctxt)
+            .unwrap_or_else(|err| {
                 error_callback(err.into());
                 PollDescriptorsFlow::Continue
             });
+        if let PollDescriptorsFlow::Return = flow {
+            return;
+        }
+        handle_output_flow(
+            stream,
+            flow,
+            &mut ctxt.buffer,
+            &mut sample_rate,
+            data_callback,
+            error_callback,
+        );
+    }
+}
 
-        match flow {
-            PollDescriptorsFlow::Continue => continue,
-            PollDescriptorsFlow::XRun => {
-                if let Err(err) = stream.channel.prepare() {
-                    error_callback(err.into());
-                }
-                continue;
+fn handle_output_flow(
+    stream: &StreamInner,
+    flow: PollDescriptorsFlow,
+    buffer: &mut [u8],
+    sample_rate: &mut SampleRate,
+    data_callback: &mut (dyn FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static),
+    error_callback: &mut (dyn FnMut(StreamError) + Send + 'static),
+) {
+    match flow {
+        PollDescriptorsFlow::Continue | PollDescriptorsFlow::Return => (),
+        PollDescriptorsFlow::XRun => {
+            error_callback(StreamError::Underrun { frames_lost: None });
+            if let Err(err) = stream.channel.prepare() {
+                error_callback(err.into());
             }
-            PollDescriptorsFlow::Return => return,
-            PollDescriptorsFlow::Ready {
+        }
+        PollDescriptorsFlow::Ready {
+            status,
+            avail_frames,
+            delay_frames,
+            stream_type,
+        } => {
+            assert_eq!(
+                stream_type,
+                StreamType::Output,
+                "expected output stream, but polling descriptors indicated input",
+            );
+            report_sample_rate(stream, sample_rate, error_callback);
+            if let Err(err) = process_output(
+                stream,
+                buffer,
                 status,
                 avail_frames,
                 delay_frames,
-                stream_type,
-            } => {
-                assert_eq!(
-                    stream_type,
-                    StreamType::Output,
-                    "expected output stream, but polling descriptors indicated input",
-                );
-                if let Err(err) = process_output(
-                    stream,
-                    &mut ctxt.buffer,
-                    status,
-                    avail_frames,
-                    delay_frames,
-                    data_callback,
-                    error_callback,
-                ) {
-                    error_callback(err.into());
-                }
+                data_callback,
+                error_callback,
+            ) {
+                error_callback(err.into());
             }
         }
     }
 }
 
+// Reports a sample rate changed by `set_sample_rate`, before the first callback at the new rate.
+fn report_sample_rate(
+    stream: &StreamInner,
+    reported: &mut SampleRate,
+    error_callback: &mut dyn FnMut(StreamError),
+) {
+    let sample_rate = stream.sample_rate();
+    if sample_rate != *reported {
+        *reported = sample_rate;
+        error_callback(StreamError::SampleRateChanged { sample_rate });
+    }
+}
+
 enum PollDescriptorsFlow {
     Continue,
     Return,
@@ -660,10 +1080,14 @@ enum PollDescriptorsFlow {
     XRun,
 }
 
+// How long the worker of a paused stream waits before checking whether it was played again.
+const PAUSED_POLL_INTERVAL_MS: i32 = 10;
+
 // This block is shared between both input and output stream worker functions.
 fn poll_descriptors_and_prepare_buffer(
     rx: &TriggerReceiver,
     stream: &StreamInner,
+    io: &mut MutexGuard<()>,
     ctxt: &mut StreamWorkerContext,
 ) -> Result<PollDescriptorsFlow, BackendSpecificError> {
     let StreamWorkerContext {
@@ -680,6 +1104,19 @@ fn poll_descriptors_and_prepare_buffer(
         revents: 0,
     });
 
+    // A paused device has nothing to poll; a stopped one would report an error right away. Wait
+    // on the self-pipe alone, waking up regularly to notice when the stream is played again.
+    if stream.paused.load(Ordering::Acquire) {
+        let res = MutexGuard::unlocked(io, || {
+            alsa::poll::poll(descriptors, PAUSED_POLL_INTERVAL_MS)
+        })?;
+        if res != 0 {
+            rx.clear_pipe();
+            return Ok(PollDescriptorsFlow::Return);
+        }
+        return Ok(PollDescriptorsFlow::Continue);
+    }
+
     // Add ALSA polling fds.
     let len = descriptors.len();
     descriptors.resize(
@@ -694,7 +1131,7 @@ fn poll_descriptors_and_prepare_buffer(
     debug_assert_eq!(filled, stream.num_descriptors);
 
     // Don't timeout, wait forever.
-    let res = alsa::poll::poll(descriptors, -1)?;
+    let res = MutexGuard::unlocked(io, || alsa::poll::poll(descriptors, -1))?;
     if res == 0 {
         let description = String::from("`alsa::poll()` spuriously returned");
         return Err(BackendSpecificError { description });
@@ -706,7 +1143,16 @@ fn poll_descriptors_and_prepare_buffer(
         return Ok(PollDescriptorsFlow::Return);
     }
 
-    let stream_type = match stream.channel.revents(&descriptors[1..])? {
+    prepare_buffer(stream, &descriptors[1..], buffer)
+}
+
+// Checks the polled ALSA descriptors of the stream, preparing the buffer if it is ready.
+fn prepare_buffer(
+    stream: &StreamInner,
+    descriptors: &[libc::pollfd],
+    buffer: &mut Vec<u8>,
+) -> Result<PollDescriptorsFlow, BackendSpecificError> {
+    let stream_type = match stream.channel.revents(descriptors)? {
         alsa::poll::Flags::OUT => StreamType::Output,
         alsa::poll::Flags::IN => StreamType::Input,
         _ => {
@@ -723,14 +1169,14 @@ fn poll_descriptors_and_prepare_buffer(
         res => res,
     }? as usize;
     let delay_frames = match status.get_delay() {
-        // Buffer underrun. TODO: Notify the user.
+        // Buffer underrun, reported once the stream fails to write.
         d if d < 0 => 0,
         d => d as usize,
     };
     let available_samples = avail_frames * stream.conf.channels as usize;
 
     // Only go on if there is at least `stream.period_len` samples.
-    if available_samples < stream.period_len {
+    if available_samples < stream.period_len.load(Ordering::Relaxed) {
         return Ok(PollDescriptorsFlow::Continue);
     }
 
@@ -760,12 +1206,14 @@ fn process_input(
     let len = buffer.len() / sample_format.sample_size();
     let data = unsafe { Data::from_parts(data, len, sample_format) };
     let callback = stream_timestamp(&status, stream.creation_instant)?;
-    let delay_duration = frames_to_duration(delay_frames, stream.conf.sample_rate);
+    let delay_duration = frames_to_duration(delay_frames, stream.sample_rate());
     let capture = callback
         .sub(delay_duration)
         .expect("`capture` is earlier than representation supported by `StreamInstant`");
     let timestamp = crate::InputStreamTimestamp { callback, capture };
-    let info = crate::InputCallbackInfo { timestamp };
+    let frames = (len / stream.conf.channels as usize) as u64;
+    let frame_position = stream.frame_position.fetch_add(frames, Ordering::Relaxed);
+    let info = crate::InputCallbackInfo::new(timestamp, frame_position);
     data_callback(&data, &info);
 
     Ok(())
@@ -790,19 +1238,20 @@ fn process_output(
         let len = buffer.len() / sample_format.sample_size();
         let mut data = unsafe { Data::from_parts(data, len, sample_format) };
         let callback = stream_timestamp(&status, stream.creation_instant)?;
-        let delay_duration = frames_to_duration(delay_frames, stream.conf.sample_rate);
+        let delay_duration = frames_to_duration(delay_frames, stream.sample_rate());
         let playback = callback
             .add(delay_duration)
             .expect("`playback` occurs beyond representation supported by `StreamInstant`");
         let timestamp = crate::OutputStreamTimestamp { callback, playback };
-        let info = crate::OutputCallbackInfo { timestamp };
+        let frames = (len / stream.conf.channels as usize) as u64;
+        let frame_position = stream.frame_position.fetch_add(frames, Ordering::Relaxed);
+        let info = crate::OutputCallbackInfo::new(timestamp, frame_position);
         data_callback(&mut data, &info);
     }
     loop {
         match stream.channel.io_bytes().writei(buffer) {
             Err(err) if err.errno() == nix::errno::Errno::EPIPE => {
-                // buffer underrun
-                // TODO: Notify the user of this.
+                error_callback(StreamError::Underrun { frames_lost: None });
                 let _ = stream.channel.try_recover(err, false);
             }
             Err(err) => {
@@ -898,7 +1347,8 @@ impl Stream {
         Stream {
             thread: Some(thread),
             inner,
-            trigger: tx,
+            trigger: Some(tx),
+            driven: None,
         }
     }
 
@@ -923,50 +1373,590 @@ impl Stream {
         Stream {
             thread: Some(thread),
             inner,
-            trigger: tx,
+            trigger: Some(tx),
+            driven: None,
+        }
+    }
+
+    fn new_driven(
+        inner: Arc<StreamInner>,
+        callback: DrivenCallback,
+        error_callback: Box<dyn FnMut(StreamError) + Send + 'static>,
+    ) -> Stream {
+        let driven = Driven {
+            callback,
+            error_callback,
+            buffer: vec![],
+            sample_rate: inner.sample_rate(),
+        };
+        Stream {
+            thread: None,
+            inner,
+            trigger: None,
+            driven: Some(Mutex::new(driven)),
+        }
+    }
+
+    /// The file descriptors of a driven stream, for integrating it into an event loop such as
+    /// mio or calloop. Call `on_ready` whenever one of them is ready.
+    ///
+    /// Returns an error for streams with a thread of their own, whose descriptors are polled by
+    /// that thread. A paused device may keep reporting its descriptors as ready, and they may
+    /// change when it is played again, so they should be deregistered while the stream is paused
+    /// and registered again once it is played.
+    pub fn poll_descriptors(&self) -> Result<Vec<PollDescriptor>, BackendSpecificError> {
+        let descriptors = self.driven_descriptors()?;
+        Ok(descriptors
+            .iter()
+            .map(|descriptor| PollDescriptor {
+                fd: descriptor.fd,
+                readable: descriptor.events & libc::POLLIN != 0,
+                writable: descriptor.events & libc::POLLOUT != 0,
+            })
+            .collect())
+    }
+
+    /// Processes the device of a driven stream after one of its `poll_descriptors` became ready,
+    /// calling the data callback if the device is ready for it.
+    ///
+    /// Never blocks, so it may be called spuriously. Does nothing while the stream is paused.
+    /// Returns an error for streams with a thread of their own; errors of the stream itself go to
+    /// its error callback.
+    pub fn on_ready(&self) -> Result<(), BackendSpecificError> {
+        let mut descriptors = self.driven_descriptors()?;
+        if self.inner.paused.load(Ordering::Acquire) {
+            return Ok(());
+        }
+        if alsa::poll::poll(&mut descriptors, 0)? != 0 {
+            self.process_ready(&descriptors);
+        }
+        Ok(())
+    }
+
+    // The ALSA descriptors of a driven stream.
+    fn driven_descriptors(&self) -> Result<Vec<libc::pollfd>, BackendSpecificError> {
+        if self.driven.is_none() {
+            let description = "the stream is not driven".to_string();
+            return Err(BackendSpecificError { description });
+        }
+        let mut descriptors = vec![
+            libc::pollfd {
+                fd: 0,
+                events: 0,
+                revents: 0,
+            };
+            self.inner.num_descriptors
+        ];
+        self.inner.channel.fill(&mut descriptors)?;
+        Ok(descriptors)
+    }
+
+    // Processes the descriptors of a driven stream, as polled by `Host::run` or `on_ready`.
+    fn process_ready(&self, descriptors: &[libc::pollfd]) {
+        let mut driven = match self.driven {
+            Some(ref driven) => driven.lock(),
+            None => return,
+        };
+        let Driven {
+            ref mut callback,
+            ref mut error_callback,
+            ref mut buffer,
+            ref mut sample_rate,
+        } = *driven;
+        let _io = self.inner.io.lock();
+        let flow = prepare_buffer(&self.inner, descriptors, buffer).unwrap_or_else(|err| {
+            error_callback(err.into());
+            PollDescriptorsFlow::Continue
+        });
+        match callback {
+            DrivenCallback::Input(data_callback) => handle_input_flow(
+                &self.inner,
+                flow,
+                buffer,
+                sample_rate,
+                &mut **data_callback,
+                &mut **error_callback,
+            ),
+            DrivenCallback::Output(data_callback) => handle_output_flow(
+                &self.inner,
+                flow,
+                buffer,
+                sample_rate,
+                &mut **data_callback,
+                &mut **error_callback,
+            ),
         }
     }
 }
 
 impl Drop for Stream {
     fn drop(&mut self) {
-        self.trigger.wakeup();
-        self.thread.take().unwrap().join().unwrap();
+        if let Some(ref trigger) = self.trigger {
+            trigger.wakeup();
+        }
+        if let Some(thread) = self.thread.take() {
+            thread.join().unwrap();
+        }
     }
 }
 
 impl StreamTrait for Stream {
     fn play(&self) -> Result<(), PlayStreamError> {
-        self.inner.channel.pause(false).ok();
+        if !self.inner.paused.load(Ordering::Acquire) {
+            return Ok(());
+        }
+        let channel = &self.inner.channel;
+        if !self.inner.stopped.load(Ordering::Acquire) {
+            channel.pause(false)?;
+        } else {
+            // The stream was stopped. Playback restarts once the worker writes to it, capture has
+            // to be started explicitly.
+            channel.prepare()?;
+            if channel.info()?.get_stream() == alsa::Direction::Capture {
+                channel.start()?;
+            }
+        }
+        self.inner.paused.store(false, Ordering::Release);
         Ok(())
     }
+
     fn pause(&self) -> Result<(), PauseStreamError> {
-        self.inner.channel.pause(true).ok();
+        if self.inner.paused.swap(true, Ordering::AcqRel) {
+            return Ok(());
+        }
+        let channel = &self.inner.channel;
+        // Only a running device can be paused in hardware, one that has not started yet is
+        // stopped as well.
+        let stop = !self.inner.can_pause || channel.state() != alsa::pcm::State::Running;
+        self.inner.stopped.store(stop, Ordering::Release);
+        let result = if stop {
+            channel.drop()
+        } else {
+            channel.pause(true)
+        };
+        if let Err(err) = result {
+            self.inner.paused.store(false, Ordering::Release);
+            return Err(err.into());
+        }
         Ok(())
     }
+
+    fn drain(&self) -> Result<(), PauseStreamError> {
+        if self.inner.paused.swap(true, Ordering::AcqRel) {
+            return Ok(());
+        }
+        // The device ends up stopped either way, and has to be prepared again to resume.
+        self.inner.stopped.store(true, Ordering::Release);
+        let channel = &self.inner.channel;
+        let result = match channel.info() {
+            Ok(info) if info.get_stream() == alsa::Direction::Playback => drain(channel),
+            Ok(_) => channel.drop(),
+            Err(err) => Err(err),
+        };
+        if let Err(err) = result {
+            self.inner.paused.store(false, Ordering::Release);
+            return Err(err.into());
+        }
+        Ok(())
+    }
+
+    fn state(&self) -> StreamState {
+        if self.inner.paused.load(Ordering::Acquire) {
+            StreamState::Paused
+        } else {
+            StreamState::Playing
+        }
+    }
+
+    fn latency(&self) -> Option<StreamLatency> {
+        let delay = self.inner.channel.delay().ok()?.max(0) as usize;
+        Some(StreamLatency {
+            buffered: Some(frames_to_duration(delay, self.inner.sample_rate())),
+            ..Default::default()
+        })
+    }
+
+    fn buffer_size(&self) -> Option<FrameCount> {
+        let (buffer, _) = self.inner.channel.get_params().ok()?;
+        Some(buffer as FrameCount)
+    }
+
+    fn set_buffer_size(&self, frames: FrameCount) -> Result<FrameCount, ReconfigureStreamError> {
+        let channel = &self.inner.channel;
+        let hw_params = alsa::pcm::HwParams::any(channel)?;
+        let min = hw_params.get_buffer_size_min()?;
+        let max = hw_params.get_buffer_size_max()?;
+        if !(min..=max).contains(&(frames as alsa::pcm::Frames)) {
+            return Err(ReconfigureStreamError::StreamConfigNotSupported);
+        }
+        let periods = current_periods(channel)?;
+        reconfigure(&self.inner, frames, self.inner.sample_rate(), periods)?;
+        let (buffer, _) = channel.get_params()?;
+        Ok(buffer as FrameCount)
+    }
+
+    fn set_sample_rate(&self, sample_rate: SampleRate) -> Result<(), ReconfigureStreamError> {
+        let channel = &self.inner.channel;
+        let hw_params = alsa::pcm::HwParams::any(channel)?;
+        let min = hw_params.get_rate_min()?;
+        let max = hw_params.get_rate_max()?;
+        if !(min..=max).contains(&sample_rate.0) {
+            return Err(ReconfigureStreamError::StreamConfigNotSupported);
+        }
+        let (buffer, _) = channel.get_params()?;
+        let periods = current_periods(channel)?;
+        reconfigure(&self.inner, buffer as FrameCount, sample_rate, periods)
+    }
+
+    fn periods(&self) -> Option<u32> {
+        current_periods(&self.inner.channel).ok()
+    }
+
+    fn set_periods(&self, periods: u32) -> Result<u32, ReconfigureStreamError> {
+        let channel = &self.inner.channel;
+        let hw_params = alsa::pcm::HwParams::any(channel)?;
+        let min = hw_params.get_buffer_size_min()?;
+        let max = hw_params.get_buffer_size_max()?;
+        let (_, period) = channel.get_params()?;
+        let frames = period as FrameCount * periods;
+        if periods < 2 || !(min..=max).contains(&(frames as alsa::pcm::Frames)) {
+            return Err(ReconfigureStreamError::StreamConfigNotSupported);
+        }
+        reconfigure(&self.inner, frames, self.inner.sample_rate(), periods)?;
+        current_periods(channel).map_err(Into::into)
+    }
+}
+
+// Stops the device and sets it up again with a buffer of `frames` frames at `sample_rate`. The
+// previous configuration is restored if the device does not accept the new one.
+fn reconfigure(
+    stream: &StreamInner,
+    frames: FrameCount,
+    sample_rate: SampleRate,
+    periods: u32,
+) -> Result<(), ReconfigureStreamError> {
+    let channel = &stream.channel;
+    // Keep the worker away from the device while it is set up again.
+    let _io = stream.io.lock();
+    let direction = channel.info()?.get_stream();
+    let (previous_frames, _) = channel.get_params()?;
+    let previous_rate = stream.sample_rate();
+    let previous_periods = current_periods(channel)?;
+    channel.drop()?;
+    let result = apply_config(stream, direction, frames, sample_rate, periods);
+    if result.is_err() {
+        apply_config(
+            stream,
+            direction,
+            previous_frames as FrameCount,
+            previous_rate,
+            previous_periods,
+        )?;
+    }
+
+    // A paused stream is resumed like a stopped one, otherwise capture has to be started again
+    // while playback starts once the worker writes to it.
+    if stream.paused.load(Ordering::Acquire) {
+        stream.stopped.store(true, Ordering::Release);
+    } else if direction == alsa::Direction::Capture {
+        channel.start()?;
+    }
+    result
+}
+
+// Sets a stopped device up with a buffer of `frames` frames split into `periods` periods at
+// `sample_rate`, leaving it prepared.
+fn apply_config(
+    stream: &StreamInner,
+    direction: alsa::Direction,
+    frames: FrameCount,
+    sample_rate: SampleRate,
+    periods: u32,
+) -> Result<(), ReconfigureStreamError> {
+    let channel = &stream.channel;
+    let conf = StreamConfig {
+        channels: stream.conf.channels,
+        sample_rate,
+        buffer_size: BufferSize::Fixed(frames),
+    };
+    set_hw_params_from_format(channel, &conf, stream.sample_format, periods).map_err(|err| {
+        match err {
+            BuildStreamError::BackendSpecific { err } => err.into(),
+            _ => ReconfigureStreamError::StreamConfigNotSupported,
+        }
+    })?;
+    // The device picks the supported rate nearest to the requested one.
+    if channel.hw_params_current()?.get_rate()? != sample_rate.0 {
+        return Err(ReconfigureStreamError::StreamConfigNotSupported);
+    }
+    let period_len = set_sw_params_from_format(channel, &conf, direction)?;
+    channel.prepare()?;
+    stream.period_len.store(period_len, Ordering::Relaxed);
+    stream.sample_rate.store(sample_rate.0, Ordering::Relaxed);
+    Ok(())
+}
+
+// The number of periods the buffer of the device is split into.
+fn current_periods(channel: &alsa::PCM) -> Result<u32, alsa::Error> {
+    let (buffer, period) = channel.get_params()?;
+    Ok((buffer / period.max(1)) as u32)
+}
+
+// Waits for a playback device to play the frames written to it, after which it is stopped.
+//
+// Streams are opened non-blocking, so `drain` returns right away and the state is polled until
+// the device is done.
+fn drain(channel: &alsa::PCM) -> Result<(), alsa::Error> {
+    match channel.drain() {
+        Err(err) if err.errno() == nix::errno::Errno::EAGAIN => (),
+        result => result?,
+    }
+    while channel.state() == alsa::pcm::State::Draining {
+        thread::sleep(std::time::Duration::from_millis(1));
+    }
+    Ok(())
+}
+
+/// Returns the native-endian ALSA format used to open a stream of the given `SampleFormat`.
+///
+/// Returns `None` for formats ALSA cannot represent.
+pub fn alsa_format_from_sample_format(sample_format: SampleFormat) -> Option<alsa::pcm::Format> {
+    let format = match sample_format {
+        SampleFormat::I16 | SampleFormat::Iec61937 => alsa::pcm::Format::s16(),
+        SampleFormat::U16 => alsa::pcm::Format::u16(),
+        SampleFormat::F32 => alsa::pcm::Format::float(),
+        // ALSA has no half-precision float format.
+        SampleFormat::F16 => return None,
+        SampleFormat::MuLaw => alsa::pcm::Format::MuLaw,
+        SampleFormat::DsdU8 => alsa::pcm::Format::DSDU8,
+        SampleFormat::DsdU32 if cfg!(target_endian = "big") => alsa::pcm::Format::DSDU32BE,
+        SampleFormat::DsdU32 => alsa::pcm::Format::DSDU32LE,
+        SampleFormat::Dop => alsa::pcm::Format::s32(),
+    };
+    Some(format)
+}
+
+/// Returns the `SampleFormat` matching a native-endian ALSA format.
+///
+/// Returns `None` for formats cpal has no equivalent for, including every non-native-endian
+/// format. S16 maps to `I16` and S32 has no match, as passthrough and DoP streams cannot be told
+/// apart from PCM by their ALSA format.
+#[cfg_attr(not(feature = "platform-formats"), allow(dead_code))]
+pub fn sample_format_from_alsa_format(format: alsa::pcm::Format) -> Option<SampleFormat> {
+    let dsd_u32 = if cfg!(target_endian = "big") {
+        alsa::pcm::Format::DSDU32BE
+    } else {
+        alsa::pcm::Format::DSDU32LE
+    };
+    let sample_format = if format == alsa::pcm::Format::s16() {
+        SampleFormat::I16
+    } else if format == alsa::pcm::Format::u16() {
+        SampleFormat::U16
+    } else if format == alsa::pcm::Format::float() {
+        SampleFormat::F32
+    } else if format == alsa::pcm::Format::MuLaw {
+        SampleFormat::MuLaw
+    } else if format == alsa::pcm::Format::DSDU8 {
+        SampleFormat::DsdU8
+    } else if format == dsd_u32 {
+        SampleFormat::DsdU32
+    } else {
+        return None;
+    };
+    Some(sample_format)
+}
+
+// The channel maps the device offers, at most one per channel count.
+//
+// The maps are read from their textual form, as `alsa::pcm::ChmapPosition` lacks several common
+// positions and panics on converting them.
+fn query_channel_layouts(handle: &alsa::PCM) -> Vec<ChannelLayout> {
+    let mut layouts: Vec<ChannelLayout> = Vec::new();
+    for (_, chmap) in handle.query_chmaps() {
+        let mut text = String::new();
+        if write!(text, "{}", chmap).is_err() {
+            continue;
+        }
+        let positions = text
+            .split_whitespace()
+            .map(channel_position_from_chmap_name)
+            .collect();
+        let layout = ChannelLayout::new(positions);
+        if !layouts.iter().any(|l| l.channels() == layout.channels()) {
+            layouts.push(layout);
+        }
+    }
+    layouts
+}
+
+fn channel_position_from_chmap_name(name: &str) -> ChannelPosition {
+    match name {
+        "MONO" | "FC" => ChannelPosition::FrontCenter,
+        "FL" => ChannelPosition::FrontLeft,
+        "FR" => ChannelPosition::FrontRight,
+        "RL" => ChannelPosition::BackLeft,
+        "RR" => ChannelPosition::BackRight,
+        "LFE" => ChannelPosition::LowFrequency,
+        "SL" => ChannelPosition::SideLeft,
+        "SR" => ChannelPosition::SideRight,
+        "RC" => ChannelPosition::BackCenter,
+        "FLC" => ChannelPosition::FrontLeftOfCenter,
+        "FRC" => ChannelPosition::FrontRightOfCenter,
+        "TC" => ChannelPosition::TopCenter,
+        "TFL" => ChannelPosition::TopFrontLeft,
+        "TFC" => ChannelPosition::TopFrontCenter,
+        "TFR" => ChannelPosition::TopFrontRight,
+        "TRL" => ChannelPosition::TopBackLeft,
+        "TRC" => ChannelPosition::TopBackCenter,
+        "TRR" => ChannelPosition::TopBackRight,
+        _ => ChannelPosition::Unknown,
+    }
+}
+
+// Whether `name` refers to one of the ALSA PCM plugins driving an S/PDIF or HDMI output.
+fn is_iec958_device(name: &str) -> bool {
+    let plugin = name.split(':').next().unwrap_or(name);
+    plugin == "iec958" || plugin == "spdif" || plugin == "hdmi"
+}
+
+// Builds the device name that opens `name` with the IEC 60958 channel status flagged as non-audio,
+// which is required for the receiver to decode an IEC 61937 bitstream.
+//
+// Returns `None` if `name` is not an S/PDIF or HDMI device or the sample rate has no channel
+// status encoding.
+fn iec958_passthrough_name(name: &str, sample_rate: SampleRate) -> Option<String> {
+    if !is_iec958_device(name) {
+        return None;
+    }
+    // IEC 60958-3 consumer channel status byte 3, sampling frequency.
+    let aes3 = match sample_rate.0 {
+        22_050 => 0x04,
+        24_000 => 0x06,
+        32_000 => 0x03,
+        44_100 => 0x00,
+        48_000 => 0x02,
+        88_200 => 0x08,
+        96_000 => 0x0a,
+        176_400 => 0x0c,
+        192_000 => 0x0e,
+        _ => return None,
+    };
+    // AES0: consumer, non-audio, copyright not asserted. AES1: original, PCM coder category.
+    let params = format!("AES0=0x06,AES1=0x82,AES2=0x00,AES3=0x{:02x}", aes3);
+    let separator = if name.contains(':') { ',' } else { ':' };
+    Some(format!("{}{}{}", name, separator, params))
+}
+
+// How a device is connected, going by its plugin and the driver of its card.
+fn transport(plugin: &str, driver: Option<&str>) -> Transport {
+    match (plugin, driver) {
+        ("hdmi", _) => Transport::Hdmi,
+        ("bluealsa", _) => Transport::Bluetooth,
+        ("pulse" | "pipewire" | "jack" | "null", _) => Transport::Virtual,
+        (_, Some("USB-Audio")) => Transport::Usb,
+        (_, Some("Loopback" | "Dummy")) => Transport::Virtual,
+        (_, Some("HDA-Intel")) => Transport::BuiltIn,
+        _ => Transport::Unknown,
+    }
+}
+
+// The profile and codec a BlueALSA device is opened with, going by the `PROFILE` and `CODEC`
+// parameters of its name, such as `bluealsa:DEV=00:11:22:33:44:55,PROFILE=sco`. BlueALSA opens
+// A2DP devices if no profile is named, and the codec is only known if named.
+fn bluealsa_profile(name: &str) -> (BluetoothProfile, Option<String>) {
+    let params = name
+        .split_once(':')
+        .map(|(_, params)| params)
+        .unwrap_or_default();
+    let param = |key: &str| {
+        params
+            .split(',')
+            .find_map(|param| param.strip_prefix(key)?.strip_prefix('='))
+    };
+    let profile = match param("PROFILE") {
+        Some(profile) if profile.eq_ignore_ascii_case("sco") => BluetoothProfile::HandsFree,
+        _ => BluetoothProfile::A2dp,
+    };
+    (profile, param("CODEC").map(str::to_owned))
+}
+
+// The name of the mixer controlling the card of the PCM device `name`, such as `hw:PCH` for
+// `front:CARD=PCH,DEV=0`. Devices without a card, such as `default`, use the default mixer.
+fn mixer_name(name: &str) -> String {
+    let params = match name.split_once(':') {
+        Some((_, params)) => params,
+        None => return "default".to_owned(),
+    };
+    let card = params
+        .split(',')
+        .find_map(|param| param.strip_prefix("CARD="))
+        .or_else(|| {
+            params
+                .split(',')
+                .next()
+                .filter(|param| !param.contains('='))
+        });
+    match card {
+        Some(card) => format!("hw:{}", card),
+        None => "default".to_owned(),
+    }
+}
+
+// The number of periods a buffer of `BufferSize::Fixed` frames is split into, unless changed with
+// `set_periods`.
+const DEFAULT_PERIODS: u32 = 4;
+
+// The buffer sizes of `periods` periods that `hw_params` allow.
+fn buffer_size_range(
+    hw_params: &alsa::pcm::HwParams,
+    periods: u32,
+) -> Result<SupportedBufferSize, alsa::Error> {
+    Ok(limit_buffer_size(
+        (
+            hw_params.get_buffer_size_min()?,
+            hw_params.get_buffer_size_max()?,
+        ),
+        (
+            hw_params.get_period_size_min()?,
+            hw_params.get_period_size_max()?,
+        ),
+        periods,
+    ))
+}
+
+// Narrows the range of buffer sizes down to the ones whose periods are in the range of period
+// sizes, falling back to the buffer sizes if none are.
+fn limit_buffer_size(
+    (min_buffer, max_buffer): (alsa::pcm::Frames, alsa::pcm::Frames),
+    (min_period, max_period): (alsa::pcm::Frames, alsa::pcm::Frames),
+    periods: u32,
+) -> SupportedBufferSize {
+    let periods = periods as alsa::pcm::Frames;
+    let min = min_buffer.max(min_period.saturating_mul(periods));
+    let max = max_buffer.min(max_period.saturating_mul(periods));
+    let (min, max) = if min <= max {
+        (min, max)
+    } else {
+        (min_buffer, max_buffer)
+    };
+    let clamp = |frames: alsa::pcm::Frames| frames.clamp(0, FrameCount::MAX as _) as FrameCount;
+    SupportedBufferSize::Range {
+        min: clamp(min),
+        max: clamp(max),
+    }
 }
 
 fn set_hw_params_from_format(
     pcm_handle: &alsa::pcm::PCM,
     config: &StreamConfig,
     sample_format: SampleFormat,
-) -> Result<bool, BackendSpecificError> {
+    periods: u32,
+) -> Result<bool, BuildStreamError> {
     let hw_params = alsa::pcm::HwParams::any(pcm_handle)?;
     hw_params.set_access(alsa::pcm::Access::RWInterleaved)?;
 
-    let sample_format = if cfg!(target_endian = "big") {
-        match sample_format {
-            SampleFormat::I16 => alsa::pcm::Format::S16BE,
-            SampleFormat::U16 => alsa::pcm::Format::U16BE,
-            SampleFormat::F32 => alsa::pcm::Format::FloatBE,
-        }
-    } else {
-        match sample_format {
-            SampleFormat::I16 => alsa::pcm::Format::S16LE,
-            SampleFormat::U16 => alsa::pcm::Format::U16LE,
-            SampleFormat::F32 => alsa::pcm::Format::FloatLE,
-        }
-    };
+    let sample_format = alsa_format_from_sample_format(sample_format)
+        .ok_or(BuildStreamError::StreamConfigNotSupported)?;
 
     hw_params.set_format(sample_format)?;
     hw_params.set_rate(config.sample_rate.0, alsa::ValueOr::Nearest)?;
@@ -974,9 +1964,20 @@ fn set_hw_params_from_format(
 
     match config.buffer_size {
         BufferSize::Fixed(v) => {
-            hw_params.set_period_size_near((v / 4) as alsa::pcm::Frames, alsa::ValueOr::Nearest)?;
+            hw_params
+                .set_period_size_near((v / periods) as alsa::pcm::Frames, alsa::ValueOr::Nearest)?;
             hw_params.set_buffer_size(v as alsa::pcm::Frames)?;
         }
+        BufferSize::Preferred { .. } => {
+            let supported = buffer_size_range(&hw_params, periods)?;
+            let v = config
+                .buffer_size
+                .negotiate(&supported)?
+                .unwrap_or_default();
+            hw_params
+                .set_period_size_near((v / periods) as alsa::pcm::Frames, alsa::ValueOr::Nearest)?;
+            hw_params.set_buffer_size_near(v as alsa::pcm::Frames)?;
+        }
         BufferSize::Default => {
             // These values together represent a moderate latency and wakeup interval.
             // Without them, we are at the mercy of the device
@@ -1070,9 +2071,75 @@ impl From<alsa::Error> for PauseStreamError {
     }
 }
 
+impl From<alsa::Error> for DirectMonitoringError {
+    fn from(err: alsa::Error) -> Self {
+        let err: BackendSpecificError = err.into();
+        err.into()
+    }
+}
+
+impl From<alsa::Error> for ReconfigureStreamError {
+    fn from(err: alsa::Error) -> Self {
+        let err: BackendSpecificError = err.into();
+        err.into()
+    }
+}
+
 impl From<alsa::Error> for StreamError {
     fn from(err: alsa::Error) -> Self {
         let err: BackendSpecificError = err.into();
         err.into()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{bluealsa_profile, limit_buffer_size, transport};
+    use crate::{BluetoothProfile, SupportedBufferSize, Transport};
+
+    #[test]
+    fn buffer_size_range_respects_period_sizes() {
+        assert_eq!(
+            limit_buffer_size((64, 65536), (32, 8192), 4),
+            SupportedBufferSize::Range {
+                min: 128,
+                max: 32768
+            }
+        );
+        assert_eq!(
+            limit_buffer_size((64, 65536), (16, 1 << 20), 2),
+            SupportedBufferSize::Range {
+                min: 64,
+                max: 65536
+            }
+        );
+        // Periods that never fit the buffer leave its own limits.
+        assert_eq!(
+            limit_buffer_size((64, 256), (1024, 2048), 4),
+            SupportedBufferSize::Range { min: 64, max: 256 }
+        );
+    }
+
+    #[test]
+    fn transport_from_plugin_and_driver() {
+        assert_eq!(transport("hdmi", Some("HDA-Intel")), Transport::Hdmi);
+        assert_eq!(transport("front", Some("HDA-Intel")), Transport::BuiltIn);
+        assert_eq!(transport("hw", Some("USB-Audio")), Transport::Usb);
+        assert_eq!(transport("pipewire", None), Transport::Virtual);
+        assert_eq!(transport("default", None), Transport::Unknown);
+    }
+
+    #[test]
+    fn bluealsa_profile_from_name() {
+        let dev = "bluealsa:DEV=00:11:22:33:44:55";
+        assert_eq!(bluealsa_profile(dev), (BluetoothProfile::A2dp, None));
+        assert_eq!(
+            bluealsa_profile(&format!("{},PROFILE=sco", dev)),
+            (BluetoothProfile::HandsFree, None)
+        );
+        assert_eq!(
+            bluealsa_profile(&format!("{},PROFILE=a2dp,CODEC=aac", dev)),
+            (BluetoothProfile::A2dp, Some("aac".to_owned()))
+        );
+    }
+}
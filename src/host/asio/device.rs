@@ -7,9 +7,12 @@ use super::sys;
 use std::hash::{Hash, Hasher};
 use std::sync::Arc;
 use BackendSpecificError;
+use BufferLayout;
 use DefaultStreamConfigError;
+use DeviceDirection;
 use DeviceNameError;
 use DevicesError;
+use DirectMonitoringError;
 use SampleFormat;
 use SampleRate;
 use SupportedBufferSize;
@@ -84,6 +87,8 @@ impl Device {
                     max_sample_rate: rate,
                     buffer_size: f.buffer_size.clone(),
                     sample_format: f.sample_format.clone(),
+                    layout: BufferLayout::NonInterleaved,
+                    channel_layout: None,
                 })
             }
         }
@@ -121,6 +126,8 @@ impl Device {
                     max_sample_rate: rate,
                     buffer_size: f.buffer_size.clone(),
                     sample_format: f.sample_format.clone(),
+                    layout: BufferLayout::NonInterleaved,
+                    channel_layout: None,
                 })
             }
         }
@@ -145,6 +152,8 @@ impl Device {
             sample_rate,
             buffer_size,
             sample_format,
+            layout: BufferLayout::NonInterleaved,
+            channel_layout: None,
         })
     }
 
@@ -165,8 +174,38 @@ impl Device {
             sample_rate,
             buffer_size,
             sample_format,
+            layout: BufferLayout::NonInterleaved,
+            channel_layout: None,
         })
     }
+
+    /// The names the driver gives the channels in `direction`.
+    pub fn channel_names(
+        &self,
+        direction: DeviceDirection,
+    ) -> Result<Vec<String>, DeviceNameError> {
+        let is_input = direction == DeviceDirection::Input;
+        self.driver.channel_names(is_input).map_err(|err| {
+            let description = format!("{}", err);
+            BackendSpecificError { description }.into()
+        })
+    }
+
+    /// Routes all inputs to the first output in the driver, or stops routing them.
+    pub fn set_direct_monitoring(&self, enabled: bool) -> Result<(), DirectMonitoringError> {
+        self.driver
+            .set_input_monitor(-1, 0, enabled)
+            .map_err(|e| match e {
+                sys::AsioError::NoDrivers | sys::AsioError::InvalidInput => {
+                    DirectMonitoringError::NotSupported
+                }
+                sys::AsioError::HardwareMalfunction => DirectMonitoringError::DeviceNotAvailable,
+                err => {
+                    let description = format!("{}", err);
+                    BackendSpecificError { description }.into()
+                }
+            })
+    }
 }
 
 impl Devices {
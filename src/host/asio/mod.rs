@@ -3,8 +3,9 @@ extern crate parking_lot;
 
 use crate::{
     BuildStreamError, Data, DefaultStreamConfigError, DeviceNameError, DevicesError,
-    InputCallbackInfo, OutputCallbackInfo, PauseStreamError, PlayStreamError, SampleFormat,
-    StreamConfig, StreamError, SupportedStreamConfig, SupportedStreamConfigsError,
+    DirectMonitoringError, FrameCount, InputCallbackInfo, OutputCallbackInfo, PauseStreamError,
+    PlayStreamError, SampleFormat, StreamConfig, StreamError, StreamState, SupportedStreamConfig,
+    SupportedStreamConfigsError,
 };
 use traits::{DeviceTrait, HostTrait, StreamTrait};
 
@@ -51,6 +52,12 @@ impl HostTrait for Host {
         // ASIO has no concept of a default device, so just use the first.
         self.output_devices().ok().and_then(|mut ds| ds.next())
     }
+
+    // ASIO loads one driver at a time, when a device of it is first opened.
+    fn backend_version(&self) -> Option<String> {
+        let driver = self.asio.loaded_driver()?;
+        Some(format!("{} {}", driver.name(), driver.version()))
+    }
 }
 
 impl DeviceTrait for Device {
@@ -109,6 +116,17 @@ impl DeviceTrait for Device {
     {
         Device::build_output_stream_raw(self, config, sample_format, data_callback, error_callback)
     }
+
+    fn set_direct_monitoring(&self, enabled: bool) -> Result<(), DirectMonitoringError> {
+        Device::set_direct_monitoring(self, enabled)
+    }
+
+    fn channel_names(
+        &self,
+        direction: crate::DeviceDirection,
+    ) -> Result<Vec<String>, DeviceNameError> {
+        Device::channel_names(self, direction)
+    }
 }
 
 impl StreamTrait for Stream {
@@ -119,4 +137,17 @@ impl StreamTrait for Stream {
     fn pause(&self) -> Result<(), PauseStreamError> {
         Stream::pause(self)
     }
+
+    fn state(&self) -> StreamState {
+        Stream::state(self)
+    }
+
+    fn buffer_size(&self) -> Option<FrameCount> {
+        Some(Stream::buffer_size(self))
+    }
+
+    fn periods(&self) -> Option<u32> {
+        // The driver alternates between two halves of its buffer.
+        Some(2)
+    }
 }
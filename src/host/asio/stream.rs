@@ -5,9 +5,9 @@ use self::num_traits::PrimInt;
 use super::parking_lot::Mutex;
 use super::Device;
 use crate::{
-    BackendSpecificError, BufferSize, BuildStreamError, Data, InputCallbackInfo,
+    BackendSpecificError, BufferSize, BuildStreamError, Data, FrameCount, InputCallbackInfo,
     OutputCallbackInfo, PauseStreamError, PlayStreamError, Sample, SampleFormat, StreamConfig,
-    StreamError,
+    StreamError, StreamState, SupportedBufferSize,
 };
 use std;
 use std::sync::atomic::{AtomicBool, Ordering};
@@ -38,6 +38,8 @@ pub struct Stream {
     driver: Arc<sys::Driver>,
     asio_streams: Arc<Mutex<sys::AsioStreams>>,
     callback_id: sys::CallbackId,
+    // The number of frames of each buffer the driver was set up with.
+    buffer_size: FrameCount,
 }
 
 impl Stream {
@@ -50,6 +52,18 @@ impl Stream {
         self.playing.store(false, Ordering::SeqCst);
         Ok(())
     }
+
+    pub fn state(&self) -> StreamState {
+        if self.playing.load(Ordering::SeqCst) {
+            StreamState::Playing
+        } else {
+            StreamState::Paused
+        }
+    }
+
+    pub fn buffer_size(&self) -> FrameCount {
+        self.buffer_size
+    }
 }
 
 impl Device {
@@ -88,6 +102,8 @@ impl Device {
         // Set the input callback.
         // This is most performance critical part of the ASIO bindings.
         let config = config.clone();
+        // The number of frames passed to or from the callback so far.
+        let mut frame_position = 0u64;
         let callback_id = self.driver.add_callback(move |callback_info| unsafe {
             // If not playing return early.
             if !playing.load(Ordering::SeqCst) {
@@ -109,6 +125,7 @@ impl Device {
                 asio_stream: &sys::AsioStream,
                 asio_info: &sys::CallbackInfo,
                 sample_rate: crate::SampleRate,
+                frame_position: u64,
                 from_endianness: F,
             ) where
                 A: AsioSample,
@@ -138,7 +155,7 @@ impl Device {
                     .sub(delay)
                     .expect("`capture` occurs before origin of alsa `StreamInstant`");
                 let timestamp = crate::InputStreamTimestamp { callback, capture };
-                let info = InputCallbackInfo { timestamp };
+                let info = InputCallbackInfo::new(timestamp, frame_position);
                 data_callback(&data, &info);
             }
 
@@ -150,6 +167,7 @@ impl Device {
                         asio_stream,
                         callback_info,
                         config.sample_rate,
+                        frame_position,
                         from_le,
                     );
                 }
@@ -160,6 +178,7 @@ impl Device {
                         asio_stream,
                         callback_info,
                         config.sample_rate,
+                        frame_position,
                         from_be,
                     );
                 }
@@ -174,6 +193,7 @@ impl Device {
                         asio_stream,
                         callback_info,
                         config.sample_rate,
+                        frame_position,
                         std::convert::identity::<f32>,
                     );
                 }
@@ -188,6 +208,7 @@ impl Device {
                         asio_stream,
                         callback_info,
                         config.sample_rate,
+                        frame_position,
                         from_le,
                     );
                 }
@@ -198,6 +219,7 @@ impl Device {
                         asio_stream,
                         callback_info,
                         config.sample_rate,
+                        frame_position,
                         from_be,
                     );
                 }
@@ -211,6 +233,7 @@ impl Device {
                         asio_stream,
                         callback_info,
                         config.sample_rate,
+                        frame_position,
                         std::convert::identity::<f64>,
                     );
                 }
@@ -221,6 +244,7 @@ impl Device {
                     unsupported_format_pair
                 ),
             }
+            frame_position += asio_stream.buffer_size as u64;
         });
 
         let driver = self.driver.clone();
@@ -234,6 +258,7 @@ impl Device {
             driver,
             asio_streams,
             callback_id,
+            buffer_size: buffer_size as FrameCount,
         })
     }
 
@@ -271,6 +296,8 @@ impl Device {
         let asio_streams = self.asio_streams.clone();
 
         let config = config.clone();
+        // The number of frames passed to or from the callback so far.
+        let mut frame_position = 0u64;
         let callback_id = self.driver.add_callback(move |callback_info| unsafe {
             // If not playing, return early.
             if !playing.load(Ordering::SeqCst) {
@@ -317,6 +344,7 @@ impl Device {
                 asio_stream: &sys::AsioStream,
                 asio_info: &sys::CallbackInfo,
                 sample_rate: crate::SampleRate,
+                frame_position: u64,
                 to_endianness: F,
             ) where
                 A: Sample,
@@ -336,7 +364,7 @@ impl Device {
                     .add(delay)
                     .expect("`playback` occurs beyond representation supported by `StreamInstant`");
                 let timestamp = crate::OutputStreamTimestamp { callback, playback };
-                let info = OutputCallbackInfo { timestamp };
+                let info = OutputCallbackInfo::new(timestamp, frame_position);
                 data_callback(&mut data, &info);
 
                 // 2. Silence ASIO channels if necessary.
@@ -371,6 +399,7 @@ impl Device {
                         asio_stream,
                         callback_info,
                         config.sample_rate,
+                        frame_position,
                         to_le,
                     );
                 }
@@ -382,6 +411,7 @@ impl Device {
                         asio_stream,
                         callback_info,
                         config.sample_rate,
+                        frame_position,
                         to_be,
                     );
                 }
@@ -397,6 +427,7 @@ impl Device {
                         asio_stream,
                         callback_info,
                         config.sample_rate,
+                        frame_position,
                         std::convert::identity::<f32>,
                     );
                 }
@@ -412,6 +443,7 @@ impl Device {
                         asio_stream,
                         callback_info,
                         config.sample_rate,
+                        frame_position,
                         to_le,
                     );
                 }
@@ -423,6 +455,7 @@ impl Device {
                         asio_stream,
                         callback_info,
                         config.sample_rate,
+                        frame_position,
                         to_be,
                     );
                 }
@@ -437,6 +470,7 @@ impl Device {
                         asio_stream,
                         callback_info,
                         config.sample_rate,
+                        frame_position,
                         std::convert::identity::<f64>,
                     );
                 }
@@ -447,6 +481,7 @@ impl Device {
                     unsupported_format_pair
                 ),
             }
+            frame_position += asio_stream.buffer_size as u64;
         });
 
         let driver = self.driver.clone();
@@ -460,6 +495,7 @@ impl Device {
             driver,
             asio_streams,
             callback_id,
+            buffer_size: buffer_size as FrameCount,
         })
     }
 
@@ -483,10 +519,7 @@ impl Device {
         let num_channels = config.channels as usize;
         let ref mut streams = *self.asio_streams.lock();
 
-        let buffer_size = match config.buffer_size {
-            BufferSize::Fixed(v) => Some(v as i32),
-            BufferSize::Default => None,
-        };
+        let buffer_size = self.requested_buffer_size(config)?;
 
         // Either create a stream if thers none or had back the
         // size of the current one.
@@ -512,6 +545,25 @@ impl Device {
         }
     }
 
+    /// The buffer size to set the driver up with, or `None` for its preferred size.
+    fn requested_buffer_size(
+        &self,
+        config: &StreamConfig,
+    ) -> Result<Option<i32>, BuildStreamError> {
+        let supported = match config.buffer_size {
+            BufferSize::Preferred { .. } => {
+                let (min, max) = self.driver.buffersize_range().map_err(build_stream_err)?;
+                SupportedBufferSize::Range {
+                    min: min as FrameCount,
+                    max: max as FrameCount,
+                }
+            }
+            _ => SupportedBufferSize::Unknown,
+        };
+        let frames = config.buffer_size.negotiate(&supported)?;
+        Ok(frames.map(|v| v as i32))
+    }
+
     /// Create a new CPAL Output Stream.
     ///
     /// If there is no existing ASIO Output Stream it will be created.
@@ -530,10 +582,7 @@ impl Device {
         let num_channels = config.channels as usize;
         let ref mut streams = *self.asio_streams.lock();
 
-        let buffer_size = match config.buffer_size {
-            BufferSize::Fixed(v) => Some(v as i32),
-            BufferSize::Default => None,
-        };
+        let buffer_size = self.requested_buffer_size(config)?;
 
         // Either create a stream if thers none or had back the
         // size of the current one.
@@ -673,10 +722,10 @@ fn check_config(
             return Err(BuildStreamError::StreamConfigNotSupported);
         }
     }
-    // unsigned formats are not supported by asio
+    // unsigned and half-precision formats are not supported by asio
     match sample_format {
         SampleFormat::I16 | SampleFormat::F32 => (),
-        SampleFormat::U16 => return Err(BuildStreamError::StreamConfigNotSupported),
+        _ => return Err(BuildStreamError::StreamConfigNotSupported),
     }
     if *channels > num_asio_channels {
         return Err(BuildStreamError::StreamConfigNotSupported);
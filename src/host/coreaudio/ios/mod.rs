@@ -19,13 +19,14 @@ use self::coreaudio::sys::{
     AudioStreamBasicDescription,
 };
 
-use super::{asbd_from_config, frames_to_duration, host_time_to_stream_instant};
+use super::{asbd_from_config, frames_to_duration, host_time_to_stream_instant, output_timestamp};
 use crate::traits::{DeviceTrait, HostTrait, StreamTrait};
 
 use crate::{
-    BackendSpecificError, BufferSize, BuildStreamError, Data, DefaultStreamConfigError,
-    DeviceNameError, DevicesError, InputCallbackInfo, OutputCallbackInfo, PauseStreamError,
-    PlayStreamError, SampleFormat, SampleRate, StreamConfig, StreamError, SupportedBufferSize,
+    BackendSpecificError, BufferLayout, BufferSize, BuildStreamError, Data,
+    DefaultStreamConfigError, DeviceNameError, DevicesError, InputCallbackInfo, InputPermission,
+    OutputCallbackInfo, PauseStreamError, PlayStreamError, SampleFormat, SampleRate, ShareMode,
+    StreamConfig, StreamError, StreamState, StreamUsage, SupportedBufferSize,
     SupportedStreamConfig, SupportedStreamConfigRange, SupportedStreamConfigsError,
 };
 
@@ -36,6 +37,7 @@ use self::enumerate::{
 use std::slice;
 
 pub mod enumerate;
+mod session;
 
 // These days the default of iOS is now F32 and no longer I16
 const SUPPORTED_SAMPLE_FORMAT: SampleFormat = SampleFormat::F32;
@@ -70,6 +72,17 @@ impl HostTrait for Host {
     fn default_output_device(&self) -> Option<Self::Device> {
         default_output_device()
     }
+
+    fn input_permission(&self) -> InputPermission {
+        super::permission::input_permission()
+    }
+
+    fn request_input_permission<F>(&self, callback: F)
+    where
+        F: FnOnce(InputPermission) + Send + 'static,
+    {
+        super::permission::request_input_permission(Box::new(callback))
+    }
 }
 
 impl Device {
@@ -93,6 +106,8 @@ impl Device {
             max_sample_rate: stream_config.sample_rate,
             buffer_size: stream_config.buffer_size.clone(),
             sample_format: SUPPORTED_SAMPLE_FORMAT,
+            layout: BufferLayout::Interleaved,
+            channel_layout: None,
         }]
         .into_iter())
     }
@@ -114,6 +129,8 @@ impl Device {
                 max_sample_rate: stream_config.sample_rate,
                 buffer_size: stream_config.buffer_size.clone(),
                 sample_format: SUPPORTED_SAMPLE_FORMAT,
+                layout: BufferLayout::Interleaved,
+                channel_layout: None,
             })
             .collect();
         Ok(configs.into_iter())
@@ -183,6 +200,11 @@ impl DeviceTrait for Device {
         let scope = Scope::Output;
         let element = Element::Input;
 
+        // Input units open without the permission, but only ever record silence.
+        if super::permission::input_permission() == InputPermission::Denied {
+            return Err(BuildStreamError::PermissionDenied);
+        }
+
         let mut audio_unit = create_audio_unit()?;
         audio_unit.uninitialize()?;
         configure_for_recording(&mut audio_unit)?;
@@ -197,13 +219,16 @@ impl DeviceTrait for Device {
             BufferSize::Fixed(_) => {
                 return Err(BuildStreamError::StreamConfigNotSupported);
             }
-            BufferSize::Default => (),
+            // The size is up to the system, a preferred one is only a hint.
+            BufferSize::Default | BufferSize::Preferred { .. } => (),
         }
 
         // Register the callback that is being called by coreaudio whenever it needs data to be
         // fed to the audio buffer.
         let bytes_per_channel = sample_format.sample_size();
         let sample_rate = config.sample_rate;
+        // The number of frames passed to or from the callback so far.
+        let mut frame_position = 0u64;
         type Args = render_callback::Args<data::Raw>;
         audio_unit.set_input_callback(move |args: Args| unsafe {
             let ptr = (*args.data.data).mBuffers.as_ptr() as *const AudioBuffer;
@@ -236,7 +261,8 @@ impl DeviceTrait for Device {
                 .expect("`capture` occurs before origin of alsa `StreamInstant`");
             let timestamp = crate::InputStreamTimestamp { callback, capture };
 
-            let info = InputCallbackInfo { timestamp };
+            let info = InputCallbackInfo::new(timestamp, frame_position);
+            frame_position += buffer_frames as u64;
             data_callback(&data, &info);
             Ok(())
         })?;
@@ -249,6 +275,29 @@ impl DeviceTrait for Device {
         }))
     }
 
+    fn build_input_stream_raw_with_usage<D, E>(
+        &self,
+        config: &StreamConfig,
+        sample_format: SampleFormat,
+        share_mode: ShareMode,
+        usage: StreamUsage,
+        data_callback: D,
+        error_callback: E,
+    ) -> Result<Self::Stream, BuildStreamError>
+    where
+        D: FnMut(&Data, &InputCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        session::set_category(usage, true)?;
+        self.build_input_stream_raw_with_share_mode(
+            config,
+            sample_format,
+            share_mode,
+            data_callback,
+            error_callback,
+        )
+    }
+
     /// Create an output stream.
     fn build_output_stream_raw<D, E>(
         &self,
@@ -265,7 +314,8 @@ impl DeviceTrait for Device {
             BufferSize::Fixed(_) => {
                 return Err(BuildStreamError::StreamConfigNotSupported);
             }
-            BufferSize::Default => (),
+            // The size is up to the system, a preferred one is only a hint.
+            BufferSize::Default | BufferSize::Preferred { .. } => (),
         };
 
         let mut audio_unit = create_audio_unit()?;
@@ -282,6 +332,8 @@ impl DeviceTrait for Device {
         // fed to the audio buffer.
         let bytes_per_channel = sample_format.sample_size();
         let sample_rate = config.sample_rate;
+        // The number of frames passed to or from the callback so far.
+        let mut frame_position = 0u64;
         type Args = render_callback::Args<data::Raw>;
         audio_unit.set_render_callback(move |args: Args| unsafe {
             // If `run()` is currently running, then a callback will be available from this list.
@@ -297,22 +349,20 @@ impl DeviceTrait for Device {
             let len = (data_byte_size as usize / bytes_per_channel) as usize;
             let mut data = Data::from_parts(data, len, sample_format);
 
-            let callback = match host_time_to_stream_instant(args.time_stamp.mHostTime) {
-                Err(err) => {
-                    error_callback(err.into());
-                    return Err(());
-                }
-                Ok(cb) => cb,
-            };
-            // TODO: Need a better way to get delay, for now we assume a double-buffer offset.
+            // The latency of the output route is not known here, so `playback` is when the frames
+            // leave the audio unit.
+            let timestamp =
+                match output_timestamp(args.time_stamp.mHostTime, std::time::Duration::ZERO) {
+                    Err(err) => {
+                        error_callback(err.into());
+                        return Err(());
+                    }
+                    Ok(timestamp) => timestamp,
+                };
             let buffer_frames = len / channels as usize;
-            let delay = frames_to_duration(buffer_frames, sample_rate);
-            let playback = callback
-                .add(delay)
-                .expect("`playback` occurs beyond representation supported by `StreamInstant`");
-            let timestamp = crate::OutputStreamTimestamp { callback, playback };
 
-            let info = OutputCallbackInfo { timestamp };
+            let info = OutputCallbackInfo::new(timestamp, frame_position);
+            frame_position += buffer_frames as u64;
             data_callback(&mut data, &info);
             Ok(())
         })?;
@@ -324,6 +374,29 @@ impl DeviceTrait for Device {
             audio_unit,
         }))
     }
+
+    fn build_output_stream_raw_with_usage<D, E>(
+        &self,
+        config: &StreamConfig,
+        sample_format: SampleFormat,
+        share_mode: ShareMode,
+        usage: StreamUsage,
+        data_callback: D,
+        error_callback: E,
+    ) -> Result<Self::Stream, BuildStreamError>
+    where
+        D: FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        session::set_category(usage, false)?;
+        self.build_output_stream_raw_with_share_mode(
+            config,
+            sample_format,
+            share_mode,
+            data_callback,
+            error_callback,
+        )
+    }
 }
 
 pub struct Stream {
@@ -367,6 +440,14 @@ impl StreamTrait for Stream {
         }
         Ok(())
     }
+
+    fn state(&self) -> StreamState {
+        if self.inner.borrow().playing {
+            StreamState::Playing
+        } else {
+            StreamState::Paused
+        }
+    }
 }
 
 struct StreamInner {
@@ -421,11 +502,14 @@ fn default_input_asbd() -> Result<AudioStreamBasicDescription, coreaudio::Error>
 }
 
 fn stream_config_from_asbd(asbd: AudioStreamBasicDescription) -> SupportedStreamConfig {
-    let buffer_size = SupportedBufferSize::Range { min: 0, max: 0 };
+    // The system picks the size of the buffers, from a range only AVAudioSession knows.
+    let buffer_size = SupportedBufferSize::Unknown;
     SupportedStreamConfig {
         channels: asbd.mChannelsPerFrame as u16,
         sample_rate: SampleRate(asbd.mSampleRate as u32),
         buffer_size: buffer_size.clone(),
         sample_format: SUPPORTED_SAMPLE_FORMAT,
+        layout: BufferLayout::Interleaved,
+        channel_layout: None,
     }
 }
@@ -0,0 +1,104 @@
+//! The category and mode of the shared `AVAudioSession`, through which iOS routes, ducks and
+//! processes the audio of an application.
+//!
+//! As with the permission to record, AVFoundation is messaged through the Objective-C runtime
+//! directly.
+
+use crate::{BackendSpecificError, StreamUsage};
+use std::ffi::CStr;
+use std::mem;
+use std::os::raw::{c_char, c_void};
+use std::ptr;
+
+type Id = *const c_void;
+
+#[link(name = "AVFoundation", kind = "framework")]
+extern "C" {
+    static AVAudioSessionCategoryAmbient: Id;
+    static AVAudioSessionCategoryPlayback: Id;
+    static AVAudioSessionCategoryPlayAndRecord: Id;
+    static AVAudioSessionModeDefault: Id;
+    static AVAudioSessionModeVoiceChat: Id;
+    static AVAudioSessionModeSpokenAudio: Id;
+}
+
+#[link(name = "objc")]
+extern "C" {
+    fn objc_getClass(name: *const c_char) -> Id;
+    fn sel_registerName(name: *const c_char) -> Id;
+    fn objc_msgSend();
+}
+
+unsafe fn selector(name: &[u8]) -> Id {
+    sel_registerName(name.as_ptr() as *const c_char)
+}
+
+/// Sets the category and mode of the audio session for a stream used for `usage`.
+///
+/// The session is shared by every stream of the application, so the last stream built decides.
+/// Sessions with an input stream need the play and record category, whatever their usage.
+pub(super) fn set_category(usage: StreamUsage, is_input: bool) -> Result<(), BackendSpecificError> {
+    unsafe {
+        let category = match usage {
+            _ if is_input => AVAudioSessionCategoryPlayAndRecord,
+            StreamUsage::Communication => AVAudioSessionCategoryPlayAndRecord,
+            // Game audio mixes with the audio of other applications and is silenced by the
+            // ring/silent switch.
+            StreamUsage::Game => AVAudioSessionCategoryAmbient,
+            StreamUsage::Media | StreamUsage::Alarm | StreamUsage::Accessibility => {
+                AVAudioSessionCategoryPlayback
+            }
+        };
+        // The voice chat mode enables the echo canceller of the device.
+        let mode = match usage {
+            StreamUsage::Communication => AVAudioSessionModeVoiceChat,
+            StreamUsage::Accessibility => AVAudioSessionModeSpokenAudio,
+            StreamUsage::Game | StreamUsage::Media | StreamUsage::Alarm => {
+                AVAudioSessionModeDefault
+            }
+        };
+
+        let shared_instance: unsafe extern "C" fn(Id, Id) -> Id =
+            mem::transmute(objc_msgSend as unsafe extern "C" fn());
+        let session = shared_instance(
+            objc_getClass(b"AVAudioSession\0".as_ptr() as *const c_char),
+            selector(b"sharedInstance\0"),
+        );
+        let set_category: unsafe extern "C" fn(Id, Id, Id, Id, usize, *mut Id) -> bool =
+            mem::transmute(objc_msgSend as unsafe extern "C" fn());
+        let mut error: Id = ptr::null();
+        if set_category(
+            session,
+            selector(b"setCategory:mode:options:error:\0"),
+            category,
+            mode,
+            0,
+            &mut error,
+        ) {
+            return Ok(());
+        }
+        Err(BackendSpecificError {
+            description: format!(
+                "failed to set the category of the audio session: {}",
+                error_description(error)
+            ),
+        })
+    }
+}
+
+// The `localizedDescription` of an `NSError`.
+unsafe fn error_description(error: Id) -> String {
+    if error.is_null() {
+        return "unknown error".to_owned();
+    }
+    let send: unsafe extern "C" fn(Id, Id) -> Id =
+        mem::transmute(objc_msgSend as unsafe extern "C" fn());
+    let description = send(error, selector(b"localizedDescription\0"));
+    let utf8: unsafe extern "C" fn(Id, Id) -> *const c_char =
+        mem::transmute(objc_msgSend as unsafe extern "C" fn());
+    let utf8 = utf8(description, selector(b"UTF8String\0"));
+    if utf8.is_null() {
+        return "unknown error".to_owned();
+    }
+    CStr::from_ptr(utf8).to_string_lossy().into_owned()
+}
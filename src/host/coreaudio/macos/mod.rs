@@ -1,32 +1,47 @@
 extern crate core_foundation_sys;
 extern crate coreaudio;
 
-use super::{asbd_from_config, check_os_status, frames_to_duration, host_time_to_stream_instant};
+use super::{
+    asbd_from_config, check_os_status, frames_to_duration, host_time_to_stream_instant,
+    output_timestamp,
+};
 
 use self::core_foundation_sys::string::{CFStringGetCString, CFStringGetCStringPtr, CFStringRef};
 use self::coreaudio::audio_unit::render_callback::{self, data};
 use self::coreaudio::audio_unit::{AudioUnit, Element, Scope};
 use self::coreaudio::sys::{
-    kAudioDevicePropertyAvailableNominalSampleRates, kAudioDevicePropertyBufferFrameSize,
-    kAudioDevicePropertyBufferFrameSizeRange, kAudioDevicePropertyDeviceNameCFString,
-    kAudioDevicePropertyNominalSampleRate, kAudioDevicePropertyScopeOutput,
-    kAudioDevicePropertyStreamConfiguration, kAudioDevicePropertyStreamFormat,
-    kAudioObjectPropertyElementMaster, kAudioObjectPropertyScopeGlobal,
+    kAudioChannelLayoutTag_UseChannelBitmap, kAudioChannelLayoutTag_UseChannelDescriptions,
+    kAudioDeviceProcessorOverload, kAudioDevicePropertyAvailableNominalSampleRates,
+    kAudioDevicePropertyBufferFrameSize, kAudioDevicePropertyBufferFrameSizeRange,
+    kAudioDevicePropertyDataSource, kAudioDevicePropertyDeviceNameCFString,
+    kAudioDevicePropertyDeviceUID, kAudioDevicePropertyHogMode, kAudioDevicePropertyLatency,
+    kAudioDevicePropertyNominalSampleRate, kAudioDevicePropertyPlayThru,
+    kAudioDevicePropertyPreferredChannelLayout, kAudioDevicePropertySafetyOffset,
+    kAudioDevicePropertyScopeOutput, kAudioDevicePropertyStreamConfiguration,
+    kAudioDevicePropertyStreamFormat, kAudioDevicePropertyStreams,
+    kAudioDevicePropertyTransportType, kAudioDevicePropertyVolumeScalar, kAudioFormatFlagIsFloat,
+    kAudioFormatLinearPCM, kAudioObjectPropertyElementMaster, kAudioObjectPropertyElementName,
+    kAudioObjectPropertyManufacturer, kAudioObjectPropertyScopeGlobal,
     kAudioObjectPropertyScopeInput, kAudioObjectPropertyScopeOutput,
     kAudioOutputUnitProperty_CurrentDevice, kAudioOutputUnitProperty_EnableIO,
+    kAudioStreamPropertyAvailablePhysicalFormats, kAudioStreamPropertyPhysicalFormat,
     kAudioUnitProperty_StreamFormat, kCFStringEncodingUTF8, AudioBuffer, AudioBufferList,
-    AudioDeviceID, AudioObjectAddPropertyListener, AudioObjectGetPropertyData,
-    AudioObjectGetPropertyDataSize, AudioObjectID, AudioObjectPropertyAddress,
-    AudioObjectPropertyScope, AudioObjectRemovePropertyListener, AudioObjectSetPropertyData,
-    AudioStreamBasicDescription, AudioValueRange, OSStatus,
+    AudioChannelDescription, AudioChannelLayout, AudioDeviceID, AudioObjectAddPropertyListener,
+    AudioObjectGetPropertyData, AudioObjectGetPropertyDataSize, AudioObjectID,
+    AudioObjectIsPropertySettable, AudioObjectPropertyAddress, AudioObjectPropertyElement,
+    AudioObjectPropertyScope, AudioObjectPropertySelector, AudioObjectRemovePropertyListener,
+    AudioObjectSetPropertyData, AudioStreamBasicDescription, AudioStreamID,
+    AudioStreamRangedDescription, AudioValueRange, OSStatus,
 };
 use crate::traits::{DeviceTrait, HostTrait, StreamTrait};
 use crate::{
-    BackendSpecificError, BufferSize, BuildStreamError, ChannelCount, Data,
-    DefaultStreamConfigError, DeviceNameError, DevicesError, InputCallbackInfo, OutputCallbackInfo,
-    PauseStreamError, PlayStreamError, SampleFormat, SampleRate, StreamConfig, StreamError,
-    SupportedBufferSize, SupportedStreamConfig, SupportedStreamConfigRange,
-    SupportedStreamConfigsError,
+    BackendSpecificError, BluetoothProfile, BufferLayout, BufferSize, BuildStreamError,
+    ChannelCount, ChannelLayout, ChannelPosition, Data, DefaultStreamConfigError, DeviceDirection,
+    DeviceNameError, DevicesError, DirectMonitoringError, FormFactor, FrameCount,
+    InputCallbackInfo, InputPermission, OutputCallbackInfo, PauseStreamError, PlayStreamError,
+    ReconfigureStreamError, SampleFormat, SampleRate, SetSampleRateError, ShareMode, StreamConfig,
+    StreamError, StreamLatency, StreamState, SupportedBufferSize, SupportedStreamConfig,
+    SupportedStreamConfigRange, SupportedStreamConfigsError, Transport,
 };
 use std::cell::RefCell;
 use std::ffi::CStr;
@@ -35,6 +50,8 @@ use std::mem;
 use std::os::raw::c_char;
 use std::ptr::null;
 use std::slice;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
 use std::thread;
 use std::time::Duration;
 
@@ -75,6 +92,17 @@ impl HostTrait for Host {
     fn default_output_device(&self) -> Option<Self::Device> {
         default_output_device()
     }
+
+    fn input_permission(&self) -> InputPermission {
+        super::permission::input_permission()
+    }
+
+    fn request_input_permission<F>(&self, callback: F)
+    where
+        F: FnOnce(InputPermission) + Send + 'static,
+    {
+        super::permission::request_input_permission(Box::new(callback))
+    }
 }
 
 impl DeviceTrait for Device {
@@ -86,6 +114,14 @@ impl DeviceTrait for Device {
         Device::name(self)
     }
 
+    fn info(&self) -> Result<crate::DeviceInfo, DeviceNameError> {
+        Device::info(self)
+    }
+
+    fn capabilities(&self) -> Result<crate::DeviceCapabilities, SupportedStreamConfigsError> {
+        Device::capabilities(self)
+    }
+
     fn supported_input_configs(
         &self,
     ) -> Result<Self::SupportedInputConfigs, SupportedStreamConfigsError> {
@@ -117,7 +153,36 @@ impl DeviceTrait for Device {
         D: FnMut(&Data, &InputCallbackInfo) + Send + 'static,
         E: FnMut(StreamError) + Send + 'static,
     {
-        Device::build_input_stream_raw(self, config, sample_format, data_callback, error_callback)
+        Device::build_input_stream_raw(
+            self,
+            config,
+            sample_format,
+            ShareMode::Shared,
+            data_callback,
+            error_callback,
+        )
+    }
+
+    fn build_input_stream_raw_with_share_mode<D, E>(
+        &self,
+        config: &StreamConfig,
+        sample_format: SampleFormat,
+        share_mode: ShareMode,
+        data_callback: D,
+        error_callback: E,
+    ) -> Result<Self::Stream, BuildStreamError>
+    where
+        D: FnMut(&Data, &InputCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        Device::build_input_stream_raw(
+            self,
+            config,
+            sample_format,
+            share_mode,
+            data_callback,
+            error_callback,
+        )
     }
 
     fn build_output_stream_raw<D, E>(
@@ -131,7 +196,77 @@ impl DeviceTrait for Device {
         D: FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static,
         E: FnMut(StreamError) + Send + 'static,
     {
-        Device::build_output_stream_raw(self, config, sample_format, data_callback, error_callback)
+        Device::build_output_stream_raw(
+            self,
+            config,
+            sample_format,
+            ShareMode::Shared,
+            data_callback,
+            error_callback,
+        )
+    }
+
+    fn build_output_stream_raw_with_share_mode<D, E>(
+        &self,
+        config: &StreamConfig,
+        sample_format: SampleFormat,
+        share_mode: ShareMode,
+        data_callback: D,
+        error_callback: E,
+    ) -> Result<Self::Stream, BuildStreamError>
+    where
+        D: FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        Device::build_output_stream_raw(
+            self,
+            config,
+            sample_format,
+            share_mode,
+            data_callback,
+            error_callback,
+        )
+    }
+
+    fn set_direct_monitoring(&self, enabled: bool) -> Result<(), DirectMonitoringError> {
+        set_play_through(self.audio_device_id, enabled)
+    }
+
+    // Reads the names of the channel elements, which are numbered from 1.
+    fn channel_names(&self, direction: DeviceDirection) -> Result<Vec<String>, DeviceNameError> {
+        let (scope, config) = match direction {
+            DeviceDirection::Input => (kAudioObjectPropertyScopeInput, self.default_input_config()),
+            DeviceDirection::Output => (
+                kAudioObjectPropertyScopeOutput,
+                self.default_output_config(),
+            ),
+        };
+        let channels = config.map_or(0, |config| config.channels());
+        Ok((1..=channels as AudioObjectPropertyElement)
+            .map(|element| {
+                self.element_string_property(kAudioObjectPropertyElementName, scope, element)
+                    .unwrap_or_default()
+            })
+            .collect())
+    }
+
+    // Sets the nominal sample rate, which every application using the device shares.
+    fn set_default_sample_rate(&self, sample_rate: SampleRate) -> Result<(), SetSampleRateError> {
+        let filter = crate::DeviceFilter {
+            sample_rate: Some(sample_rate),
+            ..Default::default()
+        };
+        if !filter.matches(self) {
+            return Err(SetSampleRateError::SampleRateNotSupported);
+        }
+        set_nominal_sample_rate(self.audio_device_id, sample_rate).map_err(|err| match err {
+            ReconfigureStreamError::NotSupported => SetSampleRateError::NotSupported,
+            ReconfigureStreamError::StreamConfigNotSupported => {
+                SetSampleRateError::SampleRateNotSupported
+            }
+            ReconfigureStreamError::DeviceNotAvailable => SetSampleRateError::DeviceNotAvailable,
+            ReconfigureStreamError::BackendSpecific { err } => err.into(),
+        })
     }
 }
 
@@ -143,10 +278,100 @@ pub struct Device {
 
 impl Device {
     fn name(&self) -> Result<String, DeviceNameError> {
+        self.string_property(kAudioDevicePropertyDeviceNameCFString)
+    }
+
+    // Adds the UID of the device, which persists across runs, its manufacturer, whether it is a
+    // default device, and what its current data source tells about it.
+    fn info(&self) -> Result<crate::DeviceInfo, DeviceNameError> {
+        let mut info = crate::device_info::from_name(self)?;
+        if let Ok(uid) = self.string_property(kAudioDevicePropertyDeviceUID) {
+            info.id = uid;
+        }
+        info.manufacturer = self.string_property(kAudioObjectPropertyManufacturer).ok();
+        info.default_input = default_input_device().as_ref() == Some(self);
+        info.default_output = default_output_device().as_ref() == Some(self);
+        let scope = if info.output {
+            kAudioObjectPropertyScopeOutput
+        } else {
+            kAudioObjectPropertyScopeInput
+        };
+        // The data sources are four-character codes.
+        info.form_factor = match device_u32_property(
+            self.audio_device_id,
+            kAudioDevicePropertyDataSource,
+            scope,
+        ) {
+            Some(0x6973_706b) => FormFactor::Speakers,   // 'ispk'
+            Some(0x6864_706e) => FormFactor::Headphones, // 'hdpn'
+            Some(0x696d_6963) => FormFactor::Microphone, // 'imic'
+            _ => FormFactor::Unknown,
+        };
+        info.transport = device_transport(self.audio_device_id);
+        // CoreAudio does not tell the profile, but hands-free profiles run at 16 kHz at most.
+        if info.transport == Transport::Bluetooth {
+            info.bluetooth_profile = nominal_sample_rate(self.audio_device_id)
+                .map(|rate| BluetoothProfile::from_sample_rate(SampleRate(rate)));
+        }
+        Ok(info)
+    }
+
+    // Adds exclusive mode through hog mode, and whether the device has a volume control.
+    fn capabilities(&self) -> Result<crate::DeviceCapabilities, SupportedStreamConfigsError> {
+        let mut capabilities = crate::device_info::capabilities_from_configs(self)?;
+        capabilities.exclusive_mode = true;
+        // Devices with a volume control have it on the main element or on every channel.
+        capabilities.hardware_volume = [
+            kAudioObjectPropertyScopeOutput,
+            kAudioObjectPropertyScopeInput,
+        ]
+        .iter()
+        .any(|&scope| {
+            [kAudioObjectPropertyElementMaster, 1]
+                .iter()
+                .any(|&element| {
+                    let property_address = AudioObjectPropertyAddress {
+                        mSelector: kAudioDevicePropertyVolumeScalar,
+                        mScope: scope,
+                        mElement: element,
+                    };
+                    let settable: u8 = 0;
+                    let status = unsafe {
+                        AudioObjectIsPropertySettable(
+                            self.audio_device_id,
+                            &property_address as *const _,
+                            &settable as *const _ as *mut _,
+                        )
+                    };
+                    status == 0 && settable != 0
+                })
+        });
+        Ok(capabilities)
+    }
+
+    // Reads a string property of the device.
+    fn string_property(
+        &self,
+        selector: AudioObjectPropertySelector,
+    ) -> Result<String, DeviceNameError> {
+        self.element_string_property(
+            selector,
+            kAudioDevicePropertyScopeOutput,
+            kAudioObjectPropertyElementMaster,
+        )
+    }
+
+    // Reads a string property of one element of the device, such as the name of a channel.
+    fn element_string_property(
+        &self,
+        selector: AudioObjectPropertySelector,
+        scope: AudioObjectPropertyScope,
+        element: AudioObjectPropertyElement,
+    ) -> Result<String, DeviceNameError> {
         let property_address = AudioObjectPropertyAddress {
-            mSelector: kAudioDevicePropertyDeviceNameCFString,
-            mScope: kAudioDevicePropertyScopeOutput,
-            mElement: kAudioObjectPropertyElementMaster,
+            mSelector: selector,
+            mScope: scope,
+            mElement: element,
         };
         let device_name: CFStringRef = null();
         let data_size = mem::size_of::<CFStringRef>();
@@ -181,7 +406,7 @@ impl Device {
                 );
                 if result == 0 {
                     let description =
-                        "core foundation failed to return device name string".to_string();
+                        "core foundation failed to return device string property".to_string();
                     let err = BackendSpecificError { description };
                     return Err(err.into());
                 }
@@ -245,6 +470,9 @@ impl Device {
                 n_channels += buffer.mNumberChannels as usize;
             }
 
+            let channel_layout =
+                preferred_channel_layout(self.audio_device_id, scope, n_channels as ChannelCount);
+
             // TODO: macOS should support U8, I16, I32, F32 and F64. This should allow for using
             // I16 but just use F32 for now as it's the default anyway.
             let sample_format = SampleFormat::F32;
@@ -289,6 +517,8 @@ impl Device {
                     max_sample_rate: SampleRate(range.mMaximum as _),
                     buffer_size: buffer_size.clone(),
                     sample_format,
+                    layout: BufferLayout::Interleaved,
+                    channel_layout: channel_layout.clone(),
                 };
                 fmts.push(fmt);
             }
@@ -382,11 +612,14 @@ impl Device {
             let audio_unit = audio_unit_from_device(self, true)?;
             let buffer_size = get_io_buffer_frame_size_range(&audio_unit)?;
 
+            let channels = asbd.mChannelsPerFrame as ChannelCount;
             let config = SupportedStreamConfig {
                 sample_rate: SampleRate(asbd.mSampleRate as _),
-                channels: asbd.mChannelsPerFrame as _,
+                channels,
                 buffer_size,
                 sample_format,
+                layout: BufferLayout::Interleaved,
+                channel_layout: preferred_channel_layout(self.audio_device_id, scope, channels),
             };
             Ok(config)
         }
@@ -417,10 +650,614 @@ struct StreamInner {
     //
     // We must do this so that we can avoid changing the device sample rate if there is already
     // a stream associated with the device.
-    #[allow(dead_code)]
+    device_id: AudioDeviceID,
+    // The scope of the device the stream uses, input or output.
+    scope: AudioObjectPropertyScope,
+    // Shared with the callback, which reports when it is changed by `set_sample_rate`.
+    sample_rate: Arc<AtomicU32>,
+    // Held by exclusive streams, and released after the audio unit is dropped.
+    _hog_mode: Option<HogMode>,
+    // Keeps the nominal sample rate seen by the callback up to date, if the listener could be
+    // added.
+    _rate_watch: Option<NominalRateWatch>,
+    // Counts the overloads of the device reported to the callback, if the listener could be
+    // added.
+    _overload_watch: Option<OverloadWatch>,
+}
+
+impl StreamInner {
+    fn sample_rate(&self) -> SampleRate {
+        SampleRate(self.sample_rate.load(Ordering::Relaxed))
+    }
+
+    // The scope and element of the audio unit the stream was built with, see
+    // `build_input_stream_raw` and `build_output_stream_raw`.
+    fn audio_unit_scope(&self) -> (Scope, Element) {
+        if self.scope == kAudioObjectPropertyScopeInput {
+            (Scope::Output, Element::Input)
+        } else {
+            (Scope::Input, Element::Output)
+        }
+    }
+}
+
+/// Sets the nominal sample rate of the device, waiting for the change to apply.
+fn set_nominal_sample_rate(
+    device_id: AudioDeviceID,
+    sample_rate: SampleRate,
+) -> Result<(), ReconfigureStreamError> {
+    let property_address = AudioObjectPropertyAddress {
+        mSelector: kAudioDevicePropertyNominalSampleRate,
+        mScope: kAudioObjectPropertyScopeGlobal,
+        mElement: kAudioObjectPropertyElementMaster,
+    };
+    let rate = sample_rate.0 as f64;
+    let data_size = mem::size_of::<f64>() as u32;
+    unsafe {
+        let status = AudioObjectSetPropertyData(
+            device_id,
+            &property_address as *const _,
+            0,
+            null(),
+            data_size,
+            &rate as *const _ as *const _,
+        );
+        coreaudio::Error::from_os_status(status)?;
+
+        // The rate changes asynchronously, poll for it as for the physical format in
+        // `set_physical_format`.
+        let timer = ::std::time::Instant::now();
+        loop {
+            let current: f64 = 0.0;
+            let status = AudioObjectGetPropertyData(
+                device_id,
+                &property_address as *const _,
+                0,
+                null(),
+                &data_size as *const _ as *mut _,
+                &current as *const _ as *mut _,
+            );
+            coreaudio::Error::from_os_status(status)?;
+            if current == rate {
+                return Ok(());
+            }
+            if timer.elapsed() > Duration::from_secs(1) {
+                let description = "timeout waiting for sample rate update for device".into();
+                let err = BackendSpecificError { description };
+                return Err(err.into());
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+}
+
+/// Routes the input of the device to its output in hardware, for devices that offer
+/// play-through.
+fn set_play_through(device_id: AudioDeviceID, enabled: bool) -> Result<(), DirectMonitoringError> {
+    let property_address = AudioObjectPropertyAddress {
+        mSelector: kAudioDevicePropertyPlayThru,
+        mScope: kAudioObjectPropertyScopeInput,
+        mElement: kAudioObjectPropertyElementMaster,
+    };
+    let settable: u8 = 0;
+    let status = unsafe {
+        AudioObjectIsPropertySettable(
+            device_id,
+            &property_address as *const _,
+            &settable as *const _ as *mut _,
+        )
+    };
+    // Devices without play-through do not have the property at all.
+    if status != 0 || settable == 0 {
+        return Err(DirectMonitoringError::NotSupported);
+    }
+    let value = enabled as u32;
+    let status = unsafe {
+        AudioObjectSetPropertyData(
+            device_id,
+            &property_address as *const _,
+            0,
+            null(),
+            mem::size_of::<u32>() as u32,
+            &value as *const _ as *const _,
+        )
+    };
+    coreaudio::Error::from_os_status(status)?;
+    Ok(())
+}
+
+/// Exclusive access to a device for this process, released on drop.
+struct HogMode {
     device_id: AudioDeviceID,
 }
 
+impl HogMode {
+    const ADDRESS: AudioObjectPropertyAddress = AudioObjectPropertyAddress {
+        mSelector: kAudioDevicePropertyHogMode,
+        mScope: kAudioObjectPropertyScopeGlobal,
+        mElement: kAudioObjectPropertyElementMaster,
+    };
+
+    /// Takes the device into hog mode, failing if another process or stream holds it.
+    fn acquire(device_id: AudioDeviceID) -> Result<Self, BuildStreamError> {
+        let pid = std::process::id() as i32;
+        if Self::owner(device_id)? != -1 {
+            return Err(BuildStreamError::DeviceBusy);
+        }
+        let status = unsafe {
+            AudioObjectSetPropertyData(
+                device_id,
+                &Self::ADDRESS as *const _,
+                0,
+                null(),
+                mem::size_of::<i32>() as u32,
+                &pid as *const _ as *const _,
+            )
+        };
+        coreaudio::Error::from_os_status(status)?;
+        if Self::owner(device_id)? != pid {
+            return Err(BuildStreamError::ShareModeNotSupported);
+        }
+        Ok(HogMode { device_id })
+    }
+
+    /// Fails if another process holds the device in hog mode, which shuts out the shared streams
+    /// of every other process.
+    fn check_shared(device_id: AudioDeviceID) -> Result<(), BuildStreamError> {
+        match Self::owner(device_id) {
+            Ok(pid) if pid != -1 && pid != std::process::id() as i32 => {
+                Err(BuildStreamError::DeviceBusy)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // The process holding the device in hog mode, or -1 if none does.
+    fn owner(device_id: AudioDeviceID) -> Result<i32, BuildStreamError> {
+        let pid = -1i32;
+        let data_size = mem::size_of::<i32>() as u32;
+        let status = unsafe {
+            AudioObjectGetPropertyData(
+                device_id,
+                &Self::ADDRESS as *const _,
+                0,
+                null(),
+                &data_size as *const _ as *mut _,
+                &pid as *const _ as *mut _,
+            )
+        };
+        coreaudio::Error::from_os_status(status)?;
+        Ok(pid)
+    }
+}
+
+impl Drop for HogMode {
+    fn drop(&mut self) {
+        let pid = -1i32;
+        unsafe {
+            AudioObjectSetPropertyData(
+                self.device_id,
+                &Self::ADDRESS as *const _,
+                0,
+                null(),
+                mem::size_of::<i32>() as u32,
+                &pid as *const _ as *const _,
+            );
+        }
+    }
+}
+
+/// Follows the nominal sample rate of a device while a stream runs, so that the callback notices
+/// when it is changed by the system or another application, and reports
+/// `StreamError::DeviceFormatChanged`.
+struct NominalRateWatch {
+    device_id: AudioDeviceID,
+    // Leaked from an `Arc` for the listener, and reclaimed when it is removed.
+    rate: *const AtomicU32,
+}
+
+// The pointer is that of an `Arc`, which is `Send`.
+unsafe impl Send for NominalRateWatch {}
+
+impl NominalRateWatch {
+    const ADDRESS: AudioObjectPropertyAddress = AudioObjectPropertyAddress {
+        mSelector: kAudioDevicePropertyNominalSampleRate,
+        mScope: kAudioObjectPropertyScopeGlobal,
+        mElement: kAudioObjectPropertyElementMaster,
+    };
+
+    fn new(device_id: AudioDeviceID, rate: Arc<AtomicU32>) -> Option<Self> {
+        let rate = Arc::into_raw(rate);
+        let status = unsafe {
+            AudioObjectAddPropertyListener(
+                device_id,
+                &Self::ADDRESS as *const _,
+                Some(nominal_rate_listener),
+                rate as *mut _,
+            )
+        };
+        if check_os_status(status).is_err() {
+            unsafe { drop(Arc::from_raw(rate)) };
+            return None;
+        }
+        Some(NominalRateWatch { device_id, rate })
+    }
+}
+
+impl Drop for NominalRateWatch {
+    fn drop(&mut self) {
+        unsafe {
+            AudioObjectRemovePropertyListener(
+                self.device_id,
+                &Self::ADDRESS as *const _,
+                Some(nominal_rate_listener),
+                self.rate as *mut _,
+            );
+            drop(Arc::from_raw(self.rate));
+        }
+    }
+}
+
+unsafe extern "C" fn nominal_rate_listener(
+    device_id: AudioObjectID,
+    _n_addresses: u32,
+    _properties: *const AudioObjectPropertyAddress,
+    rate: *mut ::std::os::raw::c_void,
+) -> OSStatus {
+    let rate = &*(rate as *const AtomicU32);
+    let sample_rate: f64 = 0.0;
+    let data_size = mem::size_of::<f64>() as u32;
+    let status = AudioObjectGetPropertyData(
+        device_id,
+        &NominalRateWatch::ADDRESS as *const _,
+        0,
+        null(),
+        &data_size as *const _ as *mut _,
+        &sample_rate as *const _ as *mut _,
+    );
+    if status == 0 {
+        rate.store(sample_rate as u32, Ordering::Relaxed);
+    }
+    status
+}
+
+/// Counts the processor overloads of a device while a stream runs, for the callback to report
+/// them as `StreamError::Underrun` or `StreamError::Overrun`.
+///
+/// CoreAudio reports an overload when an IO cycle of the device missed its deadline, without the
+/// number of frames it cost.
+struct OverloadWatch {
+    device_id: AudioDeviceID,
+    // Leaked from an `Arc` for the listener, and reclaimed when it is removed.
+    overloads: *const AtomicU32,
+}
+
+// The pointer is that of an `Arc`, which is `Send`.
+unsafe impl Send for OverloadWatch {}
+
+impl OverloadWatch {
+    const ADDRESS: AudioObjectPropertyAddress = AudioObjectPropertyAddress {
+        mSelector: kAudioDeviceProcessorOverload,
+        mScope: kAudioObjectPropertyScopeGlobal,
+        mElement: kAudioObjectPropertyElementMaster,
+    };
+
+    fn new(device_id: AudioDeviceID, overloads: Arc<AtomicU32>) -> Option<Self> {
+        let overloads = Arc::into_raw(overloads);
+        let status = unsafe {
+            AudioObjectAddPropertyListener(
+                device_id,
+                &Self::ADDRESS as *const _,
+                Some(overload_listener),
+                overloads as *mut _,
+            )
+        };
+        if check_os_status(status).is_err() {
+            unsafe { drop(Arc::from_raw(overloads)) };
+            return None;
+        }
+        Some(OverloadWatch {
+            device_id,
+            overloads,
+        })
+    }
+}
+
+impl Drop for OverloadWatch {
+    fn drop(&mut self) {
+        unsafe {
+            AudioObjectRemovePropertyListener(
+                self.device_id,
+                &Self::ADDRESS as *const _,
+                Some(overload_listener),
+                self.overloads as *mut _,
+            );
+            drop(Arc::from_raw(self.overloads));
+        }
+    }
+}
+
+unsafe extern "C" fn overload_listener(
+    _device_id: AudioObjectID,
+    _n_addresses: u32,
+    _properties: *const AudioObjectPropertyAddress,
+    overloads: *mut ::std::os::raw::c_void,
+) -> OSStatus {
+    let overloads = &*(overloads as *const AtomicU32);
+    overloads.fetch_add(1, Ordering::Relaxed);
+    0
+}
+
+/// Switches the physical format of the device's first stream in `scope` to the sample rate of
+/// `config`, and to `sample_format` where the device offers it.
+///
+/// Exclusive streams bypass the mixer, so the device has to run at the rate of the stream. Where
+/// the device has no physical format of the stream's sample format, the one with the most bits is
+/// used, and the audio unit converts between the two.
+fn set_physical_format(
+    device_id: AudioDeviceID,
+    scope: AudioObjectPropertyScope,
+    config: &StreamConfig,
+    sample_format: SampleFormat,
+) -> Result<(), BuildStreamError> {
+    unsafe {
+        let streams: Vec<AudioStreamID> =
+            property_array(device_id, kAudioDevicePropertyStreams, scope)?;
+        let stream_id = *streams
+            .first()
+            .ok_or(BuildStreamError::StreamConfigNotSupported)?;
+        let formats: Vec<AudioStreamRangedDescription> = property_array(
+            stream_id,
+            kAudioStreamPropertyAvailablePhysicalFormats,
+            kAudioObjectPropertyScopeGlobal,
+        )?;
+
+        let requested = asbd_from_config(config, sample_format);
+        let is_float = |asbd: &AudioStreamBasicDescription| {
+            asbd.mFormatFlags & kAudioFormatFlagIsFloat as u32 != 0
+        };
+        let rate = config.sample_rate.0 as f64;
+        let format = formats
+            .iter()
+            .filter(|f| {
+                f.mFormat.mFormatID == kAudioFormatLinearPCM
+                    && f.mSampleRateRange.mMinimum <= rate
+                    && rate <= f.mSampleRateRange.mMaximum
+            })
+            .max_by_key(|f| {
+                let matches = f.mFormat.mBitsPerChannel == requested.mBitsPerChannel
+                    && is_float(&f.mFormat) == is_float(&requested);
+                (matches, f.mFormat.mBitsPerChannel)
+            })
+            .ok_or(BuildStreamError::StreamConfigNotSupported)?;
+        let format = AudioStreamBasicDescription {
+            mSampleRate: rate,
+            ..format.mFormat
+        };
+
+        let property_address = AudioObjectPropertyAddress {
+            mSelector: kAudioStreamPropertyPhysicalFormat,
+            mScope: kAudioObjectPropertyScopeGlobal,
+            mElement: kAudioObjectPropertyElementMaster,
+        };
+        let data_size = mem::size_of::<AudioStreamBasicDescription>() as u32;
+        let status = AudioObjectSetPropertyData(
+            stream_id,
+            &property_address as *const _,
+            0,
+            null(),
+            data_size,
+            &format as *const _ as *const _,
+        );
+        coreaudio::Error::from_os_status(status)?;
+
+        // The format changes asynchronously. Wait for it as for the nominal sample rate in
+        // `build_input_stream_raw`.
+        let timer = ::std::time::Instant::now();
+        loop {
+            let current: AudioStreamBasicDescription = mem::zeroed();
+            let status = AudioObjectGetPropertyData(
+                stream_id,
+                &property_address as *const _,
+                0,
+                null(),
+                &data_size as *const _ as *mut _,
+                &current as *const _ as *mut _,
+            );
+            coreaudio::Error::from_os_status(status)?;
+            if current.mSampleRate == format.mSampleRate
+                && current.mBitsPerChannel == format.mBitsPerChannel
+            {
+                return Ok(());
+            }
+            if timer.elapsed() > Duration::from_secs(1) {
+                let description = "timeout waiting for physical format update for device".into();
+                let err = BackendSpecificError { description };
+                return Err(err.into());
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+}
+
+/// Reads a property of an audio object that is an array.
+unsafe fn property_array<T>(
+    object_id: AudioObjectID,
+    selector: AudioObjectPropertySelector,
+    scope: AudioObjectPropertyScope,
+) -> Result<Vec<T>, coreaudio::Error> {
+    let property_address = AudioObjectPropertyAddress {
+        mSelector: selector,
+        mScope: scope,
+        mElement: kAudioObjectPropertyElementMaster,
+    };
+    let data_size = 0u32;
+    let status = AudioObjectGetPropertyDataSize(
+        object_id,
+        &property_address as *const _,
+        0,
+        null(),
+        &data_size as *const _ as *mut _,
+    );
+    coreaudio::Error::from_os_status(status)?;
+    let len = data_size as usize / mem::size_of::<T>();
+    let mut items: Vec<T> = Vec::with_capacity(len);
+    let status = AudioObjectGetPropertyData(
+        object_id,
+        &property_address as *const _,
+        0,
+        null(),
+        &data_size as *const _ as *mut _,
+        items.as_mut_ptr() as *mut _,
+    );
+    coreaudio::Error::from_os_status(status)?;
+    items.set_len(data_size as usize / mem::size_of::<T>());
+    Ok(items)
+}
+
+/// Reads a `u32` property of the device, such as a number of frames.
+fn device_u32_property(
+    device_id: AudioDeviceID,
+    selector: AudioObjectPropertySelector,
+    scope: AudioObjectPropertyScope,
+) -> Option<u32> {
+    let property_address = AudioObjectPropertyAddress {
+        mSelector: selector,
+        mScope: scope,
+        mElement: kAudioObjectPropertyElementMaster,
+    };
+    let frames = 0u32;
+    let data_size = mem::size_of::<u32>() as u32;
+    let status = unsafe {
+        AudioObjectGetPropertyData(
+            device_id,
+            &property_address as *const _,
+            0,
+            null(),
+            &data_size as *const _ as *mut _,
+            &frames as *const _ as *mut _,
+        )
+    };
+    (status == 0).then_some(frames)
+}
+
+// The sample rate the device currently runs at.
+fn nominal_sample_rate(device_id: AudioDeviceID) -> Option<u32> {
+    let sample_rate: f64 = 0.0;
+    let data_size = mem::size_of::<f64>() as u32;
+    let status = unsafe {
+        AudioObjectGetPropertyData(
+            device_id,
+            &NominalRateWatch::ADDRESS as *const _,
+            0,
+            null(),
+            &data_size as *const _ as *mut _,
+            &sample_rate as *const _ as *mut _,
+        )
+    };
+    (status == 0).then_some(sample_rate as u32)
+}
+
+// How a device is connected, going by its transport type. These are four-character codes.
+fn device_transport(device_id: AudioDeviceID) -> Transport {
+    match device_u32_property(
+        device_id,
+        kAudioDevicePropertyTransportType,
+        kAudioObjectPropertyScopeGlobal,
+    ) {
+        Some(0x626c_746e) => Transport::BuiltIn,     // 'bltn'
+        Some(0x7573_6220) => Transport::Usb,         // 'usb '
+        Some(0x6864_6d69) => Transport::Hdmi,        // 'hdmi'
+        Some(0x6470_7274) => Transport::DisplayPort, // 'dprt'
+        Some(0x626c_7565 | 0x626c_6561) => Transport::Bluetooth, // 'blue', 'blea'
+        // Virtual, aggregate and automatic aggregate devices: 'virt', 'grup' and 'fgrp'.
+        Some(0x7669_7274 | 0x6772_7570 | 0x6667_7270) => Transport::Virtual,
+        _ => Transport::Unknown,
+    }
+}
+
+/// The speaker positions the device prefers for its channels in the given scope.
+///
+/// Returns `None` if the device does not report them for exactly `channels` channels, or reports
+/// them by a layout tag, which would have to be expanded by the AudioToolbox framework.
+fn preferred_channel_layout(
+    device_id: AudioDeviceID,
+    scope: AudioObjectPropertyScope,
+    channels: ChannelCount,
+) -> Option<ChannelLayout> {
+    let property_address = AudioObjectPropertyAddress {
+        mSelector: kAudioDevicePropertyPreferredChannelLayout,
+        mScope: scope,
+        mElement: kAudioObjectPropertyElementMaster,
+    };
+
+    unsafe {
+        let data_size = 0u32;
+        let status = AudioObjectGetPropertyDataSize(
+            device_id,
+            &property_address as *const _,
+            0,
+            null(),
+            &data_size as *const _ as *mut _,
+        );
+        if status != 0 || (data_size as usize) < mem::size_of::<AudioChannelLayout>() {
+            return None;
+        }
+
+        // The layout is variable-length. A `Vec<u32>` keeps it suitably aligned.
+        let mut buffer: Vec<u32> = vec![0; (data_size as usize + 3) / 4];
+        let status = AudioObjectGetPropertyData(
+            device_id,
+            &property_address as *const _,
+            0,
+            null(),
+            &data_size as *const _ as *mut _,
+            buffer.as_mut_ptr() as *mut _,
+        );
+        if status != 0 {
+            return None;
+        }
+        let layout = &*(buffer.as_ptr() as *const AudioChannelLayout);
+
+        if layout.mChannelLayoutTag == kAudioChannelLayoutTag_UseChannelBitmap {
+            // The channel bits share the order of the WAVE channel mask.
+            let mask = layout.mChannelBitmap as u32;
+            return Some(ChannelLayout::from_wave_channel_mask(mask, channels));
+        }
+        if layout.mChannelLayoutTag != kAudioChannelLayoutTag_UseChannelDescriptions {
+            return None;
+        }
+        let available = (data_size as usize - mem::size_of::<AudioChannelLayout>())
+            / mem::size_of::<AudioChannelDescription>()
+            + 1;
+        let n_descriptions = layout.mNumberChannelDescriptions as usize;
+        if n_descriptions != channels as usize || n_descriptions > available {
+            return None;
+        }
+        let descriptions =
+            slice::from_raw_parts(layout.mChannelDescriptions.as_ptr(), n_descriptions);
+        let positions = descriptions
+            .iter()
+            .map(|description| channel_position_from_label(description.mChannelLabel as u32))
+            .collect();
+        Some(ChannelLayout::new(positions))
+    }
+}
+
+fn channel_position_from_label(label: u32) -> ChannelPosition {
+    match label {
+        // `kAudioChannelLabel_Left` to `kAudioChannelLabel_TopBackRight` share the order of the
+        // WAVE channel mask.
+        1..=18 => ChannelPosition::WAVE_MASK_ORDER[label as usize - 1],
+        // `kAudioChannelLabel_RearSurroundLeft` and `kAudioChannelLabel_RearSurroundRight`.
+        33 => ChannelPosition::BackLeft,
+        34 => ChannelPosition::BackRight,
+        // `kAudioChannelLabel_Mono`.
+        42 => ChannelPosition::FrontCenter,
+        _ => ChannelPosition::Unknown,
+    }
+}
+
 fn audio_unit_from_device(device: &Device, input: bool) -> Result<AudioUnit, coreaudio::Error> {
     let output_type = if device.is_default && !input {
         coreaudio::audio_unit::IOType::DefaultOutput
@@ -467,6 +1304,7 @@ impl Device {
         &self,
         config: &StreamConfig,
         sample_format: SampleFormat,
+        share_mode: ShareMode,
         mut data_callback: D,
         mut error_callback: E,
     ) -> Result<Stream, BuildStreamError>
@@ -478,6 +1316,30 @@ impl Device {
         let scope = Scope::Output;
         let element = Element::Input;
 
+        // Input units open without the permission, but only ever record silence.
+        if super::permission::input_permission() == InputPermission::Denied {
+            return Err(BuildStreamError::PermissionDenied);
+        }
+
+        // Exclusive streams take the device before its format is changed, which also takes care
+        // of the sample rate.
+        let hog_mode = match share_mode {
+            ShareMode::Shared => {
+                HogMode::check_shared(self.audio_device_id)?;
+                None
+            }
+            ShareMode::Exclusive => {
+                let hog_mode = HogMode::acquire(self.audio_device_id)?;
+                set_physical_format(
+                    self.audio_device_id,
+                    kAudioObjectPropertyScopeInput,
+                    config,
+                    sample_format,
+                )?;
+                Some(hog_mode)
+            }
+        };
+
         // Check whether or not we need to change the device sample rate to suit the one specified for the stream.
         unsafe {
             // Get the current sample rate.
@@ -643,13 +1505,40 @@ impl Device {
                     SupportedBufferSize::Unknown => (),
                 }
             }
+            BufferSize::Preferred { .. } => {
+                let buffer_size_range = get_io_buffer_frame_size_range(&audio_unit)?;
+                if let Some(v) = config.buffer_size.negotiate(&buffer_size_range)? {
+                    audio_unit.set_property(
+                        kAudioDevicePropertyBufferFrameSize,
+                        scope,
+                        element,
+                        Some(&v),
+                    )?
+                }
+            }
             BufferSize::Default => (),
         }
 
         // Register the callback that is being called by coreaudio whenever it needs data to be
         // fed to the audio buffer.
         let bytes_per_channel = sample_format.sample_size();
-        let sample_rate = config.sample_rate;
+        let sample_rate = Arc::new(AtomicU32::new(config.sample_rate.0));
+        let callback_sample_rate = sample_rate.clone();
+        // The nominal sample rate of the device, and the one last seen by the callback.
+        let device_rate = Arc::new(AtomicU32::new(config.sample_rate.0));
+        let rate_watch = NominalRateWatch::new(self.audio_device_id, device_rate.clone());
+        let mut reported_device_rate = config.sample_rate.0;
+        // The overloads of the device, and the number last reported as overruns.
+        let overloads = Arc::new(AtomicU32::new(0));
+        let overload_watch = OverloadWatch::new(self.audio_device_id, overloads.clone());
+        let mut reported_overloads = 0;
+        // The profile of a Bluetooth device, which switching changes the nominal rate of.
+        let mut reported_profile = (device_transport(self.audio_device_id) == Transport::Bluetooth)
+            .then(|| BluetoothProfile::from_sample_rate(config.sample_rate));
+        // The sample rate last reported to the error callback.
+        let mut reported_sample_rate = config.sample_rate;
+        // The number of frames passed to or from the callback so far.
+        let mut frame_position = 0u64;
         type Args = render_callback::Args<data::Raw>;
         audio_unit.set_input_callback(move |args: Args| unsafe {
             let ptr = (*args.data.data).mBuffers.as_ptr() as *const AudioBuffer;
@@ -675,6 +1564,35 @@ impl Device {
                 }
                 Ok(cb) => cb,
             };
+            let sample_rate = SampleRate(callback_sample_rate.load(Ordering::Relaxed));
+            if sample_rate != reported_sample_rate {
+                reported_sample_rate = sample_rate;
+                error_callback(StreamError::SampleRateChanged { sample_rate });
+            }
+            let nominal_rate = device_rate.load(Ordering::Relaxed);
+            if nominal_rate != reported_device_rate {
+                reported_device_rate = nominal_rate;
+                let profile = BluetoothProfile::from_sample_rate(SampleRate(nominal_rate));
+                if let Some(reported) = reported_profile.as_mut().filter(|p| **p != profile) {
+                    *reported = profile;
+                    error_callback(StreamError::BluetoothProfileChanged { profile });
+                }
+                // `set_sample_rate` changes both rates while the audio unit is stopped.
+                if nominal_rate != sample_rate.0 {
+                    let config = SupportedStreamConfig::new(
+                        channels as ChannelCount,
+                        SampleRate(nominal_rate),
+                        SupportedBufferSize::Unknown,
+                        sample_format,
+                    );
+                    error_callback(StreamError::DeviceFormatChanged { config });
+                }
+            }
+            let overload_count = overloads.load(Ordering::Relaxed);
+            if overload_count != reported_overloads {
+                reported_overloads = overload_count;
+                error_callback(StreamError::Overrun { frames_lost: None });
+            }
             let buffer_frames = len / channels as usize;
             let delay = frames_to_duration(buffer_frames, sample_rate);
             let capture = callback
@@ -682,7 +1600,8 @@ impl Device {
                 .expect("`capture` occurs before origin of alsa `StreamInstant`");
             let timestamp = crate::InputStreamTimestamp { callback, capture };
 
-            let info = InputCallbackInfo { timestamp };
+            let info = InputCallbackInfo::new(timestamp, frame_position);
+            frame_position += buffer_frames as u64;
             data_callback(&data, &info);
             Ok(())
         })?;
@@ -693,6 +1612,11 @@ impl Device {
             playing: true,
             audio_unit,
             device_id: self.audio_device_id,
+            scope: kAudioObjectPropertyScopeInput,
+            sample_rate,
+            _hog_mode: hog_mode,
+            _rate_watch: rate_watch,
+            _overload_watch: overload_watch,
         }))
     }
 
@@ -700,6 +1624,7 @@ impl Device {
         &self,
         config: &StreamConfig,
         sample_format: SampleFormat,
+        share_mode: ShareMode,
         mut data_callback: D,
         mut error_callback: E,
     ) -> Result<Stream, BuildStreamError>
@@ -707,6 +1632,23 @@ impl Device {
         D: FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static,
         E: FnMut(StreamError) + Send + 'static,
     {
+        let hog_mode = match share_mode {
+            ShareMode::Shared => {
+                HogMode::check_shared(self.audio_device_id)?;
+                None
+            }
+            ShareMode::Exclusive => {
+                let hog_mode = HogMode::acquire(self.audio_device_id)?;
+                set_physical_format(
+                    self.audio_device_id,
+                    kAudioObjectPropertyScopeOutput,
+                    config,
+                    sample_format,
+                )?;
+                Some(hog_mode)
+            }
+        };
+
         let mut audio_unit = audio_unit_from_device(self, false)?;
 
         // The scope and element for working with a device's output stream.
@@ -737,13 +1679,55 @@ impl Device {
                     SupportedBufferSize::Unknown => (),
                 }
             }
+            BufferSize::Preferred { .. } => {
+                let buffer_size_range = get_io_buffer_frame_size_range(&audio_unit)?;
+                if let Some(v) = config.buffer_size.negotiate(&buffer_size_range)? {
+                    audio_unit.set_property(
+                        kAudioDevicePropertyBufferFrameSize,
+                        scope,
+                        element,
+                        Some(&v),
+                    )?
+                }
+            }
             BufferSize::Default => (),
         }
 
         // Register the callback that is being called by coreaudio whenever it needs data to be
         // fed to the audio buffer.
         let bytes_per_channel = sample_format.sample_size();
-        let sample_rate = config.sample_rate;
+        let sample_rate = Arc::new(AtomicU32::new(config.sample_rate.0));
+        let callback_sample_rate = sample_rate.clone();
+        // The nominal sample rate of the device, and the one last seen by the callback.
+        let device_rate = Arc::new(AtomicU32::new(config.sample_rate.0));
+        let rate_watch = NominalRateWatch::new(self.audio_device_id, device_rate.clone());
+        let mut reported_device_rate = config.sample_rate.0;
+        // The overloads of the device, and the number last reported as underruns.
+        let overloads = Arc::new(AtomicU32::new(0));
+        let overload_watch = OverloadWatch::new(self.audio_device_id, overloads.clone());
+        let mut reported_overloads = 0;
+        // The profile of a Bluetooth device, which switching changes the nominal rate of.
+        let mut reported_profile = (device_transport(self.audio_device_id) == Transport::Bluetooth)
+            .then(|| BluetoothProfile::from_sample_rate(config.sample_rate));
+        // The sample rate last reported to the error callback.
+        let mut reported_sample_rate = config.sample_rate;
+        // The number of frames passed to or from the callback so far.
+        let mut frame_position = 0u64;
+        // The frames between the output of the audio unit and the DAC, read up front as device
+        // properties are not to be read from the render callback.
+        let device_latency_frames = [
+            kAudioDevicePropertyLatency,
+            kAudioDevicePropertySafetyOffset,
+        ]
+        .iter()
+        .filter_map(|&selector| {
+            device_u32_property(
+                self.audio_device_id,
+                selector,
+                kAudioObjectPropertyScopeOutput,
+            )
+        })
+        .sum::<u32>() as usize;
         type Args = render_callback::Args<data::Raw>;
         audio_unit.set_render_callback(move |args: Args| unsafe {
             // If `run()` is currently running, then a callback will be available from this list.
@@ -759,22 +1743,47 @@ impl Device {
             let len = (data_byte_size as usize / bytes_per_channel) as usize;
             let mut data = Data::from_parts(data, len, sample_format);
 
-            let callback = match host_time_to_stream_instant(args.time_stamp.mHostTime) {
+            let sample_rate = SampleRate(callback_sample_rate.load(Ordering::Relaxed));
+            if sample_rate != reported_sample_rate {
+                reported_sample_rate = sample_rate;
+                error_callback(StreamError::SampleRateChanged { sample_rate });
+            }
+            let nominal_rate = device_rate.load(Ordering::Relaxed);
+            if nominal_rate != reported_device_rate {
+                reported_device_rate = nominal_rate;
+                let profile = BluetoothProfile::from_sample_rate(SampleRate(nominal_rate));
+                if let Some(reported) = reported_profile.as_mut().filter(|p| **p != profile) {
+                    *reported = profile;
+                    error_callback(StreamError::BluetoothProfileChanged { profile });
+                }
+                // `set_sample_rate` changes both rates while the audio unit is stopped.
+                if nominal_rate != sample_rate.0 {
+                    let config = SupportedStreamConfig::new(
+                        channels as ChannelCount,
+                        SampleRate(nominal_rate),
+                        SupportedBufferSize::Unknown,
+                        sample_format,
+                    );
+                    error_callback(StreamError::DeviceFormatChanged { config });
+                }
+            }
+            let overload_count = overloads.load(Ordering::Relaxed);
+            if overload_count != reported_overloads {
+                reported_overloads = overload_count;
+                error_callback(StreamError::Underrun { frames_lost: None });
+            }
+            let device_latency = frames_to_duration(device_latency_frames, sample_rate);
+            let timestamp = match output_timestamp(args.time_stamp.mHostTime, device_latency) {
                 Err(err) => {
                     error_callback(err.into());
                     return Err(());
                 }
-                Ok(cb) => cb,
+                Ok(timestamp) => timestamp,
             };
-            // TODO: Need a better way to get delay, for now we assume a double-buffer offset.
             let buffer_frames = len / channels as usize;
-            let delay = frames_to_duration(buffer_frames, sample_rate);
-            let playback = callback
-                .add(delay)
-                .expect("`playback` occurs beyond representation supported by `StreamInstant`");
-            let timestamp = crate::OutputStreamTimestamp { callback, playback };
 
-            let info = OutputCallbackInfo { timestamp };
+            let info = OutputCallbackInfo::new(timestamp, frame_position);
+            frame_position += buffer_frames as u64;
             data_callback(&mut data, &info);
             Ok(())
         })?;
@@ -785,6 +1794,11 @@ impl Device {
             playing: true,
             audio_unit,
             device_id: self.audio_device_id,
+            scope: kAudioObjectPropertyScopeOutput,
+            sample_rate,
+            _hog_mode: hog_mode,
+            _rate_watch: rate_watch,
+            _overload_watch: overload_watch,
         }))
     }
 }
@@ -830,6 +1844,106 @@ impl StreamTrait for Stream {
         }
         Ok(())
     }
+
+    fn state(&self) -> StreamState {
+        if self.inner.borrow().playing {
+            StreamState::Playing
+        } else {
+            StreamState::Paused
+        }
+    }
+
+    fn latency(&self) -> Option<StreamLatency> {
+        let stream = self.inner.borrow();
+        let duration = |selector| {
+            device_u32_property(stream.device_id, selector, stream.scope)
+                .map(|frames| frames_to_duration(frames as usize, stream.sample_rate()))
+        };
+        Some(StreamLatency {
+            buffered: duration(kAudioDevicePropertyBufferFrameSize),
+            driver: duration(kAudioDevicePropertyLatency),
+            safety: duration(kAudioDevicePropertySafetyOffset),
+        })
+    }
+
+    fn buffer_size(&self) -> Option<FrameCount> {
+        let stream = self.inner.borrow();
+        device_u32_property(
+            stream.device_id,
+            kAudioDevicePropertyBufferFrameSize,
+            stream.scope,
+        )
+    }
+
+    fn set_buffer_size(&self, frames: FrameCount) -> Result<FrameCount, ReconfigureStreamError> {
+        let mut stream = self.inner.borrow_mut();
+        if let SupportedBufferSize::Range { min, max } =
+            get_io_buffer_frame_size_range(&stream.audio_unit)?
+        {
+            if !(min..=max).contains(&frames) {
+                return Err(ReconfigureStreamError::StreamConfigNotSupported);
+            }
+        }
+        let (scope, element) = stream.audio_unit_scope();
+        stream.audio_unit.set_property(
+            kAudioDevicePropertyBufferFrameSize,
+            scope,
+            element,
+            Some(&frames),
+        )?;
+        Ok(device_u32_property(
+            stream.device_id,
+            kAudioDevicePropertyBufferFrameSize,
+            stream.scope,
+        )
+        .unwrap_or(frames))
+    }
+
+    fn set_sample_rate(&self, sample_rate: SampleRate) -> Result<(), ReconfigureStreamError> {
+        let mut stream = self.inner.borrow_mut();
+        if stream.sample_rate() == sample_rate {
+            return Ok(());
+        }
+        let ranges: Vec<AudioValueRange> = unsafe {
+            property_array(
+                stream.device_id,
+                kAudioDevicePropertyAvailableNominalSampleRates,
+                kAudioObjectPropertyScopeGlobal,
+            )?
+        };
+        let rate = sample_rate.0 as f64;
+        if !ranges
+            .iter()
+            .any(|r| r.mMinimum <= rate && rate <= r.mMaximum)
+        {
+            return Err(ReconfigureStreamError::StreamConfigNotSupported);
+        }
+
+        // The stream format of an audio unit can only be changed while it is uninitialized.
+        let (scope, element) = stream.audio_unit_scope();
+        if stream.playing {
+            stream.audio_unit.stop()?;
+        }
+        stream.audio_unit.uninitialize()?;
+        set_nominal_sample_rate(stream.device_id, sample_rate)?;
+        let mut asbd: AudioStreamBasicDescription =
+            stream
+                .audio_unit
+                .get_property(kAudioUnitProperty_StreamFormat, scope, element)?;
+        asbd.mSampleRate = rate;
+        stream.audio_unit.set_property(
+            kAudioUnitProperty_StreamFormat,
+            scope,
+            element,
+            Some(&asbd),
+        )?;
+        stream.sample_rate.store(sample_rate.0, Ordering::Relaxed);
+        stream.audio_unit.initialize()?;
+        if stream.playing {
+            stream.audio_unit.start()?;
+        }
+        Ok(())
+    }
 }
 
 fn get_io_buffer_frame_size_range(
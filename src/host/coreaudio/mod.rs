@@ -1,12 +1,15 @@
 extern crate coreaudio;
 
 use self::coreaudio::sys::{
-    kAudioFormatFlagIsFloat, kAudioFormatFlagIsPacked, kAudioFormatLinearPCM,
+    kAudioFormat60958AC3, kAudioFormatFlagIsFloat, kAudioFormatFlagIsPacked,
+    kAudioFormatFlagIsSignedInteger, kAudioFormatLinearPCM, kAudioFormatULaw,
     AudioStreamBasicDescription, OSStatus,
 };
 
 use crate::DefaultStreamConfigError;
-use crate::{BuildStreamError, SupportedStreamConfigsError};
+use crate::{
+    BuildStreamError, DirectMonitoringError, ReconfigureStreamError, SupportedStreamConfigsError,
+};
 
 use crate::{BackendSpecificError, SampleFormat, StreamConfig};
 
@@ -14,6 +17,7 @@ use crate::{BackendSpecificError, SampleFormat, StreamConfig};
 mod ios;
 #[cfg(target_os = "macos")]
 mod macos;
+mod permission;
 
 #[cfg(target_os = "ios")]
 pub use self::ios::{
@@ -39,8 +43,8 @@ fn check_os_status(os_status: OSStatus) -> Result<(), BackendSpecificError> {
     }
 }
 
-// Create a coreaudio AudioStreamBasicDescription from a CPAL Format.
-fn asbd_from_config(
+/// Create a coreaudio AudioStreamBasicDescription from a CPAL Format.
+pub fn asbd_from_config(
     config: &StreamConfig,
     sample_format: SampleFormat,
 ) -> AudioStreamBasicDescription {
@@ -51,9 +55,18 @@ fn asbd_from_config(
     let bytes_per_frame = n_channels * bytes_per_channel;
     let frames_per_packet = 1;
     let bytes_per_packet = frames_per_packet * bytes_per_frame;
-    let format_flags = match sample_format {
-        SampleFormat::F32 => (kAudioFormatFlagIsFloat | kAudioFormatFlagIsPacked) as u32,
-        _ => kAudioFormatFlagIsPacked as u32,
+    let (format_id, format_flags) = match sample_format {
+        SampleFormat::F32 | SampleFormat::F16 => (
+            kAudioFormatLinearPCM,
+            (kAudioFormatFlagIsFloat | kAudioFormatFlagIsPacked) as u32,
+        ),
+        SampleFormat::I16 => (
+            kAudioFormatLinearPCM,
+            (kAudioFormatFlagIsSignedInteger | kAudioFormatFlagIsPacked) as u32,
+        ),
+        SampleFormat::MuLaw => (kAudioFormatULaw, 0),
+        SampleFormat::Iec61937 => (kAudioFormat60958AC3, kAudioFormatFlagIsPacked as u32),
+        _ => (kAudioFormatLinearPCM, kAudioFormatFlagIsPacked as u32),
     };
     AudioStreamBasicDescription {
         mBitsPerChannel: bits_per_channel as _,
@@ -62,12 +75,32 @@ fn asbd_from_config(
         mBytesPerPacket: bytes_per_packet as _,
         mFramesPerPacket: frames_per_packet as _,
         mFormatFlags: format_flags,
-        mFormatID: kAudioFormatLinearPCM,
+        mFormatID: format_id,
         mSampleRate: sample_rate as _,
         ..Default::default()
     }
 }
 
+/// Returns the `SampleFormat` described by a coreaudio AudioStreamBasicDescription.
+///
+/// Returns `None` for formats CPAL has no equivalent for.
+#[cfg_attr(not(feature = "platform-formats"), allow(dead_code))]
+pub fn sample_format_from_asbd(asbd: &AudioStreamBasicDescription) -> Option<SampleFormat> {
+    let flags = asbd.mFormatFlags;
+    let is_float = flags & kAudioFormatFlagIsFloat as u32 != 0;
+    let is_signed = flags & kAudioFormatFlagIsSignedInteger as u32 != 0;
+    let sample_format = match (asbd.mFormatID, asbd.mBitsPerChannel) {
+        (kAudioFormatLinearPCM, 32) if is_float => SampleFormat::F32,
+        (kAudioFormatLinearPCM, 16) if is_float => SampleFormat::F16,
+        (kAudioFormatLinearPCM, 16) if is_signed => SampleFormat::I16,
+        (kAudioFormatLinearPCM, 16) => SampleFormat::U16,
+        (kAudioFormatULaw, _) => SampleFormat::MuLaw,
+        (kAudioFormat60958AC3, _) => SampleFormat::Iec61937,
+        _ => return None,
+    };
+    Some(sample_format)
+}
+
 fn host_time_to_stream_instant(
     m_host_time: u64,
 ) -> Result<crate::StreamInstant, BackendSpecificError> {
@@ -80,6 +113,19 @@ fn host_time_to_stream_instant(
     Ok(crate::StreamInstant::new(secs as i64, subsec_nanos as u32))
 }
 
+// The timestamp of an output render callback invoked now. `m_host_time` is when the first frame
+// of the buffer leaves the audio unit, and it reaches the DAC `device_latency` later.
+fn output_timestamp(
+    m_host_time: u64,
+    device_latency: std::time::Duration,
+) -> Result<crate::OutputStreamTimestamp, BackendSpecificError> {
+    let callback = host_time_to_stream_instant(unsafe { mach::mach_time::mach_absolute_time() })?;
+    let playback = host_time_to_stream_instant(m_host_time)?
+        .add(device_latency)
+        .expect("`playback` occurs beyond representation supported by `StreamInstant`");
+    Ok(crate::OutputStreamTimestamp { callback, playback })
+}
+
 // Convert the given duration in frames at the given sample rate to a `std::time::Duration`.
 fn frames_to_duration(frames: usize, rate: crate::SampleRate) -> std::time::Duration {
     let secsf = frames as f64 / rate.0 as f64;
@@ -119,3 +165,19 @@ impl From<coreaudio::Error> for DefaultStreamConfigError {
         DefaultStreamConfigError::BackendSpecific { err }
     }
 }
+
+impl From<coreaudio::Error> for ReconfigureStreamError {
+    fn from(err: coreaudio::Error) -> ReconfigureStreamError {
+        let description = format!("{}", err);
+        let err = BackendSpecificError { description };
+        ReconfigureStreamError::BackendSpecific { err }
+    }
+}
+
+impl From<coreaudio::Error> for DirectMonitoringError {
+    fn from(err: coreaudio::Error) -> DirectMonitoringError {
+        let description = format!("{}", err);
+        let err = BackendSpecificError { description };
+        DirectMonitoringError::BackendSpecific { err }
+    }
+}
@@ -0,0 +1,112 @@
+//! The permission to record, which macOS and iOS both guard through `AVCaptureDevice`.
+//!
+//! AVFoundation is an Objective-C framework, so its classes are messaged through the runtime
+//! directly, and the completion handler of the request is passed as a hand-built block.
+
+use crate::InputPermission;
+use std::mem;
+use std::os::raw::{c_char, c_int, c_void};
+
+type Id = *const c_void;
+
+#[link(name = "AVFoundation", kind = "framework")]
+extern "C" {
+    static AVMediaTypeAudio: Id;
+}
+
+#[link(name = "objc")]
+extern "C" {
+    fn objc_getClass(name: *const c_char) -> Id;
+    fn sel_registerName(name: *const c_char) -> Id;
+    fn objc_msgSend();
+}
+
+extern "C" {
+    static _NSConcreteStackBlock: [*const c_void; 32];
+}
+
+type Callback = Box<dyn FnOnce(InputPermission) + Send>;
+
+// The layout of a block literal without copy and dispose helpers, capturing the boxed callback.
+// Copying the block copies the pointer, and the callback is taken when the block is invoked.
+#[repr(C)]
+struct Block {
+    isa: *const c_void,
+    flags: c_int,
+    reserved: c_int,
+    invoke: unsafe extern "C" fn(*mut Block, u8),
+    descriptor: *const BlockDescriptor,
+    callback: *mut Callback,
+}
+
+#[repr(C)]
+struct BlockDescriptor {
+    reserved: usize,
+    size: usize,
+}
+
+static BLOCK_DESCRIPTOR: BlockDescriptor = BlockDescriptor {
+    reserved: 0,
+    size: mem::size_of::<Block>(),
+};
+
+fn capture_device_class() -> Id {
+    unsafe { objc_getClass(b"AVCaptureDevice\0".as_ptr() as *const c_char) }
+}
+
+/// The permission to record, going by `AVCaptureDevice::authorizationStatusForMediaType:`.
+pub(crate) fn input_permission() -> InputPermission {
+    unsafe {
+        let selector =
+            sel_registerName(b"authorizationStatusForMediaType:\0".as_ptr() as *const c_char);
+        let send: unsafe extern "C" fn(Id, Id, Id) -> isize =
+            mem::transmute(objc_msgSend as unsafe extern "C" fn());
+        // The values of `AVAuthorizationStatus`: not determined, restricted, denied, authorized.
+        match send(capture_device_class(), selector, AVMediaTypeAudio) {
+            0 => InputPermission::NotDetermined,
+            3 => InputPermission::Granted,
+            _ => InputPermission::Denied,
+        }
+    }
+}
+
+/// Asks the user through `AVCaptureDevice::requestAccessForMediaType:completionHandler:` if the
+/// permission is not determined yet. AVFoundation calls the handler on a thread of its own.
+pub(crate) fn request_input_permission(callback: Callback) {
+    let permission = input_permission();
+    if permission != InputPermission::NotDetermined {
+        return callback(permission);
+    }
+    unsafe {
+        // The handler is copied to the heap by the callee, before the request returns.
+        let mut block = Block {
+            isa: _NSConcreteStackBlock.as_ptr() as *const c_void,
+            flags: 0,
+            reserved: 0,
+            invoke: invoke_callback,
+            descriptor: &BLOCK_DESCRIPTOR,
+            callback: Box::into_raw(Box::new(callback)),
+        };
+        let selector = sel_registerName(
+            b"requestAccessForMediaType:completionHandler:\0".as_ptr() as *const c_char
+        );
+        let send: unsafe extern "C" fn(Id, Id, Id, *mut Block) =
+            mem::transmute(objc_msgSend as unsafe extern "C" fn());
+        send(
+            capture_device_class(),
+            selector,
+            AVMediaTypeAudio,
+            &mut block,
+        );
+    }
+}
+
+// The completion handler, called once with whether the user granted the permission.
+unsafe extern "C" fn invoke_callback(block: *mut Block, granted: u8) {
+    let callback = Box::from_raw((*block).callback);
+    callback(if granted != 0 {
+        InputPermission::Granted
+    } else {
+        InputPermission::Denied
+    });
+}
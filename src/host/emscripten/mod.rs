@@ -1,3 +1,4 @@
+use std::cell::Cell;
 use std::mem;
 use std::os::raw::c_void;
 use std::slice::from_raw_parts;
@@ -9,10 +10,10 @@ use stdweb::Reference;
 
 use crate::traits::{DeviceTrait, HostTrait, StreamTrait};
 use crate::{
-    BufferSize, BuildStreamError, Data, DefaultStreamConfigError, DeviceNameError, DevicesError,
-    InputCallbackInfo, OutputCallbackInfo, PauseStreamError, PlayStreamError, SampleFormat,
-    SampleRate, StreamConfig, StreamError, SupportedBufferSize, SupportedStreamConfig,
-    SupportedStreamConfigRange, SupportedStreamConfigsError,
+    BufferLayout, BufferSize, BuildStreamError, Data, DefaultStreamConfigError, DeviceNameError,
+    DevicesError, InputCallbackInfo, OutputCallbackInfo, PauseStreamError, PlayStreamError,
+    SampleFormat, SampleRate, StreamConfig, StreamError, StreamState, SupportedBufferSize,
+    SupportedStreamConfig, SupportedStreamConfigRange, SupportedStreamConfigsError,
 };
 
 // The emscripten backend currently works by instantiating an `AudioContext` object per `Stream`.
@@ -32,6 +33,10 @@ pub struct Device;
 pub struct Stream {
     // A reference to an `AudioContext` object.
     audio_ctxt_ref: Reference,
+    // Whether the stream was not paused since it was created or last played.
+    playing: Cell<bool>,
+    // The number of frames requested from the callback so far.
+    frame_position: Cell<u64>,
 }
 
 // Index within the `streams` array of the events loop.
@@ -92,6 +97,8 @@ impl Device {
                 max_sample_rate: MAX_SAMPLE_RATE,
                 buffer_size: buffer_size.clone(),
                 sample_format: SUPPORTED_SAMPLE_FORMAT,
+                layout: BufferLayout::NonInterleaved,
+                channel_layout: None,
             })
             .collect();
         Ok(configs.into_iter())
@@ -202,12 +209,26 @@ impl DeviceTrait for Device {
                     v as usize
                 }
             }
+            BufferSize::Preferred { .. } => {
+                let supported = SupportedBufferSize::Range {
+                    min: MIN_BUFFER_SIZE,
+                    max: MAX_BUFFER_SIZE,
+                };
+                config
+                    .buffer_size
+                    .negotiate(&supported)?
+                    .unwrap_or_default() as usize
+            }
             BufferSize::Default => DEFAULT_BUFFER_SIZE,
         };
 
         // Create the stream.
         let audio_ctxt_ref = js!(return new AudioContext()).into_reference().unwrap();
-        let stream = Stream { audio_ctxt_ref };
+        let stream = Stream {
+            audio_ctxt_ref,
+            playing: Cell::new(true),
+            frame_position: Cell::new(0),
+        };
 
         // Specify the callback.
         let mut user_data = (self, data_callback, error_callback);
@@ -239,14 +260,24 @@ impl StreamTrait for Stream {
     fn play(&self) -> Result<(), PlayStreamError> {
         let audio_ctxt = &self.audio_ctxt_ref;
         js!(@{audio_ctxt}.resume());
+        self.playing.set(true);
         Ok(())
     }
 
     fn pause(&self) -> Result<(), PauseStreamError> {
         let audio_ctxt = &self.audio_ctxt_ref;
         js!(@{audio_ctxt}.suspend());
+        self.playing.set(false);
         Ok(())
     }
+
+    fn state(&self) -> StreamState {
+        if self.playing.get() {
+            StreamState::Playing
+        } else {
+            StreamState::Paused
+        }
+    }
 }
 
 // The first argument of the callback function (a `void*`) is a cast pointer to `self`
@@ -291,7 +322,11 @@ fn audio_callback_fn<D, E>(
                 .add(buffer_duration)
                 .expect("`playback` occurs beyond representation supported by `StreamInstant`");
             let timestamp = crate::OutputStreamTimestamp { callback, playback };
-            let info = OutputCallbackInfo { timestamp };
+            let frame_position = stream.frame_position.get();
+            stream
+                .frame_position
+                .set(frame_position + buffer_size_frames as u64);
+            let info = OutputCallbackInfo::new(timestamp, frame_position);
             data_cb(&mut data, &info);
         }
 
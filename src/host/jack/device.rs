@@ -1,13 +1,14 @@
 use crate::traits::DeviceTrait;
 use crate::{
-    BackendSpecificError, BuildStreamError, Data, DefaultStreamConfigError, DeviceNameError,
-    InputCallbackInfo, OutputCallbackInfo, SampleFormat, SampleRate, StreamConfig, StreamError,
+    BackendSpecificError, BufferLayout, BuildStreamError, Data, DefaultStreamConfigError,
+    DeviceDirection, DeviceNameError, InputCallbackInfo, OutputCallbackInfo, PlanarBuffer,
+    PlanarBufferMut, Sample, SampleFormat, SampleRate, StreamConfig, StreamError,
     SupportedBufferSize, SupportedStreamConfig, SupportedStreamConfigRange,
     SupportedStreamConfigsError,
 };
 use std::hash::{Hash, Hasher};
 
-use super::stream::Stream;
+use super::stream::{InputCallback, OutputCallback, Stream};
 use super::JACK_SAMPLE_FORMAT;
 
 pub type SupportedInputConfigs = std::vec::IntoIter<SupportedStreamConfigRange>;
@@ -104,6 +105,8 @@ impl Device {
             sample_rate,
             buffer_size,
             sample_format,
+            layout: BufferLayout::NonInterleaved,
+            channel_layout: None,
         })
     }
 
@@ -122,11 +125,33 @@ impl Device {
                 max_sample_rate: f.sample_rate,
                 buffer_size: f.buffer_size.clone(),
                 sample_format: f.sample_format,
+                layout: BufferLayout::NonInterleaved,
+                channel_layout: None,
             });
         }
         supported_configs
     }
 
+    // The system ports the channels are connected to, in channel order, if they are connected
+    // automatically.
+    fn system_port_names(
+        &self,
+        direction: DeviceDirection,
+    ) -> Result<Vec<String>, DeviceNameError> {
+        let pattern = match (&self.device_type, direction) {
+            (DeviceType::InputDevice, DeviceDirection::Input) => "system:capture_.*",
+            (DeviceType::OutputDevice, DeviceDirection::Output) => "system:playback_.*",
+            _ => return Ok(Vec::new()),
+        };
+        if !self.connect_ports_automatically {
+            return Ok(Vec::new());
+        }
+        let client_options = super::get_client_options(self.start_server_automatically);
+        let client = super::get_client(&format!("{}_ports", self.name), client_options)
+            .map_err(|description| BackendSpecificError { description })?;
+        Ok(client.ports(Some(pattern), None, jack::PortFlags::empty()))
+    }
+
     pub fn is_input(&self) -> bool {
         matches!(self.device_type, DeviceType::InputDevice)
     }
@@ -145,6 +170,10 @@ impl DeviceTrait for Device {
         Ok(self.name.clone())
     }
 
+    fn channel_names(&self, direction: DeviceDirection) -> Result<Vec<String>, DeviceNameError> {
+        self.system_port_names(direction)
+    }
+
     fn supported_input_configs(
         &self,
     ) -> Result<Self::SupportedInputConfigs, SupportedStreamConfigsError> {
@@ -181,6 +210,83 @@ impl DeviceTrait for Device {
     where
         D: FnMut(&Data, &InputCallbackInfo) + Send + 'static,
         E: FnMut(StreamError) + Send + 'static,
+    {
+        let data_callback = InputCallback::Interleaved(Box::new(data_callback));
+        self.build_input(conf, sample_format, data_callback, error_callback)
+    }
+
+    fn build_output_stream_raw<D, E>(
+        &self,
+        conf: &StreamConfig,
+        sample_format: SampleFormat,
+        data_callback: D,
+        error_callback: E,
+    ) -> Result<Self::Stream, BuildStreamError>
+    where
+        D: FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        let data_callback = OutputCallback::Interleaved(Box::new(data_callback));
+        self.build_output(conf, sample_format, data_callback, error_callback)
+    }
+
+    /// Passes the buffers of the ports to the callback as they are, if it takes `f32` samples.
+    fn build_input_stream_planar<T, D, E>(
+        &self,
+        conf: &StreamConfig,
+        mut data_callback: D,
+        error_callback: E,
+    ) -> Result<Self::Stream, BuildStreamError>
+    where
+        T: Sample + Send + 'static,
+        D: FnMut(&PlanarBuffer<T>, &InputCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        if T::FORMAT != JACK_SAMPLE_FORMAT {
+            return crate::planar::build_input_stream(self, conf, data_callback, error_callback);
+        }
+        let data_callback = InputCallback::Planar(Box::new(
+            move |buffer: &PlanarBuffer<f32>, info: &InputCallbackInfo| {
+                data_callback(unsafe { &buffer.cast() }, info)
+            },
+        ));
+        self.build_input(conf, JACK_SAMPLE_FORMAT, data_callback, error_callback)
+    }
+
+    /// Passes the buffers of the ports to the callback as they are, if it takes `f32` samples.
+    fn build_output_stream_planar<T, D, E>(
+        &self,
+        conf: &StreamConfig,
+        mut data_callback: D,
+        error_callback: E,
+    ) -> Result<Self::Stream, BuildStreamError>
+    where
+        T: Sample + Send + 'static,
+        D: FnMut(&mut PlanarBufferMut<T>, &OutputCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        if T::FORMAT != JACK_SAMPLE_FORMAT {
+            return crate::planar::build_output_stream(self, conf, data_callback, error_callback);
+        }
+        let data_callback = OutputCallback::Planar(Box::new(
+            move |buffer: &mut PlanarBufferMut<f32>, info: &OutputCallbackInfo| {
+                data_callback(unsafe { &mut buffer.cast() }, info)
+            },
+        ));
+        self.build_output(conf, JACK_SAMPLE_FORMAT, data_callback, error_callback)
+    }
+}
+
+impl Device {
+    fn build_input<E>(
+        &self,
+        conf: &StreamConfig,
+        sample_format: SampleFormat,
+        data_callback: InputCallback,
+        error_callback: E,
+    ) -> Result<Stream, BuildStreamError>
+    where
+        E: FnMut(StreamError) + Send + 'static,
     {
         if let DeviceType::OutputDevice = &self.device_type {
             // Trying to create an input stream from an output device
@@ -209,15 +315,14 @@ impl DeviceTrait for Device {
         Ok(stream)
     }
 
-    fn build_output_stream_raw<D, E>(
+    fn build_output<E>(
         &self,
         conf: &StreamConfig,
         sample_format: SampleFormat,
-        data_callback: D,
+        data_callback: OutputCallback,
         error_callback: E,
-    ) -> Result<Self::Stream, BuildStreamError>
+    ) -> Result<Stream, BuildStreamError>
     where
-        D: FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static,
         E: FnMut(StreamError) + Send + 'static,
     {
         if let DeviceType::InputDevice = &self.device_type {
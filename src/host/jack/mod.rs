@@ -32,7 +32,7 @@ pub struct Host {
 impl Host {
     pub fn new() -> Result<Self, crate::HostUnavailable> {
         let mut host = Host {
-            name: "cpal_client".to_owned(),
+            name: crate::application_name().unwrap_or_else(|| "cpal_client".to_owned()),
             connect_ports_automatically: true,
             start_server_automatically: false,
             devices_created: vec![],
@@ -3,14 +3,35 @@ use crate::ChannelCount;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 
+use crate::planar::ChannelPointers;
 use crate::{
     BackendSpecificError, Data, InputCallbackInfo, OutputCallbackInfo, PauseStreamError,
-    PlayStreamError, SampleRate, StreamError,
+    PlanarBuffer, PlanarBufferMut, PlayStreamError, SampleRate, StreamError, StreamState,
+    SupportedBufferSize, SupportedStreamConfig,
 };
 
 use super::JACK_SAMPLE_FORMAT;
 
 type ErrorCallbackPtr = Arc<Mutex<dyn FnMut(StreamError) + Send + 'static>>;
+type InputDataCallback = Box<dyn FnMut(&Data, &InputCallbackInfo) + Send + 'static>;
+type InputPlanarCallback = Box<dyn FnMut(&PlanarBuffer<f32>, &InputCallbackInfo) + Send + 'static>;
+type OutputDataCallback = Box<dyn FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static>;
+type OutputPlanarCallback =
+    Box<dyn FnMut(&mut PlanarBufferMut<f32>, &OutputCallbackInfo) + Send + 'static>;
+
+/// The data callback of an input stream, passed the frames either interleaved or with the buffer
+/// of each port as it is.
+pub enum InputCallback {
+    Interleaved(InputDataCallback),
+    Planar(InputPlanarCallback),
+}
+
+/// The data callback of an output stream, writing the frames either interleaved or into the
+/// buffer of each port.
+pub enum OutputCallback {
+    Interleaved(OutputDataCallback),
+    Planar(OutputPlanarCallback),
+}
 
 pub struct Stream {
     // TODO: It might be faster to send a message when playing/pausing than to check this every iteration
@@ -23,14 +44,13 @@ pub struct Stream {
 
 impl Stream {
     // TODO: Return error messages
-    pub fn new_input<D, E>(
+    pub fn new_input<E>(
         client: jack::Client,
         channels: ChannelCount,
-        data_callback: D,
+        data_callback: InputCallback,
         mut error_callback: E,
     ) -> Stream
     where
-        D: FnMut(&Data, &InputCallbackInfo) + Send + 'static,
         E: FnMut(StreamError) + Send + 'static,
     {
         let mut ports = vec![];
@@ -68,13 +88,13 @@ impl Stream {
             ports,
             SampleRate(client.sample_rate() as u32),
             client.buffer_size() as usize,
-            Some(Box::new(data_callback)),
+            Some(data_callback),
             None,
             playing.clone(),
             Arc::clone(&error_callback_ptr),
         );
 
-        let notification_handler = JackNotificationHandler::new(error_callback_ptr);
+        let notification_handler = JackNotificationHandler::new(error_callback_ptr, channels);
 
         let async_client = client
             .activate_async(notification_handler, input_process_handler)
@@ -88,14 +108,13 @@ impl Stream {
         }
     }
 
-    pub fn new_output<D, E>(
+    pub fn new_output<E>(
         client: jack::Client,
         channels: ChannelCount,
-        data_callback: D,
+        data_callback: OutputCallback,
         mut error_callback: E,
     ) -> Stream
     where
-        D: FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static,
         E: FnMut(StreamError) + Send + 'static,
     {
         let mut ports = vec![];
@@ -134,12 +153,12 @@ impl Stream {
             SampleRate(client.sample_rate() as u32),
             client.buffer_size() as usize,
             None,
-            Some(Box::new(data_callback)),
+            Some(data_callback),
             playing.clone(),
             Arc::clone(&error_callback_ptr),
         );
 
-        let notification_handler = JackNotificationHandler::new(error_callback_ptr);
+        let notification_handler = JackNotificationHandler::new(error_callback_ptr, channels);
 
         let async_client = client
             .activate_async(notification_handler, output_process_handler)
@@ -216,6 +235,14 @@ impl StreamTrait for Stream {
         self.playing.store(false, Ordering::SeqCst);
         Ok(())
     }
+
+    fn state(&self) -> StreamState {
+        if self.playing.load(Ordering::SeqCst) {
+            StreamState::Playing
+        } else {
+            StreamState::Paused
+        }
+    }
 }
 
 struct LocalProcessHandler {
@@ -225,14 +252,19 @@ struct LocalProcessHandler {
 
     sample_rate: SampleRate,
     buffer_size: usize,
-    input_data_callback: Option<Box<dyn FnMut(&Data, &InputCallbackInfo) + Send + 'static>>,
-    output_data_callback: Option<Box<dyn FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static>>,
+    input_data_callback: Option<InputCallback>,
+    output_data_callback: Option<OutputCallback>,
 
     // JACK audio samples are 32-bit float (unless you do some custom dark magic)
     temp_input_buffer: Vec<f32>,
     temp_output_buffer: Vec<f32>,
+    // The buffers of the ports, for planar callbacks
+    input_pointers: ChannelPointers<*const f32>,
+    output_pointers: ChannelPointers<*mut f32>,
     playing: Arc<AtomicBool>,
     creation_timestamp: std::time::Instant,
+    /// The number of frames passed to or from the callbacks so far.
+    frame_position: u64,
     /// This should not be called on `process`, only on `buffer_size` because it can block.
     error_callback_ptr: ErrorCallbackPtr,
 }
@@ -243,16 +275,16 @@ impl LocalProcessHandler {
         in_ports: Vec<jack::Port<jack::AudioIn>>,
         sample_rate: SampleRate,
         buffer_size: usize,
-        input_data_callback: Option<Box<dyn FnMut(&Data, &InputCallbackInfo) + Send + 'static>>,
-        output_data_callback: Option<
-            Box<dyn FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static>,
-        >,
+        input_data_callback: Option<InputCallback>,
+        output_data_callback: Option<OutputCallback>,
         playing: Arc<AtomicBool>,
         error_callback_ptr: ErrorCallbackPtr,
     ) -> Self {
         // These may be reallocated in the `buffer_size` callback.
         let temp_input_buffer = vec![0.0; in_ports.len() * buffer_size];
         let temp_output_buffer = vec![0.0; out_ports.len() * buffer_size];
+        let input_pointers = ChannelPointers::new(in_ports.len());
+        let output_pointers = ChannelPointers::new(out_ports.len());
 
         LocalProcessHandler {
             out_ports,
@@ -263,8 +295,11 @@ impl LocalProcessHandler {
             output_data_callback,
             temp_input_buffer,
             temp_output_buffer,
+            input_pointers,
+            output_pointers,
             playing,
             creation_timestamp: std::time::Instant::now(),
+            frame_position: 0,
             error_callback_ptr,
         }
     }
@@ -313,19 +348,6 @@ impl jack::ProcessHandler for LocalProcessHandler {
 
             let num_in_channels = self.in_ports.len();
 
-            // Read the data from the input ports into the temporary buffer
-            // Go through every channel and store its data in the temporary input buffer
-            for ch_ix in 0..num_in_channels {
-                let input_channel = &self.in_ports[ch_ix].as_slice(process_scope);
-                for i in 0..current_frame_count {
-                    self.temp_input_buffer[ch_ix + i * num_in_channels] = input_channel[i];
-                }
-            }
-            // Create a slice of exactly current_frame_count frames
-            let data = temp_buffer_to_data(
-                &mut self.temp_input_buffer,
-                current_frame_count * num_in_channels,
-            );
             // Create timestamp
             let frames_since_cycle_start = process_scope.frames_since_cycle_start() as usize;
             let duration_since_cycle_start =
@@ -335,18 +357,43 @@ impl jack::ProcessHandler for LocalProcessHandler {
                 .expect("`playback` occurs beyond representation supported by `StreamInstant`");
             let capture = start_callback_instant;
             let timestamp = crate::InputStreamTimestamp { callback, capture };
-            let info = crate::InputCallbackInfo { timestamp };
-            input_callback(&data, &info);
+            let info = crate::InputCallbackInfo::new(timestamp, self.frame_position);
+
+            match input_callback {
+                InputCallback::Interleaved(input_callback) => {
+                    // Read the data from the input ports into the temporary buffer
+                    // Go through every channel and store its data in the temporary input buffer
+                    for ch_ix in 0..num_in_channels {
+                        let input_channel = &self.in_ports[ch_ix].as_slice(process_scope);
+                        for i in 0..current_frame_count {
+                            self.temp_input_buffer[ch_ix + i * num_in_channels] = input_channel[i];
+                        }
+                    }
+                    // Create a slice of exactly current_frame_count frames
+                    let data = temp_buffer_to_data(
+                        &mut self.temp_input_buffer,
+                        current_frame_count * num_in_channels,
+                    );
+                    input_callback(&data, &info);
+                }
+                InputCallback::Planar(input_callback) => {
+                    // Pass the buffers of the ports as they are
+                    let in_ports = &self.in_ports;
+                    let pointers = self.input_pointers.set(
+                        in_ports
+                            .iter()
+                            .map(|port| port.as_slice(process_scope).as_ptr()),
+                    );
+                    let buffer =
+                        unsafe { PlanarBuffer::from_raw_parts(pointers, current_frame_count) };
+                    input_callback(&buffer, &info);
+                }
+            }
         }
 
         if let Some(output_callback) = &mut self.output_data_callback {
             let num_out_channels = self.out_ports.len();
 
-            // Create a slice of exactly current_frame_count frames
-            let mut data = temp_buffer_to_data(
-                &mut self.temp_output_buffer,
-                current_frame_count * num_out_channels,
-            );
             // Create timestamp
             let frames_since_cycle_start = process_scope.frames_since_cycle_start() as usize;
             let duration_since_cycle_start =
@@ -359,18 +406,43 @@ impl jack::ProcessHandler for LocalProcessHandler {
                 .add(buffer_duration)
                 .expect("`playback` occurs beyond representation supported by `StreamInstant`");
             let timestamp = crate::OutputStreamTimestamp { callback, playback };
-            let info = crate::OutputCallbackInfo { timestamp };
-            output_callback(&mut data, &info);
-
-            // Deinterlace
-            for ch_ix in 0..num_out_channels {
-                let output_channel = &mut self.out_ports[ch_ix].as_mut_slice(process_scope);
-                for i in 0..current_frame_count {
-                    output_channel[i] = self.temp_output_buffer[ch_ix + i * num_out_channels];
+            let info = crate::OutputCallbackInfo::new(timestamp, self.frame_position);
+
+            match output_callback {
+                OutputCallback::Interleaved(output_callback) => {
+                    // Create a slice of exactly current_frame_count frames
+                    let mut data = temp_buffer_to_data(
+                        &mut self.temp_output_buffer,
+                        current_frame_count * num_out_channels,
+                    );
+                    output_callback(&mut data, &info);
+
+                    // Deinterlace
+                    for ch_ix in 0..num_out_channels {
+                        let output_channel = &mut self.out_ports[ch_ix].as_mut_slice(process_scope);
+                        for i in 0..current_frame_count {
+                            output_channel[i] =
+                                self.temp_output_buffer[ch_ix + i * num_out_channels];
+                        }
+                    }
+                }
+                OutputCallback::Planar(output_callback) => {
+                    // Let the callback write into the buffers of the ports, silenced first
+                    let out_ports = &mut self.out_ports;
+                    let pointers = self.output_pointers.set(out_ports.iter_mut().map(|port| {
+                        let output_channel = port.as_mut_slice(process_scope);
+                        output_channel.fill(0.0);
+                        output_channel.as_mut_ptr()
+                    }));
+                    let mut buffer =
+                        unsafe { PlanarBufferMut::from_raw_parts(pointers, current_frame_count) };
+                    output_callback(&mut buffer, &info);
                 }
             }
         }
 
+        self.frame_position += current_frame_count as u64;
+
         // Continue as normal
         jack::Control::Continue
     }
@@ -416,31 +488,34 @@ fn frames_to_duration(frames: usize, rate: crate::SampleRate) -> std::time::Dura
 struct JackNotificationHandler {
     error_callback_ptr: ErrorCallbackPtr,
     init_sample_rate_flag: Arc<AtomicBool>,
+    // The channels of the stream, reported along with a new sample rate of the server.
+    channels: ChannelCount,
 }
 
 impl JackNotificationHandler {
-    pub fn new(error_callback_ptr: ErrorCallbackPtr) -> Self {
+    pub fn new(error_callback_ptr: ErrorCallbackPtr, channels: ChannelCount) -> Self {
         JackNotificationHandler {
             error_callback_ptr,
             init_sample_rate_flag: Arc::new(AtomicBool::new(false)),
+            channels,
         }
     }
 
-    fn send_error(&mut self, description: String) {
+    fn send_error(&mut self, err: StreamError) {
         // This thread isn't the audio thread, it's fine to block
         if let Ok(mut mutex_guard) = self.error_callback_ptr.lock() {
-            let err = &mut *mutex_guard;
-            err(BackendSpecificError { description }.into());
+            let error_callback = &mut *mutex_guard;
+            error_callback(err);
         }
     }
 }
 
 impl jack::NotificationHandler for JackNotificationHandler {
-    fn shutdown(&mut self, _status: jack::ClientStatus, reason: &str) {
-        self.send_error(format!("JACK was shut down for reason: {}", reason));
+    fn shutdown(&mut self, _status: jack::ClientStatus, _reason: &str) {
+        self.send_error(StreamError::DriverRestarted);
     }
 
-    fn sample_rate(&mut self, _: &jack::Client, srate: jack::Frames) -> jack::Control {
+    fn sample_rate(&mut self, client: &jack::Client, srate: jack::Frames) -> jack::Control {
         match self.init_sample_rate_flag.load(Ordering::SeqCst) {
             false => {
                 // One of these notifications is sent every time a client is started.
@@ -448,16 +523,27 @@ impl jack::NotificationHandler for JackNotificationHandler {
                 jack::Control::Continue
             }
             true => {
-                self.send_error(format!("sample rate changed to: {}", srate));
-                // Since CPAL currently has no way of signaling a sample rate change in order to make
-                // all necessary changes that would bring we choose to quit.
+                let buffer_size = client.buffer_size();
+                let config = SupportedStreamConfig::new(
+                    self.channels,
+                    SampleRate(srate),
+                    SupportedBufferSize::Range {
+                        min: buffer_size,
+                        max: buffer_size,
+                    },
+                    JACK_SAMPLE_FORMAT,
+                );
+                self.send_error(StreamError::DeviceFormatChanged { config });
+                // The process handler cannot follow the new rate, so the stream quits and has to
+                // be rebuilt at it.
                 jack::Control::Quit
             }
         }
     }
 
     fn xrun(&mut self, _: &jack::Client) -> jack::Control {
-        self.send_error(String::from("xrun (buffer over or under run)"));
+        let description = String::from("xrun (buffer over or under run)");
+        self.send_error(BackendSpecificError { description }.into());
         jack::Control::Continue
     }
 }
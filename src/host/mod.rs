@@ -1,20 +1,24 @@
-#[cfg(any(target_os = "linux", target_os = "dragonfly", target_os = "freebsd"))]
+#[cfg(all(
+    feature = "backends",
+    any(target_os = "linux", target_os = "dragonfly", target_os = "freebsd")
+))]
 pub(crate) mod alsa;
-#[cfg(all(windows, feature = "asio"))]
+#[cfg(all(feature = "backends", windows, feature = "asio"))]
 pub(crate) mod asio;
-#[cfg(any(target_os = "macos", target_os = "ios"))]
+#[cfg(all(feature = "backends", any(target_os = "macos", target_os = "ios")))]
 pub(crate) mod coreaudio;
-#[cfg(target_os = "emscripten")]
+#[cfg(all(feature = "backends", target_os = "emscripten"))]
 pub(crate) mod emscripten;
 #[cfg(all(
+    feature = "backends",
     any(target_os = "linux", target_os = "dragonfly", target_os = "freebsd"),
     feature = "jack"
 ))]
 pub(crate) mod jack;
 pub(crate) mod null;
-#[cfg(target_os = "android")]
+#[cfg(all(feature = "backends", target_os = "android"))]
 pub(crate) mod oboe;
-#[cfg(windows)]
+#[cfg(all(feature = "backends", windows))]
 pub(crate) mod wasapi;
-#[cfg(all(target_arch = "wasm32", feature = "wasm-bindgen"))]
+#[cfg(all(feature = "backends", target_arch = "wasm32", feature = "wasm-bindgen"))]
 pub(crate) mod webaudio;
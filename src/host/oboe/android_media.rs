@@ -2,6 +2,7 @@ use std::sync::Arc;
 
 extern crate jni;
 
+use self::jni::objects::{JObject, JValue};
 use self::jni::Executor;
 use self::jni::{errors::Result as JResult, JNIEnv, JavaVM};
 
@@ -20,6 +21,30 @@ where
     Executor::new(vm).with_attached(|env| closure(env))
 }
 
+/// Whether the application holds the `RECORD_AUDIO` permission, going by
+/// `Context.checkSelfPermission`.
+pub fn has_record_audio_permission() -> bool {
+    let context = ndk_context::android_context().context();
+    with_attached(|env| {
+        let permission = env.new_string("android.permission.RECORD_AUDIO")?;
+        let result = env.call_method(
+            JObject::from(context as jni::sys::jobject),
+            "checkSelfPermission",
+            "(Ljava/lang/String;)I",
+            &[JValue::Object(permission.into())],
+        );
+        if result.is_err() {
+            env.exception_clear()?;
+        }
+        // Android before 6.0 grants permissions on install, and has no `checkSelfPermission`.
+        // Granted permissions are `PackageManager.PERMISSION_GRANTED`.
+        Ok(result
+            .and_then(|value| value.i())
+            .map_or(true, |status| status == 0))
+    })
+    .unwrap_or(true)
+}
+
 fn get_min_buffer_size(
     class: &'static str,
     sample_rate: i32,
@@ -4,8 +4,8 @@ use std::time::Duration;
 extern crate oboe;
 
 use crate::{
-    BackendSpecificError, BuildStreamError, PauseStreamError, PlayStreamError, StreamError,
-    StreamInstant,
+    BackendSpecificError, BuildStreamError, PauseStreamError, PlayStreamError,
+    ReconfigureStreamError, StreamError, StreamInstant,
 };
 
 pub fn to_stream_instant(duration: Duration) -> StreamInstant {
@@ -26,6 +26,20 @@ pub fn stream_instant<T: oboe::AudioStreamSafe + ?Sized>(stream: &mut T) -> Stre
     to_stream_instant(Duration::from_nanos(ts.timestamp as u64))
 }
 
+/// The instant the next frame written to an output stream reaches the DAC, extrapolated from the
+/// latest frame the device presented.
+pub fn presentation_instant<T: oboe::AudioStreamSafe + ?Sized>(stream: &mut T) -> StreamInstant {
+    const CLOCK_MONOTONIC: i32 = 1;
+    let ts = match stream.get_timestamp(CLOCK_MONOTONIC) {
+        Ok(ts) => ts,
+        Err(_) => return stream_instant(stream),
+    };
+    let sample_rate = stream.get_sample_rate().max(1) as i64;
+    let frames_ahead = stream.get_frames_written() - ts.position;
+    let nanos = ts.timestamp + frames_ahead * 1_000_000_000 / sample_rate;
+    to_stream_instant(Duration::from_nanos(nanos.max(0) as u64))
+}
+
 impl From<oboe::Error> for StreamError {
     fn from(error: oboe::Error) -> Self {
         use self::oboe::Error::*;
@@ -65,6 +79,19 @@ impl From<oboe::Error> for PauseStreamError {
     }
 }
 
+impl From<oboe::Error> for ReconfigureStreamError {
+    fn from(error: oboe::Error) -> Self {
+        use self::oboe::Error::*;
+        match error {
+            Disconnected | Unavailable | Closed => Self::DeviceNotAvailable,
+            e => (BackendSpecificError {
+                description: e.to_string(),
+            })
+            .into(),
+        }
+    }
+}
+
 impl From<oboe::Error> for BuildStreamError {
     fn from(error: oboe::Error) -> Self {
         use self::oboe::Error::*;
@@ -1,17 +1,19 @@
 use std::cell::RefCell;
 use std::cmp;
 use std::convert::TryInto;
+use std::time::Duration;
 use std::vec::IntoIter as VecIntoIter;
 
 extern crate oboe;
 
 use crate::traits::{DeviceTrait, HostTrait, StreamTrait};
 use crate::{
-    BackendSpecificError, BufferSize, BuildStreamError, Data, DefaultStreamConfigError,
-    DeviceNameError, DevicesError, InputCallbackInfo, OutputCallbackInfo, PauseStreamError,
-    PlayStreamError, Sample, SampleFormat, SampleRate, StreamConfig, StreamError,
-    SupportedBufferSize, SupportedStreamConfig, SupportedStreamConfigRange,
-    SupportedStreamConfigsError,
+    BackendSpecificError, BufferLayout, BufferSize, BuildStreamError, Data,
+    DefaultStreamConfigError, DeviceNameError, DevicesError, FrameCount, InputCallbackInfo,
+    InputPermission, OutputCallbackInfo, PauseStreamError, PlayStreamError, ReconfigureStreamError,
+    Sample, SampleFormat, SampleRate, ShareMode, StreamConfig, StreamError, StreamLatency,
+    StreamState, StreamUsage, SupportedBufferSize, SupportedStreamConfig,
+    SupportedStreamConfigRange, SupportedStreamConfigsError,
 };
 
 mod android_media;
@@ -19,9 +21,11 @@ mod convert;
 mod input_callback;
 mod output_callback;
 
-use self::android_media::{get_audio_record_min_buffer_size, get_audio_track_min_buffer_size};
+use self::android_media::{
+    get_audio_record_min_buffer_size, get_audio_track_min_buffer_size, has_record_audio_permission,
+};
 use self::input_callback::CpalInputCallback;
-use self::oboe::{AudioInputStream, AudioOutputStream};
+use self::oboe::{AudioInputStream, AudioOutputStream, AudioStreamBase, AudioStreamSafe};
 use self::output_callback::CpalOutputCallback;
 
 // Android Java API supports up to 8 channels, but oboe API
@@ -79,6 +83,14 @@ impl HostTrait for Host {
     fn default_output_device(&self) -> Option<Self::Device> {
         Some(Device(None))
     }
+
+    fn input_permission(&self) -> InputPermission {
+        if has_record_audio_permission() {
+            InputPermission::Granted
+        } else {
+            InputPermission::Denied
+        }
+    }
 }
 
 fn buffer_size_range_for_params(
@@ -128,6 +140,8 @@ fn default_supported_configs(is_output: bool) -> VecIntoIter<SupportedStreamConf
                         max_sample_rate: SampleRate(*sample_rate as u32),
                         buffer_size: SupportedBufferSize::Range { min, max },
                         sample_format: *sample_format,
+                        layout: BufferLayout::Interleaved,
+                        channel_layout: None,
                     });
                 }
             }
@@ -192,6 +206,8 @@ fn device_supported_configs(
                     max_sample_rate: SampleRate(*sample_rate as u32),
                     buffer_size,
                     sample_format,
+                    layout: BufferLayout::Interleaved,
+                    channel_layout: None,
                 });
             }
         }
@@ -204,6 +220,8 @@ fn configure_for_device<D, C, I>(
     builder: oboe::AudioStreamBuilder<D, C, I>,
     device: &Device,
     config: &StreamConfig,
+    share_mode: ShareMode,
+    usage: Option<StreamUsage>,
 ) -> oboe::AudioStreamBuilder<D, C, I> {
     let mut builder = if let Some(info) = &device.0 {
         builder.set_device_id(info.id)
@@ -211,15 +229,38 @@ fn configure_for_device<D, C, I>(
         builder
     };
     builder = builder.set_sample_rate(config.sample_rate.0.try_into().unwrap());
+    builder = builder.set_sharing_mode(match share_mode {
+        ShareMode::Shared => oboe::SharingMode::Shared,
+        ShareMode::Exclusive => oboe::SharingMode::Exclusive,
+    });
+    if let Some(usage) = usage {
+        let (usage, content_type) = match usage {
+            StreamUsage::Game => (oboe::Usage::Game, oboe::ContentType::Sonification),
+            StreamUsage::Media => (oboe::Usage::Media, oboe::ContentType::Music),
+            StreamUsage::Communication => {
+                (oboe::Usage::VoiceCommunication, oboe::ContentType::Speech)
+            }
+            StreamUsage::Alarm => (oboe::Usage::Alarm, oboe::ContentType::Sonification),
+            StreamUsage::Accessibility => (
+                oboe::Usage::AssistanceAccessibility,
+                oboe::ContentType::Speech,
+            ),
+        };
+        builder = builder.set_usage(usage).set_content_type(content_type);
+    }
     match &config.buffer_size {
         BufferSize::Default => builder,
-        BufferSize::Fixed(size) => builder.set_buffer_capacity_in_frames(*size as i32),
+        BufferSize::Fixed(size) | BufferSize::Preferred { ideal: size, .. } => {
+            builder.set_buffer_capacity_in_frames(*size as i32)
+        }
     }
 }
 
 fn build_input_stream<D, E, C, T>(
     device: &Device,
     config: &StreamConfig,
+    share_mode: ShareMode,
+    usage: Option<StreamUsage>,
     data_callback: D,
     error_callback: E,
     builder: oboe::AudioStreamBuilder<oboe::Input, C, T>,
@@ -231,19 +272,32 @@ where
     D: FnMut(&Data, &InputCallbackInfo) + Send + 'static,
     E: FnMut(StreamError) + Send + 'static,
 {
-    let builder = configure_for_device(builder, device, config);
+    let mut builder = configure_for_device(builder, device, config, share_mode, usage);
+    // The voice communication preset enables the echo canceller and noise suppressor of the
+    // device, where it has them.
+    if usage == Some(StreamUsage::Communication) {
+        builder = builder.set_input_preset(oboe::InputPreset::VoiceCommunication);
+    }
     let stream = builder
         .set_callback(CpalInputCallback::<T, C>::new(
             data_callback,
             error_callback,
         ))
         .open_stream()?;
+    // AAudio falls back to shared access when exclusive access is unavailable.
+    if share_mode == ShareMode::Exclusive
+        && stream.get_sharing_mode() != oboe::SharingMode::Exclusive
+    {
+        return Err(BuildStreamError::ShareModeNotSupported);
+    }
     Ok(Stream::Input(Box::new(RefCell::new(stream))))
 }
 
 fn build_output_stream<D, E, C, T>(
     device: &Device,
     config: &StreamConfig,
+    share_mode: ShareMode,
+    usage: Option<StreamUsage>,
     data_callback: D,
     error_callback: E,
     builder: oboe::AudioStreamBuilder<oboe::Output, C, T>,
@@ -255,81 +309,40 @@ where
     D: FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static,
     E: FnMut(StreamError) + Send + 'static,
 {
-    let builder = configure_for_device(builder, device, config);
+    let builder = configure_for_device(builder, device, config, share_mode, usage);
     let stream = builder
         .set_callback(CpalOutputCallback::<T, C>::new(
             data_callback,
             error_callback,
         ))
         .open_stream()?;
+    // AAudio falls back to shared access when exclusive access is unavailable.
+    if share_mode == ShareMode::Exclusive
+        && stream.get_sharing_mode() != oboe::SharingMode::Exclusive
+    {
+        return Err(BuildStreamError::ShareModeNotSupported);
+    }
     Ok(Stream::Output(Box::new(RefCell::new(stream))))
 }
 
-impl DeviceTrait for Device {
-    type SupportedInputConfigs = SupportedInputConfigs;
-    type SupportedOutputConfigs = SupportedOutputConfigs;
-    type Stream = Stream;
-
-    fn name(&self) -> Result<String, DeviceNameError> {
-        match &self.0 {
-            None => Ok("default".to_owned()),
-            Some(info) => Ok(info.product_name.clone()),
-        }
-    }
-
-    fn supported_input_configs(
-        &self,
-    ) -> Result<Self::SupportedInputConfigs, SupportedStreamConfigsError> {
-        if let Some(info) = &self.0 {
-            Ok(device_supported_configs(info, false))
-        } else {
-            Ok(default_supported_configs(false))
-        }
-    }
-
-    fn supported_output_configs(
-        &self,
-    ) -> Result<Self::SupportedOutputConfigs, SupportedStreamConfigsError> {
-        if let Some(info) = &self.0 {
-            Ok(device_supported_configs(info, true))
-        } else {
-            Ok(default_supported_configs(true))
-        }
-    }
-
-    fn default_input_config(&self) -> Result<SupportedStreamConfig, DefaultStreamConfigError> {
-        let mut configs: Vec<_> = self.supported_input_configs().unwrap().collect();
-        configs.sort_by(|a, b| b.cmp_default_heuristics(a));
-        let config = configs
-            .into_iter()
-            .next()
-            .ok_or(DefaultStreamConfigError::StreamTypeNotSupported)?
-            .with_max_sample_rate();
-        Ok(config)
-    }
-
-    fn default_output_config(&self) -> Result<SupportedStreamConfig, DefaultStreamConfigError> {
-        let mut configs: Vec<_> = self.supported_output_configs().unwrap().collect();
-        configs.sort_by(|a, b| b.cmp_default_heuristics(a));
-        let config = configs
-            .into_iter()
-            .next()
-            .ok_or(DefaultStreamConfigError::StreamTypeNotSupported)?
-            .with_max_sample_rate();
-        Ok(config)
-    }
-
-    fn build_input_stream_raw<D, E>(
+impl Device {
+    // Builds an input stream, for `usage` if it is `Some`.
+    fn build_input<D, E>(
         &self,
         config: &StreamConfig,
         sample_format: SampleFormat,
+        share_mode: ShareMode,
+        usage: Option<StreamUsage>,
         data_callback: D,
         error_callback: E,
-    ) -> Result<Self::Stream, BuildStreamError>
+    ) -> Result<Stream, BuildStreamError>
     where
         D: FnMut(&Data, &InputCallbackInfo) + Send + 'static,
         E: FnMut(StreamError) + Send + 'static,
     {
+        if !has_record_audio_permission() {
+            return Err(BuildStreamError::PermissionDenied);
+        }
         match sample_format {
             SampleFormat::I16 => {
                 let builder = oboe::AudioStreamBuilder::default()
@@ -339,6 +352,8 @@ impl DeviceTrait for Device {
                     build_input_stream(
                         self,
                         config,
+                        share_mode,
+                        usage,
                         data_callback,
                         error_callback,
                         builder.set_mono(),
@@ -347,6 +362,8 @@ impl DeviceTrait for Device {
                     build_input_stream(
                         self,
                         config,
+                        share_mode,
+                        usage,
                         data_callback,
                         error_callback,
                         builder.set_stereo(),
@@ -366,6 +383,8 @@ impl DeviceTrait for Device {
                     build_input_stream(
                         self,
                         config,
+                        share_mode,
+                        usage,
                         data_callback,
                         error_callback,
                         builder.set_mono(),
@@ -374,6 +393,8 @@ impl DeviceTrait for Device {
                     build_input_stream(
                         self,
                         config,
+                        share_mode,
+                        usage,
                         data_callback,
                         error_callback,
                         builder.set_stereo(),
@@ -385,20 +406,23 @@ impl DeviceTrait for Device {
                     .into())
                 }
             }
-            SampleFormat::U16 => Err(BackendSpecificError {
-                description: "U16 format is not supported on Android.".to_owned(),
+            sample_format => Err(BackendSpecificError {
+                description: format!("{:?} format is not supported on Android.", sample_format),
             }
             .into()),
         }
     }
 
-    fn build_output_stream_raw<D, E>(
+    // Builds an output stream, for `usage` if it is `Some`.
+    fn build_output<D, E>(
         &self,
         config: &StreamConfig,
         sample_format: SampleFormat,
+        share_mode: ShareMode,
+        usage: Option<StreamUsage>,
         data_callback: D,
         error_callback: E,
-    ) -> Result<Self::Stream, BuildStreamError>
+    ) -> Result<Stream, BuildStreamError>
     where
         D: FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static,
         E: FnMut(StreamError) + Send + 'static,
@@ -412,6 +436,8 @@ impl DeviceTrait for Device {
                     build_output_stream(
                         self,
                         config,
+                        share_mode,
+                        usage,
                         data_callback,
                         error_callback,
                         builder.set_mono(),
@@ -420,6 +446,8 @@ impl DeviceTrait for Device {
                     build_output_stream(
                         self,
                         config,
+                        share_mode,
+                        usage,
                         data_callback,
                         error_callback,
                         builder.set_stereo(),
@@ -439,6 +467,8 @@ impl DeviceTrait for Device {
                     build_output_stream(
                         self,
                         config,
+                        share_mode,
+                        usage,
                         data_callback,
                         error_callback,
                         builder.set_mono(),
@@ -447,6 +477,8 @@ impl DeviceTrait for Device {
                     build_output_stream(
                         self,
                         config,
+                        share_mode,
+                        usage,
                         data_callback,
                         error_callback,
                         builder.set_stereo(),
@@ -458,14 +490,206 @@ impl DeviceTrait for Device {
                     .into())
                 }
             }
-            SampleFormat::U16 => Err(BackendSpecificError {
-                description: "U16 format is not supported on Android.".to_owned(),
+            sample_format => Err(BackendSpecificError {
+                description: format!("{:?} format is not supported on Android.", sample_format),
             }
             .into()),
         }
     }
 }
 
+impl DeviceTrait for Device {
+    type SupportedInputConfigs = SupportedInputConfigs;
+    type SupportedOutputConfigs = SupportedOutputConfigs;
+    type Stream = Stream;
+
+    fn name(&self) -> Result<String, DeviceNameError> {
+        match &self.0 {
+            None => Ok("default".to_owned()),
+            Some(info) => Ok(info.product_name.clone()),
+        }
+    }
+
+    // AAudio grants exclusive access where the device allows it.
+    fn capabilities(&self) -> Result<crate::DeviceCapabilities, SupportedStreamConfigsError> {
+        let mut capabilities = crate::device_info::capabilities_from_configs(self)?;
+        capabilities.exclusive_mode = true;
+        Ok(capabilities)
+    }
+
+    fn supported_input_configs(
+        &self,
+    ) -> Result<Self::SupportedInputConfigs, SupportedStreamConfigsError> {
+        if let Some(info) = &self.0 {
+            Ok(device_supported_configs(info, false))
+        } else {
+            Ok(default_supported_configs(false))
+        }
+    }
+
+    fn supported_output_configs(
+        &self,
+    ) -> Result<Self::SupportedOutputConfigs, SupportedStreamConfigsError> {
+        if let Some(info) = &self.0 {
+            Ok(device_supported_configs(info, true))
+        } else {
+            Ok(default_supported_configs(true))
+        }
+    }
+
+    fn default_input_config(&self) -> Result<SupportedStreamConfig, DefaultStreamConfigError> {
+        let mut configs: Vec<_> = self.supported_input_configs().unwrap().collect();
+        configs.sort_by(|a, b| b.cmp_default_heuristics(a));
+        let config = configs
+            .into_iter()
+            .next()
+            .ok_or(DefaultStreamConfigError::StreamTypeNotSupported)?
+            .with_max_sample_rate();
+        Ok(config)
+    }
+
+    fn default_output_config(&self) -> Result<SupportedStreamConfig, DefaultStreamConfigError> {
+        let mut configs: Vec<_> = self.supported_output_configs().unwrap().collect();
+        configs.sort_by(|a, b| b.cmp_default_heuristics(a));
+        let config = configs
+            .into_iter()
+            .next()
+            .ok_or(DefaultStreamConfigError::StreamTypeNotSupported)?
+            .with_max_sample_rate();
+        Ok(config)
+    }
+
+    fn build_input_stream_raw<D, E>(
+        &self,
+        config: &StreamConfig,
+        sample_format: SampleFormat,
+        data_callback: D,
+        error_callback: E,
+    ) -> Result<Self::Stream, BuildStreamError>
+    where
+        D: FnMut(&Data, &InputCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        self.build_input_stream_raw_with_share_mode(
+            config,
+            sample_format,
+            ShareMode::Shared,
+            data_callback,
+            error_callback,
+        )
+    }
+
+    fn build_input_stream_raw_with_share_mode<D, E>(
+        &self,
+        config: &StreamConfig,
+        sample_format: SampleFormat,
+        share_mode: ShareMode,
+        data_callback: D,
+        error_callback: E,
+    ) -> Result<Self::Stream, BuildStreamError>
+    where
+        D: FnMut(&Data, &InputCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        self.build_input(
+            config,
+            sample_format,
+            share_mode,
+            None,
+            data_callback,
+            error_callback,
+        )
+    }
+
+    fn build_input_stream_raw_with_usage<D, E>(
+        &self,
+        config: &StreamConfig,
+        sample_format: SampleFormat,
+        share_mode: ShareMode,
+        usage: StreamUsage,
+        data_callback: D,
+        error_callback: E,
+    ) -> Result<Self::Stream, BuildStreamError>
+    where
+        D: FnMut(&Data, &InputCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        self.build_input(
+            config,
+            sample_format,
+            share_mode,
+            Some(usage),
+            data_callback,
+            error_callback,
+        )
+    }
+
+    fn build_output_stream_raw<D, E>(
+        &self,
+        config: &StreamConfig,
+        sample_format: SampleFormat,
+        data_callback: D,
+        error_callback: E,
+    ) -> Result<Self::Stream, BuildStreamError>
+    where
+        D: FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        self.build_output_stream_raw_with_share_mode(
+            config,
+            sample_format,
+            ShareMode::Shared,
+            data_callback,
+            error_callback,
+        )
+    }
+
+    fn build_output_stream_raw_with_share_mode<D, E>(
+        &self,
+        config: &StreamConfig,
+        sample_format: SampleFormat,
+        share_mode: ShareMode,
+        data_callback: D,
+        error_callback: E,
+    ) -> Result<Self::Stream, BuildStreamError>
+    where
+        D: FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        self.build_output(
+            config,
+            sample_format,
+            share_mode,
+            None,
+            data_callback,
+            error_callback,
+        )
+    }
+
+    fn build_output_stream_raw_with_usage<D, E>(
+        &self,
+        config: &StreamConfig,
+        sample_format: SampleFormat,
+        share_mode: ShareMode,
+        usage: StreamUsage,
+        data_callback: D,
+        error_callback: E,
+    ) -> Result<Self::Stream, BuildStreamError>
+    where
+        D: FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        self.build_output(
+            config,
+            sample_format,
+            share_mode,
+            Some(usage),
+            data_callback,
+            error_callback,
+        )
+    }
+}
+
 impl StreamTrait for Stream {
     fn play(&self) -> Result<(), PlayStreamError> {
         match self {
@@ -482,14 +706,61 @@ impl StreamTrait for Stream {
 
     fn pause(&self) -> Result<(), PauseStreamError> {
         match self {
-            Self::Input(_) => Err(BackendSpecificError {
-                description: "Pause called on the input stream.".to_owned(),
-            }
-            .into()),
+            // AAudio cannot pause input streams.
+            Self::Input(stream) => stream
+                .borrow_mut()
+                .request_stop()
+                .map_err(PauseStreamError::from),
             Self::Output(stream) => stream
                 .borrow_mut()
                 .request_pause()
                 .map_err(PauseStreamError::from),
         }
     }
+
+    fn drain(&self) -> Result<(), PauseStreamError> {
+        match self {
+            Self::Input(stream) => stream
+                .borrow_mut()
+                .request_stop()
+                .map_err(PauseStreamError::from),
+            // Stopping an output stream plays the frames it has buffered first.
+            Self::Output(stream) => stream.borrow_mut().stop().map_err(PauseStreamError::from),
+        }
+    }
+
+    fn state(&self) -> StreamState {
+        let state = match self {
+            Self::Input(stream) => stream.borrow().get_state(),
+            Self::Output(stream) => stream.borrow().get_state(),
+        };
+        match state {
+            oboe::StreamState::Starting | oboe::StreamState::Started => StreamState::Playing,
+            _ => StreamState::Paused,
+        }
+    }
+
+    fn latency(&self) -> Option<StreamLatency> {
+        let millis = match self {
+            Self::Input(stream) => stream.borrow_mut().calculate_latency_millis(),
+            Self::Output(stream) => stream.borrow_mut().calculate_latency_millis(),
+        }
+        .ok()?;
+        Some(StreamLatency {
+            buffered: Some(Duration::from_secs_f64(millis.max(0.0) / 1_000.0)),
+            ..Default::default()
+        })
+    }
+
+    fn set_buffer_size(&self, frames: FrameCount) -> Result<FrameCount, ReconfigureStreamError> {
+        match self {
+            Self::Input(_) => Err(ReconfigureStreamError::NotSupported),
+            // The requested size is clamped to the capacity of the stream's buffer.
+            Self::Output(stream) => stream
+                .borrow_mut()
+                .set_buffer_size_in_frames(frames.try_into().unwrap_or(i32::MAX))
+                .map(|frames| frames as FrameCount)
+                .map_err(ReconfigureStreamError::from),
+        }
+    }
 }
@@ -3,13 +3,18 @@ use std::time::Instant;
 
 extern crate oboe;
 
-use super::convert::{stream_instant, to_stream_instant};
+use self::oboe::AudioStreamSafe;
+use super::convert::{presentation_instant, to_stream_instant};
 use crate::{Data, OutputCallbackInfo, OutputStreamTimestamp, Sample, StreamError};
 
 pub struct CpalOutputCallback<I, C> {
     data_cb: Box<dyn FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static>,
     error_cb: Box<dyn FnMut(StreamError) + Send + 'static>,
     created: Instant,
+    // The number of xruns the stream reported by the previous callback.
+    xruns: i32,
+    // The number of frames passed to or from the callback so far.
+    frame_position: u64,
     phantom_channel: PhantomData<C>,
     phantom_input: PhantomData<I>,
 }
@@ -24,21 +29,37 @@ impl<I, C> CpalOutputCallback<I, C> {
             data_cb: Box::new(data_cb),
             error_cb: Box::new(error_cb),
             created: Instant::now(),
+            xruns: 0,
+            frame_position: 0,
             phantom_channel: PhantomData,
             phantom_input: PhantomData,
         }
     }
 
+    // Reports the xruns that occurred since the previous callback.
+    fn check_xruns(&mut self, audio_stream: &mut dyn oboe::AudioOutputStreamSafe) {
+        let xruns = match audio_stream.get_xrun_count() {
+            Ok(xruns) => xruns,
+            Err(_) => return,
+        };
+        if xruns > self.xruns {
+            (self.error_cb)(StreamError::Underrun { frames_lost: None });
+        }
+        self.xruns = xruns;
+    }
+
     fn make_callback_info(
-        &self,
+        &mut self,
         audio_stream: &mut dyn oboe::AudioOutputStreamSafe,
+        frames: usize,
     ) -> OutputCallbackInfo {
-        OutputCallbackInfo {
-            timestamp: OutputStreamTimestamp {
-                callback: to_stream_instant(self.created.elapsed()),
-                playback: stream_instant(audio_stream),
-            },
-        }
+        let timestamp = OutputStreamTimestamp {
+            callback: to_stream_instant(self.created.elapsed()),
+            playback: presentation_instant(audio_stream),
+        };
+        let frame_position = self.frame_position;
+        self.frame_position += frames as u64;
+        OutputCallbackInfo::new(timestamp, frame_position)
     }
 }
 
@@ -69,7 +90,8 @@ where
         audio_stream: &mut dyn oboe::AudioOutputStreamSafe,
         audio_data: &mut [<<Self as oboe::AudioOutputCallback>::FrameType as oboe::IsFrameType>::Type],
     ) -> oboe::DataCallbackResult {
-        let cb_info = self.make_callback_info(audio_stream);
+        self.check_xruns(audio_stream);
+        let cb_info = self.make_callback_info(audio_stream, audio_data.len());
         let channel_count = if C::CHANNEL_COUNT == oboe::ChannelCount::Mono {
             1
         } else {
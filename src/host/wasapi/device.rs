@@ -1,9 +1,10 @@
 use crate::FrameCount;
 use crate::{
-    BackendSpecificError, BufferSize, Data, DefaultStreamConfigError, DeviceNameError,
-    DevicesError, InputCallbackInfo, OutputCallbackInfo, SampleFormat, SampleRate, StreamConfig,
-    SupportedBufferSize, SupportedStreamConfig, SupportedStreamConfigRange,
-    SupportedStreamConfigsError, COMMON_SAMPLE_RATES,
+    BackendSpecificError, BluetoothProfile, BufferLayout, BufferSize, ChannelCount, ChannelLayout,
+    Data, DefaultStreamConfigError, DeviceNameError, DeviceRole, DevicesError, FormFactor,
+    InputCallbackInfo, OutputCallbackInfo, SampleFormat, SampleRate, ShareMode, StreamConfig,
+    StreamUsage, SupportedBufferSize, SupportedStreamConfig, SupportedStreamConfigRange,
+    SupportedStreamConfigsError, Transport, COMMON_SAMPLE_RATES,
 };
 use once_cell::sync::Lazy;
 use std;
@@ -14,6 +15,7 @@ use std::ops::{Deref, DerefMut};
 use std::os::windows::ffi::OsStringExt;
 use std::ptr;
 use std::slice;
+use std::sync::atomic::AtomicU32;
 use std::sync::{Arc, Mutex, MutexGuard};
 
 use super::com;
@@ -23,14 +25,15 @@ use windows::core::Interface;
 use windows::core::GUID;
 use windows::Win32::Devices::Properties;
 use windows::Win32::Foundation;
-use windows::Win32::Media::Audio::IAudioRenderClient;
+use windows::Win32::Media::Audio::{Endpoints, IAudioRenderClient};
 use windows::Win32::Media::{Audio, KernelStreaming, Multimedia};
 use windows::Win32::System::Com;
 use windows::Win32::System::Com::StructuredStorage;
 use windows::Win32::System::Ole;
 use windows::Win32::System::Threading;
+use windows::Win32::UI::Shell::PropertiesSystem;
 
-use super::stream::{AudioClientFlow, Stream, StreamInner};
+use super::stream::{AudioClientFlow, Stream, StreamInner, UNKNOWN_PADDING};
 use crate::{traits::DeviceTrait, BuildStreamError, StreamError};
 
 pub type SupportedInputConfigs = std::vec::IntoIter<SupportedStreamConfigRange>;
@@ -60,6 +63,14 @@ impl DeviceTrait for Device {
         Device::name(self)
     }
 
+    fn info(&self) -> Result<crate::DeviceInfo, DeviceNameError> {
+        Device::info(self)
+    }
+
+    fn capabilities(&self) -> Result<crate::DeviceCapabilities, SupportedStreamConfigsError> {
+        Device::capabilities(self)
+    }
+
     fn supported_input_configs(
         &self,
     ) -> Result<Self::SupportedInputConfigs, SupportedStreamConfigsError> {
@@ -72,6 +83,14 @@ impl DeviceTrait for Device {
         Device::supported_output_configs(self)
     }
 
+    fn supports_input_config(&self, config: &StreamConfig, sample_format: SampleFormat) -> bool {
+        Device::supports_config(self, Audio::eCapture, config, sample_format)
+    }
+
+    fn supports_output_config(&self, config: &StreamConfig, sample_format: SampleFormat) -> bool {
+        Device::supports_config(self, Audio::eRender, config, sample_format)
+    }
+
     fn default_input_config(&self) -> Result<SupportedStreamConfig, DefaultStreamConfigError> {
         Device::default_input_config(self)
     }
@@ -91,7 +110,51 @@ impl DeviceTrait for Device {
         D: FnMut(&Data, &InputCallbackInfo) + Send + 'static,
         E: FnMut(StreamError) + Send + 'static,
     {
-        let stream_inner = self.build_input_stream_raw_inner(config, sample_format)?;
+        self.build_input_stream_raw_with_share_mode(
+            config,
+            sample_format,
+            ShareMode::Shared,
+            data_callback,
+            error_callback,
+        )
+    }
+
+    fn build_input_stream_raw_with_share_mode<D, E>(
+        &self,
+        config: &StreamConfig,
+        sample_format: SampleFormat,
+        share_mode: ShareMode,
+        data_callback: D,
+        error_callback: E,
+    ) -> Result<Self::Stream, BuildStreamError>
+    where
+        D: FnMut(&Data, &InputCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        let stream_inner =
+            self.build_input_stream_raw_inner(config, sample_format, share_mode, None)?;
+        Ok(Stream::new_input(
+            stream_inner,
+            data_callback,
+            error_callback,
+        ))
+    }
+
+    fn build_input_stream_raw_with_usage<D, E>(
+        &self,
+        config: &StreamConfig,
+        sample_format: SampleFormat,
+        share_mode: ShareMode,
+        usage: StreamUsage,
+        data_callback: D,
+        error_callback: E,
+    ) -> Result<Self::Stream, BuildStreamError>
+    where
+        D: FnMut(&Data, &InputCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        let stream_inner =
+            self.build_input_stream_raw_inner(config, sample_format, share_mode, Some(usage))?;
         Ok(Stream::new_input(
             stream_inner,
             data_callback,
@@ -110,7 +173,51 @@ impl DeviceTrait for Device {
         D: FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static,
         E: FnMut(StreamError) + Send + 'static,
     {
-        let stream_inner = self.build_output_stream_raw_inner(config, sample_format)?;
+        self.build_output_stream_raw_with_share_mode(
+            config,
+            sample_format,
+            ShareMode::Shared,
+            data_callback,
+            error_callback,
+        )
+    }
+
+    fn build_output_stream_raw_with_share_mode<D, E>(
+        &self,
+        config: &StreamConfig,
+        sample_format: SampleFormat,
+        share_mode: ShareMode,
+        data_callback: D,
+        error_callback: E,
+    ) -> Result<Self::Stream, BuildStreamError>
+    where
+        D: FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        let stream_inner =
+            self.build_output_stream_raw_inner(config, sample_format, share_mode, None)?;
+        Ok(Stream::new_output(
+            stream_inner,
+            data_callback,
+            error_callback,
+        ))
+    }
+
+    fn build_output_stream_raw_with_usage<D, E>(
+        &self,
+        config: &StreamConfig,
+        sample_format: SampleFormat,
+        share_mode: ShareMode,
+        usage: StreamUsage,
+        data_callback: D,
+        error_callback: E,
+    ) -> Result<Self::Stream, BuildStreamError>
+    where
+        D: FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        let stream_inner =
+            self.build_output_stream_raw_inner(config, sample_format, share_mode, Some(usage))?;
         Ok(Stream::new_output(
             stream_inner,
             data_callback,
@@ -193,18 +300,17 @@ unsafe fn data_flow_from_immendpoint(endpoint: &Audio::IMMEndpoint) -> Audio::ED
         .expect("could not get endpoint data_flow")
 }
 
-// Given the audio client and format, returns whether or not the format is supported.
+// Given the audio client and format, returns whether or not the format is supported in the given
+// share mode.
 pub unsafe fn is_format_supported(
     client: &Audio::IAudioClient,
+    share_mode: Audio::AUDCLNT_SHAREMODE,
     waveformatex_ptr: *const Audio::WAVEFORMATEX,
 ) -> Result<bool, SupportedStreamConfigsError> {
     // Check if the given format is supported.
     let is_supported = |waveformatex_ptr, mut closest_waveformatex_ptr| {
-        let result = client.IsFormatSupported(
-            Audio::AUDCLNT_SHAREMODE_SHARED,
-            waveformatex_ptr,
-            &mut closest_waveformatex_ptr,
-        );
+        let result =
+            client.IsFormatSupported(share_mode, waveformatex_ptr, &mut closest_waveformatex_ptr);
         // `IsFormatSupported` can return `S_FALSE` (which means that a compatible format
         // has been found, but not an exact match) so we also treat this as unsupported.
         match result {
@@ -240,10 +346,18 @@ pub unsafe fn is_format_supported(
 }
 
 // Get a cpal Format from a WAVEFORMATEX.
-unsafe fn format_from_waveformatex_ptr(
+/// Returns the `SampleFormat` described by a `WAVEFORMATEX`, or by the `WAVEFORMATEXTENSIBLE` it
+/// starts if its format tag is `WAVE_FORMAT_EXTENSIBLE`.
+///
+/// Returns `None` for formats cpal has no equivalent for.
+///
+/// # Safety
+///
+/// `waveformatex_ptr` must point to a valid `WAVEFORMATEX`, or to a valid `WAVEFORMATEXTENSIBLE`
+/// if the format tag says so.
+pub unsafe fn sample_format_from_waveformatex_ptr(
     waveformatex_ptr: *const Audio::WAVEFORMATEX,
-    audio_client: &Audio::IAudioClient,
-) -> Option<SupportedStreamConfig> {
+) -> Option<SampleFormat> {
     fn cmp_guid(a: &GUID, b: &GUID) -> bool {
         (a.data1, a.data2, a.data3, a.data4) == (b.data1, b.data2, b.data3, b.data4)
     }
@@ -260,13 +374,23 @@ unsafe fn format_from_waveformatex_ptr(
                 SampleFormat::I16
             } else if n_bits == 32 && cmp_guid(&sub, &Multimedia::KSDATAFORMAT_SUBTYPE_IEEE_FLOAT) {
                 SampleFormat::F32
+            } else if n_bits == 16 && cmp_guid(&sub, &KSDATAFORMAT_SUBTYPE_IEC61937) {
+                SampleFormat::Iec61937
             } else {
                 return None;
             }
         }
-        // Unknown data format returned by GetMixFormat.
         _ => return None,
     };
+    Some(sample_format)
+}
+
+unsafe fn format_from_waveformatex_ptr(
+    waveformatex_ptr: *const Audio::WAVEFORMATEX,
+    audio_client: &Audio::IAudioClient,
+) -> Option<SupportedStreamConfig> {
+    // Unknown data format returned by GetMixFormat.
+    let sample_format = sample_format_from_waveformatex_ptr(waveformatex_ptr)?;
 
     let sample_rate = SampleRate((*waveformatex_ptr).nSamplesPerSec);
 
@@ -299,17 +423,39 @@ unsafe fn format_from_waveformatex_ptr(
             max: buffer_duration_to_frames(max_buffer_duration, sample_rate.0),
         }
     } else {
+        // The software stack takes buffers down to the minimum period of the device, and
+        // `Initialize` takes durations of up to two seconds.
+        let mut min_period = 0;
+        let min = match audio_client.GetDevicePeriod(ptr::null_mut(), &mut min_period) {
+            Ok(()) => buffer_duration_to_frames(min_period, sample_rate.0),
+            Err(_) => 0,
+        };
         SupportedBufferSize::Range {
-            min: 0,
-            max: u32::max_value(),
+            min,
+            max: buffer_duration_to_frames(MAX_BUFFER_DURATION, sample_rate.0),
         }
     };
 
+    let channels = (*waveformatex_ptr).nChannels as ChannelCount;
+    let channel_layout = match (*waveformatex_ptr).wFormatTag as u32 {
+        KernelStreaming::WAVE_FORMAT_EXTENSIBLE => {
+            let waveformatextensible_ptr = waveformatex_ptr as *const Audio::WAVEFORMATEXTENSIBLE;
+            let channel_mask = (*waveformatextensible_ptr).dwChannelMask;
+            Some(ChannelLayout::from_wave_channel_mask(
+                channel_mask,
+                channels,
+            ))
+        }
+        _ => None,
+    };
+
     let format = SupportedStreamConfig {
-        channels: (*waveformatex_ptr).nChannels as _,
+        channels,
         sample_rate,
         buffer_size,
         sample_format,
+        layout: BufferLayout::Interleaved,
+        channel_layout,
     };
     Some(format)
 }
@@ -370,6 +516,133 @@ impl Device {
         }
     }
 
+    /// Adds the endpoint id, which persists across runs, whether the endpoint is one of the
+    /// defaults, its form factor, and its transport and Bluetooth profile going by the bus its device
+    /// enumerates on.
+    pub fn info(&self) -> Result<crate::DeviceInfo, DeviceNameError> {
+        let name = self.name()?;
+        let id = unsafe {
+            let id = self.device.GetId().map_err(|err| {
+                let description = format!("failed to retrieve the endpoint id: {}", err);
+                DeviceNameError::from(BackendSpecificError { description })
+            })?;
+            let mut len = 0;
+            while *id.0.offset(len) != 0 {
+                len += 1;
+            }
+            let id_slice = slice::from_raw_parts(id.0, len as usize);
+            let id_string = OsString::from_wide(id_slice).to_string_lossy().into_owned();
+            Com::CoTaskMemFree(id.0 as *mut c_void);
+            id_string
+        };
+        let input = self.data_flow() == Audio::eCapture;
+        let mut info = crate::DeviceInfo {
+            name,
+            id,
+            input,
+            output: !input,
+            default_input: input && default_input_device().as_ref() == Some(self),
+            default_output: !input && default_output_device().as_ref() == Some(self),
+            ..Default::default()
+        };
+        // The values of `EndpointFormFactor`.
+        let form_factor = self.u32_property(&PKEY_AUDIO_ENDPOINT_FORM_FACTOR);
+        info.form_factor = match form_factor {
+            Some(1) => FormFactor::Speakers,
+            Some(2) => FormFactor::LineLevel,
+            Some(3) => FormFactor::Headphones,
+            Some(4) => FormFactor::Microphone,
+            Some(5 | 6) => FormFactor::Headset,
+            Some(7..=9) => FormFactor::Digital,
+            _ => FormFactor::Unknown,
+        };
+        let enumerator = self
+            .string_property(&Properties::DEVPKEY_Device_EnumeratorName as *const _ as *const _);
+        info.transport = match (form_factor, enumerator.as_deref()) {
+            // WASAPI does not tell HDMI and DisplayPort displays apart.
+            (Some(9), _) => Transport::Hdmi,
+            (_, Some("USB")) => Transport::Usb,
+            (_, Some("BTHENUM" | "BTHHFENUM" | "BTHLEDEVICE")) => Transport::Bluetooth,
+            (_, Some("HDAUDIO" | "INTELAUDIO")) => Transport::BuiltIn,
+            (_, Some("ROOT" | "SWD")) => Transport::Virtual,
+            _ => Transport::Unknown,
+        };
+        // Classic Bluetooth devices have an endpoint per profile.
+        info.bluetooth_profile = match enumerator.as_deref() {
+            Some("BTHENUM") => Some(BluetoothProfile::A2dp),
+            Some("BTHHFENUM") => Some(BluetoothProfile::HandsFree),
+            Some("BTHLEDEVICE") => Some(BluetoothProfile::LeAudio),
+            _ => None,
+        };
+        Ok(info)
+    }
+
+    // Reads a property of the endpoint, holding a number.
+    fn u32_property(&self, key: *const PropertiesSystem::PROPERTYKEY) -> Option<u32> {
+        unsafe {
+            let property_store = self
+                .device
+                .OpenPropertyStore(StructuredStorage::STGM_READ)
+                .ok()?;
+            let mut property_value = property_store.GetValue(key).ok()?;
+            let prop_variant = &property_value.Anonymous.Anonymous;
+            let value = (prop_variant.vt == Ole::VT_UI4.0 as _)
+                .then(|| *(&prop_variant.Anonymous as *const _ as *const u32));
+            StructuredStorage::PropVariantClear(&mut property_value).ok();
+            value
+        }
+    }
+
+    // Reads a property of the endpoint, holding a string.
+    fn string_property(&self, key: *const PropertiesSystem::PROPERTYKEY) -> Option<String> {
+        unsafe {
+            let property_store = self
+                .device
+                .OpenPropertyStore(StructuredStorage::STGM_READ)
+                .ok()?;
+            let mut property_value = property_store.GetValue(key).ok()?;
+            let prop_variant = &property_value.Anonymous.Anonymous;
+            let value = (prop_variant.vt == Ole::VT_LPWSTR.0 as _).then(|| {
+                let ptr_utf16 = *(&prop_variant.Anonymous as *const _ as *const *const u16);
+                let mut len = 0;
+                while *ptr_utf16.offset(len) != 0 {
+                    len += 1;
+                }
+                let slice = slice::from_raw_parts(ptr_utf16, len as usize);
+                OsString::from_wide(slice).to_string_lossy().into_owned()
+            });
+            StructuredStorage::PropVariantClear(&mut property_value).ok();
+            value
+        }
+    }
+
+    /// Adds exclusive mode, loopback recording of output endpoints, and whether the endpoint has a
+    /// volume control in hardware.
+    pub fn capabilities(&self) -> Result<crate::DeviceCapabilities, SupportedStreamConfigsError> {
+        let mut capabilities = crate::device_info::capabilities_from_configs(self)?;
+        capabilities.exclusive_mode = true;
+        capabilities.loopback = self.data_flow() == Audio::eRender;
+        capabilities.hardware_volume = unsafe {
+            let mut endpoint_volume = ptr::null_mut();
+            self.device
+                .Activate(
+                    &Endpoints::IAudioEndpointVolume::IID,
+                    Com::CLSCTX_ALL,
+                    ptr::null_mut(),
+                    &mut endpoint_volume,
+                )
+                .and_then(|()| {
+                    // See `ensure_future_audio_client` for the transmute.
+                    let endpoint_volume = mem::transmute::<_, Endpoints::IAudioEndpointVolume>(
+                        endpoint_volume as *mut _,
+                    );
+                    endpoint_volume.QueryHardwareSupport()
+                })
+                .is_ok_and(|support| support & Endpoints::ENDPOINT_HARDWARE_SUPPORT_VOLUME != 0)
+        };
+        Ok(capabilities)
+    }
+
     #[inline]
     fn from_immdevice(device: Audio::IMMDevice) -> Self {
         Device {
@@ -454,7 +727,11 @@ impl Device {
 
             // If the default format can't succeed we have no hope of finding other formats.
             assert_eq!(
-                is_format_supported(client, default_waveformatex_ptr.0)?,
+                is_format_supported(
+                    client,
+                    Audio::AUDCLNT_SHAREMODE_SHARED,
+                    default_waveformatex_ptr.0
+                )?,
                 true
             );
 
@@ -478,7 +755,11 @@ impl Device {
                 test_format.nSamplesPerSec = rate;
                 test_format.nAvgBytesPerSec =
                     rate * u32::from((*default_waveformatex_ptr.0).nBlockAlign);
-                if is_format_supported(client, test_format.as_ptr())? {
+                if is_format_supported(
+                    client,
+                    Audio::AUDCLNT_SHAREMODE_SHARED,
+                    test_format.as_ptr(),
+                )? {
                     supported_sample_rates.push(rate);
                 }
             }
@@ -514,6 +795,8 @@ impl Device {
                     max_sample_rate: SampleRate(rate as _),
                     buffer_size: format.buffer_size.clone(),
                     sample_format: format.sample_format.clone(),
+                    layout: BufferLayout::Interleaved,
+                    channel_layout: format.channel_layout.clone(),
                 })
             }
             Ok(supported_formats.into_iter())
@@ -542,6 +825,31 @@ impl Device {
         }
     }
 
+    /// Asks the shared-mode mixer of the endpoint whether it takes the format of `config`, for
+    /// an endpoint of `data_flow`.
+    pub fn supports_config(
+        &self,
+        data_flow: Audio::EDataFlow,
+        config: &StreamConfig,
+        sample_format: SampleFormat,
+    ) -> bool {
+        if self.data_flow() != data_flow {
+            return false;
+        }
+        let format = match config_to_waveformatextensible(config, sample_format) {
+            Some(format) => format,
+            None => return false,
+        };
+        com::com_initialized();
+        let lock = match self.ensure_future_audio_client() {
+            Ok(lock) => lock,
+            Err(_) => return false,
+        };
+        let client = &lock.as_ref().unwrap().0;
+        unsafe { is_format_supported(client, Audio::AUDCLNT_SHAREMODE_SHARED, &format.Format) }
+            .unwrap_or(false)
+    }
+
     // We always create voices in shared mode, therefore all samples go through an audio
     // processor to mix them together.
     //
@@ -596,10 +904,96 @@ impl Device {
         }
     }
 
+    // Initializes the audio client of an event-driven stream in the given share mode, in the
+    // stream category of `usage` if it is `Some`, returning the client to use from then on.
+    //
+    // Exclusive streams take the format as is, without the conversions of the shared-mode mixer,
+    // and must be initialized with a periodicity equal to their buffer duration. If the device
+    // rejects the duration as not aligned to its requirements, a new client is initialized with
+    // the aligned duration it reports instead.
+    unsafe fn initialize_audio_client(
+        &self,
+        audio_client: Audio::IAudioClient,
+        share_mode: ShareMode,
+        usage: Option<StreamUsage>,
+        stream_flags: u32,
+        buffer_duration: i64,
+        format: &Audio::WAVEFORMATEXTENSIBLE,
+        sample_rate: u32,
+    ) -> Result<Audio::IAudioClient, BuildStreamError> {
+        let audio_share_mode = match share_mode {
+            ShareMode::Shared => Audio::AUDCLNT_SHAREMODE_SHARED,
+            ShareMode::Exclusive => Audio::AUDCLNT_SHAREMODE_EXCLUSIVE,
+        };
+
+        // Ensure the format is supported.
+        match is_format_supported(&audio_client, audio_share_mode, &format.Format) {
+            Ok(false) => return Err(BuildStreamError::StreamConfigNotSupported),
+            Err(_) => return Err(BuildStreamError::DeviceNotAvailable),
+            _ => (),
+        }
+
+        let mut buffer_duration = buffer_duration;
+        if share_mode == ShareMode::Exclusive && buffer_duration == 0 {
+            let mut default_period = 0;
+            audio_client
+                .GetDevicePeriod(&mut default_period, ptr::null_mut())
+                .map_err(windows_err_to_cpal_err::<BuildStreamError>)?;
+            buffer_duration = default_period;
+        }
+
+        let mut audio_client = audio_client;
+        let mut realigned = false;
+        loop {
+            let periodicity = match share_mode {
+                ShareMode::Shared => 0,
+                ShareMode::Exclusive => buffer_duration,
+            };
+            // The category must be set before the client is initialized.
+            if let Some(usage) = usage {
+                set_stream_category(&audio_client, usage)?;
+            }
+            let result = audio_client.Initialize(
+                audio_share_mode,
+                stream_flags,
+                buffer_duration,
+                periodicity,
+                &format.Format,
+                ptr::null(),
+            );
+            let e = match result {
+                Ok(()) => return Ok(audio_client),
+                Err(e) => e,
+            };
+            match e.code() {
+                Audio::AUDCLNT_E_BUFFER_SIZE_NOT_ALIGNED if !realigned => {
+                    let frames = audio_client
+                        .GetBufferSize()
+                        .map_err(windows_err_to_cpal_err::<BuildStreamError>)?;
+                    buffer_duration =
+                        buffer_size_to_duration(&BufferSize::Fixed(frames), sample_rate);
+                    audio_client = self
+                        .build_audioclient()
+                        .map_err(windows_err_to_cpal_err::<BuildStreamError>)?;
+                    realigned = true;
+                }
+                Audio::AUDCLNT_E_EXCLUSIVE_MODE_NOT_ALLOWED => {
+                    return Err(BuildStreamError::ShareModeNotSupported);
+                }
+                Audio::AUDCLNT_E_DEVICE_IN_USE => {
+                    return Err(BuildStreamError::DeviceBusy);
+                }
+                _ => return Err(windows_err_to_cpal_err::<BuildStreamError>(e)),
+            }
+        }
+    }
+
     pub(crate) fn build_input_stream_raw_inner(
         &self,
         config: &StreamConfig,
         sample_format: SampleFormat,
+        share_mode: ShareMode,
+        usage: Option<StreamUsage>,
     ) -> Result<StreamInner, BuildStreamError> {
         unsafe {
             // Making sure that COM is initialized.
@@ -625,45 +1019,26 @@ impl Device {
             let mut stream_flags = Audio::AUDCLNT_STREAMFLAGS_EVENTCALLBACK;
 
             if self.data_flow() == Audio::eRender {
+                // Loopback captures the output of the shared-mode mixer.
+                if share_mode == ShareMode::Exclusive {
+                    return Err(BuildStreamError::ShareModeNotSupported);
+                }
                 stream_flags |= Audio::AUDCLNT_STREAMFLAGS_LOOPBACK;
             }
 
             // Computing the format and initializing the device.
-            let waveformatex = {
-                let format_attempt = config_to_waveformatextensible(config, sample_format)
-                    .ok_or(BuildStreamError::StreamConfigNotSupported)?;
-                let share_mode = Audio::AUDCLNT_SHAREMODE_SHARED;
-
-                // Ensure the format is supported.
-                match super::device::is_format_supported(&audio_client, &format_attempt.Format) {
-                    Ok(false) => return Err(BuildStreamError::StreamConfigNotSupported),
-                    Err(_) => return Err(BuildStreamError::DeviceNotAvailable),
-                    _ => (),
-                }
-
-                // Finally, initializing the audio client
-                let hresult = audio_client.Initialize(
-                    share_mode,
-                    stream_flags,
-                    buffer_duration,
-                    0,
-                    &format_attempt.Format,
-                    ptr::null(),
-                );
-                match hresult {
-                    Err(ref e) if e.code() == Audio::AUDCLNT_E_DEVICE_INVALIDATED => {
-                        return Err(BuildStreamError::DeviceNotAvailable);
-                    }
-                    Err(e) => {
-                        let description = format!("{}", e);
-                        let err = BackendSpecificError { description };
-                        return Err(err.into());
-                    }
-                    Ok(()) => (),
-                };
-
-                format_attempt.Format
-            };
+            let format_attempt = config_to_waveformatextensible(config, sample_format)
+                .ok_or(BuildStreamError::StreamConfigNotSupported)?;
+            let audio_client = self.initialize_audio_client(
+                audio_client,
+                share_mode,
+                usage,
+                stream_flags,
+                buffer_duration,
+                &format_attempt,
+                config.sample_rate.0,
+            )?;
+            let waveformatex = format_attempt.Format;
 
             // obtaining the size of the samples buffer in number of frames
             let max_frames_in_buffer = audio_client
@@ -719,6 +1094,9 @@ impl Device {
                 bytes_per_frame: waveformatex.nBlockAlign,
                 config: config.clone(),
                 sample_format,
+                padding_frames: Arc::new(AtomicU32::new(UNKNOWN_PADDING)),
+                frame_position: 0,
+                next_device_position: None,
             })
         }
     }
@@ -727,6 +1105,8 @@ impl Device {
         &self,
         config: &StreamConfig,
         sample_format: SampleFormat,
+        share_mode: ShareMode,
+        usage: Option<StreamUsage>,
     ) -> Result<StreamInner, BuildStreamError> {
         unsafe {
             // Making sure that COM is initialized.
@@ -742,32 +1122,18 @@ impl Device {
                 buffer_size_to_duration(&config.buffer_size, config.sample_rate.0);
 
             // Computing the format and initializing the device.
-            let waveformatex = {
-                let format_attempt = config_to_waveformatextensible(config, sample_format)
-                    .ok_or(BuildStreamError::StreamConfigNotSupported)?;
-                let share_mode = Audio::AUDCLNT_SHAREMODE_SHARED;
-
-                // Ensure the format is supported.
-                match super::device::is_format_supported(&audio_client, &format_attempt.Format) {
-                    Ok(false) => return Err(BuildStreamError::StreamConfigNotSupported),
-                    Err(_) => return Err(BuildStreamError::DeviceNotAvailable),
-                    _ => (),
-                }
-
-                // Finally, initializing the audio client
-                audio_client
-                    .Initialize(
-                        share_mode,
-                        Audio::AUDCLNT_STREAMFLAGS_EVENTCALLBACK,
-                        buffer_duration,
-                        0,
-                        &format_attempt.Format,
-                        ptr::null(),
-                    )
-                    .map_err(windows_err_to_cpal_err::<BuildStreamError>)?;
-
-                format_attempt.Format
-            };
+            let format_attempt = config_to_waveformatextensible(config, sample_format)
+                .ok_or(BuildStreamError::StreamConfigNotSupported)?;
+            let audio_client = self.initialize_audio_client(
+                audio_client,
+                share_mode,
+                usage,
+                Audio::AUDCLNT_STREAMFLAGS_EVENTCALLBACK,
+                buffer_duration,
+                &format_attempt,
+                config.sample_rate.0,
+            )?;
+            let waveformatex = format_attempt.Format;
 
             // Creating the event that will be signalled whenever we need to submit some samples.
             let event = {
@@ -826,6 +1192,9 @@ impl Device {
                 bytes_per_frame: waveformatex.nBlockAlign,
                 config: config.clone(),
                 sample_format,
+                padding_frames: Arc::new(AtomicU32::new(UNKNOWN_PADDING)),
+                frame_position: 0,
+                next_device_position: None,
             })
         }
     }
@@ -916,6 +1285,13 @@ static ENUMERATOR: Lazy<Enumerator> = Lazy::new(|| {
     }
 });
 
+// `PKEY_AudioEndpoint_FormFactor` of mmdeviceapi.h, holding an `EndpointFormFactor`.
+const PKEY_AUDIO_ENDPOINT_FORM_FACTOR: PropertiesSystem::PROPERTYKEY =
+    PropertiesSystem::PROPERTYKEY {
+        fmtid: GUID::from_u128(0x1da5d803_d492_4edd_8c23_e0c0ffee7f0e),
+        pid: 0,
+    };
+
 /// Send/Sync wrapper around `IMMDeviceEnumerator`.
 struct Enumerator(Audio::IMMDeviceEnumerator);
 
@@ -931,11 +1307,16 @@ pub struct Devices {
 
 impl Devices {
     pub fn new() -> Result<Self, DevicesError> {
+        Self::with_data_flow(Audio::eAll)
+    }
+
+    /// The active endpoints of `data_flow`.
+    pub fn with_data_flow(data_flow: Audio::EDataFlow) -> Result<Self, DevicesError> {
         unsafe {
             // can fail because of wrong parameters (should never happen) or out of memory
             let collection = ENUMERATOR
                 .0
-                .EnumAudioEndpoints(Audio::eAll, Audio::DEVICE_STATE_ACTIVE)
+                .EnumAudioEndpoints(data_flow, Audio::DEVICE_STATE_ACTIVE)
                 .map_err(BackendSpecificError::from)?;
 
             let count = collection.GetCount().map_err(BackendSpecificError::from)?;
@@ -975,23 +1356,33 @@ impl Iterator for Devices {
     }
 }
 
-fn default_device(data_flow: Audio::EDataFlow) -> Option<Device> {
+fn default_device(data_flow: Audio::EDataFlow, role: DeviceRole) -> Option<Device> {
+    let role = match role {
+        DeviceRole::Console => Audio::eConsole,
+        DeviceRole::Multimedia => Audio::eMultimedia,
+        DeviceRole::Communications => Audio::eCommunications,
+    };
     unsafe {
-        let device = ENUMERATOR
-            .0
-            .GetDefaultAudioEndpoint(data_flow, Audio::eConsole)
-            .ok()?;
+        let device = ENUMERATOR.0.GetDefaultAudioEndpoint(data_flow, role).ok()?;
         // TODO: check specifically for `E_NOTFOUND`, and panic otherwise
         Some(Device::from_immdevice(device))
     }
 }
 
 pub fn default_input_device() -> Option<Device> {
-    default_device(Audio::eCapture)
+    default_device(Audio::eCapture, DeviceRole::Console)
 }
 
 pub fn default_output_device() -> Option<Device> {
-    default_device(Audio::eRender)
+    default_device(Audio::eRender, DeviceRole::Console)
+}
+
+pub fn default_input_device_for(role: DeviceRole) -> Option<Device> {
+    default_device(Audio::eCapture, role)
+}
+
+pub fn default_output_device_for(role: DeviceRole) -> Option<Device> {
+    default_device(Audio::eRender, role)
 }
 
 /// Get the audio clock used to produce `StreamInstant`s.
@@ -1005,17 +1396,60 @@ unsafe fn get_audio_clock(
         })
 }
 
-// Turns a `Format` into a `WAVEFORMATEXTENSIBLE`.
-//
-// Returns `None` if the WAVEFORMATEXTENSIBLE does not support the given format.
-fn config_to_waveformatextensible(
+/// Sets the audio stream category matching `usage` on a client that is not initialized yet.
+///
+/// The category is only a hint, so clients without `IAudioClient2`, as on Windows 7, are left as
+/// they are.
+unsafe fn set_stream_category(
+    audio_client: &Audio::IAudioClient,
+    usage: StreamUsage,
+) -> Result<(), BuildStreamError> {
+    let audio_client = match audio_client.cast::<Audio::IAudioClient2>() {
+        Ok(audio_client) => audio_client,
+        Err(_) => return Ok(()),
+    };
+    let category = match usage {
+        StreamUsage::Game => Audio::AudioCategory_GameEffects,
+        StreamUsage::Media => Audio::AudioCategory_Media,
+        StreamUsage::Communication => Audio::AudioCategory_Communications,
+        StreamUsage::Alarm => Audio::AudioCategory_Alerts,
+        StreamUsage::Accessibility => Audio::AudioCategory_Speech,
+    };
+    let properties = Audio::AudioClientProperties {
+        cbSize: mem::size_of::<Audio::AudioClientProperties>() as u32,
+        bIsOffload: Foundation::BOOL(0),
+        eCategory: category,
+        Options: Audio::AUDCLNT_STREAMOPTIONS_NONE,
+    };
+    audio_client.SetClientProperties(&properties).map_err(|e| {
+        windows_err_to_cpal_err_message::<BuildStreamError>(
+            e,
+            "failed to set the stream category: ",
+        )
+    })
+}
+
+// `KSDATAFORMAT_SUBTYPE_IEC61937_DOLBY_DIGITAL`, the subformat Windows passes IEC 61937 bursts
+// through S/PDIF and HDMI with, whatever codec they carry.
+const KSDATAFORMAT_SUBTYPE_IEC61937: GUID = GUID::from_u128(0x00000092_0000_0010_8000_00aa00389b71);
+
+/// Turns a stream config and sample format into the `WAVEFORMATEXTENSIBLE` cpal opens the stream
+/// with.
+///
+/// Returns `None` if the WAVEFORMATEXTENSIBLE does not support the given format.
+pub fn config_to_waveformatextensible(
     config: &StreamConfig,
     sample_format: SampleFormat,
 ) -> Option<Audio::WAVEFORMATEXTENSIBLE> {
     let format_tag = match sample_format {
         SampleFormat::I16 => Audio::WAVE_FORMAT_PCM,
-        SampleFormat::F32 => KernelStreaming::WAVE_FORMAT_EXTENSIBLE,
-        SampleFormat::U16 => return None,
+        SampleFormat::F32 | SampleFormat::Iec61937 => KernelStreaming::WAVE_FORMAT_EXTENSIBLE,
+        SampleFormat::U16
+        | SampleFormat::F16
+        | SampleFormat::MuLaw
+        | SampleFormat::DsdU8
+        | SampleFormat::DsdU32
+        | SampleFormat::Dop => return None,
     } as u16;
     let channels = config.channels;
     let sample_rate = config.sample_rate.0;
@@ -1025,12 +1459,17 @@ fn config_to_waveformatextensible(
     let bits_per_sample = 8 * sample_bytes;
     let cb_size = match sample_format {
         SampleFormat::I16 => 0,
-        SampleFormat::F32 => {
+        SampleFormat::F32 | SampleFormat::Iec61937 => {
             let extensible_size = mem::size_of::<Audio::WAVEFORMATEXTENSIBLE>();
             let ex_size = mem::size_of::<Audio::WAVEFORMATEX>();
             (extensible_size - ex_size) as u16
         }
-        SampleFormat::U16 => return None,
+        SampleFormat::U16
+        | SampleFormat::F16
+        | SampleFormat::MuLaw
+        | SampleFormat::DsdU8
+        | SampleFormat::DsdU32
+        | SampleFormat::Dop => return None,
     };
     let waveformatex = Audio::WAVEFORMATEX {
         wFormatTag: format_tag,
@@ -1048,7 +1487,13 @@ fn config_to_waveformatextensible(
     let sub_format = match sample_format {
         SampleFormat::I16 => KernelStreaming::KSDATAFORMAT_SUBTYPE_PCM,
         SampleFormat::F32 => Multimedia::KSDATAFORMAT_SUBTYPE_IEEE_FLOAT,
-        SampleFormat::U16 => return None,
+        SampleFormat::Iec61937 => KSDATAFORMAT_SUBTYPE_IEC61937,
+        SampleFormat::U16
+        | SampleFormat::F16
+        | SampleFormat::MuLaw
+        | SampleFormat::DsdU8
+        | SampleFormat::DsdU32
+        | SampleFormat::Dop => return None,
     };
     let waveformatextensible = Audio::WAVEFORMATEXTENSIBLE {
         Format: waveformatex,
@@ -1062,9 +1507,14 @@ fn config_to_waveformatextensible(
     Some(waveformatextensible)
 }
 
+// The longest buffer `IAudioClient::Initialize` accepts, in 100-nanosecond units.
+const MAX_BUFFER_DURATION: i64 = 2 * 10_000_000;
+
 fn buffer_size_to_duration(buffer_size: &BufferSize, sample_rate: u32) -> i64 {
     match buffer_size {
-        BufferSize::Fixed(frames) => *frames as i64 * (1_000_000_000 / 100) / sample_rate as i64,
+        BufferSize::Fixed(frames) | BufferSize::Preferred { ideal: frames, .. } => {
+            *frames as i64 * (1_000_000_000 / 100) / sample_rate as i64
+        }
         BufferSize::Default => 0,
     }
 }
@@ -1,12 +1,15 @@
 pub use self::device::{
-    default_input_device, default_output_device, Device, Devices, SupportedInputConfigs,
-    SupportedOutputConfigs,
+    config_to_waveformatextensible, default_input_device, default_input_device_for,
+    default_output_device, default_output_device_for, sample_format_from_waveformatex_ptr, Device,
+    Devices, SupportedInputConfigs, SupportedOutputConfigs,
 };
 pub use self::stream::Stream;
 use crate::traits::HostTrait;
 use crate::BackendSpecificError;
 use crate::DevicesError;
+use crate::{DeviceDirection, DeviceFilter, DeviceRole};
 use std::io::Error as IoError;
+use windows::core::HRESULT;
 use windows::Win32::Media::Audio;
 
 mod com;
@@ -47,6 +50,28 @@ impl HostTrait for Host {
     fn default_output_device(&self) -> Option<Self::Device> {
         default_output_device()
     }
+
+    fn default_input_device_for(&self, role: DeviceRole) -> Option<Self::Device> {
+        default_input_device_for(role)
+    }
+
+    fn default_output_device_for(&self, role: DeviceRole) -> Option<Self::Device> {
+        default_output_device_for(role)
+    }
+
+    // Only enumerates the endpoints of the direction asked for. Loopback records render
+    // endpoints.
+    fn devices_filtered(&self, filter: DeviceFilter) -> Result<Vec<Self::Device>, DevicesError> {
+        let data_flow = match filter.direction {
+            _ if filter.loopback => Audio::eRender,
+            Some(DeviceDirection::Input) => Audio::eCapture,
+            Some(DeviceDirection::Output) => Audio::eRender,
+            None => Audio::eAll,
+        };
+        Ok(Devices::with_data_flow(data_flow)?
+            .filter(|device| filter.matches(device))
+            .collect())
+    }
 }
 
 impl From<windows::core::Error> for BackendSpecificError {
@@ -59,6 +84,11 @@ impl From<windows::core::Error> for BackendSpecificError {
 
 trait ErrDeviceNotAvailable: From<BackendSpecificError> {
     fn device_not_available() -> Self;
+
+    // Maps the errors that only occur on a running stream, `None` for other error types.
+    fn stream_error(_code: HRESULT) -> Option<Self> {
+        None
+    }
 }
 
 impl ErrDeviceNotAvailable for crate::BuildStreamError {
@@ -79,10 +109,29 @@ impl ErrDeviceNotAvailable for crate::DefaultStreamConfigError {
     }
 }
 
+impl ErrDeviceNotAvailable for crate::ReconfigureStreamError {
+    fn device_not_available() -> Self {
+        Self::DeviceNotAvailable
+    }
+}
+
 impl ErrDeviceNotAvailable for crate::StreamError {
     fn device_not_available() -> Self {
         Self::DeviceNotAvailable
     }
+
+    fn stream_error(code: HRESULT) -> Option<Self> {
+        match code {
+            Audio::AUDCLNT_E_UNSUPPORTED_FORMAT => Some(Self::StreamConfigNotSupported),
+            Audio::AUDCLNT_E_DEVICE_IN_USE | Audio::AUDCLNT_E_EXCLUSIVE_MODE_NOT_ALLOWED => {
+                Some(Self::ExclusiveAccessLost)
+            }
+            Audio::AUDCLNT_E_SERVICE_NOT_RUNNING | Audio::AUDCLNT_E_RESOURCES_INVALIDATED => {
+                Some(Self::DriverRestarted)
+            }
+            _ => None,
+        }
+    }
 }
 
 fn windows_err_to_cpal_err<E: ErrDeviceNotAvailable>(e: windows::core::Error) -> E {
@@ -95,10 +144,10 @@ fn windows_err_to_cpal_err_message<E: ErrDeviceNotAvailable>(
 ) -> E {
     match e.code() {
         Audio::AUDCLNT_E_DEVICE_INVALIDATED => E::device_not_available(),
-        _ => {
+        code => E::stream_error(code).unwrap_or_else(|| {
             let description = format!("{}{}", message, e);
             let err = BackendSpecificError { description };
             err.into()
-        }
+        }),
     }
 }
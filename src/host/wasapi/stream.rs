@@ -1,13 +1,17 @@
 use super::windows_err_to_cpal_err;
 use crate::traits::StreamTrait;
 use crate::{
-    BackendSpecificError, Data, InputCallbackInfo, OutputCallbackInfo, PauseStreamError,
-    PlayStreamError, SampleFormat, StreamError,
+    BackendSpecificError, Data, FrameCount, InputCallbackInfo, OutputCallbackInfo,
+    PauseStreamError, PlayStreamError, ReconfigureStreamError, SampleFormat, StreamError,
+    StreamLatency, StreamState,
 };
 use std::mem;
 use std::ptr;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
 use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Arc;
 use std::thread::{self, JoinHandle};
+use std::time::Duration;
 use windows::Win32::Foundation;
 use windows::Win32::Media::Audio;
 use windows::Win32::System::SystemServices;
@@ -29,8 +33,33 @@ pub struct Stream {
     // This event is signalled after a new entry is added to `commands`, so that the `run()`
     // method can be notified.
     pending_scheduled_event: Foundation::HANDLE,
+
+    // Whether `play` was called more recently than `pause`, as the commands are only processed
+    // later by the stream's thread.
+    playing: AtomicBool,
+
+    // The padding of the audio client, updated by the stream's thread.
+    padding_frames: Arc<AtomicU32>,
+
+    // The stream latency reported by the audio client, if any.
+    driver_latency: Option<Duration>,
+
+    // The sample rate of the stream, to convert the padding to a duration.
+    sample_rate: crate::SampleRate,
+
+    // The size of the buffer of the audio client in frames.
+    buffer_size: FrameCount,
+
+    // The volume of the stream's audio session in the system mixer, if the audio client
+    // provides it.
+    simple_volume: Option<SimpleAudioVolumeWrapper>,
 }
 
+// The session volume is free-threaded, like the audio client it is obtained from.
+struct SimpleAudioVolumeWrapper(Audio::ISimpleAudioVolume);
+unsafe impl Send for SimpleAudioVolumeWrapper {}
+unsafe impl Sync for SimpleAudioVolumeWrapper {}
+
 struct RunContext {
     // Streams that have been created in this event loop.
     stream: StreamInner,
@@ -76,8 +105,19 @@ pub struct StreamInner {
     pub config: crate::StreamConfig,
     // The sample format with which the stream was created.
     pub sample_format: SampleFormat,
+    // The padding of the audio client when it was last queried by an output stream, shared with
+    // the `Stream` to report its latency. `UNKNOWN_PADDING` until then.
+    pub padding_frames: Arc<AtomicU32>,
+    // The number of frames passed to or from the callback so far.
+    pub frame_position: u64,
+    // The device position expected for the next packet of an input stream, to count the frames
+    // the audio engine dropped in between. `None` until the first packet.
+    pub next_device_position: Option<u64>,
 }
 
+// The value of `StreamInner::padding_frames` until the padding is first queried.
+pub const UNKNOWN_PADDING: u32 = u32::MAX;
+
 impl Stream {
     pub(crate) fn new_input<D, E>(
         stream_inner: StreamInner,
@@ -99,6 +139,12 @@ impl Stream {
         .expect("cpal: could not create input stream event");
         let (tx, rx) = channel();
 
+        let padding_frames = stream_inner.padding_frames.clone();
+        let driver_latency = driver_latency(&stream_inner);
+        let sample_rate = stream_inner.config.sample_rate;
+        let buffer_size = stream_inner.max_frames_in_buffer;
+        let simple_volume = simple_volume(&stream_inner);
+        set_session_name(&stream_inner);
         let run_context = RunContext {
             handles: vec![pending_scheduled_event, stream_inner.event],
             stream: stream_inner,
@@ -114,6 +160,12 @@ impl Stream {
             thread: Some(thread),
             commands: tx,
             pending_scheduled_event,
+            playing: AtomicBool::new(false),
+            padding_frames,
+            driver_latency,
+            sample_rate,
+            buffer_size,
+            simple_volume,
         }
     }
 
@@ -137,6 +189,12 @@ impl Stream {
         .expect("cpal: could not create output stream event");
         let (tx, rx) = channel();
 
+        let padding_frames = stream_inner.padding_frames.clone();
+        let driver_latency = driver_latency(&stream_inner);
+        let sample_rate = stream_inner.config.sample_rate;
+        let buffer_size = stream_inner.max_frames_in_buffer;
+        let simple_volume = simple_volume(&stream_inner);
+        set_session_name(&stream_inner);
         let run_context = RunContext {
             handles: vec![pending_scheduled_event, stream_inner.event],
             stream: stream_inner,
@@ -152,6 +210,12 @@ impl Stream {
             thread: Some(thread),
             commands: tx,
             pending_scheduled_event,
+            playing: AtomicBool::new(false),
+            padding_frames,
+            driver_latency,
+            sample_rate,
+            buffer_size,
+            simple_volume,
         }
     }
 
@@ -180,12 +244,59 @@ impl Drop for Stream {
 impl StreamTrait for Stream {
     fn play(&self) -> Result<(), PlayStreamError> {
         self.push_command(Command::PlayStream);
+        self.playing.store(true, Ordering::SeqCst);
         Ok(())
     }
+
     fn pause(&self) -> Result<(), PauseStreamError> {
         self.push_command(Command::PauseStream);
+        self.playing.store(false, Ordering::SeqCst);
         Ok(())
     }
+
+    fn state(&self) -> StreamState {
+        if self.playing.load(Ordering::SeqCst) {
+            StreamState::Playing
+        } else {
+            StreamState::Paused
+        }
+    }
+
+    fn latency(&self) -> Option<StreamLatency> {
+        let padding = self.padding_frames.load(Ordering::Relaxed);
+        let buffered =
+            (padding != UNKNOWN_PADDING).then(|| frames_to_duration(padding, self.sample_rate));
+        Some(StreamLatency {
+            buffered,
+            driver: self.driver_latency,
+            safety: None,
+        })
+    }
+
+    fn buffer_size(&self) -> Option<FrameCount> {
+        Some(self.buffer_size)
+    }
+
+    fn set_mixer_volume(&self, volume: f32) -> Result<(), ReconfigureStreamError> {
+        let simple_volume = self
+            .simple_volume
+            .as_ref()
+            .ok_or(ReconfigureStreamError::NotSupported)?;
+        if !(0.0..=1.0).contains(&volume) {
+            return Err(ReconfigureStreamError::StreamConfigNotSupported);
+        }
+        unsafe { simple_volume.0.SetMasterVolume(volume, ptr::null()) }
+            .map_err(windows_err_to_cpal_err::<ReconfigureStreamError>)
+    }
+
+    fn mixer_volume(&self) -> Result<f32, ReconfigureStreamError> {
+        let simple_volume = self
+            .simple_volume
+            .as_ref()
+            .ok_or(ReconfigureStreamError::NotSupported)?;
+        unsafe { simple_volume.0.GetMasterVolume() }
+            .map_err(windows_err_to_cpal_err::<ReconfigureStreamError>)
+    }
 }
 
 impl Drop for StreamInner {
@@ -267,10 +378,45 @@ fn get_available_frames(stream: &StreamInner) -> Result<u32, StreamError> {
             .audio_client
             .GetCurrentPadding()
             .map_err(windows_err_to_cpal_err::<StreamError>)?;
+        stream.padding_frames.store(padding, Ordering::Relaxed);
         Ok(stream.max_frames_in_buffer - padding)
     }
 }
 
+// The stream latency reported by the audio client, which is in 100 nanosecond units.
+fn driver_latency(stream: &StreamInner) -> Option<Duration> {
+    let latency = unsafe { stream.audio_client.GetStreamLatency() }.ok()?;
+    Some(Duration::from_nanos(latency.max(0) as u64 * 100))
+}
+
+// The volume control of the stream's audio session in the system mixer.
+fn simple_volume(stream: &StreamInner) -> Option<SimpleAudioVolumeWrapper> {
+    let simple_volume = unsafe {
+        stream
+            .audio_client
+            .GetService::<Audio::ISimpleAudioVolume>()
+    };
+    simple_volume.ok().map(SimpleAudioVolumeWrapper)
+}
+
+// Names the audio session after `crate::application_name`, if set. Failing to is not an error,
+// the session keeps its default name.
+fn set_session_name(stream: &StreamInner) {
+    let name = match crate::application_name() {
+        Some(name) => name,
+        None => return,
+    };
+    let wide: Vec<u16> = name.encode_utf16().chain(Some(0)).collect();
+    unsafe {
+        if let Ok(session) = stream
+            .audio_client
+            .GetService::<Audio::IAudioSessionControl>()
+        {
+            let _ = session.SetDisplayName(windows::core::PCWSTR(wide.as_ptr()), ptr::null());
+        }
+    }
+}
+
 fn run_input(
     mut run_ctxt: RunContext,
     data_callback: &mut dyn FnMut(&Data, &InputCallbackInfo),
@@ -364,7 +510,7 @@ fn process_commands_and_await_signal(
 
 // The loop for processing pending input data.
 fn process_input(
-    stream: &StreamInner,
+    stream: &mut StreamInner,
     capture_client: Audio::IAudioCaptureClient,
     data_callback: &mut dyn FnMut(&Data, &InputCallbackInfo),
     error_callback: &mut dyn FnMut(StreamError),
@@ -382,12 +528,13 @@ fn process_input(
                     return ControlFlow::Break;
                 }
             };
+            let mut device_position: u64 = 0;
             let mut qpc_position: u64 = 0;
             let result = capture_client.GetBuffer(
                 &mut buffer,
                 &mut frames_available,
                 flags.as_mut_ptr(),
-                ptr::null_mut(),
+                &mut device_position,
                 &mut qpc_position,
             );
 
@@ -403,6 +550,21 @@ fn process_input(
 
             debug_assert!(!buffer.is_null());
 
+            // The audio engine flags the first packet after frames it had to drop. Their number
+            // is the gap in the device position.
+            let frames_dropped = stream
+                .next_device_position
+                .map_or(0, |next| device_position.saturating_sub(next));
+            stream.next_device_position = Some(device_position + frames_available as u64);
+            let discontinuity = Audio::AUDCLNT_BUFFERFLAGS_DATA_DISCONTINUITY.0 as u32;
+            if flags.assume_init() & discontinuity != 0 || frames_dropped > 0 {
+                let frames_lost = match frames_dropped {
+                    0 => None,
+                    n => Some(n.try_into().unwrap_or(FrameCount::MAX)),
+                };
+                error_callback(StreamError::Overrun { frames_lost });
+            }
+
             let data = buffer as *mut ();
             let len = frames_available as usize * stream.bytes_per_frame as usize
                 / stream.sample_format.sample_size();
@@ -416,7 +578,9 @@ fn process_input(
                     return ControlFlow::Break;
                 }
             };
-            let info = InputCallbackInfo { timestamp };
+            let info = InputCallbackInfo::new(timestamp, stream.frame_position)
+                .with_frames_dropped(frames_dropped);
+            stream.frame_position += frames_available as u64;
             data_callback(&data, &info);
 
             // Release the buffer.
@@ -433,12 +597,13 @@ fn process_input(
 
 // The loop for writing output data.
 fn process_output(
-    stream: &StreamInner,
+    stream: &mut StreamInner,
     render_client: Audio::IAudioRenderClient,
     data_callback: &mut dyn FnMut(&mut Data, &OutputCallbackInfo),
     error_callback: &mut dyn FnMut(StreamError),
 ) -> ControlFlow {
     // The number of frames available for writing.
+    let previous_padding = stream.padding_frames.load(Ordering::Relaxed);
     let frames_available = match get_available_frames(&stream) {
         Ok(0) => return ControlFlow::Continue, // TODO: Can this happen?
         Ok(n) => n,
@@ -448,6 +613,12 @@ fn process_output(
         }
     };
 
+    // The buffer is empty before the first write only. Finding it empty later means the device
+    // played all of it before the stream could refill it.
+    if previous_padding != UNKNOWN_PADDING && frames_available == stream.max_frames_in_buffer {
+        error_callback(StreamError::Underrun { frames_lost: None });
+    }
+
     unsafe {
         let buffer = match render_client.GetBuffer(frames_available) {
             Ok(b) => b,
@@ -464,14 +635,15 @@ fn process_output(
             / stream.sample_format.sample_size();
         let mut data = Data::from_parts(data, len, stream.sample_format);
         let sample_rate = stream.config.sample_rate;
-        let timestamp = match output_timestamp(stream, frames_available, sample_rate) {
+        let timestamp = match output_timestamp(stream, sample_rate) {
             Ok(ts) => ts,
             Err(err) => {
                 error_callback(err);
                 return ControlFlow::Break;
             }
         };
-        let info = OutputCallbackInfo { timestamp };
+        let info = OutputCallbackInfo::new(timestamp, stream.frame_position);
+        stream.frame_position += frames_available as u64;
         data_callback(&mut data, &info);
 
         if let Err(err) = render_client.ReleaseBuffer(frames_available, 0) {
@@ -495,6 +667,12 @@ fn frames_to_duration(frames: u32, rate: crate::SampleRate) -> std::time::Durati
 ///
 /// Uses the QPC position produced via the `GetPosition` method.
 fn stream_instant(stream: &StreamInner) -> Result<crate::StreamInstant, StreamError> {
+    clock_position(stream).map(|(_, instant)| instant)
+}
+
+/// The device position of the stream, in the units of `IAudioClock::GetFrequency`, along with the
+/// stream instant at which the device was at that position.
+fn clock_position(stream: &StreamInner) -> Result<(u64, crate::StreamInstant), StreamError> {
     let mut position: u64 = 0;
     let mut qpc_position: u64 = 0;
     unsafe {
@@ -507,7 +685,7 @@ fn stream_instant(stream: &StreamInner) -> Result<crate::StreamInstant, StreamEr
     let qpc_nanos = qpc_position as i128 * 100;
     let instant = crate::StreamInstant::from_nanos_i128(qpc_nanos)
         .expect("performance counter out of range of `StreamInstant` representation");
-    Ok(instant)
+    Ok((position, instant))
 }
 
 /// Produce the input stream timestamp.
@@ -529,23 +707,22 @@ fn input_timestamp(
 
 /// Produce the output stream timestamp.
 ///
-/// `frames_available` is the number of frames available for writing as reported by subtracting the
-/// result of `GetCurrentPadding` from the maximum buffer size.
+/// The device position reported by `IAudioClock::GetPosition` is that of the frame playing
+/// through the DAC at the instant reported along with it. The first frame of the buffer plays
+/// once the frames written before it, counted by `frame_position`, have been played.
 ///
 /// `sample_rate` is the rate at which audio frames are processed by the device.
-///
-/// TODO: The returned `playback` is an estimate that assumes audio is delivered immediately after
-/// `frames_available` are consumed. The reality is that there is likely a tiny amount of latency
-/// after this, but not sure how to determine this.
 fn output_timestamp(
     stream: &StreamInner,
-    frames_available: u32,
     sample_rate: crate::SampleRate,
 ) -> Result<crate::OutputStreamTimestamp, StreamError> {
-    let callback = stream_instant(stream)?;
-    let buffer_duration = frames_to_duration(frames_available, sample_rate);
+    let (position, callback) = clock_position(stream)?;
+    let frequency = unsafe { stream.audio_clock.GetFrequency() }
+        .map_err(windows_err_to_cpal_err::<StreamError>)?;
+    let played = (position as u128 * sample_rate.0 as u128 / frequency.max(1) as u128) as u64;
+    let queued = stream.frame_position.saturating_sub(played);
     let playback = callback
-        .add(buffer_duration)
+        .add(frames_to_duration(queued as u32, sample_rate))
         .expect("`playback` occurs beyond representation supported by `StreamInstant`");
     Ok(crate::OutputStreamTimestamp { callback, playback })
 }
@@ -8,12 +8,15 @@ use self::wasm_bindgen::JsCast;
 use self::web_sys::{AudioContext, AudioContextOptions};
 use crate::traits::{DeviceTrait, HostTrait, StreamTrait};
 use crate::{
-    BackendSpecificError, BufferSize, BuildStreamError, Data, DefaultStreamConfigError,
-    DeviceNameError, DevicesError, InputCallbackInfo, OutputCallbackInfo, PauseStreamError,
-    PlayStreamError, SampleFormat, SampleRate, StreamConfig, StreamError, SupportedBufferSize,
-    SupportedStreamConfig, SupportedStreamConfigRange, SupportedStreamConfigsError,
+    BackendSpecificError, BufferLayout, BufferSize, BuildStreamError, Data,
+    DefaultStreamConfigError, DeviceNameError, DevicesError, InputCallbackInfo, OutputCallbackInfo,
+    PauseStreamError, PlayStreamError, SampleFormat, SampleRate, StreamConfig, StreamError,
+    StreamState, SupportedBufferSize, SupportedStreamConfig, SupportedStreamConfigRange,
+    SupportedStreamConfigsError,
 };
+use std::cell::Cell;
 use std::ops::DerefMut;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, Mutex, RwLock};
 
 /// Content is false if the iterator is empty.
@@ -29,6 +32,11 @@ pub struct Stream {
     on_ended_closures: Vec<Arc<RwLock<Option<Closure<dyn FnMut()>>>>>,
     config: StreamConfig,
     buffer_size_frames: usize,
+    // Whether the stream was played and not paused since.
+    playing: Cell<bool>,
+    // Whether the closures were scheduled by the first call to `play`. They keep rescheduling
+    // themselves, so resuming the context is enough to resume the stream after that.
+    scheduled: Cell<bool>,
 }
 
 pub type SupportedInputConfigs = ::std::vec::IntoIter<SupportedStreamConfigRange>;
@@ -107,6 +115,8 @@ impl Device {
                 max_sample_rate: MAX_SAMPLE_RATE,
                 buffer_size: buffer_size.clone(),
                 sample_format: SUPPORTED_SAMPLE_FORMAT,
+                layout: BufferLayout::NonInterleaved,
+                channel_layout: None,
             })
             .collect();
         Ok(configs.into_iter())
@@ -207,6 +217,16 @@ impl DeviceTrait for Device {
                     v as usize
                 }
             }
+            BufferSize::Preferred { .. } => {
+                let supported = SupportedBufferSize::Range {
+                    min: MIN_BUFFER_SIZE,
+                    max: MAX_BUFFER_SIZE,
+                };
+                config
+                    .buffer_size
+                    .negotiate(&supported)?
+                    .unwrap_or_default() as usize
+            }
             BufferSize::Default => DEFAULT_BUFFER_SIZE,
         };
         let buffer_size_samples = buffer_size_frames * n_channels;
@@ -233,6 +253,9 @@ impl DeviceTrait for Device {
         // A cursor keeping track of the current time at which new frames should be scheduled.
         let time = Arc::new(RwLock::new(0f64));
 
+        // The number of frames requested from the callback so far, shared by the workers.
+        let frame_position = Arc::new(AtomicU64::new(0));
+
         // Create a set of closures / callbacks which will continuously fetch and schedule sample
         // playback. Starting with two workers, e.g. a front and back buffer so that audio frames
         // can be fetched in the background.
@@ -240,6 +263,7 @@ impl DeviceTrait for Device {
             let data_callback_handle = data_callback.clone();
             let ctx_handle = ctx.clone();
             let time_handle = time.clone();
+            let frame_position_handle = frame_position.clone();
 
             // A set of temporary buffers to be used for intermediate sample transformation steps.
             let mut temporary_buffer = vec![0f32; buffer_size_samples];
@@ -292,7 +316,9 @@ impl DeviceTrait for Device {
                         let callback = crate::StreamInstant::from_secs_f64(now);
                         let playback = crate::StreamInstant::from_secs_f64(time_at_start_of_buffer);
                         let timestamp = crate::OutputStreamTimestamp { callback, playback };
-                        let info = OutputCallbackInfo { timestamp };
+                        let frame_position = frame_position_handle
+                            .fetch_add(buffer_size_frames as u64, Ordering::Relaxed);
+                        let info = OutputCallbackInfo::new(timestamp, frame_position);
                         (data_callback.deref_mut())(&mut data, &info);
                     }
 
@@ -346,6 +372,8 @@ impl DeviceTrait for Device {
             on_ended_closures,
             config: config.clone(),
             buffer_size_frames,
+            playing: Cell::new(false),
+            scheduled: Cell::new(false),
         })
     }
 }
@@ -360,9 +388,16 @@ impl Stream {
 
 impl StreamTrait for Stream {
     fn play(&self) -> Result<(), PlayStreamError> {
+        if self.playing.get() {
+            return Ok(());
+        }
         let window = web_sys::window().unwrap();
         match self.ctx.resume() {
             Ok(_) => {
+                self.playing.set(true);
+                if self.scheduled.replace(true) {
+                    return Ok(());
+                }
                 // Begin webaudio playback, initially scheduling the closures to fire on a timeout
                 // event.
                 let mut offset_ms = 10;
@@ -396,7 +431,10 @@ impl StreamTrait for Stream {
 
     fn pause(&self) -> Result<(), PauseStreamError> {
         match self.ctx.suspend() {
-            Ok(_) => Ok(()),
+            Ok(_) => {
+                self.playing.set(false);
+                Ok(())
+            }
             Err(err) => {
                 let description = format!("{:?}", err);
                 let err = BackendSpecificError { description };
@@ -404,6 +442,14 @@ impl StreamTrait for Stream {
             }
         }
     }
+
+    fn state(&self) -> StreamState {
+        if self.playing.get() {
+            StreamState::Playing
+        } else {
+            StreamState::Paused
+        }
+    }
 }
 
 impl Drop for Stream {
@@ -100,6 +100,7 @@
 //!     SampleFormat::F32 => device.build_output_stream(&config, write_silence::<f32>, err_fn),
 //!     SampleFormat::I16 => device.build_output_stream(&config, write_silence::<i16>, err_fn),
 //!     SampleFormat::U16 => device.build_output_stream(&config, write_silence::<u16>, err_fn),
+//!     sample_format => panic!("Unsupported sample format '{:?}'", sample_format),
 //! }.unwrap();
 //!
 //! fn write_silence<T: Sample>(data: &mut [T], _: &cpal::OutputCallbackInfo) {
@@ -144,26 +145,62 @@
 #![recursion_limit = "2048"]
 
 // Extern crate declarations with `#[macro_use]` must unfortunately be at crate root.
-#[cfg(target_os = "emscripten")]
+#[cfg(all(feature = "backends", target_os = "emscripten"))]
 #[macro_use]
 extern crate stdweb;
 extern crate thiserror;
 
+pub use channel_layout::{
+    AmbisonicNormalization, AmbisonicOrdering, Ambisonics, ChannelLayout, ChannelPosition,
+};
+pub use conversion::{StreamControls, StreamStats};
+pub use device_filter::DeviceFilter;
+pub use device_info::{BluetoothProfile, DeviceCapabilities, DeviceInfo, FormFactor, Transport};
+pub use device_watch::{DevicePropertyChange, DevicePropertyWatcher};
 pub use error::*;
+pub use follow::{DefaultDeviceChange, DefaultDevicePoller, DefaultDeviceWatcher, DeviceDirection};
+#[doc(hidden)]
+#[cfg(feature = "half")]
+pub use half as __half;
+pub use handle::{StopGuard, StreamHandle};
+pub use planar::{PlanarBuffer, PlanarBufferMut};
 pub use platform::{
-    available_hosts, default_host, host_from_id, Device, Devices, Host, HostId, Stream,
-    SupportedInputConfigs, SupportedOutputConfigs, ALL_HOSTS,
+    all_devices, available_hosts, default_host, host_from_id, Device, Devices, Host, HostId,
+    Stream, SupportedInputConfigs, SupportedOutputConfigs, ALL_HOSTS,
 };
-pub use samples_formats::{Sample, SampleFormat};
+pub use samples_formats::{MuLaw, Sample, SampleFormat, Q15, Q31};
+pub use session::{SessionCursor, StreamSession};
 use std::convert::TryInto;
-use std::ops::{Div, Mul};
-use std::time::Duration;
-
+use std::ops::{Deref, Div, Mul};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+use traits::StreamTrait;
+
+#[cfg(feature = "buffered")]
+pub mod buffered;
+mod channel_layout;
+mod conversion;
+#[cfg(feature = "dasp")]
+pub mod dasp;
+mod device_filter;
+mod device_info;
+mod device_watch;
 mod error;
+mod follow;
+pub mod generators;
+mod handle;
 mod host;
+mod planar;
 pub mod platform;
+pub mod processing;
+mod realtime;
+mod rebuild;
 mod samples_formats;
+mod session;
+#[cfg(feature = "symphonia")]
+pub mod symphonia;
 pub mod traits;
+mod unwind;
 
 /// A host's device iterator yielding only *input* devices.
 pub type InputDevices<I> = std::iter::Filter<I, fn(&<I as Iterator>::Item) -> bool>;
@@ -175,7 +212,8 @@ pub type OutputDevices<I> = std::iter::Filter<I, fn(&<I as Iterator>::Item) -> b
 pub type ChannelCount = u16;
 
 /// The number of samples processed per second for a single channel of audio.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SampleRate(pub u32);
 
 impl<T> Mul<T> for SampleRate
@@ -208,24 +246,285 @@ pub type FrameCount = u32;
 /// large, leading to latency issues. If low latency is desired, Fixed(BufferSize)
 /// should be used in accordance with the SupportedBufferSize range produced by
 /// the SupportedStreamConfig API.  
-#[derive(Clone, Debug, Eq, PartialEq)]
+///
+/// `Preferred` lets the host pick the size closest to `ideal` within `min..=max` instead, for
+/// devices whose supported sizes are not known up front or get rounded by the driver. The size
+/// chosen is reported by `StreamTrait::buffer_size`.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BufferSize {
     Default,
     Fixed(FrameCount),
+    Preferred {
+        min: FrameCount,
+        max: FrameCount,
+        ideal: FrameCount,
+    },
+}
+
+impl BufferSize {
+    /// The size to ask a host supporting `supported` sizes for, or `None` to leave it to the
+    /// host's default.
+    ///
+    /// A `Fixed` size is returned as is. A `Preferred` size is `ideal` clamped to the sizes in
+    /// both its own and the supported range, failing with `StreamConfigNotSupported` if the two
+    /// ranges do not overlap.
+    pub fn negotiate(
+        &self,
+        supported: &SupportedBufferSize,
+    ) -> Result<Option<FrameCount>, BuildStreamError> {
+        match *self {
+            BufferSize::Default => Ok(None),
+            BufferSize::Fixed(frames) => Ok(Some(frames)),
+            BufferSize::Preferred { min, max, ideal } => {
+                let (min, max) = match *supported {
+                    SupportedBufferSize::Range {
+                        min: supported_min,
+                        max: supported_max,
+                    } => (min.max(supported_min), max.min(supported_max)),
+                    SupportedBufferSize::Unknown => (min, max),
+                };
+                if min > max {
+                    return Err(BuildStreamError::StreamConfigNotSupported);
+                }
+                Ok(Some(ideal.clamp(min, max)))
+            }
+        }
+    }
+
+    // Whether a host supporting `supported` sizes can open a stream with this size.
+    pub(crate) fn fits(&self, supported: &SupportedBufferSize) -> bool {
+        match (self, supported) {
+            (&BufferSize::Fixed(frames), &SupportedBufferSize::Range { min, max }) => {
+                (min..=max).contains(&frames)
+            }
+            (buffer_size, supported) => buffer_size.negotiate(supported).is_ok(),
+        }
+    }
 }
 
 /// The set of parameters used to describe how to open a stream.
 ///
 /// The sample format is omitted in favour of using a sample type.
-#[derive(Clone, Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct StreamConfig {
     pub channels: ChannelCount,
     pub sample_rate: SampleRate,
     pub buffer_size: BufferSize,
 }
 
+/// Options for streams built with `build_input_stream_with_options` and
+/// `build_output_stream_with_options`.
+///
+/// The default options open the stream exactly as `build_input_stream` and `build_output_stream`
+/// would.
+#[derive(Clone, Debug, Default)]
+pub struct StreamOptions {
+    /// Open the device in another sample format when it does not support the callback's sample
+    /// type, and convert every buffer between the two.
+    pub convert_sample_format: bool,
+    /// Open the device with another channel count when it does not support the requested one, and
+    /// map every buffer between the two as described by `processing::channels::adapt_channels`.
+    pub adapt_channels: bool,
+    /// The dither applied when converting floating-point samples to an integer format.
+    pub dither: processing::dither::DitherMode,
+    /// How output samples exceeding full scale are brought back into range before they are
+    /// converted to the device format.
+    pub clip: processing::limiter::ClipMode,
+    /// Remove DC offset from input buffers with a `processing::dc::DcBlocker` before they are
+    /// passed to the callback. Ignored by output streams.
+    pub remove_dc: bool,
+    /// Measure the loudness of the stream with a `processing::loudness::LoudnessMeter`, to be read
+    /// through `StreamControls`. Output streams are measured as sent to the device.
+    pub meter_loudness: bool,
+    /// The length of the fade applied when the stream starts, and when it is paused or resumed
+    /// through `StreamControls::pause` and `StreamControls::play`. Zero disables fading.
+    pub fade: Duration,
+    /// Custom processing applied to every buffer in order, see `processing::ProcessStage`.
+    pub stages: Vec<processing::SharedStage>,
+    /// Guarantee that samples pass between the callback and the device unaltered: no sample format
+    /// or channel conversion, no resampling and none of the processing above.
+    ///
+    /// Building the stream fails with `BuildStreamError::NotBitPerfect` if that cannot be
+    /// guaranteed. The device must report support for the exact config requested. Processing
+    /// done by the system outside of the device cpal opens, such as a shared-mode mixer, cannot be
+    /// detected, but is bypassed by opening the device with `ShareMode::Exclusive`.
+    pub bit_perfect: bool,
+    /// Whether the device is shared with other applications or opened for this stream alone.
+    pub share_mode: ShareMode,
+    /// Promote the thread calling the data callback to realtime scheduling when it is first
+    /// called, making dropouts at small buffer sizes less likely. The outcome is reported by
+    /// `StreamControls::realtime_priority`; the stream runs either way.
+    pub realtime_priority: bool,
+    /// A handle for monitoring the stream, including the callback statistics of
+    /// `StreamControls::stats`. Setting it makes the stream pass through the conversion layer
+    /// even if no conversion is needed.
+    pub controls: Option<StreamControls>,
+    /// Report `StreamError::Stalled` to the error callback once the data callback has not been
+    /// called for this many periods, as when the driver stalls or the device goes to sleep.
+    ///
+    /// A period is the duration of the audio of the last callback. The watchdog is armed by the
+    /// first callback. Pause the stream through `StreamControls::pause` to keep the pause from
+    /// being taken for a stall.
+    pub watchdog: Option<u32>,
+    /// Play this many periods of silence when an output stream starts, before the data callback
+    /// is first called, to absorb the underruns some hosts report around their first callbacks.
+    /// Ignored by input streams.
+    ///
+    /// A period is the buffer of one callback. The silence is counted by
+    /// `OutputCallbackInfo::frame_position`, so the first buffer of the data callback starts at
+    /// the pre-roll length in frames. It is also reported by `StreamControls::pre_roll`.
+    pub pre_roll: u32,
+    /// Open the device at the sample rate it is currently running at, ignoring the sample rate
+    /// of the requested config, so that the system does not resample the stream behind the
+    /// scenes, as shared-mode WASAPI and CoreAudio do. The rate is that of the device's default
+    /// config, and is reported in the config returned along with the stream.
+    pub native_sample_rate: bool,
+    /// What the stream is used for, letting the system route it, duck other streams around it
+    /// and process it accordingly. See `DeviceTrait::build_input_stream_raw_with_usage` for the
+    /// hosts taking it into account. `None` leaves the host to its defaults.
+    pub usage: Option<StreamUsage>,
+}
+
+/// Whether a device is opened for a stream alone, as set by `StreamOptions::share_mode`.
+///
+/// See `DeviceTrait::build_input_stream_raw_with_share_mode` for the hosts supporting exclusive
+/// access.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum ShareMode {
+    /// The device is shared with other applications, with the system mixing and converting their
+    /// streams as needed.
+    #[default]
+    Shared,
+    /// The device is opened for the stream alone and switched to its format, bypassing the system
+    /// mixer. This allows lower latencies and bit-perfect streams, but the device is unavailable
+    /// to other applications while the stream exists.
+    Exclusive,
+}
+
+/// What a stream is used for, as set by `StreamOptions::usage`.
+///
+/// The system picks the route, volume policy and processing of a stream by its usage. For example,
+/// communication streams may get echo cancellation applied, and cause media streams to be ducked.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum StreamUsage {
+    /// Game audio, such as effects and music.
+    Game,
+    /// Music, video and other media playback.
+    Media,
+    /// Voice or video calls.
+    Communication,
+    /// Alarms and timers, which may play even when other sounds are muted.
+    Alarm,
+    /// Accessibility features, such as screen readers.
+    Accessibility,
+}
+
+/// What an application uses a default device for, see `HostTrait::default_output_device_for`.
+///
+/// Windows keeps a default device for each role, so that calls can go to a headset while music
+/// keeps playing on the speakers. Other hosts have one default device for all roles.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum DeviceRole {
+    /// Games, system sounds and most other applications. This is the role of
+    /// `HostTrait::default_input_device` and `HostTrait::default_output_device`.
+    #[default]
+    Console,
+    /// Music and video playback.
+    Multimedia,
+    /// Voice calls, such as those of softphones and chat applications.
+    Communications,
+}
+
+/// Whether the application may record from input devices, as reported by
+/// `HostTrait::input_permission`.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum InputPermission {
+    /// Input streams may be opened.
+    Granted,
+    /// The user or a policy denied recording. Input streams fail with
+    /// `BuildStreamError::PermissionDenied`, until the user grants it in the system settings.
+    Denied,
+    /// The user has not been asked yet. See `HostTrait::request_input_permission`.
+    NotDetermined,
+}
+
+/// A stream that is drained with `StreamTrait::drain` when dropped, rather than stopped right
+/// away.
+///
+/// Dropping it blocks until the audio queued for the device has been played. Errors while
+/// draining are ignored.
+pub struct DrainOnDrop<S: StreamTrait>(pub S);
+
+impl<S: StreamTrait> Deref for DrainOnDrop<S> {
+    type Target = S;
+
+    fn deref(&self) -> &S {
+        &self.0
+    }
+}
+
+impl<S: StreamTrait> Drop for DrainOnDrop<S> {
+    fn drop(&mut self) {
+        let _ = self.0.drain();
+    }
+}
+
+/// Whether a stream is running, as reported by `StreamTrait::state`.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum StreamState {
+    /// The stream is running and its data callback is being called.
+    Playing,
+    /// The stream is paused and its data callback is not called until it is played again.
+    Paused,
+}
+
+/// The outcome of `StreamOptions::realtime_priority`, as reported by
+/// `StreamControls::realtime_priority`.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum RealtimePriority {
+    /// The callback thread was promoted: to `SCHED_RR` on Linux and the BSDs, and to the "Pro
+    /// Audio" task of the Multimedia Class Scheduler Service on Windows.
+    Promoted,
+    /// The host already calls back on a realtime thread, as CoreAudio and JACK do.
+    AlreadyRealtime,
+    /// The system refused to promote the thread, usually for lack of permission. On Linux this
+    /// takes an `RLIMIT_RTPRIO` above zero, commonly granted to members of an `audio` group.
+    Denied,
+    /// Promoting threads is not supported on this platform.
+    Unsupported,
+}
+
+/// The latency of a stream as reported by `StreamTrait::latency`, split into its components.
+///
+/// Each component is `None` where the host does not report it.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct StreamLatency {
+    /// The time it takes the device to reach the frames queued in the buffers between the stream
+    /// and the device.
+    pub buffered: Option<Duration>,
+    /// The latency the driver and the device add on top of the buffered frames, such as the
+    /// delay of their converters.
+    pub driver: Option<Duration>,
+    /// The margin the host keeps between the position of the device and the buffers it reads or
+    /// writes, to be safe from timing jitter.
+    pub safety: Option<Duration>,
+}
+
+impl StreamLatency {
+    /// The sum of the known components.
+    pub fn total(&self) -> Duration {
+        [self.buffered, self.driver, self.safety]
+            .into_iter()
+            .flatten()
+            .sum()
+    }
+}
+
 /// Describes the minimum and maximum supported buffer size for the device
-#[derive(Clone, Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum SupportedBufferSize {
     Range {
         min: FrameCount,
@@ -236,9 +535,20 @@ pub enum SupportedBufferSize {
     Unknown,
 }
 
+/// How the channels of a buffer are arranged in memory.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BufferLayout {
+    /// The samples of each frame are stored next to each other, `LRLRLR...` for stereo.
+    Interleaved,
+    /// Each channel is stored in its own contiguous buffer, `LLL...` and `RRR...` for stereo.
+    NonInterleaved,
+}
+
 /// Describes a range of supported stream configurations, retrieved via the
 /// `Device::supported_input/output_configs` method.
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SupportedStreamConfigRange {
     pub(crate) channels: ChannelCount,
     /// Minimum value for the samples rate of the supported formats.
@@ -249,16 +559,37 @@ pub struct SupportedStreamConfigRange {
     pub(crate) buffer_size: SupportedBufferSize,
     /// Type of data expected by the device.
     pub(crate) sample_format: SampleFormat,
+    /// Buffer layout native to the device.
+    pub(crate) layout: BufferLayout,
+    /// Speaker positions of the channels, if reported by the host.
+    pub(crate) channel_layout: Option<ChannelLayout>,
 }
 
 /// Describes a single supported stream configuration, retrieved via either a
 /// `SupportedStreamConfigRange` instance or one of the `Device::default_input/output_config` methods.
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SupportedStreamConfig {
     channels: ChannelCount,
     sample_rate: SampleRate,
     buffer_size: SupportedBufferSize,
     sample_format: SampleFormat,
+    layout: BufferLayout,
+    channel_layout: Option<ChannelLayout>,
+}
+
+/// The configuration a stream is running with, as returned by `build_input_stream_with_options`
+/// and `build_output_stream_with_options`.
+///
+/// Unlike the requested `StreamConfig`, it describes the device side of the stream: the format
+/// and channel count the device was opened with, which differ from those of the callback when the
+/// stream converts between them.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct NegotiatedConfig {
+    device_config: SupportedStreamConfig,
+    buffer_size: Option<FrameCount>,
+    periods: Option<u32>,
+    share_mode: ShareMode,
 }
 
 /// A buffer of dynamically typed audio data, passed to raw stream callbacks.
@@ -314,7 +645,15 @@ pub struct OutputStreamTimestamp {
     pub callback: StreamInstant,
     /// The predicted instant that data written will be delivered to the device for playback.
     ///
-    /// E.g. The instant data will be played by a DAC.
+    /// E.g. The instant data will be played by a DAC. `OutputCallbackInfo::to_instant` and
+    /// `to_system_time` convert it for synchronizing audio with video.
+    ///
+    /// | Host | Source |
+    /// | ---- | ------ |
+    /// | alsa | the status timestamp plus the delay reported by `snd_pcm_status` |
+    /// | coreaudio | the `mHostTime` of the render callback, plus the device latency and safety offset on macOS |
+    /// | wasapi | the position reported by `IAudioClock::GetPosition`, plus the frames written since |
+    /// | oboe | the latest frame timestamp of `AAudioStream_getTimestamp`, plus the frames written since |
     pub playback: StreamInstant,
 }
 
@@ -322,12 +661,17 @@ pub struct OutputStreamTimestamp {
 #[derive(Debug, Clone, PartialEq)]
 pub struct InputCallbackInfo {
     timestamp: InputStreamTimestamp,
+    frame_position: u64,
+    frames_dropped: u64,
+    callback_instant: Instant,
 }
 
 /// Information relevant to a single call to the user's output stream data callback.
 #[derive(Debug, Clone, PartialEq)]
 pub struct OutputCallbackInfo {
     timestamp: OutputStreamTimestamp,
+    frame_position: u64,
+    callback_instant: Instant,
 }
 
 impl SupportedStreamConfig {
@@ -342,6 +686,8 @@ impl SupportedStreamConfig {
             sample_rate,
             buffer_size,
             sample_format,
+            layout: BufferLayout::Interleaved,
+            channel_layout: None,
         }
     }
 
@@ -361,6 +707,24 @@ impl SupportedStreamConfig {
         self.sample_format
     }
 
+    /// The buffer layout native to the device.
+    ///
+    /// Stream callbacks receive interleaved data, except those of `build_input_stream_planar` and
+    /// `build_output_stream_planar`. A `NonInterleaved` device layout means the host interleaves
+    /// or deinterleaves each buffer on the way, unless it passes the buffers to a planar callback
+    /// as they are.
+    pub fn layout(&self) -> BufferLayout {
+        self.layout
+    }
+
+    /// The speaker positions of the channels, if reported by the host.
+    ///
+    /// Where the host does not report them, `ChannelLayout::default_for` gives the conventional
+    /// layout for the channel count.
+    pub fn channel_layout(&self) -> Option<&ChannelLayout> {
+        self.channel_layout.as_ref()
+    }
+
     pub fn config(&self) -> StreamConfig {
         StreamConfig {
             channels: self.channels,
@@ -370,6 +734,80 @@ impl SupportedStreamConfig {
     }
 }
 
+impl NegotiatedConfig {
+    pub(crate) fn new<S: StreamTrait>(
+        stream: &S,
+        device_config: SupportedStreamConfig,
+        share_mode: ShareMode,
+    ) -> Self {
+        NegotiatedConfig {
+            device_config,
+            buffer_size: stream.buffer_size(),
+            periods: stream.periods(),
+            share_mode,
+        }
+    }
+
+    /// The number of channels the device was opened with.
+    pub fn channels(&self) -> ChannelCount {
+        self.device_config.channels
+    }
+
+    /// The sample rate the device was opened at.
+    pub fn sample_rate(&self) -> SampleRate {
+        self.device_config.sample_rate
+    }
+
+    /// The sample format the device was opened in.
+    pub fn sample_format(&self) -> SampleFormat {
+        self.device_config.sample_format
+    }
+
+    /// The number of frames of each callback, if the host reports it. See
+    /// `StreamTrait::buffer_size`.
+    pub fn buffer_size(&self) -> Option<FrameCount> {
+        self.buffer_size
+    }
+
+    /// The number of periods of the device buffer, if the host reports it. See
+    /// `StreamTrait::periods`.
+    pub fn periods(&self) -> Option<u32> {
+        self.periods
+    }
+
+    /// Whether the device is shared with other applications or opened for the stream alone.
+    pub fn share_mode(&self) -> ShareMode {
+        self.share_mode
+    }
+
+    /// The buffer layout native to the device. See `SupportedStreamConfig::layout`.
+    pub fn layout(&self) -> BufferLayout {
+        self.device_config.layout
+    }
+
+    /// The speaker positions of the channels, if reported by the host.
+    pub fn channel_layout(&self) -> Option<&ChannelLayout> {
+        self.device_config.channel_layout.as_ref()
+    }
+
+    /// The config the device was opened with, along with the buffer sizes it supports.
+    pub fn device_config(&self) -> &SupportedStreamConfig {
+        &self.device_config
+    }
+
+    /// The config to request to open the device the same way again, with the buffer size fixed
+    /// where it is known.
+    pub fn config(&self) -> StreamConfig {
+        StreamConfig {
+            channels: self.channels(),
+            sample_rate: self.sample_rate(),
+            buffer_size: self
+                .buffer_size
+                .map_or(BufferSize::Default, BufferSize::Fixed),
+        }
+    }
+}
+
 impl StreamInstant {
     /// The amount of time elapsed from another instant to this one.
     ///
@@ -406,7 +844,37 @@ impl StreamInstant {
             .and_then(Self::from_nanos_i128)
     }
 
-    fn as_nanos(&self) -> i128 {
+    /// Returns the instant in time after the given duration has passed, or the latest
+    /// representable instant on overflow.
+    pub fn saturating_add(&self, duration: Duration) -> Self {
+        self.add(duration).unwrap_or(StreamInstant {
+            secs: i64::MAX,
+            nanos: 999_999_999,
+        })
+    }
+
+    /// Returns the instant in time one `duration` ago, or the earliest representable instant on
+    /// underflow.
+    pub fn saturating_sub(&self, duration: Duration) -> Self {
+        self.sub(duration).unwrap_or(StreamInstant {
+            secs: i64::MIN,
+            nanos: 0,
+        })
+    }
+
+    /// The amount of time between this instant and another, regardless of their order.
+    pub fn abs_diff(&self, other: &Self) -> Duration {
+        self.duration_since(other)
+            .or_else(|| other.duration_since(self))
+            .unwrap_or(Duration::MAX)
+    }
+
+    /// The number of nanoseconds since the origin of the stream's clock.
+    ///
+    /// The origin is host-specific: the creation of the stream on some hosts, the start of the
+    /// system's monotonic clock on others. Only differences between instants of the same stream
+    /// are meaningful across hosts.
+    pub fn as_nanos(&self) -> i128 {
         (self.secs as i128 * 1_000_000_000) + self.nanos as i128
     }
 
@@ -417,14 +885,13 @@ impl StreamInstant {
         Self::new(secs as i64, subsec_nanos as u32)
     }
 
-    #[allow(dead_code)]
     fn from_nanos_i128(nanos: i128) -> Option<Self> {
-        let secs = nanos / 1_000_000_000;
+        // Rounding towards negative infinity keeps the subsecond nanoseconds positive.
+        let secs = nanos.div_euclid(1_000_000_000);
         if secs > i64::MAX as i128 || secs < i64::MIN as i128 {
             None
         } else {
-            let subsec_nanos = nanos - secs * 1_000_000_000;
-            debug_assert!(subsec_nanos < u32::MAX as i128);
+            let subsec_nanos = nanos.rem_euclid(1_000_000_000);
             Some(Self::new(secs as i64, subsec_nanos as u32))
         }
     }
@@ -441,18 +908,169 @@ impl StreamInstant {
     }
 }
 
+// Converts `instant` to an `Instant`, given the `Instant` corresponding to `reference`.
+fn correlate_instant(
+    instant: &StreamInstant,
+    reference: &StreamInstant,
+    reference_instant: Instant,
+) -> Option<Instant> {
+    match instant.duration_since(reference) {
+        Some(after) => reference_instant.checked_add(after),
+        None => reference_instant.checked_sub(reference.duration_since(instant)?),
+    }
+}
+
+// Converts `instant` to a `SystemTime`, correlating the clocks now.
+fn correlate_system_time(instant: Instant) -> Option<SystemTime> {
+    let (now, system_now) = (Instant::now(), SystemTime::now());
+    match instant.checked_duration_since(now) {
+        Some(after) => system_now.checked_add(after),
+        None => system_now.checked_sub(now.duration_since(instant)),
+    }
+}
+
 impl InputCallbackInfo {
+    // Internal constructor for host implementations, to be called as the callback is invoked.
+    #[cfg_attr(not(feature = "backends"), allow(dead_code))]
+    pub(crate) fn new(timestamp: InputStreamTimestamp, frame_position: u64) -> Self {
+        InputCallbackInfo {
+            timestamp,
+            frame_position,
+            frames_dropped: 0,
+            callback_instant: Instant::now(),
+        }
+    }
+
+    // Records the frames the host dropped right before this buffer.
+    #[allow(dead_code)]
+    pub(crate) fn with_frames_dropped(mut self, frames_dropped: u64) -> Self {
+        self.frames_dropped = frames_dropped;
+        self
+    }
+
     /// The timestamp associated with the call to an input stream's data callback.
     pub fn timestamp(&self) -> InputStreamTimestamp {
         self.timestamp
     }
+
+    /// The number of frames the stream delivered before this buffer, counted from its first
+    /// callback. Pausing the stream does not reset it.
+    pub fn frame_position(&self) -> u64 {
+        self.frame_position
+    }
+
+    /// The number of captured frames that were dropped right before this buffer, because the
+    /// stream overran.
+    ///
+    /// A recorder can insert this much silence to keep the recording in time with the device.
+    /// The dropped frames are not included in `frame_position`. Only WASAPI reports the count,
+    /// other hosts report zero and only signal the loss with `StreamError::Overrun`.
+    pub fn frames_dropped(&self) -> u64 {
+        self.frames_dropped
+    }
+
+    /// The `Instant` at which the callback was invoked, corresponding to `timestamp().callback`.
+    pub fn callback_instant(&self) -> Instant {
+        self.callback_instant
+    }
+
+    /// Converts an instant of the stream's clock, such as `timestamp().capture`, to an `Instant`.
+    ///
+    /// The clocks are correlated at the invocation of the callback. Returns `None` if the result
+    /// cannot be represented as an `Instant`.
+    pub fn to_instant(&self, instant: &StreamInstant) -> Option<Instant> {
+        correlate_instant(instant, &self.timestamp.callback, self.callback_instant)
+    }
+
+    /// Converts an instant of the stream's clock to wall-clock time, such as for timestamping a
+    /// recording.
+    ///
+    /// See `to_instant`. The system clock may be adjusted at any time, so the result is only as
+    /// accurate as the system clock at the time of the call. Returns `None` if the result cannot
+    /// be represented as a `SystemTime`.
+    pub fn to_system_time(&self, instant: &StreamInstant) -> Option<SystemTime> {
+        correlate_system_time(self.to_instant(instant)?)
+    }
 }
 
 impl OutputCallbackInfo {
+    // Internal constructor for host implementations, to be called as the callback is invoked.
+    #[cfg_attr(not(feature = "backends"), allow(dead_code))]
+    pub(crate) fn new(timestamp: OutputStreamTimestamp, frame_position: u64) -> Self {
+        OutputCallbackInfo {
+            timestamp,
+            frame_position,
+            callback_instant: Instant::now(),
+        }
+    }
+
+    // The info for the part of the buffer after its first `frames` frames, for callbacks that are
+    // only given that part.
+    pub(crate) fn skip_frames(&self, frames: u64, sample_rate: SampleRate) -> Self {
+        let skipped = Duration::from_nanos(frames * 1_000_000_000 / sample_rate.0.max(1) as u64);
+        OutputCallbackInfo {
+            timestamp: OutputStreamTimestamp {
+                playback: self
+                    .timestamp
+                    .playback
+                    .add(skipped)
+                    .unwrap_or(self.timestamp.playback),
+                ..self.timestamp
+            },
+            frame_position: self.frame_position + frames,
+            callback_instant: self.callback_instant,
+        }
+    }
+
     /// The timestamp associated with the call to an output stream's data callback.
     pub fn timestamp(&self) -> OutputStreamTimestamp {
         self.timestamp
     }
+
+    /// The number of frames the stream was given before this buffer, counted from its first
+    /// callback. Pausing the stream does not reset it.
+    pub fn frame_position(&self) -> u64 {
+        self.frame_position
+    }
+
+    /// The `Instant` at which the callback was invoked, corresponding to `timestamp().callback`.
+    pub fn callback_instant(&self) -> Instant {
+        self.callback_instant
+    }
+
+    /// Converts an instant of the stream's clock, such as `timestamp().playback`, to an
+    /// `Instant`.
+    ///
+    /// The clocks are correlated at the invocation of the callback. Returns `None` if the result
+    /// cannot be represented as an `Instant`.
+    pub fn to_instant(&self, instant: &StreamInstant) -> Option<Instant> {
+        correlate_instant(instant, &self.timestamp.callback, self.callback_instant)
+    }
+
+    /// Converts an instant of the stream's clock to wall-clock time.
+    ///
+    /// See `to_instant`. The system clock may be adjusted at any time, so the result is only as
+    /// accurate as the system clock at the time of the call. Returns `None` if the result cannot
+    /// be represented as a `SystemTime`.
+    pub fn to_system_time(&self, instant: &StreamInstant) -> Option<SystemTime> {
+        correlate_system_time(self.to_instant(instant)?)
+    }
+
+    /// The `Instant` by which the buffer must be filled for it to play without a gap.
+    ///
+    /// This is when the first frame of the buffer is played, `timestamp().playback`, which hosts
+    /// derive from the frames still queued for the device or from the buffer size. Adaptive
+    /// processing can compare it against its progress and lower its quality rather than miss it.
+    pub fn deadline(&self) -> Instant {
+        self.to_instant(&self.timestamp.playback)
+            .filter(|deadline| *deadline > self.callback_instant)
+            .unwrap_or(self.callback_instant)
+    }
+
+    /// The time left until the `deadline`, or zero once it has passed.
+    pub fn budget(&self) -> Duration {
+        self.deadline().saturating_duration_since(Instant::now())
+    }
 }
 
 #[allow(clippy::len_without_is_empty)]
@@ -466,6 +1084,7 @@ impl Data {
     //   format specified via the `sample_format` argument.
     // - The `sample_format` must correctly represent the underlying sample data delivered/expected
     //   by the stream.
+    #[cfg_attr(not(feature = "backends"), allow(dead_code))]
     pub(crate) unsafe fn from_parts(
         data: *mut (),
         len: usize,
@@ -563,6 +1182,8 @@ impl SupportedStreamConfigRange {
             max_sample_rate,
             buffer_size,
             sample_format,
+            layout: BufferLayout::Interleaved,
+            channel_layout: None,
         }
     }
 
@@ -586,6 +1207,34 @@ impl SupportedStreamConfigRange {
         self.sample_format
     }
 
+    /// The buffer layout native to the device.
+    ///
+    /// Stream callbacks receive interleaved data, except those of `build_input_stream_planar` and
+    /// `build_output_stream_planar`. A `NonInterleaved` device layout means the host interleaves
+    /// or deinterleaves each buffer on the way, unless it passes the buffers to a planar callback
+    /// as they are.
+    pub fn layout(&self) -> BufferLayout {
+        self.layout
+    }
+
+    /// The speaker positions of the channels, if reported by the host.
+    ///
+    /// Where the host does not report them, `ChannelLayout::default_for` gives the conventional
+    /// layout for the channel count.
+    pub fn channel_layout(&self) -> Option<&ChannelLayout> {
+        self.channel_layout.as_ref()
+    }
+
+    /// Whether a stream with `config` and `sample_format` falls within this range: the same
+    /// channel count and sample format, a sample rate within the range, and a buffer size the
+    /// range allows.
+    pub fn supports(&self, config: &StreamConfig, sample_format: SampleFormat) -> bool {
+        self.channels == config.channels
+            && self.sample_format == sample_format
+            && (self.min_sample_rate..=self.max_sample_rate).contains(&config.sample_rate)
+            && config.buffer_size.fits(&self.buffer_size)
+    }
+
     /// Retrieve a `SupportedStreamConfig` with the given sample rate and buffer size.
     ///
     /// **panic!**s if the given `sample_rate` is outside the range specified within this
@@ -597,6 +1246,8 @@ impl SupportedStreamConfigRange {
             sample_rate,
             sample_format: self.sample_format,
             buffer_size: self.buffer_size,
+            layout: self.layout,
+            channel_layout: self.channel_layout,
         }
     }
 
@@ -608,6 +1259,8 @@ impl SupportedStreamConfigRange {
             sample_rate: self.max_sample_rate,
             sample_format: self.sample_format,
             buffer_size: self.buffer_size,
+            layout: self.layout,
+            channel_layout: self.channel_layout,
         }
     }
 
@@ -682,6 +1335,118 @@ impl SupportedStreamConfigRange {
     }
 }
 
+static APPLICATION_NAME: Mutex<Option<String>> = Mutex::new(None);
+
+/// Sets the name the application is listed under in system mixers and patchbays.
+///
+/// Call it before creating hosts and streams, which pick the name up as they are created:
+///
+/// - WASAPI sets it as the display name of the audio session of every stream.
+/// - JACK names its clients after it, instead of `cpal_client`.
+///
+/// Other hosts ignore it. The PulseAudio plugin of ALSA, also used by PipeWire, only reads the name
+/// from the `PULSE_PROP_application.name` environment variable. cpal does not set it, as changing
+/// the environment races with other threads reading it: set it before spawning any threads.
+pub fn set_application_name(name: &str) {
+    *APPLICATION_NAME.lock().unwrap() = Some(name.to_owned());
+}
+
+/// The name set with `set_application_name`, if any.
+pub fn application_name() -> Option<String> {
+    APPLICATION_NAME.lock().unwrap().clone()
+}
+
+/// Picks the supported config that comes closest to the desired `config` and `sample_format`.
+///
+/// Candidates are ranked by, in order of importance:
+///
+/// - **Channels**: the exact count, then the smallest larger count, then the largest smaller one.
+/// - **Sample rate**: the distance between the desired rate and the closest rate in the range.
+/// - **Sample format**: the exact format, then another format of the same class (signed, unsigned
+///   or floating point), then any other PCM format. Bitstream formats such as DSD are only chosen
+///   when requested exactly.
+/// - **Buffer size**: ranges containing a `BufferSize::Fixed` size, or overlapping a
+///   `BufferSize::Preferred` range, are preferred.
+///
+/// The returned config uses the sample rate of its range closest to the desired one. Returns `None`
+/// if no candidate has a suitable sample format.
+pub fn closest_supported_config<I>(
+    supported: I,
+    config: &StreamConfig,
+    sample_format: SampleFormat,
+) -> Option<SupportedStreamConfig>
+where
+    I: IntoIterator<Item = SupportedStreamConfigRange>,
+{
+    let rate = |range: &SupportedStreamConfigRange| {
+        config
+            .sample_rate
+            .clamp(range.min_sample_rate, range.max_sample_rate)
+    };
+    supported
+        .into_iter()
+        .filter_map(|range| {
+            let format_distance = format_distance(sample_format, range.sample_format)?;
+            let channels = (
+                range.channels != config.channels,
+                range.channels < config.channels,
+                range.channels.abs_diff(config.channels),
+            );
+            let rate_distance = rate(&range).0.abs_diff(config.sample_rate.0);
+            let buffer_fits = match (&config.buffer_size, &range.buffer_size) {
+                (BufferSize::Fixed(size), SupportedBufferSize::Range { min, max }) => {
+                    min <= size && size <= max
+                }
+                (buffer_size, supported) => buffer_size.negotiate(supported).is_ok(),
+            };
+            let score = (channels, rate_distance, format_distance, !buffer_fits);
+            Some((score, range))
+        })
+        .min_by_key(|(score, _)| *score)
+        .map(|(_, range)| {
+            let sample_rate = rate(&range);
+            range.with_sample_rate(sample_rate)
+        })
+}
+
+// How far `candidate` is from the `preferred` format, or `None` if it cannot stand in for it.
+fn format_distance(preferred: SampleFormat, candidate: SampleFormat) -> Option<u8> {
+    fn class(format: SampleFormat) -> Option<u8> {
+        match format {
+            _ if format.is_int() => Some(0),
+            _ if format.is_uint() => Some(1),
+            _ if format.is_float() => Some(2),
+            SampleFormat::MuLaw => Some(3),
+            _ => None,
+        }
+    }
+    if preferred == candidate {
+        return Some(0);
+    }
+    match (class(preferred)?, class(candidate)?) {
+        (a, b) if a == b => Some(1),
+        _ => Some(2),
+    }
+}
+
+#[test]
+fn test_preferred_host_reports_skipped_hosts() {
+    let err = Host::preferred(&[]).map(|host| host.id()).unwrap_err();
+    assert!(err.skipped.is_empty());
+    assert_eq!(err.to_string(), "none of the preferred hosts is usable");
+}
+
+#[test]
+fn test_host_id_from_str() {
+    for &id in ALL_HOSTS {
+        assert_eq!(id.to_string().parse::<HostId>().unwrap(), id);
+        assert_eq!(id.name().to_uppercase().parse::<HostId>().unwrap(), id);
+    }
+    assert_eq!(ALL_HOSTS.iter().filter(|id| id.is_default()).count(), 1);
+    let err = "no such host".parse::<HostId>().unwrap_err();
+    assert_eq!(err.name, "no such host");
+}
+
 #[test]
 fn test_cmp_default_heuristics() {
     let mut formats = vec![
@@ -691,6 +1456,8 @@ fn test_cmp_default_heuristics() {
             min_sample_rate: SampleRate(1),
             max_sample_rate: SampleRate(96000),
             sample_format: SampleFormat::F32,
+            layout: BufferLayout::Interleaved,
+            channel_layout: None,
         },
         SupportedStreamConfigRange {
             buffer_size: SupportedBufferSize::Range { min: 256, max: 512 },
@@ -698,6 +1465,8 @@ fn test_cmp_default_heuristics() {
             min_sample_rate: SampleRate(1),
             max_sample_rate: SampleRate(96000),
             sample_format: SampleFormat::F32,
+            layout: BufferLayout::Interleaved,
+            channel_layout: None,
         },
         SupportedStreamConfigRange {
             buffer_size: SupportedBufferSize::Range { min: 256, max: 512 },
@@ -705,6 +1474,8 @@ fn test_cmp_default_heuristics() {
             min_sample_rate: SampleRate(1),
             max_sample_rate: SampleRate(96000),
             sample_format: SampleFormat::I16,
+            layout: BufferLayout::Interleaved,
+            channel_layout: None,
         },
         SupportedStreamConfigRange {
             buffer_size: SupportedBufferSize::Range { min: 256, max: 512 },
@@ -712,6 +1483,8 @@ fn test_cmp_default_heuristics() {
             min_sample_rate: SampleRate(1),
             max_sample_rate: SampleRate(96000),
             sample_format: SampleFormat::U16,
+            layout: BufferLayout::Interleaved,
+            channel_layout: None,
         },
         SupportedStreamConfigRange {
             buffer_size: SupportedBufferSize::Range { min: 256, max: 512 },
@@ -719,6 +1492,8 @@ fn test_cmp_default_heuristics() {
             min_sample_rate: SampleRate(1),
             max_sample_rate: SampleRate(22050),
             sample_format: SampleFormat::F32,
+            layout: BufferLayout::Interleaved,
+            channel_layout: None,
         },
     ];
 
@@ -751,6 +1526,52 @@ fn test_cmp_default_heuristics() {
     assert_eq!(formats[4].channels(), 2);
 }
 
+#[test]
+fn test_closest_supported_config() {
+    let range = |channels, min, max, sample_format| SupportedStreamConfigRange {
+        buffer_size: SupportedBufferSize::Range { min: 256, max: 512 },
+        channels,
+        min_sample_rate: SampleRate(min),
+        max_sample_rate: SampleRate(max),
+        sample_format,
+        layout: BufferLayout::Interleaved,
+        channel_layout: None,
+    };
+    let supported = vec![
+        range(2, 44_100, 44_100, SampleFormat::I16),
+        range(2, 8_000, 48_000, SampleFormat::U16),
+        range(6, 8_000, 192_000, SampleFormat::F32),
+        range(2, 8_000, 192_000, SampleFormat::DsdU8),
+        range(1, 8_000, 192_000, SampleFormat::F32),
+    ];
+    let config = StreamConfig {
+        channels: 2,
+        sample_rate: SampleRate(96_000),
+        buffer_size: BufferSize::Default,
+    };
+
+    // The closest rate beats the closest format.
+    let closest = closest_supported_config(supported.clone(), &config, SampleFormat::F32).unwrap();
+    assert_eq!(closest.channels(), 2);
+    assert_eq!(closest.sample_rate(), SampleRate(48_000));
+    assert_eq!(closest.sample_format(), SampleFormat::U16);
+
+    // Larger channel counts are preferred over smaller ones.
+    let config = StreamConfig {
+        channels: 4,
+        ..config
+    };
+    let closest = closest_supported_config(supported.clone(), &config, SampleFormat::F32).unwrap();
+    assert_eq!(closest.channels(), 6);
+    assert_eq!(closest.sample_rate(), SampleRate(96_000));
+
+    // Bitstream formats are only used when asked for.
+    assert_eq!(
+        closest_supported_config(supported[3..4].to_vec(), &config, SampleFormat::F32),
+        None
+    );
+}
+
 impl From<SupportedStreamConfig> for StreamConfig {
     fn from(conf: SupportedStreamConfig) -> Self {
         conf.config()
@@ -761,7 +1582,7 @@ impl From<SupportedStreamConfig> for StreamConfig {
 // of commonly used rates. This is always the case for wasapi and is sometimes the case for alsa.
 //
 // If a rate you desire is missing from this list, feel free to add it!
-#[cfg(target_os = "windows")]
+#[cfg(all(feature = "backends", target_os = "windows"))]
 const COMMON_SAMPLE_RATES: &'static [SampleRate] = &[
     SampleRate(5512),
     SampleRate(8000),
@@ -811,3 +1632,205 @@ fn test_stream_instant() {
     );
     assert_eq!(max.add(Duration::from_secs(1)), None);
 }
+
+#[test]
+fn test_stream_instant_arithmetic() {
+    let a = StreamInstant::new(-2, 500_000_000);
+    assert_eq!(a.as_nanos(), -1_500_000_000);
+    assert_eq!(
+        a.sub(Duration::from_millis(1)),
+        Some(StreamInstant::new(-2, 499_000_000))
+    );
+    assert_eq!(
+        StreamInstant::new(0, 0).sub(Duration::from_nanos(1)),
+        Some(StreamInstant::new(-1, 999_999_999))
+    );
+    let b = StreamInstant::new(1, 0);
+    assert_eq!(a.abs_diff(&b), Duration::from_millis(2500));
+    assert_eq!(b.abs_diff(&a), Duration::from_millis(2500));
+
+    let max = StreamInstant::new(i64::MAX, 999_999_999);
+    let min = StreamInstant::new(i64::MIN, 0);
+    assert_eq!(max.saturating_add(Duration::from_secs(1)), max);
+    assert_eq!(min.saturating_sub(Duration::from_secs(1)), min);
+    assert_eq!(
+        b.saturating_add(Duration::from_secs(1)),
+        StreamInstant::new(2, 0)
+    );
+}
+
+#[test]
+fn test_buffer_size_negotiate() {
+    let supported = SupportedBufferSize::Range { min: 64, max: 1024 };
+    let preferred = |min, max, ideal| BufferSize::Preferred { min, max, ideal };
+    assert_eq!(BufferSize::Default.negotiate(&supported).unwrap(), None);
+    assert_eq!(
+        BufferSize::Fixed(32).negotiate(&supported).unwrap(),
+        Some(32)
+    );
+    assert_eq!(
+        preferred(128, 512, 256).negotiate(&supported).unwrap(),
+        Some(256)
+    );
+    assert_eq!(
+        preferred(16, 2048, 32).negotiate(&supported).unwrap(),
+        Some(64)
+    );
+    assert_eq!(
+        preferred(512, 2048, 4096)
+            .negotiate(&SupportedBufferSize::Unknown)
+            .unwrap(),
+        Some(2048)
+    );
+    assert!(matches!(
+        preferred(2048, 4096, 2048).negotiate(&supported),
+        Err(BuildStreamError::StreamConfigNotSupported)
+    ));
+}
+
+#[test]
+fn test_stream_latency_total() {
+    let latency = StreamLatency {
+        buffered: Some(Duration::from_millis(10)),
+        driver: None,
+        safety: Some(Duration::from_micros(500)),
+    };
+    assert_eq!(latency.total(), Duration::from_micros(10_500));
+    assert_eq!(StreamLatency::default().total(), Duration::ZERO);
+}
+
+#[test]
+fn test_callback_info_to_instant() {
+    let timestamp = OutputStreamTimestamp {
+        callback: StreamInstant::new(10, 0),
+        playback: StreamInstant::new(10, 20_000_000),
+    };
+    let info = OutputCallbackInfo::new(timestamp, 480);
+    assert_eq!(info.frame_position(), 480);
+    let playback = info.to_instant(&timestamp.playback).unwrap();
+    assert_eq!(
+        playback.duration_since(info.callback_instant()),
+        Duration::from_millis(20)
+    );
+    let earlier = info
+        .to_instant(&StreamInstant::new(9, 990_000_000))
+        .unwrap();
+    assert_eq!(
+        info.callback_instant().duration_since(earlier),
+        Duration::from_millis(10)
+    );
+}
+
+#[test]
+fn test_input_callback_info_frames_dropped() {
+    let timestamp = InputStreamTimestamp {
+        callback: StreamInstant::new(10, 0),
+        capture: StreamInstant::new(9, 990_000_000),
+    };
+    let info = InputCallbackInfo::new(timestamp, 480);
+    assert_eq!(info.frames_dropped(), 0);
+    let info = info.with_frames_dropped(96);
+    assert_eq!(info.frames_dropped(), 96);
+    assert_eq!(info.frame_position(), 480);
+}
+
+#[test]
+fn test_output_callback_info_deadline() {
+    let timestamp = OutputStreamTimestamp {
+        callback: StreamInstant::new(10, 0),
+        playback: StreamInstant::new(10, 20_000_000),
+    };
+    let info = OutputCallbackInfo::new(timestamp, 0);
+    assert_eq!(
+        info.deadline().duration_since(info.callback_instant()),
+        Duration::from_millis(20)
+    );
+    assert!(info.budget() <= Duration::from_millis(20));
+
+    let late = OutputStreamTimestamp {
+        playback: StreamInstant::new(9, 0),
+        ..timestamp
+    };
+    let info = OutputCallbackInfo::new(late, 0);
+    assert_eq!(info.deadline(), info.callback_instant());
+    assert_eq!(info.budget(), Duration::ZERO);
+}
+
+#[test]
+fn test_callback_info_to_system_time() {
+    let timestamp = OutputStreamTimestamp {
+        callback: StreamInstant::new(10, 0),
+        playback: StreamInstant::new(10, 20_000_000),
+    };
+    let before = SystemTime::now();
+    let info = OutputCallbackInfo::new(timestamp, 0);
+    let callback = info.to_system_time(&timestamp.callback).unwrap();
+    let playback = info.to_system_time(&timestamp.playback).unwrap();
+    let after = SystemTime::now();
+    // Allow for the rounding in correlating the clocks, and for the thread being preempted
+    // between the correlations of the two timestamps.
+    let slack = Duration::from_millis(1);
+    assert!(callback + slack >= before && callback <= after + slack);
+    let delay = playback.duration_since(callback).unwrap();
+    assert!(delay.abs_diff(Duration::from_millis(20)) < slack);
+}
+
+#[test]
+fn test_stream_error_recovery() {
+    let underrun = StreamError::Underrun { frames_lost: None };
+    assert_eq!(underrun.recovery(), StreamRecovery::None);
+    assert_eq!(
+        StreamError::DriverRestarted.recovery(),
+        StreamRecovery::Rebuild
+    );
+    assert_eq!(
+        StreamError::ExclusiveAccessLost.recovery(),
+        StreamRecovery::ChangeConfig
+    );
+    assert_eq!(
+        StreamError::DeviceNotAvailable.recovery(),
+        StreamRecovery::ChangeDevice
+    );
+}
+
+#[test]
+fn test_supported_config_range_supports() {
+    let range = SupportedStreamConfigRange::new(
+        2,
+        SampleRate(44100),
+        SampleRate(96000),
+        SupportedBufferSize::Range { min: 64, max: 4096 },
+        SampleFormat::F32,
+    );
+    let config = StreamConfig {
+        channels: 2,
+        sample_rate: SampleRate(48000),
+        buffer_size: BufferSize::Fixed(256),
+    };
+    assert!(range.supports(&config, SampleFormat::F32));
+    assert!(!range.supports(&config, SampleFormat::I16));
+    let mono = StreamConfig {
+        channels: 1,
+        ..config.clone()
+    };
+    assert!(!range.supports(&mono, SampleFormat::F32));
+    let slow = StreamConfig {
+        sample_rate: SampleRate(22050),
+        ..config.clone()
+    };
+    assert!(!range.supports(&slow, SampleFormat::F32));
+    let huge = StreamConfig {
+        buffer_size: BufferSize::Fixed(8192),
+        ..config.clone()
+    };
+    assert!(!range.supports(&huge, SampleFormat::F32));
+    let preferred = StreamConfig {
+        buffer_size: BufferSize::Preferred {
+            min: 2048,
+            max: 8192,
+            ideal: 8192,
+        },
+        ..config
+    };
+    assert!(range.supports(&preferred, SampleFormat::F32));
+}
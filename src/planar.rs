@@ -0,0 +1,296 @@
+//! Buffers holding each channel on its own, as passed to the callbacks of planar streams.
+//!
+//! Backs `DeviceTrait::build_input_stream_planar` and `DeviceTrait::build_output_stream_planar`.
+//! Hosts whose devices are planar, such as JACK with its port per channel, pass their buffers to
+//! the callback as they are. Other hosts build an interleaved stream, and copy the frames between
+//! its buffers and planar staging buffers.
+
+use crate::traits::DeviceTrait;
+use crate::{
+    BuildStreamError, InputCallbackInfo, OutputCallbackInfo, Sample, StreamConfig, StreamError,
+};
+use std::marker::PhantomData;
+use std::slice;
+
+/// The frames captured by a planar input stream, with a buffer for each channel.
+pub struct PlanarBuffer<'a, T> {
+    channels: &'a [*const T],
+    frames: usize,
+    marker: PhantomData<&'a [T]>,
+}
+
+impl<'a, T> PlanarBuffer<'a, T> {
+    /// # Safety
+    ///
+    /// Every pointer in `channels` must point to `frames` initialized samples, which are not
+    /// written to for `'a`.
+    pub(crate) unsafe fn from_raw_parts(channels: &'a [*const T], frames: usize) -> Self {
+        PlanarBuffer {
+            channels,
+            frames,
+            marker: PhantomData,
+        }
+    }
+
+    /// The number of channels.
+    pub fn channels(&self) -> usize {
+        self.channels.len()
+    }
+
+    /// The number of frames, which is the length of the buffer of every channel.
+    pub fn frames(&self) -> usize {
+        self.frames
+    }
+
+    /// The samples of the channel at `index`.
+    ///
+    /// Panics if `index` is not less than `channels()`.
+    pub fn channel(&self, index: usize) -> &[T] {
+        unsafe { slice::from_raw_parts(self.channels[index], self.frames) }
+    }
+
+    /// The buffers of all channels, in order.
+    pub fn iter(&self) -> impl Iterator<Item = &[T]> {
+        (0..self.channels()).map(move |index| self.channel(index))
+    }
+
+    /// The same buffer, of samples of type `U`.
+    ///
+    /// # Safety
+    ///
+    /// `U` must be `T`, as for a host checking `T::FORMAT`.
+    #[allow(dead_code)]
+    pub(crate) unsafe fn cast<U>(&self) -> PlanarBuffer<'a, U> {
+        let channels = self.channels.as_ptr() as *const *const U;
+        PlanarBuffer::from_raw_parts(
+            slice::from_raw_parts(channels, self.channels()),
+            self.frames,
+        )
+    }
+}
+
+/// The frames to be played by a planar output stream, with a buffer for each channel.
+pub struct PlanarBufferMut<'a, T> {
+    channels: &'a [*mut T],
+    frames: usize,
+    marker: PhantomData<&'a mut [T]>,
+}
+
+impl<'a, T> PlanarBufferMut<'a, T> {
+    /// # Safety
+    ///
+    /// Every pointer in `channels` must point to `frames` initialized samples of its own, which
+    /// are not accessed otherwise for `'a`.
+    pub(crate) unsafe fn from_raw_parts(channels: &'a [*mut T], frames: usize) -> Self {
+        PlanarBufferMut {
+            channels,
+            frames,
+            marker: PhantomData,
+        }
+    }
+
+    /// The number of channels.
+    pub fn channels(&self) -> usize {
+        self.channels.len()
+    }
+
+    /// The number of frames, which is the length of the buffer of every channel.
+    pub fn frames(&self) -> usize {
+        self.frames
+    }
+
+    /// The samples of the channel at `index`.
+    ///
+    /// Panics if `index` is not less than `channels()`.
+    pub fn channel(&self, index: usize) -> &[T] {
+        unsafe { slice::from_raw_parts(self.channels[index], self.frames) }
+    }
+
+    /// The samples of the channel at `index`, to be written.
+    ///
+    /// Panics if `index` is not less than `channels()`.
+    pub fn channel_mut(&mut self, index: usize) -> &mut [T] {
+        unsafe { slice::from_raw_parts_mut(self.channels[index], self.frames) }
+    }
+
+    /// The buffers of all channels, in order, to be written.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut [T]> {
+        let frames = self.frames;
+        // The buffers do not overlap, so they can be borrowed mutably at once.
+        self.channels
+            .iter()
+            .map(move |&channel| unsafe { slice::from_raw_parts_mut(channel, frames) })
+    }
+
+    /// The same buffer, of samples of type `U`.
+    ///
+    /// # Safety
+    ///
+    /// `U` must be `T`, as for a host checking `T::FORMAT`.
+    #[allow(dead_code)]
+    pub(crate) unsafe fn cast<U>(&mut self) -> PlanarBufferMut<'_, U> {
+        let channels = self.channels.as_ptr() as *const *mut U;
+        PlanarBufferMut::from_raw_parts(
+            slice::from_raw_parts(channels, self.channels()),
+            self.frames,
+        )
+    }
+}
+
+/// Pointers to the channels of a planar buffer, kept between callbacks so that they are not
+/// allocated on the audio thread. They are only dereferenced by the callback setting them.
+pub(crate) struct ChannelPointers<P>(Vec<P>);
+
+unsafe impl<P> Send for ChannelPointers<P> {}
+
+impl<P> ChannelPointers<P> {
+    pub(crate) fn new(channels: usize) -> Self {
+        ChannelPointers(Vec::with_capacity(channels))
+    }
+
+    // Replaces the pointers with `pointers`, returning them.
+    pub(crate) fn set(&mut self, pointers: impl IntoIterator<Item = P>) -> &[P] {
+        self.0.clear();
+        self.0.extend(pointers);
+        &self.0
+    }
+}
+
+/// `DeviceTrait::build_input_stream_planar` for hosts with interleaved buffers.
+pub(crate) fn build_input_stream<Dev, T, D, E>(
+    device: &Dev,
+    config: &StreamConfig,
+    data_callback: D,
+    error_callback: E,
+) -> Result<Dev::Stream, BuildStreamError>
+where
+    Dev: DeviceTrait + ?Sized,
+    T: Sample + Send + 'static,
+    D: FnMut(&PlanarBuffer<T>, &InputCallbackInfo) + Send + 'static,
+    E: FnMut(StreamError) + Send + 'static,
+{
+    let data_callback = deinterleaving(config.channels as usize, data_callback);
+    device.build_input_stream(config, data_callback, error_callback)
+}
+
+/// `DeviceTrait::build_output_stream_planar` for hosts with interleaved buffers.
+pub(crate) fn build_output_stream<Dev, T, D, E>(
+    device: &Dev,
+    config: &StreamConfig,
+    data_callback: D,
+    error_callback: E,
+) -> Result<Dev::Stream, BuildStreamError>
+where
+    Dev: DeviceTrait + ?Sized,
+    T: Sample + Send + 'static,
+    D: FnMut(&mut PlanarBufferMut<T>, &OutputCallbackInfo) + Send + 'static,
+    E: FnMut(StreamError) + Send + 'static,
+{
+    let data_callback = interleaving(config.channels as usize, data_callback);
+    device.build_output_stream(config, data_callback, error_callback)
+}
+
+// Wraps a planar input callback into one taking interleaved frames of `channels` channels.
+fn deinterleaving<T, D>(
+    channels: usize,
+    mut data_callback: D,
+) -> impl FnMut(&[T], &InputCallbackInfo) + Send + 'static
+where
+    T: Sample + Send + 'static,
+    D: FnMut(&PlanarBuffer<T>, &InputCallbackInfo) + Send + 'static,
+{
+    let mut staging = vec![];
+    let mut pointers = ChannelPointers::new(channels);
+    move |data: &[T], info: &InputCallbackInfo| {
+        let frames = data.len() / channels.max(1);
+        staging.resize(frames * channels, T::from(&0.0f32));
+        for (frame, samples) in data.chunks_exact(channels).enumerate() {
+            for (channel, &sample) in samples.iter().enumerate() {
+                staging[channel * frames + frame] = sample;
+            }
+        }
+        let pointers = pointers.set((0..channels).map(|c| staging[c * frames..].as_ptr()));
+        let buffer = unsafe { PlanarBuffer::from_raw_parts(pointers, frames) };
+        data_callback(&buffer, info);
+    }
+}
+
+// Wraps a planar output callback into one writing interleaved frames of `channels` channels.
+fn interleaving<T, D>(
+    channels: usize,
+    mut data_callback: D,
+) -> impl FnMut(&mut [T], &OutputCallbackInfo) + Send + 'static
+where
+    T: Sample + Send + 'static,
+    D: FnMut(&mut PlanarBufferMut<T>, &OutputCallbackInfo) + Send + 'static,
+{
+    let mut staging = vec![];
+    let mut pointers = ChannelPointers::new(channels);
+    move |data: &mut [T], info: &OutputCallbackInfo| {
+        let frames = data.len() / channels.max(1);
+        staging.clear();
+        staging.resize(frames * channels, T::from(&0.0f32));
+        let start = staging.as_mut_ptr();
+        let pointers = pointers.set((0..channels).map(|c| start.wrapping_add(c * frames)));
+        let mut buffer = unsafe { PlanarBufferMut::from_raw_parts(pointers, frames) };
+        data_callback(&mut buffer, info);
+        for (frame, samples) in data.chunks_exact_mut(channels).enumerate() {
+            for (channel, sample) in samples.iter_mut().enumerate() {
+                *sample = staging[channel * frames + frame];
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{deinterleaving, interleaving, PlanarBuffer, PlanarBufferMut};
+    use crate::{
+        InputCallbackInfo, InputStreamTimestamp, OutputCallbackInfo, OutputStreamTimestamp,
+        StreamInstant,
+    };
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn copies_between_planar_and_interleaved() {
+        let instant = StreamInstant::new(0, 0);
+        let captured = Arc::new(Mutex::new(vec![]));
+        let mut input = {
+            let captured = captured.clone();
+            deinterleaving(
+                2,
+                move |buffer: &PlanarBuffer<i16>, _: &InputCallbackInfo| {
+                    let channels = buffer.iter().map(|channel| channel.to_vec()).collect();
+                    *captured.lock().unwrap() = channels;
+                },
+            )
+        };
+        let timestamp = InputStreamTimestamp {
+            callback: instant,
+            capture: instant,
+        };
+        input(
+            &[1, -1, 2, -2, 3, -3],
+            &InputCallbackInfo::new(timestamp, 0),
+        );
+        assert_eq!(*captured.lock().unwrap(), [vec![1, 2, 3], vec![-1, -2, -3]]);
+
+        let mut output = interleaving(
+            2,
+            |buffer: &mut PlanarBufferMut<i16>, _: &OutputCallbackInfo| {
+                assert_eq!(buffer.frames(), 3);
+                buffer.channel_mut(0).copy_from_slice(&[1, 2, 3]);
+                for sample in buffer.iter_mut().nth(1).unwrap() {
+                    *sample = -7;
+                }
+            },
+        );
+        let timestamp = OutputStreamTimestamp {
+            callback: instant,
+            playback: instant,
+        };
+        let mut data = [0; 6];
+        output(&mut data, &OutputCallbackInfo::new(timestamp, 0));
+        assert_eq!(data, [1, -7, 2, -7, 3, -7]);
+    }
+}
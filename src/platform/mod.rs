@@ -66,7 +66,11 @@ macro_rules! impl_platform_host {
         // functions within the callback.
         //
         // TODO: Confirm this and add more specific detail and references.
-        pub struct Stream(StreamInner, crate::platform::NotSendSyncAcrossAllPlatforms);
+        pub struct Stream(
+            StreamInner,
+            crate::platform::NotSendSyncAcrossAllPlatforms,
+            Option<crate::rebuild::Lent>,
+        );
 
         /// The **SupportedInputConfigs** iterator associated with the platform's dynamically
         /// dispatched **Host** type.
@@ -140,6 +144,34 @@ macro_rules! impl_platform_host {
                     )*
                 }
             }
+
+            /// Whether this is the host returned by `default_host`.
+            pub fn is_default(&self) -> bool {
+                *self == DEFAULT_HOST
+            }
+        }
+
+        impl std::fmt::Display for HostId {
+            fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str(self.name())
+            }
+        }
+
+        impl std::str::FromStr for HostId {
+            type Err = crate::HostIdParseError;
+
+            /// Parses the name of a host, as returned by `HostId::name`, ignoring case.
+            fn from_str(name: &str) -> Result<Self, Self::Err> {
+                $(
+                    $(#[cfg($feat)])?
+                    if name.eq_ignore_ascii_case($host_name) {
+                        return Ok(HostId::$HostVariant);
+                    }
+                )*
+                Err(crate::HostIdParseError {
+                    name: name.to_owned(),
+                })
+            }
         }
 
         impl Devices {
@@ -191,6 +223,45 @@ macro_rules! impl_platform_host {
                 }
             }
 
+            /// The name of this host, as returned by `HostId::name`.
+            pub fn name(&self) -> &'static str {
+                self.id().name()
+            }
+
+            /// Whether this is the host returned by `default_host`.
+            pub fn is_default(&self) -> bool {
+                self.id().is_default()
+            }
+
+            /// Initialises the first host of `ids` that is available and has devices.
+            ///
+            /// Hosts that are not available on the system, fail to initialise, or have no devices
+            /// are skipped, such as JACK without a running server. If none is left, the error tells
+            /// why each one was skipped.
+            pub fn preferred(ids: &[HostId]) -> Result<Host, crate::NoPreferredHost> {
+                let available = available_hosts();
+                let mut skipped = Vec::new();
+                for &id in ids {
+                    if !available.contains(&id) {
+                        skipped.push((id, crate::HostSkipReason::NotAvailable));
+                        continue;
+                    }
+                    let host = match host_from_id(id) {
+                        Ok(host) => host,
+                        Err(err) => {
+                            skipped.push((id, err.into()));
+                            continue;
+                        }
+                    };
+                    match crate::traits::HostTrait::devices(&host).map(|mut d| d.next().is_some()) {
+                        Ok(true) => return Ok(host),
+                        Ok(false) => skipped.push((id, crate::HostSkipReason::NoDevices)),
+                        Err(err) => skipped.push((id, err.into())),
+                    }
+                }
+                Err(crate::NoPreferredHost { skipped })
+            }
+
             /// Returns a reference to the underlying platform specific implementation of this
             /// `Host`.
             pub fn as_inner(&self) -> &HostInner {
@@ -226,6 +297,162 @@ macro_rules! impl_platform_host {
             pub fn into_inner(self) -> StreamInner {
                 self.0
             }
+
+            /// Builds the stream again on `device` with `config`, keeping its callbacks, sample
+            /// format and share mode, as after `StreamError::DeviceFormatChanged`.
+            ///
+            /// `device` should be the device the stream was built on. This stream is dropped
+            /// before the new one is built, so the callbacks are never called by both. The new
+            /// stream is played or paused as this one was, as hosts differ in whether streams run
+            /// once built. On failure, the callbacks are dropped along with the stream.
+            ///
+            /// Streams built with the planar builders, or with a host's device type directly,
+            /// cannot be rebuilt and fail with a `BackendSpecific` error. So do streams whose host
+            /// still holds on to their callbacks after dropping them.
+            pub fn rebuild_with(
+                self,
+                device: &Device,
+                config: &crate::StreamConfig,
+            ) -> Result<Stream, crate::BuildStreamError> {
+                use crate::traits::StreamTrait;
+
+                let paused = self.state() == crate::StreamState::Paused;
+                // Dropping the host's stream hands the callbacks back.
+                let lent = {
+                    let Stream(_inner, _, lent) = self;
+                    lent
+                };
+                let callbacks = lent
+                    .and_then(crate::rebuild::Lent::take_back)
+                    .ok_or_else(|| crate::BackendSpecificError {
+                        description: "the stream cannot be rebuilt".to_string(),
+                    })?;
+                let stream = if callbacks.is_input() {
+                    device.build_input_stream_rebuildable(config, callbacks)?
+                } else {
+                    device.build_output_stream_rebuildable(config, callbacks)?
+                };
+                if paused {
+                    stream.pause().map_err(|err| match err {
+                        crate::PauseStreamError::DeviceNotAvailable => {
+                            crate::BuildStreamError::DeviceNotAvailable
+                        }
+                        crate::PauseStreamError::BackendSpecific { err } => err.into(),
+                    })?;
+                } else {
+                    stream.play().map_err(|err| match err {
+                        crate::PlayStreamError::DeviceNotAvailable => {
+                            crate::BuildStreamError::DeviceNotAvailable
+                        }
+                        crate::PlayStreamError::BackendSpecific { err } => err.into(),
+                    })?;
+                }
+                Ok(stream)
+            }
+        }
+
+        impl Device {
+            // Builds an input stream lending `callbacks` to the host, to be taken back by
+            // `Stream::rebuild_with`.
+            fn build_input_stream_rebuildable(
+                &self,
+                config: &crate::StreamConfig,
+                callbacks: crate::rebuild::Callbacks,
+            ) -> Result<Stream, crate::BuildStreamError> {
+                use crate::traits::DeviceTrait;
+
+                let sample_format = callbacks.sample_format();
+                let (share_mode, usage) = (callbacks.share_mode(), callbacks.usage());
+                let (data_callback, error_callback, lent) = callbacks.lend_input();
+                let (data_callback, error_callback) =
+                    crate::unwind::input(data_callback, error_callback);
+                let inner = match (&self.0, share_mode, usage) {
+                    $(
+                        $(#[cfg($feat)])?
+                        (DeviceInner::$HostVariant(ref d), None, None) => d
+                            .build_input_stream_raw(
+                                config,
+                                sample_format,
+                                data_callback,
+                                error_callback,
+                            )
+                            .map(StreamInner::$HostVariant)?,
+                        $(#[cfg($feat)])?
+                        (DeviceInner::$HostVariant(ref d), Some(share_mode), None) => d
+                            .build_input_stream_raw_with_share_mode(
+                                config,
+                                sample_format,
+                                share_mode,
+                                data_callback,
+                                error_callback,
+                            )
+                            .map(StreamInner::$HostVariant)?,
+                        $(#[cfg($feat)])?
+                        (DeviceInner::$HostVariant(ref d), share_mode, Some(usage)) => d
+                            .build_input_stream_raw_with_usage(
+                                config,
+                                sample_format,
+                                share_mode.unwrap_or_default(),
+                                usage,
+                                data_callback,
+                                error_callback,
+                            )
+                            .map(StreamInner::$HostVariant)?,
+                    )*
+                };
+                Ok(Stream(inner, Default::default(), Some(lent)))
+            }
+
+            // Builds an output stream lending `callbacks` to the host, to be taken back by
+            // `Stream::rebuild_with`.
+            fn build_output_stream_rebuildable(
+                &self,
+                config: &crate::StreamConfig,
+                callbacks: crate::rebuild::Callbacks,
+            ) -> Result<Stream, crate::BuildStreamError> {
+                use crate::traits::DeviceTrait;
+
+                let sample_format = callbacks.sample_format();
+                let (share_mode, usage) = (callbacks.share_mode(), callbacks.usage());
+                let (data_callback, error_callback, lent) = callbacks.lend_output();
+                let (data_callback, error_callback) =
+                    crate::unwind::output(config.channels, data_callback, error_callback);
+                let inner = match (&self.0, share_mode, usage) {
+                    $(
+                        $(#[cfg($feat)])?
+                        (DeviceInner::$HostVariant(ref d), None, None) => d
+                            .build_output_stream_raw(
+                                config,
+                                sample_format,
+                                data_callback,
+                                error_callback,
+                            )
+                            .map(StreamInner::$HostVariant)?,
+                        $(#[cfg($feat)])?
+                        (DeviceInner::$HostVariant(ref d), Some(share_mode), None) => d
+                            .build_output_stream_raw_with_share_mode(
+                                config,
+                                sample_format,
+                                share_mode,
+                                data_callback,
+                                error_callback,
+                            )
+                            .map(StreamInner::$HostVariant)?,
+                        $(#[cfg($feat)])?
+                        (DeviceInner::$HostVariant(ref d), share_mode, Some(usage)) => d
+                            .build_output_stream_raw_with_usage(
+                                config,
+                                sample_format,
+                                share_mode.unwrap_or_default(),
+                                usage,
+                                data_callback,
+                                error_callback,
+                            )
+                            .map(StreamInner::$HostVariant)?,
+                    )*
+                };
+                Ok(Stream(inner, Default::default(), Some(lent)))
+            }
         }
 
         impl Iterator for Devices {
@@ -310,6 +537,45 @@ macro_rules! impl_platform_host {
                 }
             }
 
+            fn info(&self) -> Result<crate::DeviceInfo, crate::DeviceNameError> {
+                match self.0 {
+                    $(
+                        $(#[cfg($feat)])?
+                        DeviceInner::$HostVariant(ref d) => d.info().map(|info| crate::DeviceInfo {
+                            host: Some(HostId::$HostVariant),
+                            ..info
+                        }),
+                    )*
+                }
+            }
+
+            fn supports_input_config(&self, config: &crate::StreamConfig, sample_format: crate::SampleFormat) -> bool {
+                match self.0 {
+                    $(
+                        $(#[cfg($feat)])?
+                        DeviceInner::$HostVariant(ref d) => d.supports_input_config(config, sample_format),
+                    )*
+                }
+            }
+
+            fn supports_output_config(&self, config: &crate::StreamConfig, sample_format: crate::SampleFormat) -> bool {
+                match self.0 {
+                    $(
+                        $(#[cfg($feat)])?
+                        DeviceInner::$HostVariant(ref d) => d.supports_output_config(config, sample_format),
+                    )*
+                }
+            }
+
+            fn capabilities(&self) -> Result<crate::DeviceCapabilities, crate::SupportedStreamConfigsError> {
+                match self.0 {
+                    $(
+                        $(#[cfg($feat)])?
+                        DeviceInner::$HostVariant(ref d) => d.capabilities(),
+                    )*
+                }
+            }
+
             fn supported_input_configs(&self) -> Result<Self::SupportedInputConfigs, crate::SupportedStreamConfigsError> {
                 match self.0 {
                     $(
@@ -354,6 +620,33 @@ macro_rules! impl_platform_host {
                 }
             }
 
+            fn set_direct_monitoring(&self, enabled: bool) -> Result<(), crate::DirectMonitoringError> {
+                match self.0 {
+                    $(
+                        $(#[cfg($feat)])?
+                        DeviceInner::$HostVariant(ref d) => d.set_direct_monitoring(enabled),
+                    )*
+                }
+            }
+
+            fn set_default_sample_rate(&self, sample_rate: crate::SampleRate) -> Result<(), crate::SetSampleRateError> {
+                match self.0 {
+                    $(
+                        $(#[cfg($feat)])?
+                        DeviceInner::$HostVariant(ref d) => d.set_default_sample_rate(sample_rate),
+                    )*
+                }
+            }
+
+            fn channel_names(&self, direction: crate::DeviceDirection) -> Result<Vec<String>, crate::DeviceNameError> {
+                match self.0 {
+                    $(
+                        $(#[cfg($feat)])?
+                        DeviceInner::$HostVariant(ref d) => d.channel_names(direction),
+                    )*
+                }
+            }
+
             fn build_input_stream_raw<D, E>(
                 &self,
                 config: &crate::StreamConfig,
@@ -365,43 +658,167 @@ macro_rules! impl_platform_host {
                 D: FnMut(&crate::Data, &crate::InputCallbackInfo) + Send + 'static,
                 E: FnMut(crate::StreamError) + Send + 'static,
             {
+                let callbacks = crate::rebuild::Callbacks::input(
+                    sample_format,
+                    None,
+                    data_callback,
+                    error_callback,
+                );
+                self.build_input_stream_rebuildable(config, callbacks)
+            }
+
+            fn build_input_stream_raw_with_share_mode<D, E>(
+                &self,
+                config: &crate::StreamConfig,
+                sample_format: crate::SampleFormat,
+                share_mode: crate::ShareMode,
+                data_callback: D,
+                error_callback: E,
+            ) -> Result<Self::Stream, crate::BuildStreamError>
+            where
+                D: FnMut(&crate::Data, &crate::InputCallbackInfo) + Send + 'static,
+                E: FnMut(crate::StreamError) + Send + 'static,
+            {
+                let callbacks = crate::rebuild::Callbacks::input(
+                    sample_format,
+                    Some(share_mode),
+                    data_callback,
+                    error_callback,
+                );
+                self.build_input_stream_rebuildable(config, callbacks)
+            }
+
+            fn build_input_stream_raw_with_usage<D, E>(
+                &self,
+                config: &crate::StreamConfig,
+                sample_format: crate::SampleFormat,
+                share_mode: crate::ShareMode,
+                usage: crate::StreamUsage,
+                data_callback: D,
+                error_callback: E,
+            ) -> Result<Self::Stream, crate::BuildStreamError>
+            where
+                D: FnMut(&crate::Data, &crate::InputCallbackInfo) + Send + 'static,
+                E: FnMut(crate::StreamError) + Send + 'static,
+            {
+                let callbacks = crate::rebuild::Callbacks::input(
+                    sample_format,
+                    Some(share_mode),
+                    data_callback,
+                    error_callback,
+                )
+                .with_usage(usage);
+                self.build_input_stream_rebuildable(config, callbacks)
+            }
+
+            fn build_output_stream_raw<D, E>(
+                &self,
+                config: &crate::StreamConfig,
+                sample_format: crate::SampleFormat,
+                data_callback: D,
+                error_callback: E,
+            ) -> Result<Self::Stream, crate::BuildStreamError>
+            where
+                D: FnMut(&mut crate::Data, &crate::OutputCallbackInfo) + Send + 'static,
+                E: FnMut(crate::StreamError) + Send + 'static,
+            {
+                let callbacks = crate::rebuild::Callbacks::output(
+                    sample_format,
+                    None,
+                    data_callback,
+                    error_callback,
+                );
+                self.build_output_stream_rebuildable(config, callbacks)
+            }
+
+            fn build_output_stream_raw_with_share_mode<D, E>(
+                &self,
+                config: &crate::StreamConfig,
+                sample_format: crate::SampleFormat,
+                share_mode: crate::ShareMode,
+                data_callback: D,
+                error_callback: E,
+            ) -> Result<Self::Stream, crate::BuildStreamError>
+            where
+                D: FnMut(&mut crate::Data, &crate::OutputCallbackInfo) + Send + 'static,
+                E: FnMut(crate::StreamError) + Send + 'static,
+            {
+                let callbacks = crate::rebuild::Callbacks::output(
+                    sample_format,
+                    Some(share_mode),
+                    data_callback,
+                    error_callback,
+                );
+                self.build_output_stream_rebuildable(config, callbacks)
+            }
+
+            fn build_output_stream_raw_with_usage<D, E>(
+                &self,
+                config: &crate::StreamConfig,
+                sample_format: crate::SampleFormat,
+                share_mode: crate::ShareMode,
+                usage: crate::StreamUsage,
+                data_callback: D,
+                error_callback: E,
+            ) -> Result<Self::Stream, crate::BuildStreamError>
+            where
+                D: FnMut(&mut crate::Data, &crate::OutputCallbackInfo) + Send + 'static,
+                E: FnMut(crate::StreamError) + Send + 'static,
+            {
+                let callbacks = crate::rebuild::Callbacks::output(
+                    sample_format,
+                    Some(share_mode),
+                    data_callback,
+                    error_callback,
+                )
+                .with_usage(usage);
+                self.build_output_stream_rebuildable(config, callbacks)
+            }
+
+            fn build_input_stream_planar<T, D, E>(
+                &self,
+                config: &crate::StreamConfig,
+                data_callback: D,
+                error_callback: E,
+            ) -> Result<Self::Stream, crate::BuildStreamError>
+            where
+                T: crate::Sample + Send + 'static,
+                D: FnMut(&crate::PlanarBuffer<T>, &crate::InputCallbackInfo) + Send + 'static,
+                E: FnMut(crate::StreamError) + Send + 'static,
+            {
+                let (data_callback, error_callback) =
+                    crate::unwind::planar_input(data_callback, error_callback);
                 match self.0 {
                     $(
                         $(#[cfg($feat)])?
                         DeviceInner::$HostVariant(ref d) => d
-                            .build_input_stream_raw(
-                                config,
-                                sample_format,
-                                data_callback,
-                                error_callback,
-                            )
+                            .build_input_stream_planar(config, data_callback, error_callback)
                             .map(StreamInner::$HostVariant)
                             .map(Stream::from),
                     )*
                 }
             }
 
-            fn build_output_stream_raw<D, E>(
+            fn build_output_stream_planar<T, D, E>(
                 &self,
                 config: &crate::StreamConfig,
-                sample_format: crate::SampleFormat,
                 data_callback: D,
                 error_callback: E,
             ) -> Result<Self::Stream, crate::BuildStreamError>
             where
-                D: FnMut(&mut crate::Data, &crate::OutputCallbackInfo) + Send + 'static,
+                T: crate::Sample + Send + 'static,
+                D: FnMut(&mut crate::PlanarBufferMut<T>, &crate::OutputCallbackInfo)
+                    + Send
+                    + 'static,
                 E: FnMut(crate::StreamError) + Send + 'static,
             {
+                let (data_callback, error_callback) =
+                    crate::unwind::planar_output(data_callback, error_callback);
                 match self.0 {
                     $(
                         $(#[cfg($feat)])?
                         DeviceInner::$HostVariant(ref d) => d
-                            .build_output_stream_raw(
-                                config,
-                                sample_format,
-                                data_callback,
-                                error_callback,
-                            )
+                            .build_output_stream_planar(config, data_callback, error_callback)
                             .map(StreamInner::$HostVariant)
                             .map(Stream::from),
                     )*
@@ -432,6 +849,28 @@ macro_rules! impl_platform_host {
                 }
             }
 
+            fn default_input_device_for(&self, role: crate::DeviceRole) -> Option<Self::Device> {
+                match self.0 {
+                    $(
+                        $(#[cfg($feat)])?
+                        HostInner::$HostVariant(ref h) => {
+                            h.default_input_device_for(role).map(DeviceInner::$HostVariant).map(Device::from)
+                        }
+                    )*
+                }
+            }
+
+            fn default_output_device_for(&self, role: crate::DeviceRole) -> Option<Self::Device> {
+                match self.0 {
+                    $(
+                        $(#[cfg($feat)])?
+                        HostInner::$HostVariant(ref h) => {
+                            h.default_output_device_for(role).map(DeviceInner::$HostVariant).map(Device::from)
+                        }
+                    )*
+                }
+            }
+
             fn default_input_device(&self) -> Option<Self::Device> {
                 match self.0 {
                     $(
@@ -453,6 +892,53 @@ macro_rules! impl_platform_host {
                     )*
                 }
             }
+
+            fn devices_filtered(&self, filter: crate::DeviceFilter) -> Result<Vec<Self::Device>, crate::DevicesError> {
+                match self.0 {
+                    $(
+                        $(#[cfg($feat)])?
+                        HostInner::$HostVariant(ref h) => {
+                            h.devices_filtered(filter).map(|devices| {
+                                devices
+                                    .into_iter()
+                                    .map(DeviceInner::$HostVariant)
+                                    .map(Device::from)
+                                    .collect()
+                            })
+                        }
+                    )*
+                }
+            }
+
+            fn backend_version(&self) -> Option<String> {
+                match self.0 {
+                    $(
+                        $(#[cfg($feat)])?
+                        HostInner::$HostVariant(ref h) => h.backend_version(),
+                    )*
+                }
+            }
+
+            fn input_permission(&self) -> crate::InputPermission {
+                match self.0 {
+                    $(
+                        $(#[cfg($feat)])?
+                        HostInner::$HostVariant(ref h) => h.input_permission(),
+                    )*
+                }
+            }
+
+            fn request_input_permission<F>(&self, callback: F)
+            where
+                F: FnOnce(crate::InputPermission) + Send + 'static,
+            {
+                match self.0 {
+                    $(
+                        $(#[cfg($feat)])?
+                        HostInner::$HostVariant(ref h) => h.request_input_permission(callback),
+                    )*
+                }
+            }
         }
 
         impl crate::traits::StreamTrait for Stream {
@@ -477,6 +963,122 @@ macro_rules! impl_platform_host {
                     )*
                 }
             }
+
+            fn state(&self) -> crate::StreamState {
+                match self.0 {
+                    $(
+                        $(#[cfg($feat)])?
+                        StreamInner::$HostVariant(ref s) => {
+                            s.state()
+                        }
+                    )*
+                }
+            }
+
+            fn latency(&self) -> Option<crate::StreamLatency> {
+                match self.0 {
+                    $(
+                        $(#[cfg($feat)])?
+                        StreamInner::$HostVariant(ref s) => {
+                            s.latency()
+                        }
+                    )*
+                }
+            }
+
+            fn periods(&self) -> Option<u32> {
+                match self.0 {
+                    $(
+                        $(#[cfg($feat)])?
+                        StreamInner::$HostVariant(ref s) => {
+                            s.periods()
+                        }
+                    )*
+                }
+            }
+
+            fn set_periods(&self, periods: u32) -> Result<u32, crate::ReconfigureStreamError> {
+                match self.0 {
+                    $(
+                        $(#[cfg($feat)])?
+                        StreamInner::$HostVariant(ref s) => {
+                            s.set_periods(periods)
+                        }
+                    )*
+                }
+            }
+
+            fn buffer_size(&self) -> Option<crate::FrameCount> {
+                match self.0 {
+                    $(
+                        $(#[cfg($feat)])?
+                        StreamInner::$HostVariant(ref s) => {
+                            s.buffer_size()
+                        }
+                    )*
+                }
+            }
+
+            fn drain(&self) -> Result<(), crate::PauseStreamError> {
+                match self.0 {
+                    $(
+                        $(#[cfg($feat)])?
+                        StreamInner::$HostVariant(ref s) => {
+                            s.drain()
+                        }
+                    )*
+                }
+            }
+
+            fn set_buffer_size(
+                &self,
+                frames: crate::FrameCount,
+            ) -> Result<crate::FrameCount, crate::ReconfigureStreamError> {
+                match self.0 {
+                    $(
+                        $(#[cfg($feat)])?
+                        StreamInner::$HostVariant(ref s) => {
+                            s.set_buffer_size(frames)
+                        }
+                    )*
+                }
+            }
+
+            fn set_sample_rate(
+                &self,
+                sample_rate: crate::SampleRate,
+            ) -> Result<(), crate::ReconfigureStreamError> {
+                match self.0 {
+                    $(
+                        $(#[cfg($feat)])?
+                        StreamInner::$HostVariant(ref s) => {
+                            s.set_sample_rate(sample_rate)
+                        }
+                    )*
+                }
+            }
+
+            fn set_mixer_volume(&self, volume: f32) -> Result<(), crate::ReconfigureStreamError> {
+                match self.0 {
+                    $(
+                        $(#[cfg($feat)])?
+                        StreamInner::$HostVariant(ref s) => {
+                            s.set_mixer_volume(volume)
+                        }
+                    )*
+                }
+            }
+
+            fn mixer_volume(&self) -> Result<f32, crate::ReconfigureStreamError> {
+                match self.0 {
+                    $(
+                        $(#[cfg($feat)])?
+                        StreamInner::$HostVariant(ref s) => {
+                            s.mixer_volume()
+                        }
+                    )*
+                }
+            }
         }
 
         impl From<DeviceInner> for Device {
@@ -499,7 +1101,7 @@ macro_rules! impl_platform_host {
 
         impl From<StreamInner> for Stream {
             fn from(s: StreamInner) -> Self {
-                Stream(s, Default::default())
+                Stream(s, Default::default(), None)
             }
         }
 
@@ -545,6 +1147,35 @@ macro_rules! impl_platform_host {
             host_ids
         }
 
+        /// The devices of every available host, paired with the host they belong to, in the order
+        /// of `available_hosts`.
+        ///
+        /// Hosts that fail to initialise or to enumerate their devices are left out. So are devices
+        /// named like a device of an earlier host, which usually is the same device reached
+        /// through another host.
+        pub fn all_devices() -> Vec<(HostId, Device)> {
+            use crate::traits::{DeviceTrait, HostTrait};
+            let mut all = Vec::new();
+            let mut earlier_names = std::collections::HashSet::new();
+            for id in available_hosts() {
+                let devices = match host_from_id(id).map(|host| host.devices()) {
+                    Ok(Ok(devices)) => devices,
+                    _ => continue,
+                };
+                let mut names = Vec::new();
+                for device in devices {
+                    let name = device.name().ok();
+                    if name.as_ref().is_some_and(|name| earlier_names.contains(name)) {
+                        continue;
+                    }
+                    names.extend(name);
+                    all.push((id, device));
+                }
+                earlier_names.extend(names);
+            }
+            all
+        }
+
         /// Given a unique host identifier, initialise and produce the host if it is available.
         pub fn host_from_id(id: HostId) -> Result<Host, crate::HostUnavailable> {
             match id {
@@ -562,10 +1193,16 @@ macro_rules! impl_platform_host {
 }
 
 // TODO: Add pulseaudio and jack here eventually.
-#[cfg(any(target_os = "linux", target_os = "dragonfly", target_os = "freebsd"))]
+#[cfg(all(
+    feature = "backends",
+    any(target_os = "linux", target_os = "dragonfly", target_os = "freebsd")
+))]
 mod platform_impl {
+    #[cfg(feature = "platform-formats")]
+    pub use crate::host::alsa::{alsa_format_from_sample_format, sample_format_from_alsa_format};
     pub use crate::host::alsa::{
-        Device as AlsaDevice, Devices as AlsaDevices, Host as AlsaHost, Stream as AlsaStream,
+        Device as AlsaDevice, Devices as AlsaDevices, Host as AlsaHost,
+        PollDescriptor as AlsaPollDescriptor, Stream as AlsaStream,
         SupportedInputConfigs as AlsaSupportedInputConfigs,
         SupportedOutputConfigs as AlsaSupportedOutputConfigs,
     };
@@ -578,6 +1215,9 @@ mod platform_impl {
 
     impl_platform_host!(#[cfg(feature = "jack")] Jack jack "JACK", Alsa alsa "ALSA");
 
+    // The host returned by `default_host`.
+    const DEFAULT_HOST: HostId = HostId::Alsa;
+
     /// The default host for the current compilation target platform.
     pub fn default_host() -> Host {
         AlsaHost::new()
@@ -586,8 +1226,10 @@ mod platform_impl {
     }
 }
 
-#[cfg(any(target_os = "macos", target_os = "ios"))]
+#[cfg(all(feature = "backends", any(target_os = "macos", target_os = "ios")))]
 mod platform_impl {
+    #[cfg(feature = "platform-formats")]
+    pub use crate::host::coreaudio::{asbd_from_config, sample_format_from_asbd};
     pub use crate::host::coreaudio::{
         Device as CoreAudioDevice, Devices as CoreAudioDevices, Host as CoreAudioHost,
         Stream as CoreAudioStream, SupportedInputConfigs as CoreAudioSupportedInputConfigs,
@@ -596,6 +1238,9 @@ mod platform_impl {
 
     impl_platform_host!(CoreAudio coreaudio "CoreAudio");
 
+    // The host returned by `default_host`.
+    const DEFAULT_HOST: HostId = HostId::CoreAudio;
+
     /// The default host for the current compilation target platform.
     pub fn default_host() -> Host {
         CoreAudioHost::new()
@@ -604,7 +1249,7 @@ mod platform_impl {
     }
 }
 
-#[cfg(target_os = "emscripten")]
+#[cfg(all(feature = "backends", target_os = "emscripten"))]
 mod platform_impl {
     pub use crate::host::emscripten::{
         Device as EmscriptenDevice, Devices as EmscriptenDevices, Host as EmscriptenHost,
@@ -614,6 +1259,9 @@ mod platform_impl {
 
     impl_platform_host!(Emscripten emscripten "Emscripten");
 
+    // The host returned by `default_host`.
+    const DEFAULT_HOST: HostId = HostId::Emscripten;
+
     /// The default host for the current compilation target platform.
     pub fn default_host() -> Host {
         EmscriptenHost::new()
@@ -622,7 +1270,7 @@ mod platform_impl {
     }
 }
 
-#[cfg(all(target_arch = "wasm32", feature = "wasm-bindgen"))]
+#[cfg(all(feature = "backends", target_arch = "wasm32", feature = "wasm-bindgen"))]
 mod platform_impl {
     pub use crate::host::webaudio::{
         Device as WebAudioDevice, Devices as WebAudioDevices, Host as WebAudioHost,
@@ -632,6 +1280,9 @@ mod platform_impl {
 
     impl_platform_host!(WebAudio webaudio "WebAudio");
 
+    // The host returned by `default_host`.
+    const DEFAULT_HOST: HostId = HostId::WebAudio;
+
     /// The default host for the current compilation target platform.
     pub fn default_host() -> Host {
         WebAudioHost::new()
@@ -640,7 +1291,7 @@ mod platform_impl {
     }
 }
 
-#[cfg(windows)]
+#[cfg(all(feature = "backends", windows))]
 mod platform_impl {
     #[cfg(feature = "asio")]
     pub use crate::host::asio::{
@@ -648,6 +1299,10 @@ mod platform_impl {
         SupportedInputConfigs as AsioSupportedInputConfigs,
         SupportedOutputConfigs as AsioSupportedOutputConfigs,
     };
+    #[cfg(feature = "platform-formats")]
+    pub use crate::host::wasapi::{
+        config_to_waveformatextensible, sample_format_from_waveformatex_ptr,
+    };
     pub use crate::host::wasapi::{
         Device as WasapiDevice, Devices as WasapiDevices, Host as WasapiHost,
         Stream as WasapiStream, SupportedInputConfigs as WasapiSupportedInputConfigs,
@@ -656,6 +1311,9 @@ mod platform_impl {
 
     impl_platform_host!(#[cfg(feature = "asio")] Asio asio "ASIO", Wasapi wasapi "WASAPI");
 
+    // The host returned by `default_host`.
+    const DEFAULT_HOST: HostId = HostId::Wasapi;
+
     /// The default host for the current compilation target platform.
     pub fn default_host() -> Host {
         WasapiHost::new()
@@ -664,7 +1322,7 @@ mod platform_impl {
     }
 }
 
-#[cfg(target_os = "android")]
+#[cfg(all(feature = "backends", target_os = "android"))]
 mod platform_impl {
     pub use crate::host::oboe::{
         Device as OboeDevice, Devices as OboeDevices, Host as OboeHost, Stream as OboeStream,
@@ -674,6 +1332,9 @@ mod platform_impl {
 
     impl_platform_host!(Oboe oboe "Oboe");
 
+    // The host returned by `default_host`.
+    const DEFAULT_HOST: HostId = HostId::Oboe;
+
     /// The default host for the current compilation target platform.
     pub fn default_host() -> Host {
         OboeHost::new()
@@ -682,17 +1343,20 @@ mod platform_impl {
     }
 }
 
-#[cfg(not(any(
-    windows,
-    target_os = "linux",
-    target_os = "dragonfly",
-    target_os = "freebsd",
-    target_os = "macos",
-    target_os = "ios",
-    target_os = "emscripten",
-    target_os = "android",
-    all(target_arch = "wasm32", feature = "wasm-bindgen"),
-)))]
+#[cfg(any(
+    not(feature = "backends"),
+    not(any(
+        windows,
+        target_os = "linux",
+        target_os = "dragonfly",
+        target_os = "freebsd",
+        target_os = "macos",
+        target_os = "ios",
+        target_os = "emscripten",
+        target_os = "android",
+        all(target_arch = "wasm32", feature = "wasm-bindgen"),
+    ))
+))]
 mod platform_impl {
     pub use crate::host::null::{
         Device as NullDevice, Devices as NullDevices, Host as NullHost,
@@ -702,6 +1366,9 @@ mod platform_impl {
 
     impl_platform_host!(Null null "Null");
 
+    // The host returned by `default_host`.
+    const DEFAULT_HOST: HostId = HostId::Null;
+
     /// The default host for the current compilation target platform.
     pub fn default_host() -> Host {
         NullHost::new()
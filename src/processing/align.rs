@@ -0,0 +1,155 @@
+//! Time-aligning the input and output streams of a duplex setup.
+//!
+//! Audio written to an output stream is played some time after the callback, and audio read from
+//! an input stream was captured some time before it. Echo cancellers, loopers recording overdubs
+//! and latency-compensated monitors all need to know which captured frame was recorded while a
+//! given rendered frame was playing. [`alignment_offset`] computes this from the callback
+//! timestamps, and [`DelayLine`] delays one signal by that offset.
+
+use crate::{InputStreamTimestamp, OutputStreamTimestamp, SampleRate, StreamInstant};
+use std::collections::VecDeque;
+
+/// The number of frames from the capture of the first frame of an input buffer to the playback of
+/// the first frame of an output buffer.
+///
+/// Delaying the rendered signal by this many frames lines it up with the captured signal, as
+/// needed for the reference of an echo canceller. A negative offset means the output buffer is
+/// played before the input buffer was captured.
+pub fn alignment_offset(
+    input: &InputStreamTimestamp,
+    output: &OutputStreamTimestamp,
+    sample_rate: SampleRate,
+) -> i64 {
+    let nanos = signed_nanos_between(&input.capture, &output.playback);
+    (nanos as f64 * sample_rate.0 as f64 / 1e9).round() as i64
+}
+
+// The signed time in nanoseconds from `earlier` to `later`.
+fn signed_nanos_between(earlier: &StreamInstant, later: &StreamInstant) -> i128 {
+    match later.duration_since(earlier) {
+        Some(duration) => duration.as_nanos() as i128,
+        None => -(earlier.duration_since(later).unwrap_or_default().as_nanos() as i128),
+    }
+}
+
+/// A ring buffer delaying interleaved frames by a fixed number of frames.
+///
+/// Push the frames of one stream and pop them in the callback of the other to apply an offset
+/// between the two. The buffer is not synchronized; share it between the callbacks of both
+/// streams behind a lock.
+#[derive(Clone, Debug)]
+pub struct DelayLine {
+    channels: usize,
+    delay: usize,
+    samples: VecDeque<f32>,
+    // Samples to discard from upcoming pushes, as they were due before they arrived.
+    skip: usize,
+}
+
+impl DelayLine {
+    /// Creates a delay line of `delay` frames for `channels` channels, initially holding silence.
+    pub fn new(channels: usize, delay: usize) -> Self {
+        let mut samples = VecDeque::with_capacity(2 * delay * channels);
+        samples.resize(delay * channels, 0.0);
+        DelayLine {
+            channels,
+            delay,
+            samples,
+            skip: 0,
+        }
+    }
+
+    /// The delay in frames.
+    pub fn delay(&self) -> usize {
+        self.delay
+    }
+
+    /// Changes the delay, dropping buffered frames or inserting silence as needed.
+    pub fn set_delay(&mut self, delay: usize) {
+        if delay > self.delay {
+            let added = (delay - self.delay) * self.channels;
+            let unskipped = added.min(self.skip);
+            self.skip -= unskipped;
+            for _ in unskipped..added {
+                self.samples.push_front(0.0);
+            }
+        } else {
+            let dropped = (self.delay - delay) * self.channels;
+            let buffered = dropped.min(self.samples.len());
+            self.samples.drain(..buffered);
+            self.skip += dropped - buffered;
+        }
+        self.delay = delay;
+    }
+
+    /// The number of frames waiting to be popped.
+    pub fn buffered_frames(&self) -> usize {
+        self.samples.len() / self.channels.max(1)
+    }
+
+    /// Appends the interleaved frames of `samples`.
+    pub fn push(&mut self, samples: &[f32]) {
+        let skipped = self.skip.min(samples.len());
+        self.skip -= skipped;
+        self.samples.extend(&samples[skipped..]);
+    }
+
+    /// Fills `dst` with the oldest buffered frames.
+    ///
+    /// If not enough frames were pushed, the rest of `dst` is silenced and the missing frames are
+    /// skipped once they arrive, keeping the delay constant. Returns the number of frames that
+    /// were missing.
+    pub fn pop(&mut self, dst: &mut [f32]) -> usize {
+        let available = self.samples.len().min(dst.len());
+        for (dst, sample) in dst.iter_mut().zip(self.samples.drain(..available)) {
+            *dst = sample;
+        }
+        dst[available..].fill(0.0);
+        let missing = dst.len() - available;
+        self.skip += missing;
+        missing / self.channels.max(1)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{alignment_offset, DelayLine};
+    use crate::{InputStreamTimestamp, OutputStreamTimestamp, SampleRate, StreamInstant};
+
+    #[test]
+    fn offset_from_timestamps() {
+        let input = InputStreamTimestamp {
+            callback: StreamInstant::new(1, 0),
+            capture: StreamInstant::new(0, 990_000_000),
+        };
+        let output = OutputStreamTimestamp {
+            callback: StreamInstant::new(1, 0),
+            playback: StreamInstant::new(1, 20_000_000),
+        };
+        assert_eq!(alignment_offset(&input, &output, SampleRate(48_000)), 1440);
+        let late_input = InputStreamTimestamp {
+            capture: StreamInstant::new(1, 30_000_000),
+            ..input
+        };
+        assert_eq!(
+            alignment_offset(&late_input, &output, SampleRate(48_000)),
+            -480
+        );
+    }
+
+    #[test]
+    fn delays_frames() {
+        let mut line = DelayLine::new(2, 2);
+        line.push(&[1.0, 1.0, 2.0, 2.0]);
+        let mut dst = [9.0; 6];
+        assert_eq!(line.pop(&mut dst), 0);
+        assert_eq!(dst, [0.0, 0.0, 0.0, 0.0, 1.0, 1.0]);
+
+        // An underrun is made up for by skipping the late frames.
+        assert_eq!(line.pop(&mut dst), 2);
+        assert_eq!(dst, [2.0, 2.0, 0.0, 0.0, 0.0, 0.0]);
+        line.push(&[3.0, 3.0, 4.0, 4.0, 5.0, 5.0]);
+        assert_eq!(line.pop(&mut dst[..2]), 0);
+        assert_eq!(dst[..2], [5.0, 5.0]);
+    }
+}
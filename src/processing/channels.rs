@@ -0,0 +1,211 @@
+//! Mapping interleaved buffers between channel counts.
+//!
+//! Surround buffers are expected in the channel order used by WAVE files and most hosts:
+//!
+//! - 5.1: front left, front right, centre, LFE, surround left, surround right.
+//! - 7.1: front left, front right, centre, LFE, back left, back right, side left, side right.
+
+use std::f32::consts::FRAC_1_SQRT_2;
+
+// The ITU-R BS.775 downmix gains: centre and surround channels are mixed in at -3 dB, the LFE is
+// dropped. Each row is scaled so that its gains sum to one, which keeps the downmix from clipping.
+const DOWNMIX_5_1_SCALE: f32 = 1.0 / (1.0 + 2.0 * FRAC_1_SQRT_2);
+const DOWNMIX_7_1_SCALE: f32 = 1.0 / (1.0 + 3.0 * FRAC_1_SQRT_2);
+
+/// The matrix downmixing 5.1 to stereo following ITU-R BS.775, normalized against clipping.
+pub const DOWNMIX_5_1_TO_STEREO: [[f32; 6]; 2] = [
+    [
+        DOWNMIX_5_1_SCALE,
+        0.0,
+        FRAC_1_SQRT_2 * DOWNMIX_5_1_SCALE,
+        0.0,
+        FRAC_1_SQRT_2 * DOWNMIX_5_1_SCALE,
+        0.0,
+    ],
+    [
+        0.0,
+        DOWNMIX_5_1_SCALE,
+        FRAC_1_SQRT_2 * DOWNMIX_5_1_SCALE,
+        0.0,
+        0.0,
+        FRAC_1_SQRT_2 * DOWNMIX_5_1_SCALE,
+    ],
+];
+
+/// The matrix downmixing 7.1 to stereo following ITU-R BS.775, normalized against clipping.
+pub const DOWNMIX_7_1_TO_STEREO: [[f32; 8]; 2] = [
+    [
+        DOWNMIX_7_1_SCALE,
+        0.0,
+        FRAC_1_SQRT_2 * DOWNMIX_7_1_SCALE,
+        0.0,
+        FRAC_1_SQRT_2 * DOWNMIX_7_1_SCALE,
+        0.0,
+        FRAC_1_SQRT_2 * DOWNMIX_7_1_SCALE,
+        0.0,
+    ],
+    [
+        0.0,
+        DOWNMIX_7_1_SCALE,
+        FRAC_1_SQRT_2 * DOWNMIX_7_1_SCALE,
+        0.0,
+        0.0,
+        FRAC_1_SQRT_2 * DOWNMIX_7_1_SCALE,
+        0.0,
+        FRAC_1_SQRT_2 * DOWNMIX_7_1_SCALE,
+    ],
+];
+
+/// The matrix upmixing stereo to 5.1.
+///
+/// The front channels are passed through, the centre receives the sum of both at -9 dB, and each
+/// surround channel receives its side at -6 dB. The LFE is left silent.
+pub const UPMIX_STEREO_TO_5_1: [[f32; 2]; 6] = [
+    [1.0, 0.0],
+    [0.0, 1.0],
+    [0.5 * FRAC_1_SQRT_2, 0.5 * FRAC_1_SQRT_2],
+    [0.0, 0.0],
+    [0.5, 0.0],
+    [0.0, 0.5],
+];
+
+/// Mixes the interleaved frames of `src` into `dst` through a matrix of gains.
+///
+/// `matrix` holds one row per destination channel, with one gain per source channel. Returns the
+/// number of frames mixed, which is the smaller of the frame counts of both buffers.
+pub fn mix<const S: usize, const D: usize>(
+    src: &[f32],
+    dst: &mut [f32],
+    matrix: &[[f32; S]; D],
+) -> usize {
+    if S == 0 || D == 0 {
+        return 0;
+    }
+    let src_frames = src.chunks_exact(S);
+    let dst_frames = dst.chunks_exact_mut(D);
+    let frames = src_frames.len().min(dst_frames.len());
+    for (dst_frame, src_frame) in dst_frames.zip(src_frames) {
+        for (dst_sample, gains) in dst_frame.iter_mut().zip(matrix) {
+            *dst_sample = gains.iter().zip(src_frame).map(|(gain, s)| gain * s).sum();
+        }
+    }
+    frames
+}
+
+/// Downmixes interleaved 5.1 frames to stereo with [`DOWNMIX_5_1_TO_STEREO`].
+pub fn downmix_5_1_to_stereo(src: &[f32], dst: &mut [f32]) -> usize {
+    mix(src, dst, &DOWNMIX_5_1_TO_STEREO)
+}
+
+/// Downmixes interleaved 7.1 frames to stereo with [`DOWNMIX_7_1_TO_STEREO`].
+pub fn downmix_7_1_to_stereo(src: &[f32], dst: &mut [f32]) -> usize {
+    mix(src, dst, &DOWNMIX_7_1_TO_STEREO)
+}
+
+/// Upmixes interleaved stereo frames to 5.1 with [`UPMIX_STEREO_TO_5_1`].
+pub fn upmix_stereo_to_5_1(src: &[f32], dst: &mut [f32]) -> usize {
+    mix(src, dst, &UPMIX_STEREO_TO_5_1)
+}
+
+/// Copies the interleaved frames of `src` into `dst`, mapping between their channel counts.
+///
+/// - Equal channel counts are copied unchanged.
+/// - A mono source is duplicated to every destination channel.
+/// - A mono destination receives the average of all source channels.
+/// - 5.1 and 7.1 are downmixed to stereo, and stereo is upmixed to 5.1, with the standard
+///   matrices of this module.
+/// - Otherwise the channels present in both buffers are copied, extra destination channels are
+///   silenced and extra source channels are dropped.
+///
+/// Returns the number of frames copied, which is the smaller of the frame counts of both buffers.
+pub fn adapt_channels(
+    src: &[f32],
+    src_channels: usize,
+    dst: &mut [f32],
+    dst_channels: usize,
+) -> usize {
+    match (src_channels, dst_channels) {
+        (0, _) | (_, 0) => return 0,
+        (6, 2) => return downmix_5_1_to_stereo(src, dst),
+        (8, 2) => return downmix_7_1_to_stereo(src, dst),
+        (2, 6) => return upmix_stereo_to_5_1(src, dst),
+        _ => (),
+    }
+    let src_frames = src.chunks_exact(src_channels);
+    let dst_frames = dst.chunks_exact_mut(dst_channels);
+    let frames = src_frames.len().min(dst_frames.len());
+
+    if src_channels == dst_channels {
+        let len = frames * src_channels;
+        dst[..len].copy_from_slice(&src[..len]);
+    } else if src_channels == 1 {
+        for (dst_frame, src_frame) in dst_frames.zip(src_frames) {
+            dst_frame.fill(src_frame[0]);
+        }
+    } else if dst_channels == 1 {
+        let scale = 1.0 / src_channels as f32;
+        for (dst_frame, src_frame) in dst_frames.zip(src_frames) {
+            dst_frame[0] = src_frame.iter().sum::<f32>() * scale;
+        }
+    } else {
+        let shared = src_channels.min(dst_channels);
+        for (dst_frame, src_frame) in dst_frames.zip(src_frames) {
+            dst_frame[..shared].copy_from_slice(&src_frame[..shared]);
+            dst_frame[shared..].fill(0.0);
+        }
+    }
+    frames
+}
+
+#[cfg(test)]
+mod test {
+    use super::{adapt_channels, downmix_5_1_to_stereo, upmix_stereo_to_5_1};
+
+    #[test]
+    fn mono_to_surround_and_back() {
+        let mono = [0.25, -0.5];
+        let mut surround = [1.0; 16];
+        assert_eq!(adapt_channels(&mono, 1, &mut surround, 8), 2);
+        assert_eq!(surround[..8], [0.25; 8]);
+        assert_eq!(surround[8..], [-0.5; 8]);
+
+        let stereo = [0.5, 0.0, 1.0, -1.0];
+        let mut mono = [1.0; 2];
+        assert_eq!(adapt_channels(&stereo, 2, &mut mono, 1), 2);
+        assert_eq!(mono, [0.25, 0.0]);
+    }
+
+    #[test]
+    fn extra_channels_are_dropped_or_silenced() {
+        let stereo = [0.5, -0.5];
+        let mut quad = [1.0; 8];
+        assert_eq!(adapt_channels(&stereo, 2, &mut quad, 4), 1);
+        assert_eq!(quad, [0.5, -0.5, 0.0, 0.0, 1.0, 1.0, 1.0, 1.0]);
+
+        let mut stereo = [1.0; 2];
+        assert_eq!(adapt_channels(&quad[..4], 4, &mut stereo, 2), 1);
+        assert_eq!(stereo, [0.5, -0.5]);
+    }
+
+    #[test]
+    fn surround_matrices() {
+        // Full scale on every channel but the LFE must not clip the downmix.
+        let surround = [1.0, -1.0, 1.0, 1.0, 1.0, -1.0];
+        let mut stereo = [0.0; 2];
+        assert_eq!(downmix_5_1_to_stereo(&surround, &mut stereo), 1);
+        assert!((stereo[0] - 1.0).abs() < 1e-6);
+        // The centre cancels against the inverted right surround.
+        assert!((stereo[1] + 1.0 / (1.0 + std::f32::consts::SQRT_2)).abs() < 1e-6);
+
+        let mut surround = [1.0; 6];
+        assert_eq!(upmix_stereo_to_5_1(&[0.5, -0.5], &mut surround), 1);
+        assert_eq!(surround, [0.5, -0.5, 0.0, 0.0, 0.25, -0.25]);
+
+        let mut stereo = [0.0; 2];
+        assert_eq!(
+            adapt_channels(&[0.0, 0.0, 0.0, 1.0, 0.0, 0.0], 6, &mut stereo, 2),
+            1
+        );
+        assert_eq!(stereo, [0.0, 0.0]);
+    }
+}
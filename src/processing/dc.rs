@@ -0,0 +1,82 @@
+//! Removal of DC offset from captured audio.
+//!
+//! Cheap capture hardware often adds a constant offset to its samples. It is inaudible, but it
+//! wastes headroom, skews level meters and shows up as a large bin at 0 Hz in spectra.
+
+use crate::SampleRate;
+use std::f32::consts::TAU;
+
+/// The cutoff used by [`DcBlocker::new`], low enough to leave audible content untouched.
+pub const DEFAULT_CUTOFF: f32 = 5.0;
+
+/// A one-pole high-pass filter removing DC offset from interleaved frames.
+#[derive(Clone, Debug)]
+pub struct DcBlocker {
+    // The pole of the filter, just below one.
+    pole: f32,
+    // The previous input and output sample of each channel.
+    previous_input: Vec<f32>,
+    previous_output: Vec<f32>,
+}
+
+impl DcBlocker {
+    /// Creates a filter for `channels` channels with the [`DEFAULT_CUTOFF`].
+    pub fn new(channels: usize, sample_rate: SampleRate) -> Self {
+        Self::with_cutoff(channels, sample_rate, DEFAULT_CUTOFF)
+    }
+
+    /// Creates a filter for `channels` channels that attenuates frequencies below `cutoff` Hz.
+    pub fn with_cutoff(channels: usize, sample_rate: SampleRate, cutoff: f32) -> Self {
+        let pole = (1.0 - TAU * cutoff / sample_rate.0 as f32).clamp(0.0, 1.0);
+        DcBlocker {
+            pole,
+            previous_input: vec![0.0; channels],
+            previous_output: vec![0.0; channels],
+        }
+    }
+
+    /// Filters the interleaved frames of `samples` in place.
+    pub fn process(&mut self, samples: &mut [f32]) {
+        let channels = self.previous_input.len();
+        if channels == 0 {
+            return;
+        }
+        for frame in samples.chunks_exact_mut(channels) {
+            let state = self
+                .previous_input
+                .iter_mut()
+                .zip(self.previous_output.iter_mut());
+            for (sample, (input, output)) in frame.iter_mut().zip(state) {
+                let filtered = *sample - *input + self.pole * *output;
+                *input = *sample;
+                *output = filtered;
+                *sample = filtered;
+            }
+        }
+    }
+
+    /// Clears the state of the filter.
+    pub fn reset(&mut self) {
+        self.previous_input.fill(0.0);
+        self.previous_output.fill(0.0);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::DcBlocker;
+    use crate::SampleRate;
+
+    #[test]
+    fn removes_offset() {
+        let mut blocker = DcBlocker::new(2, SampleRate(48000));
+        let mut samples: Vec<f32> = (0..48000)
+            .flat_map(|i| [0.25, if i % 2 == 0 { 0.5 } else { -0.5 }])
+            .collect();
+        blocker.process(&mut samples);
+        let tail = &samples[samples.len() - 2..];
+        // The offset on the left has decayed, the Nyquist tone on the right passes.
+        assert!(tail[0].abs() < 1e-3);
+        assert!((tail[1].abs() - 0.5).abs() < 1e-2);
+    }
+}
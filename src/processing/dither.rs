@@ -0,0 +1,336 @@
+//! Dithering for reducing floating-point samples to an integer format.
+//!
+//! Converting a floating-point signal to 16 bits by plain rounding correlates the quantization
+//! error with the signal, which is heard as distortion on quiet material. Adding a small amount
+//! of noise before quantizing turns that error into a constant, benign noise floor.
+
+use crate::{Sample, SampleFormat};
+
+/// A generator of triangular probability density function (TPDF) dither.
+///
+/// The noise is the difference of two independent uniform values, spanning ±1 LSB of the target
+/// format. This is the lowest level of noise that fully decorrelates the quantization error from
+/// the signal.
+#[derive(Clone, Debug)]
+pub struct TpdfDither {
+    state: u32,
+}
+
+impl TpdfDither {
+    /// Creates a dither generator with a fixed default seed.
+    pub fn new() -> Self {
+        Self::with_seed(0x9E37_79B9)
+    }
+
+    /// Creates a dither generator from the given seed.
+    ///
+    /// Streams dithered with different seeds produce uncorrelated noise.
+    pub fn with_seed(seed: u32) -> Self {
+        // The xorshift generator is stuck at zero.
+        let state = if seed == 0 { 0x9E37_79B9 } else { seed };
+        TpdfDither { state }
+    }
+
+    // Xorshift32, returning a value in [0.0, 1.0).
+    #[inline]
+    fn next_uniform(&mut self) -> f32 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 17;
+        self.state ^= self.state << 5;
+        (self.state >> 8) as f32 / (1 << 24) as f32
+    }
+
+    /// Returns the next noise value, in LSBs of the target format, in the range (-1.0, 1.0).
+    #[inline]
+    pub fn next_noise(&mut self) -> f32 {
+        self.next_uniform() - self.next_uniform()
+    }
+
+    /// Adds dither noise to every sample in place, ahead of quantizing them to `bits` bits.
+    ///
+    /// The quantizer that follows is expected to round to the nearest step.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bits` is not between 1 and 32, the range of depths of [`dither_bits`].
+    pub fn apply(&mut self, samples: &mut [f32], bits: u32) {
+        assert!(
+            (1..=32).contains(&bits),
+            "cannot dither to {} bits, expected 1 to 32",
+            bits
+        );
+        let lsb = lsb(bits);
+        for sample in samples.iter_mut() {
+            *sample += self.next_noise() * lsb;
+        }
+    }
+
+    /// Converts `src` into `dst` sample by sample, dithering if `T` is an integer format.
+    ///
+    /// Converts `min(src.len(), dst.len())` samples.
+    pub fn convert<T>(&mut self, src: &[f32], dst: &mut [T])
+    where
+        T: Sample,
+    {
+        match dither_bits(T::FORMAT) {
+            // All integer formats convert through `i16`. Quantize here with rounding, as
+            // `Sample::to_i16` truncates and would drop most of the noise.
+            Some(_) => {
+                for (dst, &src) in dst.iter_mut().zip(src) {
+                    let quantized = quantize_i16(src * I16_SCALE + self.next_noise());
+                    *dst = T::from(&quantized);
+                }
+            }
+            None => {
+                for (dst, &src) in dst.iter_mut().zip(src) {
+                    *dst = T::from(&src);
+                }
+            }
+        }
+    }
+}
+
+impl Default for TpdfDither {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// TPDF dither with second-order noise shaping.
+///
+/// The quantization error of each sample is fed back into the following samples of the same
+/// channel, which moves the noise floor out of the midrange, where hearing is most sensitive, and
+/// towards the top of the spectrum. The shaping filter is tuned for 44.1 and 48 kHz; at much higher
+/// sample rates plain [`TpdfDither`] is preferable.
+#[derive(Clone, Debug)]
+pub struct NoiseShapedDither {
+    dither: TpdfDither,
+    channels: usize,
+    // The last two quantization errors of each channel, in LSBs, most recent first.
+    errors: Vec<[f32; 2]>,
+}
+
+impl NoiseShapedDither {
+    /// Creates a noise shaper for interleaved buffers with the given number of channels.
+    pub fn new(channels: usize) -> Self {
+        Self::with_dither(channels, TpdfDither::new())
+    }
+
+    /// Creates a noise shaper drawing its noise from `dither`.
+    pub fn with_dither(channels: usize, dither: TpdfDither) -> Self {
+        let channels = channels.max(1);
+        NoiseShapedDither {
+            dither,
+            channels,
+            errors: vec![[0.0; 2]; channels],
+        }
+    }
+
+    /// Clears the error history, e.g. after a discontinuity in the stream.
+    pub fn reset(&mut self) {
+        for errors in &mut self.errors {
+            *errors = [0.0; 2];
+        }
+    }
+
+    /// Converts the interleaved `src` into `dst`, dithering and noise shaping if `T` is an integer
+    /// format.
+    ///
+    /// Both buffers are expected to start on a frame boundary. Converts
+    /// `min(src.len(), dst.len())` samples.
+    pub fn convert<T>(&mut self, src: &[f32], dst: &mut [T])
+    where
+        T: Sample,
+    {
+        if dither_bits(T::FORMAT).is_none() {
+            for (dst, &src) in dst.iter_mut().zip(src) {
+                *dst = T::from(&src);
+            }
+            return;
+        }
+        let channels = self.channels;
+        for (i, (dst, &src)) in dst.iter_mut().zip(src).enumerate() {
+            let errors = &mut self.errors[i % channels];
+            // Error feedback with a noise transfer function of (1 - z^-1)^2.
+            let shaped = src * I16_SCALE - (2.0 * errors[0] - errors[1]);
+            let quantized = quantize_i16(shaped + self.dither.next_noise());
+            // Bound the error so that clipping cannot make the feedback loop diverge.
+            let error = (quantized as f32 - shaped).clamp(-MAX_ERROR, MAX_ERROR);
+            *errors = [error, errors[0]];
+            *dst = T::from(&quantized);
+        }
+    }
+}
+
+/// The dither applied when a stream converts floating-point samples to an integer format.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum DitherMode {
+    /// Round to the nearest step without adding noise.
+    Off,
+    /// Plain TPDF dither, see [`TpdfDither`].
+    #[default]
+    Tpdf,
+    /// TPDF dither with second-order noise shaping, see [`NoiseShapedDither`].
+    NoiseShaped,
+}
+
+/// A dither of the [`DitherMode`] chosen at runtime.
+#[derive(Clone, Debug)]
+pub struct Dither {
+    kind: DitherKind,
+}
+
+#[derive(Clone, Debug)]
+enum DitherKind {
+    Off,
+    Tpdf(TpdfDither),
+    NoiseShaped(NoiseShapedDither),
+}
+
+impl Dither {
+    /// Creates a dither of the given mode for interleaved buffers with `channels` channels.
+    pub fn new(mode: DitherMode, channels: usize) -> Self {
+        let kind = match mode {
+            DitherMode::Off => DitherKind::Off,
+            DitherMode::Tpdf => DitherKind::Tpdf(TpdfDither::new()),
+            DitherMode::NoiseShaped => DitherKind::NoiseShaped(NoiseShapedDither::new(channels)),
+        };
+        Dither { kind }
+    }
+
+    /// Converts the interleaved `src` into `dst`, dithering if `T` is an integer format.
+    ///
+    /// Converts `min(src.len(), dst.len())` samples.
+    pub fn convert<T>(&mut self, src: &[f32], dst: &mut [T])
+    where
+        T: Sample,
+    {
+        match self.kind {
+            DitherKind::Off if dither_bits(T::FORMAT).is_some() => {
+                for (dst, &src) in dst.iter_mut().zip(src) {
+                    *dst = T::from(&quantize_i16(src * I16_SCALE));
+                }
+            }
+            DitherKind::Off => {
+                for (dst, &src) in dst.iter_mut().zip(src) {
+                    *dst = T::from(&src);
+                }
+            }
+            DitherKind::Tpdf(ref mut dither) => dither.convert(src, dst),
+            DitherKind::NoiseShaped(ref mut dither) => dither.convert(src, dst),
+        }
+    }
+}
+
+/// Returns the bit depth to dither to when converting floating-point samples to `format`.
+///
+/// Returns `None` for formats that should not be dithered: floating-point formats have no fixed
+/// quantization step, and companded and bitstream formats are not linear.
+pub fn dither_bits(format: SampleFormat) -> Option<u32> {
+    if format.is_int() || format.is_uint() {
+        Some(format.bit_depth())
+    } else {
+        None
+    }
+}
+
+// Integer formats are dithered at 16 bits, the depth `Sample` converts through.
+const I16_SCALE: f32 = 32768.0;
+const MAX_ERROR: f32 = 2.0;
+
+#[inline]
+fn quantize_i16(scaled: f32) -> i16 {
+    scaled.round().clamp(i16::MIN as f32, i16::MAX as f32) as i16
+}
+
+// The size of one quantization step of a `bits`-bit format on the [-1.0, 1.0] scale.
+#[inline]
+fn lsb(bits: u32) -> f32 {
+    1.0 / (1u64 << (bits - 1)) as f32
+}
+
+#[cfg(test)]
+mod test {
+    use super::{NoiseShapedDither, TpdfDither};
+
+    #[test]
+    fn noise_is_triangular() {
+        let mut dither = TpdfDither::new();
+        let noise: Vec<f32> = (0..100_000).map(|_| dither.next_noise()).collect();
+        assert!(noise.iter().all(|n| n.abs() < 1.0));
+        let mean = noise.iter().sum::<f32>() / noise.len() as f32;
+        assert!(mean.abs() < 0.01);
+        // The variance of a triangular distribution over (-1, 1) is 1/6.
+        let variance = noise.iter().map(|n| n * n).sum::<f32>() / noise.len() as f32;
+        assert!((variance - 1.0 / 6.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn apply_scales_noise_to_the_bit_depth() {
+        let mut samples = [0.0f32; 1000];
+        TpdfDither::new().apply(&mut samples, 1);
+        assert!(samples.iter().all(|s| s.abs() < 1.0));
+        let mut samples = [0.0f32; 1000];
+        TpdfDither::new().apply(&mut samples, 32);
+        assert!(samples.iter().all(|s| s.abs() < 1.0 / 2_147_483_648.0));
+    }
+
+    #[test]
+    #[should_panic]
+    fn apply_rejects_zero_bits() {
+        TpdfDither::new().apply(&mut [0.0], 0);
+    }
+
+    #[test]
+    fn convert_dithers_integer_formats() {
+        let mut dither = TpdfDither::new();
+        let src = [0.0f32; 1000];
+        let mut dst = [0i16; 1000];
+        dither.convert(&src, &mut dst);
+        assert!(dst.iter().all(|&s| (-1..=1).contains(&s)));
+        assert!(dst.iter().any(|&s| s != 0));
+
+        let mut dst = [1.0f32; 1000];
+        dither.convert(&src, &mut dst);
+        assert!(dst.iter().all(|&s| s == 0.0));
+    }
+
+    #[test]
+    fn dither_preserves_signal_below_one_lsb() {
+        // A constant 0.25 LSB offset disappears without dither but survives on average with it.
+        let level = 0.25 / 32768.0;
+        let src = vec![level; 100_000];
+        let mut dst = vec![0i16; src.len()];
+        TpdfDither::new().convert(&src, &mut dst);
+        let mean = dst.iter().map(|&s| s as f32).sum::<f32>() / dst.len() as f32;
+        assert!(mean > 0.0);
+    }
+
+    #[test]
+    fn noise_shaping_tracks_the_signal() {
+        let src: Vec<f32> = (0..48_000)
+            .map(|i| 0.25 * (i as f32 * 2.0 * std::f32::consts::PI * 440.0 / 48_000.0).sin())
+            .collect();
+        let mut dst = vec![0i16; src.len()];
+        NoiseShapedDither::new(1).convert(&src, &mut dst);
+        // The shaped noise is larger than plain TPDF per sample but must stay bounded.
+        for (&s, &d) in src.iter().zip(&dst) {
+            assert!((s * 32768.0 - d as f32).abs() < 8.0);
+        }
+        // Averaged over a few samples the high-frequency noise cancels out.
+        let error: f32 = src
+            .iter()
+            .zip(&dst)
+            .map(|(&s, &d)| s * 32768.0 - d as f32)
+            .sum();
+        assert!((error / src.len() as f32).abs() < 0.01);
+    }
+
+    #[test]
+    fn noise_shaping_is_per_channel() {
+        let src = [0.5f32, -0.5].repeat(100);
+        let mut dst = vec![0i16; src.len()];
+        NoiseShapedDither::new(2).convert(&src, &mut dst);
+        assert!(dst.chunks(2).all(|f| f[0] > 16_000 && f[1] < -16_000));
+    }
+}
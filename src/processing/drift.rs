@@ -0,0 +1,164 @@
+//! Measuring the drift between the clocks of two streams.
+//!
+//! Each device runs from a clock of its own, so a USB microphone and the onboard output play and
+//! capture at slightly different rates, even if both are opened at the same nominal sample rate.
+//! Passing audio between them gradually runs out of frames or piles them up, unless one side is
+//! resampled by the ratio between the clocks. [`ClockDrift`] estimates that ratio from the
+//! callback timestamps of both streams, to drive an adaptive resampler.
+
+use crate::{SampleRate, StreamInstant};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// Recent callbacks count more towards the estimate than older ones, with their weight halving
+/// roughly every 20 seconds, so the estimate follows clocks drifting as the devices warm up.
+const TIME_CONSTANT_SECS: f64 = 30.0;
+
+/// The callbacks of a probe must span this long before it estimates the rate of its clock.
+const MIN_SPAN_SECS: f64 = 1.0;
+
+/// Estimates the drift between the clocks of two running streams.
+///
+/// Created along with a [`DriftProbe`] for each stream, to be moved into its data callback. The
+/// estimate is available from the drift itself on any thread, once both streams have run for a
+/// second.
+///
+/// The rates are measured against the clock of the callback timestamps, so both streams have to
+/// be of the same host.
+#[derive(Clone, Debug)]
+pub struct ClockDrift {
+    first: Arc<AtomicU64>,
+    second: Arc<AtomicU64>,
+}
+
+impl ClockDrift {
+    /// Creates a drift estimator for two streams running at the given nominal sample rates, with
+    /// the probes for the first and second stream.
+    pub fn new(first: SampleRate, second: SampleRate) -> (Self, DriftProbe, DriftProbe) {
+        let drift = ClockDrift {
+            first: Arc::new(AtomicU64::new(f64::NAN.to_bits())),
+            second: Arc::new(AtomicU64::new(f64::NAN.to_bits())),
+        };
+        let first = DriftProbe::new(drift.first.clone(), first);
+        let second = DriftProbe::new(drift.second.clone(), second);
+        (drift, first, second)
+    }
+
+    /// The rate of the first clock relative to the second, both taken relative to their nominal
+    /// sample rates, or `None` until both probes have an estimate.
+    ///
+    /// A ratio above 1 means the first stream produces or consumes frames faster than nominal,
+    /// compared to the second. Resampling the frames of the first stream by `1 / ratio` matches
+    /// them to the second.
+    pub fn ratio(&self) -> Option<f64> {
+        let first = f64::from_bits(self.first.load(Ordering::Relaxed));
+        let second = f64::from_bits(self.second.load(Ordering::Relaxed));
+        let ratio = first / second;
+        (!ratio.is_nan()).then_some(ratio)
+    }
+
+    /// How much faster the first clock runs than the second, in parts per million, or `None`
+    /// until both probes have an estimate.
+    pub fn ppm(&self) -> Option<f64> {
+        self.ratio().map(|ratio| (ratio - 1.0) * 1e6)
+    }
+}
+
+/// Measures the rate of the clock of one stream, as returned by [`ClockDrift::new`].
+#[derive(Debug)]
+pub struct DriftProbe {
+    estimate: Arc<AtomicU64>,
+    nominal: f64,
+    start: Option<StreamInstant>,
+    frames: u64,
+    fit: Fit,
+}
+
+impl DriftProbe {
+    fn new(estimate: Arc<AtomicU64>, sample_rate: SampleRate) -> Self {
+        DriftProbe {
+            estimate,
+            nominal: sample_rate.0 as f64,
+            start: None,
+            frames: 0,
+            fit: Fit::default(),
+        }
+    }
+
+    /// Records a buffer of `frames` frames passed to the data callback at `callback`, the
+    /// `callback` instant of the callback's timestamp.
+    ///
+    /// Call it from every callback of the stream. Does not allocate or block.
+    pub fn record(&mut self, frames: usize, callback: StreamInstant) {
+        let start = *self.start.get_or_insert(callback);
+        let secs = (callback.as_nanos() - start.as_nanos()) as f64 / 1e9;
+        self.fit.add(secs, self.frames as f64);
+        self.frames += frames as u64;
+        if secs >= MIN_SPAN_SECS {
+            if let Some(rate) = self.fit.slope() {
+                let estimate = rate / self.nominal;
+                self.estimate.store(estimate.to_bits(), Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+// An exponentially weighted least-squares fit of frame positions over time. Timestamps jitter by
+// up to a period around the callbacks, which averages out over many of them.
+#[derive(Debug, Default)]
+struct Fit {
+    weight: f64,
+    last_secs: f64,
+    mean_secs: f64,
+    mean_frames: f64,
+    var_secs: f64,
+    cov: f64,
+}
+
+impl Fit {
+    fn add(&mut self, secs: f64, frames: f64) {
+        let decay = (-(secs - self.last_secs) / TIME_CONSTANT_SECS).exp();
+        self.last_secs = secs;
+        self.weight = self.weight * decay + 1.0;
+        let d_secs = secs - self.mean_secs;
+        self.mean_secs += d_secs / self.weight;
+        self.mean_frames += (frames - self.mean_frames) / self.weight;
+        self.var_secs = self.var_secs * decay + d_secs * (secs - self.mean_secs);
+        self.cov = self.cov * decay + d_secs * (frames - self.mean_frames);
+    }
+
+    // The frames per second of the fit.
+    fn slope(&self) -> Option<f64> {
+        (self.var_secs > 0.0).then(|| self.cov / self.var_secs)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ClockDrift;
+    use crate::{SampleRate, StreamInstant};
+
+    // Feeds `secs` of callbacks of `frames` frames to a probe of a clock running at `rate`, with
+    // the timestamps jittering by up to half a millisecond.
+    fn run(probe: &mut super::DriftProbe, rate: f64, frames: usize, secs: f64) {
+        let callbacks = (secs * rate / frames as f64) as u64;
+        for i in 0..callbacks {
+            let jitter = ((i * 7919) % 1000) as f64 * 1e-6 - 0.5e-3;
+            let nanos = (i as f64 * frames as f64 / rate + jitter + 1.0) * 1e9;
+            let callback = StreamInstant::from_nanos(nanos as i64);
+            probe.record(frames, callback);
+        }
+    }
+
+    #[test]
+    fn estimates_drift_between_clocks() {
+        let (drift, mut first, mut second) = ClockDrift::new(SampleRate(48000), SampleRate(44100));
+        assert_eq!(drift.ppm(), None);
+
+        run(&mut first, 48000.0 * (1.0 + 100e-6), 480, 60.0);
+        assert_eq!(drift.ppm(), None);
+        run(&mut second, 44100.0 * (1.0 - 20e-6), 512, 60.0);
+        let ppm = drift.ppm().unwrap();
+        assert!((ppm - 120.0).abs() < 2.0, "{}", ppm);
+    }
+}
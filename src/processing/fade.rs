@@ -0,0 +1,158 @@
+//! Gain ramps for fading buffers in and out without clicks.
+//!
+//! Jumping from one gain to another between two samples produces a step in the waveform, which is
+//! heard as a click. A [`Ramp`] moves towards its target gain linearly over a number of frames
+//! instead, and [`ChannelGains`] does the same for each channel of a buffer separately.
+
+/// A gain that moves linearly towards a target, one step per frame.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Ramp {
+    gain: f32,
+    target: f32,
+    step: f32,
+}
+
+impl Ramp {
+    /// Creates a ramp settled at `gain`, which takes `frames` frames to move by a gain of one.
+    ///
+    /// A ramp of zero frames jumps to its target immediately.
+    pub fn new(gain: f32, frames: usize) -> Self {
+        Ramp {
+            gain,
+            target: gain,
+            step: if frames == 0 {
+                f32::INFINITY
+            } else {
+                1.0 / frames as f32
+            },
+        }
+    }
+
+    /// Sets the gain to move towards.
+    pub fn set_target(&mut self, target: f32) {
+        self.target = target;
+    }
+
+    /// The gain applied to the next frame.
+    pub fn gain(&self) -> f32 {
+        self.gain
+    }
+
+    /// The gain the ramp is moving towards.
+    pub fn target(&self) -> f32 {
+        self.target
+    }
+
+    /// Whether the gain has reached its target.
+    pub fn is_settled(&self) -> bool {
+        self.gain == self.target
+    }
+
+    /// Applies the gain to the interleaved frames of `samples` in place, advancing the ramp by one
+    /// step per frame.
+    pub fn apply(&mut self, samples: &mut [f32], channels: usize) {
+        if self.is_settled() {
+            if self.gain != 1.0 {
+                samples.iter_mut().for_each(|sample| *sample *= self.gain);
+            }
+            return;
+        }
+        for frame in samples.chunks_mut(channels.max(1)) {
+            let gain = self.advance();
+            frame.iter_mut().for_each(|sample| *sample *= gain);
+        }
+    }
+
+    // Moves the gain one step towards the target and returns it.
+    #[inline]
+    fn advance(&mut self) -> f32 {
+        self.gain = if self.gain < self.target {
+            (self.gain + self.step).min(self.target)
+        } else {
+            (self.gain - self.step).max(self.target)
+        };
+        self.gain
+    }
+}
+
+/// A separate [`Ramp`] for each channel of interleaved frames.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChannelGains {
+    ramps: Vec<Ramp>,
+}
+
+impl ChannelGains {
+    /// Creates unity gains for `channels` channels, whose ramps take `frames` frames to move by a
+    /// gain of one.
+    pub fn new(channels: usize, frames: usize) -> Self {
+        ChannelGains {
+            ramps: vec![Ramp::new(1.0, frames); channels],
+        }
+    }
+
+    /// Sets the gain for `channel` to move towards. Channels out of range are ignored.
+    pub fn set_target(&mut self, channel: usize, target: f32) {
+        if let Some(ramp) = self.ramps.get_mut(channel) {
+            ramp.set_target(target);
+        }
+    }
+
+    /// The ramp of each channel.
+    pub fn ramps(&self) -> &[Ramp] {
+        &self.ramps
+    }
+
+    /// Applies the gain of each channel to the interleaved frames of `samples` in place.
+    pub fn apply(&mut self, samples: &mut [f32]) {
+        if self
+            .ramps
+            .iter()
+            .all(|ramp| ramp.is_settled() && ramp.gain == 1.0)
+        {
+            return;
+        }
+        for frame in samples.chunks_exact_mut(self.ramps.len()) {
+            for (sample, ramp) in frame.iter_mut().zip(&mut self.ramps) {
+                *sample *= ramp.advance();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ChannelGains, Ramp};
+
+    #[test]
+    fn ramps_towards_target() {
+        let mut ramp = Ramp::new(0.0, 4);
+        ramp.set_target(1.0);
+        let mut samples = [1.0; 12];
+        ramp.apply(&mut samples, 2);
+        assert_eq!(
+            samples,
+            [0.25, 0.25, 0.5, 0.5, 0.75, 0.75, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0]
+        );
+        assert!(ramp.is_settled());
+
+        ramp.set_target(0.0);
+        let mut samples = [1.0; 3];
+        ramp.apply(&mut samples, 1);
+        assert_eq!(samples, [0.75, 0.5, 0.25]);
+
+        let mut ramp = Ramp::new(1.0, 0);
+        ramp.set_target(0.0);
+        let mut samples = [1.0; 2];
+        ramp.apply(&mut samples, 1);
+        assert_eq!(samples, [0.0, 0.0]);
+    }
+
+    #[test]
+    fn ramps_channels_separately() {
+        let mut gains = ChannelGains::new(2, 2);
+        gains.set_target(1, 0.0);
+        let mut samples = [1.0; 6];
+        gains.apply(&mut samples);
+        assert_eq!(samples, [1.0, 0.5, 1.0, 0.0, 1.0, 0.0]);
+    }
+}
@@ -0,0 +1,87 @@
+//! Protection against samples exceeding full scale.
+//!
+//! Integer formats cannot represent samples beyond [-1.0, 1.0], so overs are clipped harshly by
+//! the conversion, or wrap around entirely with a careless one. The functions here bring every
+//! sample into range first.
+
+// Samples below this level pass the soft clipper unchanged.
+const SOFT_CLIP_KNEE: f32 = 0.9;
+
+/// How samples exceeding full scale are brought back into range.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum ClipMode {
+    /// Leave samples untouched.
+    #[default]
+    Off,
+    /// Clamp samples to [-1.0, 1.0], see [`hard_clip`].
+    Hard,
+    /// Compress samples above a knee smoothly towards full scale, see [`soft_clip`].
+    Soft,
+}
+
+impl ClipMode {
+    /// Processes `samples` in place according to the mode.
+    ///
+    /// Returns the number of samples that exceeded full scale.
+    pub fn process(self, samples: &mut [f32]) -> usize {
+        match self {
+            ClipMode::Off => 0,
+            ClipMode::Hard => hard_clip(samples),
+            ClipMode::Soft => soft_clip(samples),
+        }
+    }
+}
+
+/// Clamps every sample to [-1.0, 1.0].
+///
+/// Returns the number of samples that exceeded full scale.
+pub fn hard_clip(samples: &mut [f32]) -> usize {
+    let mut clipped = 0;
+    for sample in samples.iter_mut() {
+        if sample.abs() > 1.0 {
+            *sample = sample.clamp(-1.0, 1.0);
+            clipped += 1;
+        }
+    }
+    clipped
+}
+
+/// Passes samples below 90% of full scale unchanged and compresses louder ones smoothly towards
+/// full scale.
+///
+/// This adds less distortion to occasional overs than [`hard_clip`]. Returns the number of samples
+/// that exceeded full scale.
+pub fn soft_clip(samples: &mut [f32]) -> usize {
+    let range = 1.0 - SOFT_CLIP_KNEE;
+    let mut clipped = 0;
+    for sample in samples.iter_mut() {
+        let level = sample.abs();
+        if level > 1.0 {
+            clipped += 1;
+        }
+        if level > SOFT_CLIP_KNEE {
+            let compressed = SOFT_CLIP_KNEE + range * ((level - SOFT_CLIP_KNEE) / range).tanh();
+            *sample = compressed.copysign(*sample);
+        }
+    }
+    clipped
+}
+
+#[cfg(test)]
+mod test {
+    use super::{hard_clip, soft_clip};
+
+    #[test]
+    fn clipping() {
+        let mut samples = [0.5, 1.5, -2.0, 1.0, 0.95];
+        assert_eq!(hard_clip(&mut samples), 2);
+        assert_eq!(samples, [0.5, 1.0, -1.0, 1.0, 0.95]);
+
+        let mut samples = [0.5, 1.5, -2.0, 1.0, 0.95];
+        assert_eq!(soft_clip(&mut samples), 2);
+        assert_eq!(samples[0], 0.5);
+        assert!(samples[1] > samples[4] && samples[1] <= 1.0);
+        assert!(samples[2] >= -1.0 && samples[2] <= -samples[1]);
+        assert!(samples[3] > samples[4] && samples[3] < 1.0);
+    }
+}
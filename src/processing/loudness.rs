@@ -0,0 +1,199 @@
+//! Loudness metering following ITU-R BS.1770.
+//!
+//! Loudness is measured in LUFS: the mean square of the K-weighted signal, which approximates the
+//! frequency response of hearing, summed over the channels and expressed in decibels. A full-scale
+//! 1 kHz sine in one channel measures -3.01 LUFS.
+//!
+//! [`LoudnessMeter`] reports the momentary loudness over the last 400 ms and the short-term
+//! loudness over the last 3 s, both updated every 100 ms.
+
+use crate::SampleRate;
+use std::f64::consts::PI;
+
+// The length of the blocks the signal is measured in, in seconds.
+const BLOCK_SECONDS: f64 = 0.1;
+// The number of blocks in the momentary and short-term windows.
+const MOMENTARY_BLOCKS: usize = 4;
+const SHORT_TERM_BLOCKS: usize = 30;
+
+// A biquad filter in direct form I.
+#[derive(Clone, Debug)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    x: [f64; 2],
+    y: [f64; 2],
+}
+
+impl Biquad {
+    fn new(b: [f64; 3], a: [f64; 2]) -> Self {
+        Biquad {
+            b,
+            a,
+            x: [0.0; 2],
+            y: [0.0; 2],
+        }
+    }
+
+    // The two stages of the K-weighting filter, derived for any sample rate as in the reference
+    // implementation of libebur128.
+    fn k_weighting(sample_rate: SampleRate) -> [Biquad; 2] {
+        let rate = sample_rate.0 as f64;
+
+        // A high shelf of about +4 dB above 1.5 kHz, modelling the head.
+        let k = (PI * 1681.974450955533 / rate).tan();
+        let q = 0.7071752369554196;
+        let vh = 10f64.powf(3.999843853973347 / 20.0);
+        let vb = vh.powf(0.4996667741545416);
+        let a0 = 1.0 + k / q + k * k;
+        let shelf = Biquad::new(
+            [
+                (vh + vb * k / q + k * k) / a0,
+                2.0 * (k * k - vh) / a0,
+                (vh - vb * k / q + k * k) / a0,
+            ],
+            [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        );
+
+        // A high pass below about 40 Hz.
+        let k = (PI * 38.13547087602444 / rate).tan();
+        let q = 0.5003270373238773;
+        let a0 = 1.0 + k / q + k * k;
+        let high_pass = Biquad::new(
+            [1.0, -2.0, 1.0],
+            [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        );
+
+        [shelf, high_pass]
+    }
+
+    #[inline]
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b[0] * x + self.b[1] * self.x[0] + self.b[2] * self.x[1]
+            - self.a[0] * self.y[0]
+            - self.a[1] * self.y[1];
+        self.x = [x, self.x[0]];
+        self.y = [y, self.y[0]];
+        y
+    }
+
+    fn reset(&mut self) {
+        self.x = [0.0; 2];
+        self.y = [0.0; 2];
+    }
+}
+
+/// Measures the momentary and short-term loudness of interleaved frames.
+///
+/// Six-channel buffers are taken to be 5.1 in WAVE order: the LFE channel is ignored and the
+/// surround channels are weighted by +1.5 dB, as specified by BS.1770. All other channels are
+/// weighted equally.
+#[derive(Clone, Debug)]
+pub struct LoudnessMeter {
+    filters: Vec<[Biquad; 2]>,
+    weights: Vec<f64>,
+    block_frames: usize,
+    // The frames and the weighted sum of squares of the block being measured.
+    block_position: usize,
+    block_sum: f64,
+    // The mean squares of the most recent blocks, in a ring buffer.
+    blocks: [f64; SHORT_TERM_BLOCKS],
+    next_block: usize,
+    measured_blocks: usize,
+}
+
+impl LoudnessMeter {
+    /// Creates a meter for `channels` channels at the given sample rate.
+    pub fn new(channels: usize, sample_rate: SampleRate) -> Self {
+        let weights = if channels == 6 {
+            vec![1.0, 1.0, 1.0, 0.0, 1.41, 1.41]
+        } else {
+            vec![1.0; channels]
+        };
+        LoudnessMeter {
+            filters: vec![Biquad::k_weighting(sample_rate); channels],
+            weights,
+            block_frames: ((sample_rate.0 as f64 * BLOCK_SECONDS) as usize).max(1),
+            block_position: 0,
+            block_sum: 0.0,
+            blocks: [0.0; SHORT_TERM_BLOCKS],
+            next_block: 0,
+            measured_blocks: 0,
+        }
+    }
+
+    /// Measures the interleaved frames of `samples`.
+    pub fn process(&mut self, samples: &[f32]) {
+        let channels = self.filters.len();
+        if channels == 0 {
+            return;
+        }
+        for frame in samples.chunks_exact(channels) {
+            let channels = frame.iter().zip(&mut self.filters).zip(&self.weights);
+            for ((&sample, [shelf, high_pass]), weight) in channels {
+                let filtered = high_pass.process(shelf.process(sample as f64));
+                self.block_sum += weight * filtered * filtered;
+            }
+            self.block_position += 1;
+            if self.block_position == self.block_frames {
+                self.blocks[self.next_block] = self.block_sum / self.block_frames as f64;
+                self.next_block = (self.next_block + 1) % SHORT_TERM_BLOCKS;
+                self.measured_blocks = (self.measured_blocks + 1).min(SHORT_TERM_BLOCKS);
+                self.block_position = 0;
+                self.block_sum = 0.0;
+            }
+        }
+    }
+
+    /// The loudness of the last 400 ms in LUFS, or `None` until that much has been measured.
+    pub fn momentary(&self) -> Option<f32> {
+        self.loudness(MOMENTARY_BLOCKS)
+    }
+
+    /// The loudness of the last 3 s in LUFS, or `None` until that much has been measured.
+    pub fn short_term(&self) -> Option<f32> {
+        self.loudness(SHORT_TERM_BLOCKS)
+    }
+
+    /// Discards everything measured so far.
+    pub fn reset(&mut self) {
+        self.filters.iter_mut().flatten().for_each(Biquad::reset);
+        self.block_position = 0;
+        self.block_sum = 0.0;
+        self.measured_blocks = 0;
+    }
+
+    fn loudness(&self, blocks: usize) -> Option<f32> {
+        if self.measured_blocks < blocks {
+            return None;
+        }
+        let sum: f64 = (1..=blocks)
+            .map(|age| self.blocks[(self.next_block + SHORT_TERM_BLOCKS - age) % SHORT_TERM_BLOCKS])
+            .sum();
+        Some((-0.691 + 10.0 * (sum / blocks as f64).log10()) as f32)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::LoudnessMeter;
+    use crate::SampleRate;
+    use std::f32::consts::TAU;
+
+    #[test]
+    fn full_scale_sine() {
+        for rate in [44_100, 48_000] {
+            let mut meter = LoudnessMeter::new(2, SampleRate(rate));
+            let samples: Vec<f32> = (0..rate * 3)
+                .flat_map(|i| [(i as f32 * TAU * 1000.0 / rate as f32).sin(), 0.0])
+                .collect();
+            meter.process(&samples[..samples.len() / 10]);
+            assert_eq!(meter.short_term(), None);
+            meter.process(&samples[samples.len() / 10..]);
+            let momentary = meter.momentary().unwrap();
+            let short_term = meter.short_term().unwrap();
+            assert!((momentary + 3.01).abs() < 0.05, "{}", momentary);
+            assert!((short_term + 3.01).abs() < 0.05, "{}", short_term);
+        }
+    }
+}
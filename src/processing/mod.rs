@@ -0,0 +1,86 @@
+//! Processing applied to interleaved sample buffers on their way between a stream callback and
+//! the device.
+//!
+//! Each submodule works on plain slices, so it can be used from within a callback as well as by
+//! the stream conversion layer. Custom processing can be inserted into streams built with options
+//! by implementing [`ProcessStage`].
+
+use crate::SampleRate;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+pub mod align;
+pub mod channels;
+pub mod dc;
+pub mod dither;
+pub mod drift;
+pub mod fade;
+pub mod limiter;
+pub mod loudness;
+#[cfg(feature = "rubato")]
+pub mod resample;
+
+/// A step processing every buffer of a stream, inserted through `StreamOptions::stages`.
+///
+/// Stages see the buffers as `f32` with the callback's channel count: input buffers after they
+/// were converted from the device, output buffers before they are converted for it. Closures
+/// taking the samples and a [`StageInfo`] are stages too.
+pub trait ProcessStage: Send {
+    /// Processes the interleaved frames of `samples` in place.
+    fn process(&mut self, samples: &mut [f32], info: &StageInfo);
+}
+
+/// Describes the buffers passed to a [`ProcessStage`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StageInfo {
+    /// The number of channels of each frame.
+    pub channels: usize,
+    /// The sample rate of the stream.
+    pub sample_rate: SampleRate,
+}
+
+/// A stage shared between a stream and the code setting it up, which may keep a handle to adjust
+/// the stage while the stream runs.
+///
+/// The stream never waits for the lock: a buffer processed while the handle holds it passes
+/// through the stage untouched.
+pub type SharedStage = Arc<Mutex<dyn ProcessStage>>;
+
+impl fmt::Debug for dyn ProcessStage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ProcessStage")
+    }
+}
+
+impl<F> ProcessStage for F
+where
+    F: FnMut(&mut [f32], &StageInfo) + Send,
+{
+    fn process(&mut self, samples: &mut [f32], info: &StageInfo) {
+        self(samples, info)
+    }
+}
+
+impl ProcessStage for dc::DcBlocker {
+    fn process(&mut self, samples: &mut [f32], _: &StageInfo) {
+        dc::DcBlocker::process(self, samples);
+    }
+}
+
+impl ProcessStage for fade::ChannelGains {
+    fn process(&mut self, samples: &mut [f32], _: &StageInfo) {
+        self.apply(samples);
+    }
+}
+
+impl ProcessStage for limiter::ClipMode {
+    fn process(&mut self, samples: &mut [f32], _: &StageInfo) {
+        limiter::ClipMode::process(*self, samples);
+    }
+}
+
+impl ProcessStage for loudness::LoudnessMeter {
+    fn process(&mut self, samples: &mut [f32], _: &StageInfo) {
+        loudness::LoudnessMeter::process(self, samples);
+    }
+}
@@ -0,0 +1,126 @@
+//! High-quality sample rate conversion backed by the sinc resamplers of rubato.
+//!
+//! Enable the `rubato` feature to use this module. Rubato works on planar buffers of fixed-size
+//! chunks; [`SincResampler`] takes interleaved buffers of any length instead, and keeps whatever
+//! does not fill a chunk for the next call.
+
+use crate::{ChannelCount, SampleRate};
+use rubato::{
+    ResampleError, Resampler, ResamplerConstructionError, SincFixedIn, SincInterpolationParameters,
+    SincInterpolationType, WindowFunction,
+};
+
+/// Converts interleaved `f32` buffers between two sample rates.
+pub struct SincResampler {
+    resampler: SincFixedIn<f32>,
+    channels: usize,
+    // Interleaved input not yet passed to the resampler.
+    pending: Vec<f32>,
+    input: Vec<Vec<f32>>,
+    output: Vec<Vec<f32>>,
+}
+
+impl SincResampler {
+    /// Creates a resampler from `from` to `to` for interleaved buffers of `channels` channels.
+    ///
+    /// The input is resampled in chunks of `chunk_frames` frames. Smaller chunks lower the
+    /// latency at the cost of more overhead per frame.
+    pub fn new(
+        from: SampleRate,
+        to: SampleRate,
+        channels: ChannelCount,
+        chunk_frames: usize,
+    ) -> Result<Self, ResamplerConstructionError> {
+        let parameters = SincInterpolationParameters {
+            sinc_len: 256,
+            f_cutoff: 0.95,
+            oversampling_factor: 128,
+            interpolation: SincInterpolationType::Cubic,
+            window: WindowFunction::BlackmanHarris2,
+        };
+        let ratio = to.0 as f64 / from.0 as f64;
+        let channels = channels as usize;
+        let resampler = SincFixedIn::new(ratio, 1.0, parameters, chunk_frames, channels)?;
+        let input = resampler.input_buffer_allocate(true);
+        let output = resampler.output_buffer_allocate(true);
+        Ok(SincResampler {
+            resampler,
+            channels,
+            pending: Vec::with_capacity(chunk_frames * channels),
+            input,
+            output,
+        })
+    }
+
+    /// The number of frames by which the output lags behind the input.
+    pub fn delay(&self) -> usize {
+        self.resampler.output_delay()
+    }
+
+    /// Resamples the interleaved frames of `src` and appends the result to `dst`.
+    ///
+    /// Frames that do not fill a whole chunk are kept until the next call, so the output may be
+    /// shorter than the ratio of the rates suggests, or even empty.
+    pub fn process(&mut self, src: &[f32], dst: &mut Vec<f32>) -> Result<(), ResampleError> {
+        if self.channels == 0 {
+            return Ok(());
+        }
+        self.pending.extend_from_slice(src);
+        let mut consumed = 0;
+        loop {
+            let frames = self.resampler.input_frames_next();
+            let len = frames * self.channels;
+            if self.pending.len() - consumed < len {
+                break;
+            }
+            let chunk = &self.pending[consumed..consumed + len];
+            for (channel, input) in self.input.iter_mut().enumerate() {
+                input.clear();
+                input.extend(chunk.iter().skip(channel).step_by(self.channels));
+            }
+            let (_, written) =
+                self.resampler
+                    .process_into_buffer(&self.input, &mut self.output, None)?;
+            dst.reserve(written * self.channels);
+            for frame in 0..written {
+                dst.extend(self.output.iter().map(|output| output[frame]));
+            }
+            consumed += len;
+        }
+        self.pending.drain(..consumed);
+        Ok(())
+    }
+
+    /// Discards any pending input and the state of the filter, as if the resampler was new.
+    pub fn reset(&mut self) {
+        self.pending.clear();
+        self.resampler.reset();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::SincResampler;
+    use crate::SampleRate;
+
+    #[test]
+    fn resamples_interleaved_frames() {
+        let mut resampler =
+            SincResampler::new(SampleRate(48000), SampleRate(24000), 2, 256).unwrap();
+        let src: Vec<f32> = (0..1000)
+            .flat_map(|i| [0.5, -(i as f32) / 1000.0])
+            .collect();
+        let mut dst = Vec::new();
+        resampler.process(&src, &mut dst).unwrap();
+        // Three whole chunks of 256 frames are resampled to half as many, less the delay of the
+        // filter, with the rest pending.
+        let delay = resampler.delay();
+        assert_eq!(dst.len() % 2, 0);
+        assert!((dst.len() / 2 + delay).abs_diff(3 * 128) <= 1);
+
+        // Past the delay of the filter, the constant channel passes through unchanged.
+        for frame in dst.chunks_exact(2).skip(delay) {
+            assert!((frame[0] - 0.5).abs() < 1e-3);
+        }
+    }
+}
@@ -0,0 +1,90 @@
+//! Promoting the thread calling a stream's callback to realtime scheduling.
+//!
+//! Backs `StreamOptions::realtime_priority`.
+
+use crate::RealtimePriority;
+
+/// Promotes the calling thread to realtime scheduling, unless it already has it.
+pub(crate) fn promote_current_thread() -> RealtimePriority {
+    imp::promote_current_thread()
+}
+
+// Linux and the BSDs promote the thread to `SCHED_RR` through pthreads. This needs an
+// `RLIMIT_RTPRIO` above zero, as commonly granted to an `audio` group. Asking rtkit over D-Bus
+// instead is left to the application, as it would add a D-Bus dependency.
+#[cfg(all(
+    any(target_os = "linux", target_os = "dragonfly", target_os = "freebsd"),
+    feature = "libc"
+))]
+mod imp {
+    use crate::RealtimePriority;
+
+    // The priority requested, below the threaded interrupt handlers of Linux at 50.
+    const PRIORITY: libc::c_int = 40;
+
+    pub(super) fn promote_current_thread() -> RealtimePriority {
+        unsafe {
+            let thread = libc::pthread_self();
+            let mut policy = 0;
+            let mut param: libc::sched_param = std::mem::zeroed();
+            if libc::pthread_getschedparam(thread, &mut policy, &mut param) == 0
+                && (policy == libc::SCHED_FIFO || policy == libc::SCHED_RR)
+            {
+                return RealtimePriority::AlreadyRealtime;
+            }
+            let min = libc::sched_get_priority_min(libc::SCHED_RR);
+            let max = libc::sched_get_priority_max(libc::SCHED_RR);
+            param.sched_priority = PRIORITY.min(max).max(min);
+            match libc::pthread_setschedparam(thread, libc::SCHED_RR, &param) {
+                0 => RealtimePriority::Promoted,
+                _ => RealtimePriority::Denied,
+            }
+        }
+    }
+}
+
+// Windows registers the thread with the Multimedia Class Scheduler Service as a "Pro Audio" task.
+// The registration lasts until the thread exits.
+#[cfg(all(target_os = "windows", feature = "windows"))]
+mod imp {
+    use crate::RealtimePriority;
+    use windows::core::PCWSTR;
+    use windows::Win32::System::Threading::AvSetMmThreadCharacteristicsW;
+
+    pub(super) fn promote_current_thread() -> RealtimePriority {
+        let task: Vec<u16> = "Pro Audio\0".encode_utf16().collect();
+        let mut task_index = 0;
+        let result =
+            unsafe { AvSetMmThreadCharacteristicsW(PCWSTR(task.as_ptr()), &mut task_index) };
+        match result {
+            Ok(_) => RealtimePriority::Promoted,
+            Err(_) => RealtimePriority::Denied,
+        }
+    }
+}
+
+// CoreAudio calls back on its own time-constraint thread.
+#[cfg(all(any(target_os = "macos", target_os = "ios"), feature = "coreaudio-rs"))]
+mod imp {
+    use crate::RealtimePriority;
+
+    pub(super) fn promote_current_thread() -> RealtimePriority {
+        RealtimePriority::AlreadyRealtime
+    }
+}
+
+#[cfg(not(any(
+    all(
+        any(target_os = "linux", target_os = "dragonfly", target_os = "freebsd"),
+        feature = "libc"
+    ),
+    all(target_os = "windows", feature = "windows"),
+    all(any(target_os = "macos", target_os = "ios"), feature = "coreaudio-rs"),
+)))]
+mod imp {
+    use crate::RealtimePriority;
+
+    pub(super) fn promote_current_thread() -> RealtimePriority {
+        RealtimePriority::Unsupported
+    }
+}
@@ -0,0 +1,287 @@
+//! Building a stream again, keeping its callbacks.
+//!
+//! Backs `Stream::rebuild_with` of the platform `Stream`. The streams of the platform `Device`
+//! lend their callbacks to the host, which calls them directly and hands them back when its stream
+//! is dropped, so that they can be passed to the next one.
+
+use crate::{
+    Data, InputCallbackInfo, OutputCallbackInfo, SampleFormat, ShareMode, StreamError, StreamUsage,
+};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+type InputDataCallback = Box<dyn FnMut(&Data, &InputCallbackInfo) + Send + 'static>;
+type OutputDataCallback = Box<dyn FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static>;
+type ErrorCallback = Box<dyn FnMut(StreamError) + Send + 'static>;
+
+// Where a lent callback is handed back to.
+type Slot<T> = Arc<Mutex<Option<T>>>;
+
+/// The callbacks of a stream, along with what it was built with apart from its config.
+pub(crate) struct Callbacks {
+    sample_format: SampleFormat,
+    share_mode: Option<ShareMode>,
+    usage: Option<StreamUsage>,
+    data: DataCallback,
+    error: ErrorCallback,
+}
+
+enum DataCallback {
+    Input(InputDataCallback),
+    Output(OutputDataCallback),
+}
+
+/// The callbacks of a stream while they are lent to the host, to be taken back once the host's
+/// stream is dropped.
+pub(crate) struct Lent {
+    sample_format: SampleFormat,
+    share_mode: Option<ShareMode>,
+    usage: Option<StreamUsage>,
+    data: DataSlot,
+    error: Slot<ErrorCallback>,
+}
+
+enum DataSlot {
+    Input(Slot<InputDataCallback>),
+    Output(Slot<OutputDataCallback>),
+}
+
+impl Callbacks {
+    /// The callbacks of an input stream, built with `share_mode` if it is `Some`.
+    pub(crate) fn input<D, E>(
+        sample_format: SampleFormat,
+        share_mode: Option<ShareMode>,
+        data_callback: D,
+        error_callback: E,
+    ) -> Self
+    where
+        D: FnMut(&Data, &InputCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        Callbacks {
+            sample_format,
+            share_mode,
+            usage: None,
+            data: DataCallback::Input(Box::new(data_callback)),
+            error: Box::new(error_callback),
+        }
+    }
+
+    /// The callbacks of an output stream, built with `share_mode` if it is `Some`.
+    pub(crate) fn output<D, E>(
+        sample_format: SampleFormat,
+        share_mode: Option<ShareMode>,
+        data_callback: D,
+        error_callback: E,
+    ) -> Self
+    where
+        D: FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        Callbacks {
+            sample_format,
+            share_mode,
+            usage: None,
+            data: DataCallback::Output(Box::new(data_callback)),
+            error: Box::new(error_callback),
+        }
+    }
+
+    /// These callbacks, for a stream built with `usage`.
+    pub(crate) fn with_usage(mut self, usage: StreamUsage) -> Self {
+        self.usage = Some(usage);
+        self
+    }
+
+    pub(crate) fn sample_format(&self) -> SampleFormat {
+        self.sample_format
+    }
+
+    pub(crate) fn share_mode(&self) -> Option<ShareMode> {
+        self.share_mode
+    }
+
+    pub(crate) fn usage(&self) -> Option<StreamUsage> {
+        self.usage
+    }
+
+    pub(crate) fn is_input(&self) -> bool {
+        matches!(self.data, DataCallback::Input(_))
+    }
+
+    /// The callbacks to pass to the host for an input stream, and what to take them back with.
+    ///
+    /// Panics if these are the callbacks of an output stream.
+    pub(crate) fn lend_input(
+        self,
+    ) -> (
+        impl FnMut(&Data, &InputCallbackInfo) + Send + 'static,
+        impl FnMut(StreamError) + Send + 'static,
+        Lent,
+    ) {
+        let data_callback = match self.data {
+            DataCallback::Input(data_callback) => data_callback,
+            DataCallback::Output(_) => panic!("expected the callbacks of an input stream"),
+        };
+        let slot = Slot::default();
+        let mut data_callback = Loan::new(data_callback, &slot);
+        let data_callback = move |data: &Data, info: &InputCallbackInfo| {
+            if let Some(data_callback) = &mut data_callback.callback {
+                data_callback(data, info)
+            }
+        };
+        let (error_callback, error) = lend_error(self.error);
+        let lent = Lent {
+            sample_format: self.sample_format,
+            share_mode: self.share_mode,
+            usage: self.usage,
+            data: DataSlot::Input(slot),
+            error,
+        };
+        (data_callback, error_callback, lent)
+    }
+
+    /// The callbacks to pass to the host for an output stream, and what to take them back with.
+    ///
+    /// Panics if these are the callbacks of an input stream.
+    pub(crate) fn lend_output(
+        self,
+    ) -> (
+        impl FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static,
+        impl FnMut(StreamError) + Send + 'static,
+        Lent,
+    ) {
+        let data_callback = match self.data {
+            DataCallback::Output(data_callback) => data_callback,
+            DataCallback::Input(_) => panic!("expected the callbacks of an output stream"),
+        };
+        let slot = Slot::default();
+        let mut data_callback = Loan::new(data_callback, &slot);
+        let data_callback = move |data: &mut Data, info: &OutputCallbackInfo| {
+            if let Some(data_callback) = &mut data_callback.callback {
+                data_callback(data, info)
+            }
+        };
+        let (error_callback, error) = lend_error(self.error);
+        let lent = Lent {
+            sample_format: self.sample_format,
+            share_mode: self.share_mode,
+            usage: self.usage,
+            data: DataSlot::Output(slot),
+            error,
+        };
+        (data_callback, error_callback, lent)
+    }
+}
+
+impl Lent {
+    /// Takes the callbacks back, once the host has dropped them along with its stream.
+    ///
+    /// Returns `None` if the host still holds on to either of them.
+    pub(crate) fn take_back(self) -> Option<Callbacks> {
+        let data = match self.data {
+            DataSlot::Input(slot) => DataCallback::Input(lock(&slot).take()?),
+            DataSlot::Output(slot) => DataCallback::Output(lock(&slot).take()?),
+        };
+        let error = lock(&self.error).take()?;
+        Some(Callbacks {
+            sample_format: self.sample_format,
+            share_mode: self.share_mode,
+            usage: self.usage,
+            data,
+            error,
+        })
+    }
+}
+
+fn lend_error(
+    error_callback: ErrorCallback,
+) -> (
+    impl FnMut(StreamError) + Send + 'static,
+    Slot<ErrorCallback>,
+) {
+    let slot = Slot::default();
+    let mut error_callback = Loan::new(error_callback, &slot);
+    let error_callback = move |err| {
+        if let Some(error_callback) = &mut error_callback.callback {
+            error_callback(err)
+        }
+    };
+    (error_callback, slot)
+}
+
+// A callback owned by a closure passed to the host, which hands it back to its slot when the host
+// drops the closure. The slot is only locked then, never while the callback is called.
+struct Loan<T> {
+    callback: Option<T>,
+    slot: Slot<T>,
+}
+
+impl<T> Loan<T> {
+    fn new(callback: T, slot: &Slot<T>) -> Self {
+        Loan {
+            callback: Some(callback),
+            slot: slot.clone(),
+        }
+    }
+}
+
+impl<T> Drop for Loan<T> {
+    fn drop(&mut self) {
+        *lock(&self.slot) = self.callback.take();
+    }
+}
+
+// A callback that panicked while being handed back is handed back all the same.
+fn lock<T>(slot: &Mutex<T>) -> MutexGuard<'_, T> {
+    slot.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[cfg(test)]
+mod test {
+    use super::Callbacks;
+    use crate::{Data, InputCallbackInfo, InputStreamTimestamp, SampleFormat, StreamInstant};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[test]
+    fn callbacks_outlive_the_stream() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let callbacks = {
+            let calls = calls.clone();
+            Callbacks::input(
+                SampleFormat::F32,
+                None,
+                move |_: &Data, _: &InputCallbackInfo| {
+                    calls.fetch_add(1, Ordering::Relaxed);
+                },
+                |_| (),
+            )
+        };
+        assert!(callbacks.is_input());
+        let instant = StreamInstant::new(0, 0);
+        let timestamp = InputStreamTimestamp {
+            callback: instant,
+            capture: instant,
+        };
+        let info = InputCallbackInfo::new(timestamp, 0);
+        let mut samples = [0.0f32; 4];
+        let data =
+            unsafe { Data::from_parts(samples.as_mut_ptr() as *mut (), 4, SampleFormat::F32) };
+
+        let (mut first, first_error, lent) = callbacks.lend_input();
+        first(&data, &info);
+        // The host drops both callbacks along with its stream.
+        drop(first);
+        drop(first_error);
+        let callbacks = lent.take_back().unwrap();
+        assert!(callbacks.is_input());
+
+        let (mut second, second_error, lent) = callbacks.lend_input();
+        second(&data, &info);
+        assert_eq!(calls.load(Ordering::Relaxed), 2);
+        // The host still holds the data callback.
+        drop(second_error);
+        assert!(lent.take_back().is_none());
+        drop(second);
+    }
+}
@@ -1,7 +1,13 @@
+use std::cmp::Ordering;
+use std::fmt;
 use std::mem;
 
 /// Format that each sample has.
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+///
+/// Formats are ordered by class (signed integer, unsigned integer, floating point, then the
+/// companded and bitstream formats), then by bit depth, then by declaration order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum SampleFormat {
     /// The value 0 corresponds to 0.
     I16,
@@ -9,6 +15,38 @@ pub enum SampleFormat {
     U16,
     /// The boundaries are (-1.0, 1.0).
     F32,
+    /// Half-precision floating point. The boundaries are (-1.0, 1.0).
+    ///
+    /// Enable the `half` feature to use `half::f16` as the corresponding `Sample` type.
+    F16,
+    /// 8-bit G.711 μ-law companded samples. See the `MuLaw` sample type.
+    MuLaw,
+    /// Compressed bitstream (AC-3, DTS, E-AC-3, ...) framed as IEC 61937 bursts and carried in
+    /// 16-bit stereo PCM frames, for passthrough to an S/PDIF or HDMI receiver.
+    ///
+    /// Requesting this format asks the backend to flag the stream as non-audio. The data is
+    /// opaque to cpal, so there is no corresponding `Sample` type: use the `*_stream_raw` methods.
+    ///
+    /// ALSA only supports the format on playback streams of `iec958`, `spdif` and `hdmi` devices.
+    /// WASAPI only supports it on exclusive-mode streams, as the shared-mode mixer takes PCM.
+    Iec61937,
+    /// Native DSD, 8 one-bit samples per byte with the oldest bit in the MSB.
+    ///
+    /// The sample rate counts bytes per channel, so DSD64 is 352 800 Hz and DSD128 705 600 Hz.
+    /// There is no corresponding `Sample` type: access the bitstream with `Data::bytes(_mut)`.
+    DsdU8,
+    /// Native DSD, 32 one-bit samples per native-endian `u32` with the oldest bit in the MSB.
+    ///
+    /// The sample rate counts words per channel, so DSD64 is 88 200 Hz and DSD128 176 400 Hz.
+    DsdU32,
+    /// DSD over PCM (DoP) in 32-bit frames: a marker alternating between 0x05 and 0xFA in the top
+    /// byte, 16 DSD bits below it (oldest in the MSB) and a zero low byte.
+    ///
+    /// DSD64 runs at 176 400 Hz and DSD128 at 352 800 Hz. A DoP stream is plain PCM as far as the
+    /// device is concerned, so backends cannot detect DAC support and do not advertise this
+    /// format. `supports_output_config` only tells whether the device takes the 32-bit PCM
+    /// carrying it. Only use it when the DAC is known to decode DoP.
+    Dop,
 }
 
 impl SampleFormat {
@@ -19,8 +57,185 @@ impl SampleFormat {
             SampleFormat::I16 => mem::size_of::<i16>(),
             SampleFormat::U16 => mem::size_of::<u16>(),
             SampleFormat::F32 => mem::size_of::<f32>(),
+            // Stored as the raw IEEE 754 binary16 bits.
+            SampleFormat::F16 => mem::size_of::<u16>(),
+            SampleFormat::MuLaw => mem::size_of::<MuLaw>(),
+            SampleFormat::Iec61937 => mem::size_of::<u16>(),
+            SampleFormat::DsdU8 => mem::size_of::<u8>(),
+            SampleFormat::DsdU32 | SampleFormat::Dop => mem::size_of::<u32>(),
         }
     }
+
+    /// Returns every sample format, in declaration order.
+    pub fn all() -> &'static [SampleFormat] {
+        &[
+            SampleFormat::I16,
+            SampleFormat::U16,
+            SampleFormat::F32,
+            SampleFormat::F16,
+            SampleFormat::MuLaw,
+            SampleFormat::Iec61937,
+            SampleFormat::DsdU8,
+            SampleFormat::DsdU32,
+            SampleFormat::Dop,
+        ]
+    }
+
+    /// Returns `true` if this is a signed linear PCM integer format.
+    #[inline]
+    pub fn is_int(&self) -> bool {
+        *self == SampleFormat::I16
+    }
+
+    /// Returns `true` if this is an unsigned linear PCM integer format.
+    #[inline]
+    pub fn is_uint(&self) -> bool {
+        *self == SampleFormat::U16
+    }
+
+    /// Returns `true` if this is a floating point PCM format.
+    #[inline]
+    pub fn is_float(&self) -> bool {
+        matches!(*self, SampleFormat::F32 | SampleFormat::F16)
+    }
+
+    /// Returns the number of bits carrying audio in each sample.
+    ///
+    /// This is smaller than `8 * sample_size()` for companded and DSD formats: a `MuLaw` sample
+    /// carries 8 bits and every DSD format carries 1-bit samples.
+    #[inline]
+    pub fn bit_depth(&self) -> u32 {
+        match *self {
+            SampleFormat::MuLaw => 8,
+            SampleFormat::DsdU8 | SampleFormat::DsdU32 | SampleFormat::Dop => 1,
+            _ => 8 * self.sample_size() as u32,
+        }
+    }
+
+    fn sort_key(&self) -> (u8, u32, u8) {
+        let class = if self.is_int() {
+            0
+        } else if self.is_uint() {
+            1
+        } else if self.is_float() {
+            2
+        } else {
+            3
+        };
+        (class, self.bit_depth(), *self as u8)
+    }
+}
+
+impl fmt::Display for SampleFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match *self {
+            SampleFormat::I16 => "i16",
+            SampleFormat::U16 => "u16",
+            SampleFormat::F32 => "f32",
+            SampleFormat::F16 => "f16",
+            SampleFormat::MuLaw => "μ-law",
+            SampleFormat::Iec61937 => "IEC 61937",
+            SampleFormat::DsdU8 => "DSD (u8)",
+            SampleFormat::DsdU32 => "DSD (u32)",
+            SampleFormat::Dop => "DoP",
+        };
+        f.pad(name)
+    }
+}
+
+impl PartialOrd for SampleFormat {
+    #[inline]
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for SampleFormat {
+    #[inline]
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.sort_key().cmp(&other.sort_key())
+    }
+}
+
+/// Dispatches on a `SampleFormat` to generic code, once for each format that has a `Sample` type.
+///
+/// In the body, the identifier given between the pipes names the `Sample` type matching the
+/// format. Formats without a `Sample` type, and those whose type is behind a disabled feature, are
+/// passed to the optional fallback arm. Without one, the macro panics on them.
+///
+/// ```no_run
+/// use cpal::traits::{DeviceTrait, HostTrait};
+/// use cpal::Sample;
+///
+/// fn silence<T: Sample>(data: &mut [T], _: &cpal::OutputCallbackInfo) {
+///     for sample in data.iter_mut() {
+///         *sample = Sample::from(&0.0);
+///     }
+/// }
+///
+/// let device = cpal::default_host().default_output_device().unwrap();
+/// let supported_config = device.default_output_config().unwrap();
+/// let sample_format = supported_config.sample_format();
+/// let config = supported_config.into();
+/// let err_fn = |err| eprintln!("an error occurred on the output audio stream: {}", err);
+/// let stream = cpal::for_each_sample_format!(sample_format => |T| {
+///     device.build_output_stream(&config, silence::<T>, err_fn)
+/// }, format => panic!("Unsupported sample format '{:?}'", format));
+/// ```
+#[macro_export]
+macro_rules! for_each_sample_format {
+    ($format:expr => |$t:ident| $body:expr $(, $other:pat => $fallback:expr)? $(,)?) => {
+        $crate::__with_sample_types!($format => |$t| $body $(, $other => $fallback)?)
+    };
+}
+
+// The list of `Sample` types depends on cpal's features, which must be resolved here rather than
+// in the crate invoking `for_each_sample_format!`.
+#[cfg(feature = "half")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __with_sample_types {
+    ($($rest:tt)*) => {
+        $crate::__match_sample_format!(
+            [I16: i16, U16: u16, F32: f32, F16: $crate::__half::f16, MuLaw: $crate::MuLaw]
+            $($rest)*
+        )
+    };
+}
+
+#[cfg(not(feature = "half"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __with_sample_types {
+    ($($rest:tt)*) => {
+        $crate::__match_sample_format!(
+            [I16: i16, U16: u16, F32: f32, MuLaw: $crate::MuLaw]
+            $($rest)*
+        )
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __match_sample_format {
+    ([$($variant:ident: $ty:ty),*] $format:expr => |$t:ident| $body:expr) => {
+        $crate::__match_sample_format!(
+            [$($variant: $ty),*]
+            $format => |$t| $body,
+            format => panic!("Unsupported sample format '{}'", format)
+        )
+    };
+    ([$($variant:ident: $ty:ty),*] $format:expr => |$t:ident| $body:expr, $other:pat => $fallback:expr) => {
+        match $format {
+            $($crate::SampleFormat::$variant => {
+                #[allow(non_camel_case_types, dead_code)]
+                type $t = $ty;
+                $body
+            })*
+            #[allow(unreachable_patterns)]
+            $other => $fallback,
+        }
+    };
 }
 
 /// Trait for containers that contain PCM data.
@@ -131,9 +346,292 @@ unsafe impl Sample for f32 {
     }
 }
 
+#[cfg(feature = "half")]
+unsafe impl Sample for half::f16 {
+    const FORMAT: SampleFormat = SampleFormat::F16;
+
+    #[inline]
+    fn to_f32(&self) -> f32 {
+        half::f16::to_f32(*self)
+    }
+
+    #[inline]
+    fn to_i16(&self) -> i16 {
+        Sample::to_f32(self).to_i16()
+    }
+
+    #[inline]
+    fn to_u16(&self) -> u16 {
+        Sample::to_f32(self).to_u16()
+    }
+
+    #[inline]
+    fn from<S>(sample: &S) -> Self
+    where
+        S: Sample,
+    {
+        half::f16::from_f32(sample.to_f32())
+    }
+}
+
+/// An 8-bit G.711 μ-law code word.
+///
+/// μ-law is a companded (logarithmic) encoding of 14-bit linear PCM commonly used by telephony
+/// and VoIP hardware. Conversions to the other sample types go through the 16-bit linear
+/// representation defined by G.711.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[repr(transparent)]
+pub struct MuLaw(pub u8);
+
+// Offset added to the linear magnitude before encoding, so that every segment starts at a power
+// of two.
+const MULAW_BIAS: i32 = 0x84;
+// The largest linear magnitude that can be encoded without overflowing the last segment.
+const MULAW_CLIP: i32 = 32635;
+
+// The 16-bit linear value of every μ-law code word.
+const MULAW_TO_I16: [i16; 256] = mulaw_expansion_table();
+
+const fn mulaw_expansion_table() -> [i16; 256] {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < table.len() {
+        // Code words are transmitted with all bits inverted.
+        let code = !(i as u8);
+        let exponent = (code >> 4) & 0x07;
+        let mantissa = (code & 0x0F) as i32;
+        let magnitude = (((mantissa << 3) + MULAW_BIAS) << exponent) - MULAW_BIAS;
+        table[i] = if code & 0x80 != 0 {
+            -magnitude as i16
+        } else {
+            magnitude as i16
+        };
+        i += 1;
+    }
+    table
+}
+
+impl MuLaw {
+    // Compresses a 16-bit linear sample into its μ-law code word.
+    fn encode(linear: i16) -> Self {
+        let linear = linear as i32;
+        let (sign, magnitude) = if linear < 0 {
+            (0x80, -linear)
+        } else {
+            (0x00, linear)
+        };
+        let biased = magnitude.min(MULAW_CLIP) + MULAW_BIAS;
+        // `biased >> 7` is within `1..=255`, so its highest set bit selects one of 8 segments.
+        let exponent = 31 - ((biased >> 7) as u32).leading_zeros() as i32;
+        let mantissa = (biased >> (exponent + 3)) & 0x0F;
+        MuLaw(!((sign | (exponent << 4) | mantissa) as u8))
+    }
+}
+
+// SAFETY: `MuLaw` is a `repr(transparent)` wrapper around `u8`, for which every bit pattern,
+// including zero, is valid.
+#[cfg(feature = "bytemuck")]
+unsafe impl bytemuck::Zeroable for MuLaw {}
+#[cfg(feature = "bytemuck")]
+unsafe impl bytemuck::Pod for MuLaw {}
+
+unsafe impl Sample for MuLaw {
+    const FORMAT: SampleFormat = SampleFormat::MuLaw;
+
+    #[inline]
+    fn to_f32(&self) -> f32 {
+        self.to_i16().to_f32()
+    }
+
+    #[inline]
+    fn to_i16(&self) -> i16 {
+        MULAW_TO_I16[self.0 as usize]
+    }
+
+    #[inline]
+    fn to_u16(&self) -> u16 {
+        self.to_i16().to_u16()
+    }
+
+    #[inline]
+    fn from<S>(sample: &S) -> Self
+    where
+        S: Sample,
+    {
+        MuLaw::encode(sample.to_i16())
+    }
+}
+
+/// A Q15 fixed-point sample: a signed 16-bit integer scaled by 2^-15, covering [-1.0, 1.0).
+///
+/// Q15 shares the layout of `i16`, so buffers of `SampleFormat::I16` can be viewed as `Q15`
+/// without conversion. The floating-point conversions use the exact Q15 scale, unlike the
+/// asymmetric `i16` mapping.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(transparent)]
+pub struct Q15(pub i16);
+
+/// A Q31 fixed-point sample: a signed 32-bit integer scaled by 2^-31, covering [-1.0, 1.0).
+///
+/// There is no 32-bit integer `SampleFormat`, so `Q31` does not implement `Sample`. Use
+/// `from_sample` and `to_sample` to convert from and to the `Sample` types.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(transparent)]
+pub struct Q31(pub i32);
+
+const Q15_SCALE: f32 = 32_768.0;
+const Q31_SCALE: f64 = 2_147_483_648.0;
+
+// SAFETY: `Q15` and `Q31` are `repr(transparent)` wrappers around primitive integers.
+#[cfg(feature = "bytemuck")]
+unsafe impl bytemuck::Zeroable for Q15 {}
+#[cfg(feature = "bytemuck")]
+unsafe impl bytemuck::Pod for Q15 {}
+#[cfg(feature = "bytemuck")]
+unsafe impl bytemuck::Zeroable for Q31 {}
+#[cfg(feature = "bytemuck")]
+unsafe impl bytemuck::Pod for Q31 {}
+
+unsafe impl Sample for Q15 {
+    const FORMAT: SampleFormat = SampleFormat::I16;
+
+    #[inline]
+    fn to_f32(&self) -> f32 {
+        self.0 as f32 / Q15_SCALE
+    }
+
+    #[inline]
+    fn to_i16(&self) -> i16 {
+        self.0
+    }
+
+    #[inline]
+    fn to_u16(&self) -> u16 {
+        self.0.to_u16()
+    }
+
+    #[inline]
+    fn from<S>(sample: &S) -> Self
+    where
+        S: Sample,
+    {
+        Q15(sample.to_i16())
+    }
+}
+
+impl Q15 {
+    /// Converts a floating-point sample, saturating values outside [-1.0, 1.0).
+    #[inline]
+    pub fn from_f32(sample: f32) -> Self {
+        // Float to int `as` casts saturate.
+        Q15((sample * Q15_SCALE).round() as i16)
+    }
+}
+
+impl Q31 {
+    /// Converts a floating-point sample, saturating values outside [-1.0, 1.0).
+    #[inline]
+    pub fn from_f32(sample: f32) -> Self {
+        Q31((sample as f64 * Q31_SCALE).round() as i32)
+    }
+
+    /// Converts this sample to floating-point.
+    #[inline]
+    pub fn to_f32(self) -> f32 {
+        (self.0 as f64 / Q31_SCALE) as f32
+    }
+
+    /// Converts any `Sample` type to Q31.
+    #[inline]
+    pub fn from_sample<S>(sample: &S) -> Self
+    where
+        S: Sample,
+    {
+        Q31::from_f32(sample.to_f32())
+    }
+
+    /// Converts this sample to any `Sample` type.
+    #[inline]
+    pub fn to_sample<S>(self) -> S
+    where
+        S: Sample,
+    {
+        S::from(&self.to_f32())
+    }
+}
+
+impl From<Q15> for Q31 {
+    #[inline]
+    fn from(sample: Q15) -> Self {
+        Q31((sample.0 as i32) << 16)
+    }
+}
+
+impl From<Q31> for Q15 {
+    /// Truncates to the 16 most significant bits, like CMSIS-DSP's `arm_q31_to_q15`.
+    #[inline]
+    fn from(sample: Q31) -> Self {
+        Q15((sample.0 >> 16) as i16)
+    }
+}
+
+// The sample wrappers display their value on the `f32` scale, honouring the formatter's options,
+// so that logs print every sample type the same way.
+macro_rules! impl_display_as_f32 {
+    ($($ty:ty),*) => {
+        $(impl fmt::Display for $ty {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                fmt::Display::fmt(&self.to_f32(), f)
+            }
+        })*
+    };
+}
+
+impl_display_as_f32!(MuLaw, Q15, Q31);
+
 #[cfg(test)]
 mod test {
-    use super::Sample;
+    use super::{MuLaw, Sample, SampleFormat, Q15, Q31};
+
+    #[test]
+    fn sample_format_classification() {
+        for format in SampleFormat::all() {
+            let classes = [format.is_int(), format.is_uint(), format.is_float()];
+            assert!(classes.iter().filter(|&&c| c).count() <= 1);
+            assert!(format.bit_depth() as usize <= 8 * format.sample_size());
+        }
+        assert!(SampleFormat::I16.is_int());
+        assert!(SampleFormat::U16.is_uint());
+        assert!(SampleFormat::F16.is_float());
+        assert!(!SampleFormat::MuLaw.is_int());
+        assert_eq!(SampleFormat::F32.bit_depth(), 32);
+        assert_eq!(SampleFormat::DsdU32.bit_depth(), 1);
+    }
+
+    #[test]
+    fn sample_format_ordering() {
+        let mut formats = SampleFormat::all().to_vec();
+        formats.sort();
+        assert_eq!(
+            formats[..4],
+            [
+                SampleFormat::I16,
+                SampleFormat::U16,
+                SampleFormat::F16,
+                SampleFormat::F32
+            ]
+        );
+        assert_eq!(formats[4], SampleFormat::DsdU8);
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(SampleFormat::F32.to_string(), "f32");
+        assert_eq!(format!("{:>5}", SampleFormat::I16), "  i16");
+        assert_eq!(Q15(16384).to_string(), "0.5");
+        assert_eq!(format!("{:.2}", Q31(-1 << 30)), "-0.50");
+        assert_eq!(MuLaw(0xFF).to_string(), "0");
+    }
 
     #[test]
     fn i16_to_i16() {
@@ -203,4 +701,86 @@ mod test {
         assert_eq!((-0.7f32).to_f32(), -0.7);
         assert_eq!(1.0f32.to_f32(), 1.0);
     }
+
+    #[cfg(feature = "half")]
+    #[test]
+    fn f16_to_i16() {
+        use half::f16;
+        assert_eq!(f16::from_f32(0.0).to_i16(), 0);
+        assert_eq!(f16::from_f32(-0.5).to_i16(), i16::MIN / 2);
+        assert_eq!(f16::from_f32(1.0).to_i16(), i16::MAX);
+        assert_eq!(f16::from_f32(-1.0).to_i16(), i16::MIN);
+    }
+
+    #[cfg(feature = "half")]
+    #[test]
+    fn f32_to_f16() {
+        use half::f16;
+        assert_eq!(<f16 as Sample>::from(&0.0f32), f16::from_f32(0.0));
+        assert_eq!(<f16 as Sample>::from(&-0.5f32), f16::from_f32(-0.5));
+        assert_eq!(<f16 as Sample>::from(&i16::MAX), f16::from_f32(1.0));
+        assert_eq!(<f16 as Sample>::from(&0u16), f16::from_f32(-1.0));
+    }
+
+    #[test]
+    fn mulaw_to_i16() {
+        // Reference values from the G.711 expansion tables.
+        assert_eq!(MuLaw(0xFF).to_i16(), 0);
+        assert_eq!(MuLaw(0x7F).to_i16(), 0);
+        assert_eq!(MuLaw(0xFE).to_i16(), 8);
+        assert_eq!(MuLaw(0x7E).to_i16(), -8);
+        assert_eq!(MuLaw(0xEF).to_i16(), 132);
+        assert_eq!(MuLaw(0x80).to_i16(), 32124);
+        assert_eq!(MuLaw(0x00).to_i16(), -32124);
+    }
+
+    #[test]
+    fn i16_to_mulaw() {
+        assert_eq!(<MuLaw as Sample>::from(&0i16), MuLaw(0xFF));
+        assert_eq!(<MuLaw as Sample>::from(&8i16), MuLaw(0xFE));
+        assert_eq!(<MuLaw as Sample>::from(&-8i16), MuLaw(0x7E));
+        assert_eq!(<MuLaw as Sample>::from(&i16::MAX), MuLaw(0x80));
+        assert_eq!(<MuLaw as Sample>::from(&i16::MIN), MuLaw(0x00));
+    }
+
+    #[test]
+    fn mulaw_round_trip() {
+        for code in 0..=u8::MAX {
+            let linear = MuLaw(code).to_i16();
+            let expected = if code == 0x7F { 0xFF } else { code };
+            assert_eq!(<MuLaw as Sample>::from(&linear), MuLaw(expected));
+        }
+    }
+
+    #[test]
+    fn mulaw_follows_compression_curve() {
+        // The segmented G.711 encoding approximates `ln(1 + μ|x|) / ln(1 + μ)` with μ = 255.
+        for code in 0x80..=0xFFu8 {
+            let x = MuLaw(code).to_f32();
+            let expected = (1.0 + 255.0 * x).ln() / 256f32.ln();
+            let step = (0xFF - code) as f32 / 128.0;
+            assert!((expected - step).abs() < 0.025, "code {:#04x}", code);
+        }
+    }
+
+    #[test]
+    fn q15_to_f32() {
+        assert_eq!(Q15(0).to_f32(), 0.0);
+        assert_eq!(Q15(16384).to_f32(), 0.5);
+        assert_eq!(Q15(i16::MIN).to_f32(), -1.0);
+        assert_eq!(Q15::from_f32(-0.25), Q15(-8192));
+        assert_eq!(Q15::from_f32(1.0), Q15(i16::MAX));
+        assert_eq!(Q15::from_f32(-2.0), Q15(i16::MIN));
+    }
+
+    #[test]
+    fn q31_conversions() {
+        assert_eq!(Q31::from_f32(0.5), Q31(1 << 30));
+        assert_eq!(Q31::from_f32(1.0), Q31(i32::MAX));
+        assert_eq!(Q31(i32::MIN).to_f32(), -1.0);
+        assert_eq!(Q31::from(Q15(-8192)), Q31(-8192 << 16));
+        assert_eq!(Q15(0x1234), Q31(0x1234_5678).into());
+        assert_eq!(Q31::from_sample(&0i16), Q31(0));
+        assert_eq!(Q31(1 << 30).to_sample::<f32>(), 0.5);
+    }
 }
@@ -0,0 +1,222 @@
+//! Running several streams on a common timeline.
+//!
+//! Backs `StreamSession`.
+
+use crate::traits::StreamTrait;
+use crate::{PauseStreamError, PlayStreamError, SampleRate, StreamState};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Plays, pauses and seeks a group of streams together, such as the tracks of a DAW or the stems
+/// of a player.
+///
+/// The session keeps a timeline counted in frames at its sample rate. Each stream reads its
+/// position on the timeline from a [`SessionCursor`] moved into its data callback, so all streams
+/// of the session render the same frames at the same time.
+///
+/// The streams should run on the same device, or on devices sharing a clock, at the sample rate
+/// of the session. Otherwise they drift apart over time, see
+/// [`ClockDrift`](crate::processing::drift::ClockDrift).
+pub struct StreamSession {
+    shared: Arc<Shared>,
+    sample_rate: SampleRate,
+    streams: Vec<Box<dyn StreamTrait>>,
+}
+
+// The transport, shared by the session and the cursors.
+#[derive(Debug)]
+struct Shared {
+    playing: AtomicBool,
+    // Bumped by every seek, telling the cursors to jump to `seek_position`.
+    seek_generation: AtomicU64,
+    seek_position: AtomicU64,
+    // The furthest position reached by any cursor.
+    position: AtomicU64,
+}
+
+impl StreamSession {
+    /// Creates a paused session at the start of its timeline, for streams running at
+    /// `sample_rate`.
+    pub fn new(sample_rate: SampleRate) -> Self {
+        StreamSession {
+            shared: Arc::new(Shared {
+                playing: AtomicBool::new(false),
+                seek_generation: AtomicU64::new(0),
+                seek_position: AtomicU64::new(0),
+                position: AtomicU64::new(0),
+            }),
+            sample_rate,
+            streams: vec![],
+        }
+    }
+
+    /// A cursor following the timeline, to be moved into the data callback of a stream that is
+    /// then added with `add`.
+    pub fn cursor(&self) -> SessionCursor {
+        SessionCursor {
+            shared: self.shared.clone(),
+            generation: self.shared.seek_generation.load(Ordering::Acquire),
+            position: self.shared.seek_position.load(Ordering::Acquire),
+        }
+    }
+
+    /// Adds a stream to the session, which plays and pauses it from now on.
+    ///
+    /// The stream keeps its state until the session is played or paused next. Its cursor renders
+    /// silence while the session is paused, so a stream that is already running does no harm.
+    pub fn add<S: StreamTrait + 'static>(&mut self, stream: S) {
+        self.streams.push(Box::new(stream));
+    }
+
+    /// The number of streams in the session.
+    pub fn len(&self) -> usize {
+        self.streams.len()
+    }
+
+    /// Whether the session has no streams.
+    pub fn is_empty(&self) -> bool {
+        self.streams.is_empty()
+    }
+
+    /// Plays all streams, from the current position of the timeline.
+    ///
+    /// Every stream is played even if some fail, returning the first error.
+    pub fn play(&self) -> Result<(), PlayStreamError> {
+        self.shared.playing.store(true, Ordering::Release);
+        first_error(self.streams.iter().map(|stream| stream.play()))
+    }
+
+    /// Pauses all streams, keeping the position of the timeline.
+    ///
+    /// The cursors stop advancing right away, so the streams resume in step even if some were
+    /// paused later than others. Every stream is paused even if some fail, returning the first
+    /// error.
+    pub fn pause(&self) -> Result<(), PauseStreamError> {
+        self.shared.playing.store(false, Ordering::Release);
+        first_error(self.streams.iter().map(|stream| stream.pause()))
+    }
+
+    /// Whether the session is playing.
+    pub fn state(&self) -> StreamState {
+        if self.shared.playing.load(Ordering::Acquire) {
+            StreamState::Playing
+        } else {
+            StreamState::Paused
+        }
+    }
+
+    /// Moves all cursors to `frame` of the timeline, from their next callback on.
+    pub fn seek(&self, frame: u64) {
+        self.shared.seek_position.store(frame, Ordering::Release);
+        self.shared.position.store(frame, Ordering::Release);
+        self.shared.seek_generation.fetch_add(1, Ordering::AcqRel);
+    }
+
+    /// The position of the timeline, in frames: the end of the furthest buffer rendered by any
+    /// stream.
+    pub fn position(&self) -> u64 {
+        self.shared.position.load(Ordering::Acquire)
+    }
+
+    /// The position of the timeline, as the time since its start.
+    pub fn time(&self) -> Duration {
+        let position = self.position();
+        let rate = self.sample_rate.0 as u64;
+        let secs = position / rate;
+        let nanos = (position % rate) * 1_000_000_000 / rate;
+        Duration::new(secs, nanos as u32)
+    }
+
+    /// The sample rate of the timeline.
+    pub fn sample_rate(&self) -> SampleRate {
+        self.sample_rate
+    }
+}
+
+impl fmt::Debug for StreamSession {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StreamSession")
+            .field("sample_rate", &self.sample_rate)
+            .field("streams", &self.streams.len())
+            .field("state", &self.state())
+            .field("position", &self.position())
+            .finish()
+    }
+}
+
+// Runs through all of `results`, returning the first error.
+fn first_error<E>(results: impl Iterator<Item = Result<(), E>>) -> Result<(), E> {
+    let mut first = Ok(());
+    for result in results {
+        if first.is_ok() {
+            first = result;
+        }
+    }
+    first
+}
+
+/// Follows the timeline of a `StreamSession` from the data callback of one of its streams, as
+/// returned by `StreamSession::cursor`.
+#[derive(Debug)]
+pub struct SessionCursor {
+    shared: Arc<Shared>,
+    generation: u64,
+    position: u64,
+}
+
+impl SessionCursor {
+    /// The position on the timeline of the first of the `frames` frames of the current buffer,
+    /// or `None` while the session is paused, in which case the callback should render silence.
+    ///
+    /// Call it once from every callback of the stream. Does not allocate or block.
+    pub fn advance(&mut self, frames: usize) -> Option<u64> {
+        if !self.shared.playing.load(Ordering::Acquire) {
+            return None;
+        }
+        let generation = self.shared.seek_generation.load(Ordering::Acquire);
+        if generation != self.generation {
+            self.generation = generation;
+            self.position = self.shared.seek_position.load(Ordering::Acquire);
+        }
+        let position = self.position;
+        self.position += frames as u64;
+        self.shared
+            .position
+            .fetch_max(self.position, Ordering::AcqRel);
+        Some(position)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::StreamSession;
+    use crate::{SampleRate, StreamState};
+    use std::time::Duration;
+
+    #[test]
+    fn cursors_follow_transport() {
+        let session = StreamSession::new(SampleRate(48000));
+        let mut first = session.cursor();
+        let mut second = session.cursor();
+        assert_eq!(first.advance(256), None);
+
+        session.play().unwrap();
+        assert_eq!(session.state(), StreamState::Playing);
+        assert_eq!(first.advance(256), Some(0));
+        assert_eq!(second.advance(256), Some(0));
+        assert_eq!(first.advance(256), Some(256));
+        assert_eq!(session.position(), 512);
+
+        session.pause().unwrap();
+        assert_eq!(second.advance(256), None);
+        session.play().unwrap();
+        assert_eq!(second.advance(256), Some(256));
+
+        session.seek(48000);
+        assert_eq!(first.advance(128), Some(48000));
+        assert_eq!(second.advance(128), Some(48000));
+        assert_eq!(session.time(), Duration::new(1, 2_666_666));
+    }
+}
@@ -0,0 +1,120 @@
+//! Conversions between cpal and the symphonia decoding framework.
+//!
+//! Enable the `symphonia` feature to use this module.
+
+use crate::{Sample, SampleFormat};
+use std::cmp;
+use std::convert::TryFrom;
+use symphonia_core::audio::{AudioBuffer, AudioBufferRef, Signal};
+use symphonia_core::conv::IntoSample;
+use symphonia_core::sample::{Sample as SymphoniaSample, SampleFormat as SymphoniaSampleFormat};
+use thiserror::Error;
+
+/// The sample format has no equivalent on the other side of the conversion.
+#[derive(Clone, Debug, Error)]
+#[error("the sample format has no equivalent")]
+pub struct UnsupportedSampleFormat;
+
+impl TryFrom<SampleFormat> for SymphoniaSampleFormat {
+    type Error = UnsupportedSampleFormat;
+
+    fn try_from(sample_format: SampleFormat) -> Result<Self, Self::Error> {
+        match sample_format {
+            SampleFormat::I16 => Ok(SymphoniaSampleFormat::S16),
+            SampleFormat::U16 => Ok(SymphoniaSampleFormat::U16),
+            SampleFormat::F32 => Ok(SymphoniaSampleFormat::F32),
+            _ => Err(UnsupportedSampleFormat),
+        }
+    }
+}
+
+impl TryFrom<SymphoniaSampleFormat> for SampleFormat {
+    type Error = UnsupportedSampleFormat;
+
+    fn try_from(sample_format: SymphoniaSampleFormat) -> Result<Self, Self::Error> {
+        match sample_format {
+            SymphoniaSampleFormat::S16 => Ok(SampleFormat::I16),
+            SymphoniaSampleFormat::U16 => Ok(SampleFormat::U16),
+            SymphoniaSampleFormat::F32 => Ok(SampleFormat::F32),
+            _ => Err(UnsupportedSampleFormat),
+        }
+    }
+}
+
+/// Copies the frames of a decoded symphonia buffer into an interleaved stream buffer, converting
+/// each sample to `T`.
+///
+/// `dst` is expected to hold as many channels as `src`. Only whole frames are copied: if `dst` is
+/// too small for all of `src`, the remaining frames are left out. Returns the number of frames
+/// copied.
+pub fn copy_interleaved<T>(src: &AudioBufferRef<'_>, dst: &mut [T]) -> usize
+where
+    T: Sample,
+{
+    match src {
+        AudioBufferRef::U8(buf) => copy_interleaved_from(buf, dst),
+        AudioBufferRef::U16(buf) => copy_interleaved_from(buf, dst),
+        AudioBufferRef::U24(buf) => copy_interleaved_from(buf, dst),
+        AudioBufferRef::U32(buf) => copy_interleaved_from(buf, dst),
+        AudioBufferRef::S8(buf) => copy_interleaved_from(buf, dst),
+        AudioBufferRef::S16(buf) => copy_interleaved_from(buf, dst),
+        AudioBufferRef::S24(buf) => copy_interleaved_from(buf, dst),
+        AudioBufferRef::S32(buf) => copy_interleaved_from(buf, dst),
+        AudioBufferRef::F32(buf) => copy_interleaved_from(buf, dst),
+        AudioBufferRef::F64(buf) => copy_interleaved_from(buf, dst),
+    }
+}
+
+fn copy_interleaved_from<S, T>(src: &AudioBuffer<S>, dst: &mut [T]) -> usize
+where
+    S: SymphoniaSample + IntoSample<f32>,
+    T: Sample,
+{
+    let channels = src.spec().channels.count();
+    if channels == 0 {
+        return 0;
+    }
+    let frames = cmp::min(src.frames(), dst.len() / channels);
+    for channel in 0..channels {
+        let samples = &src.chan(channel)[..frames];
+        let dst_samples = dst.iter_mut().skip(channel).step_by(channels);
+        for (dst_sample, &sample) in dst_samples.zip(samples) {
+            *dst_sample = T::from(&sample.into_sample());
+        }
+    }
+    frames
+}
+
+#[cfg(test)]
+mod test {
+    use super::copy_interleaved;
+    use std::borrow::Cow;
+    use symphonia_core::audio::{AudioBuffer, AudioBufferRef, Channels, Signal, SignalSpec};
+
+    #[test]
+    fn copy_interleaves_planes() {
+        let spec = SignalSpec::new(48_000, Channels::FRONT_LEFT | Channels::FRONT_RIGHT);
+        let mut buf = AudioBuffer::<i16>::new(4, spec);
+        buf.render_reserved(Some(3));
+        buf.chan_mut(0).copy_from_slice(&[0, 16384, -16384]);
+        buf.chan_mut(1).copy_from_slice(&[i16::MIN, 0, 0]);
+        let src = AudioBufferRef::S16(Cow::Borrowed(&buf));
+
+        let mut dst = [1.0f32; 5];
+        assert_eq!(copy_interleaved(&src, &mut dst), 2);
+        assert_eq!(dst, [0.0, -1.0, 0.5, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn copy_ignores_partial_frames() {
+        let spec = SignalSpec::new(48_000, Channels::FRONT_LEFT | Channels::FRONT_RIGHT);
+        let mut buf = AudioBuffer::<i16>::new(4, spec);
+        buf.render_reserved(Some(3));
+        let src = AudioBufferRef::S16(Cow::Borrowed(&buf));
+
+        assert_eq!(copy_interleaved(&src, &mut [0.0f32; 0]), 0);
+        let mut dst = [1.0f32; 1];
+        assert_eq!(copy_interleaved(&src, &mut dst), 0);
+        assert_eq!(dst, [1.0]);
+    }
+}
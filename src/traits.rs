@@ -1,11 +1,16 @@
 //! The suite of traits allowing CPAL to abstract over hosts, devices, event loops and stream IDs.
 
 use crate::{
-    BuildStreamError, Data, DefaultStreamConfigError, DeviceNameError, DevicesError,
-    InputCallbackInfo, InputDevices, OutputCallbackInfo, OutputDevices, PauseStreamError,
-    PlayStreamError, Sample, SampleFormat, StreamConfig, StreamError, SupportedStreamConfig,
-    SupportedStreamConfigRange, SupportedStreamConfigsError,
+    BuildStreamError, Data, DefaultStreamConfigError, DeviceCapabilities, DeviceDirection,
+    DeviceFilter, DeviceInfo, DeviceNameError, DeviceRole, DevicesError, DirectMonitoringError,
+    FrameCount, InputCallbackInfo, InputDevices, InputPermission, NegotiatedConfig,
+    OutputCallbackInfo, OutputDevices, PauseStreamError, PlanarBuffer, PlanarBufferMut,
+    PlayStreamError, ReconfigureStreamError, Sample, SampleFormat, SampleRate, SetSampleRateError,
+    ShareMode, StreamConfig, StreamError, StreamLatency, StreamOptions, StreamState, StreamUsage,
+    SupportedStreamConfig, SupportedStreamConfigRange, SupportedStreamConfigsError,
 };
+#[cfg(feature = "buffered")]
+use std::time::Duration;
 
 /// A **Host** provides access to the available audio devices on the system.
 ///
@@ -50,6 +55,25 @@ pub trait HostTrait {
     /// Returns `None` if no output device is available.
     fn default_output_device(&self) -> Option<Self::Device>;
 
+    /// The default input device for `role`.
+    ///
+    /// Only WASAPI keeps separate default devices for the roles. Other hosts return the
+    /// default input device for all of them.
+    fn default_input_device_for(&self, role: DeviceRole) -> Option<Self::Device> {
+        let _ = role;
+        self.default_input_device()
+    }
+
+    /// The default output device for `role`, such as the headset for
+    /// `DeviceRole::Communications` while music plays on the speakers.
+    ///
+    /// Only WASAPI keeps separate default devices for the roles. Other hosts return the
+    /// default output device for all of them.
+    fn default_output_device_for(&self, role: DeviceRole) -> Option<Self::Device> {
+        let _ = role;
+        self.default_output_device()
+    }
+
     /// An iterator yielding all `Device`s currently available to the system that support one or more
     /// input stream formats.
     ///
@@ -77,6 +101,49 @@ pub trait HostTrait {
         }
         Ok(self.devices()?.filter(supports_output::<Self::Device>))
     }
+
+    /// The devices that pass `filter`.
+    ///
+    /// The provided implementation probes every device with
+    /// [`DeviceFilter::matches`](crate::DeviceFilter::matches). ALSA and WASAPI skip the devices
+    /// of the wrong direction, and ALSA the virtual ones, before opening them.
+    fn devices_filtered(&self, filter: DeviceFilter) -> Result<Vec<Self::Device>, DevicesError> {
+        Ok(self
+            .devices()?
+            .filter(|device| filter.matches(device))
+            .collect())
+    }
+
+    /// Whether the application may record from the input devices of the host.
+    ///
+    /// CoreAudio asks `AVCaptureDevice` on macOS and iOS, and Oboe checks the `RECORD_AUDIO`
+    /// permission on Android, which does not tell `NotDetermined` from `Denied`. Other hosts do
+    /// not guard recording, and report `Granted`.
+    fn input_permission(&self) -> InputPermission {
+        InputPermission::Granted
+    }
+
+    /// Asks the user for permission to record if they have not been asked yet, and calls
+    /// `callback` with the answer.
+    ///
+    /// The callback is called right away when the permission is already determined, and on
+    /// another thread once the user answers otherwise. Android delivers the answer to the
+    /// activity, so applications have to request `RECORD_AUDIO` there; this reports the current
+    /// permission.
+    fn request_input_permission<F>(&self, callback: F)
+    where
+        F: FnOnce(InputPermission) + Send + 'static,
+    {
+        callback(self.input_permission())
+    }
+
+    /// The version of the library or driver behind the host, for diagnostics.
+    ///
+    /// ALSA reports the version of `alsa-lib`, and ASIO the name and version of the loaded
+    /// driver, if any. Other hosts return `None`.
+    fn backend_version(&self) -> Option<String> {
+        None
+    }
 }
 
 /// A device that is capable of audio input and/or output.
@@ -94,6 +161,29 @@ pub trait DeviceTrait {
     /// The human-readable name of the device.
     fn name(&self) -> Result<String, DeviceNameError>;
 
+    /// Describes the device in more detail than its name, such as for a settings UI.
+    ///
+    /// The provided implementation only knows the name, which doubles as the id, and whether the
+    /// device supports input and output configs.
+    fn info(&self) -> Result<DeviceInfo, DeviceNameError> {
+        crate::device_info::from_name(self)
+    }
+
+    /// What the device can do, such as for deciding how to open it.
+    ///
+    /// The provided implementation derives the latency and the channel layouts from the supported
+    /// configs, and the default flags from `info`. Hosts add what else they know:
+    ///
+    /// - WASAPI supports exclusive mode, records output devices in loopback, and reports the
+    ///   hardware volume support of the endpoint.
+    /// - CoreAudio on macOS supports exclusive mode and reports whether the device has a volume
+    ///   control.
+    /// - ALSA reports whether the mixer of the card has a volume control.
+    /// - AAudio supports exclusive mode.
+    fn capabilities(&self) -> Result<DeviceCapabilities, SupportedStreamConfigsError> {
+        crate::device_info::capabilities_from_configs(self)
+    }
+
     /// An iterator yielding formats that are supported by the backend.
     ///
     /// Can return an error if the device is no longer valid (e.g. it has been disconnected).
@@ -108,6 +198,23 @@ pub trait DeviceTrait {
         &self,
     ) -> Result<Self::SupportedOutputConfigs, SupportedStreamConfigsError>;
 
+    /// Whether the device supports input streams with `config` and `sample_format`.
+    ///
+    /// The provided implementation looks for a supported input config that
+    /// [`supports`](SupportedStreamConfigRange::supports) them. ALSA and WASAPI ask the device
+    /// instead, which is faster and also covers combinations the supported configs leave out.
+    fn supports_input_config(&self, config: &StreamConfig, sample_format: SampleFormat) -> bool {
+        self.supported_input_configs()
+            .is_ok_and(|mut configs| configs.any(|c| c.supports(config, sample_format)))
+    }
+
+    /// Whether the device supports output streams with `config` and `sample_format`. See
+    /// [`supports_input_config`](Self::supports_input_config).
+    fn supports_output_config(&self, config: &StreamConfig, sample_format: SampleFormat) -> bool {
+        self.supported_output_configs()
+            .is_ok_and(|mut configs| configs.any(|c| c.supports(config, sample_format)))
+    }
+
     /// The default input stream format for the device.
     fn default_input_config(&self) -> Result<SupportedStreamConfig, DefaultStreamConfigError>;
 
@@ -166,6 +273,222 @@ pub trait DeviceTrait {
         )
     }
 
+    /// Create an input stream, adapting the device to the callback as allowed by `options`.
+    ///
+    /// Returns the stream along with the configuration it runs with. With
+    /// `options.convert_sample_format` set, its sample format may differ from `T`, in which case
+    /// every buffer is converted before it reaches `data_callback`.
+    fn build_input_stream_with_options<T, D, E>(
+        &self,
+        config: &StreamConfig,
+        options: &StreamOptions,
+        data_callback: D,
+        error_callback: E,
+    ) -> Result<(Self::Stream, NegotiatedConfig), BuildStreamError>
+    where
+        T: Sample + Send + 'static,
+        D: FnMut(&[T], &InputCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        crate::conversion::build_input_stream(self, config, options, data_callback, error_callback)
+    }
+
+    /// Create an output stream, adapting the device to the callback as allowed by `options`.
+    ///
+    /// Returns the stream along with the configuration it runs with. With
+    /// `options.convert_sample_format` set, its sample format may differ from `T`, in which case
+    /// the samples written by `data_callback` are converted to it.
+    fn build_output_stream_with_options<T, D, E>(
+        &self,
+        config: &StreamConfig,
+        options: &StreamOptions,
+        data_callback: D,
+        error_callback: E,
+    ) -> Result<(Self::Stream, NegotiatedConfig), BuildStreamError>
+    where
+        T: Sample + Send + 'static,
+        D: FnMut(&mut [T], &OutputCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        crate::conversion::build_output_stream(self, config, options, data_callback, error_callback)
+    }
+
+    /// Create an output stream whose callback always writes `f32` samples, whatever the sample
+    /// format of the device.
+    ///
+    /// The device is opened in `f32` where it supports it, and in another convertible format
+    /// otherwise, with the samples dithered and converted on their way to it. Returns the stream
+    /// along with the configuration it runs with.
+    fn build_output_stream_f32<D, E>(
+        &self,
+        config: &StreamConfig,
+        data_callback: D,
+        error_callback: E,
+    ) -> Result<(Self::Stream, NegotiatedConfig), BuildStreamError>
+    where
+        D: FnMut(&mut [f32], &OutputCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        let options = StreamOptions {
+            convert_sample_format: true,
+            ..Default::default()
+        };
+        self.build_output_stream_with_options(config, &options, data_callback, error_callback)
+    }
+
+    /// Create an input stream whose callback receives the frames with a buffer for each channel.
+    ///
+    /// Hosts whose devices are planar pass their buffers to the callback as they are. Otherwise
+    /// the interleaved frames are copied into a buffer for each channel before every callback.
+    fn build_input_stream_planar<T, D, E>(
+        &self,
+        config: &StreamConfig,
+        data_callback: D,
+        error_callback: E,
+    ) -> Result<Self::Stream, BuildStreamError>
+    where
+        T: Sample + Send + 'static,
+        D: FnMut(&PlanarBuffer<T>, &InputCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        crate::planar::build_input_stream(self, config, data_callback, error_callback)
+    }
+
+    /// Create an output stream whose callback writes the frames with a buffer for each channel.
+    ///
+    /// Hosts whose devices are planar pass their buffers to the callback as they are. Otherwise
+    /// the buffers are interleaved into the device's buffer after every callback. The buffers
+    /// hold silence when passed to the callback.
+    fn build_output_stream_planar<T, D, E>(
+        &self,
+        config: &StreamConfig,
+        data_callback: D,
+        error_callback: E,
+    ) -> Result<Self::Stream, BuildStreamError>
+    where
+        T: Sample + Send + 'static,
+        D: FnMut(&mut PlanarBufferMut<T>, &OutputCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        crate::planar::build_output_stream(self, config, data_callback, error_callback)
+    }
+
+    /// Create an input stream whose samples are read with
+    /// [`BufferedInput::read`](crate::buffered::BufferedInput::read) rather than a callback.
+    ///
+    /// The samples pass through a ring buffer holding `latency` worth of frames. Frames captured
+    /// while it is full are dropped.
+    #[cfg(feature = "buffered")]
+    fn build_input_stream_buffered<T, E>(
+        &self,
+        config: &StreamConfig,
+        latency: Duration,
+        error_callback: E,
+    ) -> Result<(Self::Stream, crate::buffered::BufferedInput<T>), BuildStreamError>
+    where
+        T: Sample + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        let (mut callback, buffered) = crate::buffered::input(config, latency);
+        let stream = self.build_input_stream(
+            config,
+            move |data: &[T], _: &InputCallbackInfo| callback(data),
+            error_callback,
+        )?;
+        Ok((stream, buffered))
+    }
+
+    /// Create an input stream read by several consumers, each through its own
+    /// [`BufferedInput`](crate::buffered::BufferedInput).
+    ///
+    /// This lets metering, recording and streaming share one capture of the device, which many
+    /// hosts do not allow to be opened more than once. Every consumer reads all captured frames
+    /// from a ring buffer of its own, holding `latency` worth of frames. A consumer that falls
+    /// behind drops frames, as counted by its `dropped_frames`, without affecting the others.
+    #[cfg(feature = "buffered")]
+    fn build_input_stream_tee<T, E>(
+        &self,
+        config: &StreamConfig,
+        latency: Duration,
+        consumers: usize,
+        error_callback: E,
+    ) -> Result<(Self::Stream, Vec<crate::buffered::BufferedInput<T>>), BuildStreamError>
+    where
+        T: Sample + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        let (mut callback, buffered) = crate::buffered::tee(config, latency, consumers);
+        let stream = self.build_input_stream(
+            config,
+            move |data: &[T], _: &InputCallbackInfo| callback(data),
+            error_callback,
+        )?;
+        Ok((stream, buffered))
+    }
+
+    /// Create an output stream whose samples are written with
+    /// [`BufferedOutput::write`](crate::buffered::BufferedOutput::write) rather than a callback.
+    ///
+    /// The samples pass through a ring buffer holding `latency` worth of frames, which `write`
+    /// waits on when it is full. The stream plays silence while it is empty.
+    #[cfg(feature = "buffered")]
+    fn build_output_stream_buffered<T, E>(
+        &self,
+        config: &StreamConfig,
+        latency: Duration,
+        error_callback: E,
+    ) -> Result<(Self::Stream, crate::buffered::BufferedOutput<T>), BuildStreamError>
+    where
+        T: Sample + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        let (mut callback, buffered) = crate::buffered::output(config, latency);
+        let stream = self.build_output_stream(
+            config,
+            move |data: &mut [T], _: &OutputCallbackInfo| callback(data),
+            error_callback,
+        )?;
+        Ok((stream, buffered))
+    }
+
+    /// Create an input stream whose samples are read from async code with
+    /// [`BufferedInput::recv`](crate::buffered::BufferedInput::recv).
+    ///
+    /// This is [`build_input_stream_buffered`](Self::build_input_stream_buffered), with the
+    /// async reading enabled by the `async` feature.
+    #[cfg(feature = "async")]
+    fn build_input_stream_async<T, E>(
+        &self,
+        config: &StreamConfig,
+        latency: Duration,
+        error_callback: E,
+    ) -> Result<(Self::Stream, crate::buffered::BufferedInput<T>), BuildStreamError>
+    where
+        T: Sample + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        self.build_input_stream_buffered(config, latency, error_callback)
+    }
+
+    /// Create an output stream whose samples are written from async code with
+    /// [`BufferedOutput::send`](crate::buffered::BufferedOutput::send).
+    ///
+    /// This is [`build_output_stream_buffered`](Self::build_output_stream_buffered), with the
+    /// async writing enabled by the `async` feature.
+    #[cfg(feature = "async")]
+    fn build_output_stream_async<T, E>(
+        &self,
+        config: &StreamConfig,
+        latency: Duration,
+        error_callback: E,
+    ) -> Result<(Self::Stream, crate::buffered::BufferedOutput<T>), BuildStreamError>
+    where
+        T: Sample + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        self.build_output_stream_buffered(config, latency, error_callback)
+    }
+
     /// Create a dynamically typed input stream.
     fn build_input_stream_raw<D, E>(
         &self,
@@ -189,9 +512,181 @@ pub trait DeviceTrait {
     where
         D: FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static,
         E: FnMut(StreamError) + Send + 'static;
+
+    /// Create a dynamically typed input stream, opening the device in the given share mode.
+    ///
+    /// `ShareMode::Exclusive` is supported by WASAPI, CoreAudio on macOS through hog mode, and
+    /// AAudio. It switches the device to the format of the stream, which may be one the system
+    /// mixer does not offer. Other hosts fail with `BuildStreamError::ShareModeNotSupported`, as
+    /// does AAudio when it cannot grant exclusive access.
+    fn build_input_stream_raw_with_share_mode<D, E>(
+        &self,
+        config: &StreamConfig,
+        sample_format: SampleFormat,
+        share_mode: ShareMode,
+        data_callback: D,
+        error_callback: E,
+    ) -> Result<Self::Stream, BuildStreamError>
+    where
+        D: FnMut(&Data, &InputCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        match share_mode {
+            ShareMode::Shared => {
+                self.build_input_stream_raw(config, sample_format, data_callback, error_callback)
+            }
+            ShareMode::Exclusive => Err(BuildStreamError::ShareModeNotSupported),
+        }
+    }
+
+    /// Create a dynamically typed output stream, opening the device in the given share mode.
+    ///
+    /// See [`build_input_stream_raw_with_share_mode`](Self::build_input_stream_raw_with_share_mode)
+    /// for the hosts supporting `ShareMode::Exclusive`.
+    fn build_output_stream_raw_with_share_mode<D, E>(
+        &self,
+        config: &StreamConfig,
+        sample_format: SampleFormat,
+        share_mode: ShareMode,
+        data_callback: D,
+        error_callback: E,
+    ) -> Result<Self::Stream, BuildStreamError>
+    where
+        D: FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        match share_mode {
+            ShareMode::Shared => {
+                self.build_output_stream_raw(config, sample_format, data_callback, error_callback)
+            }
+            ShareMode::Exclusive => Err(BuildStreamError::ShareModeNotSupported),
+        }
+    }
+
+    /// Create a dynamically typed input stream in the given share mode, telling the system what
+    /// it is used for.
+    ///
+    /// - AAudio sets the usage and content type of the stream, and records communication streams
+    ///   with the voice communication input preset, which enables echo cancellation.
+    /// - WASAPI sets the audio stream category of the stream.
+    /// - CoreAudio on iOS sets the category and mode of the shared audio session, affecting every
+    ///   stream of the application.
+    ///
+    /// Other hosts ignore the usage.
+    fn build_input_stream_raw_with_usage<D, E>(
+        &self,
+        config: &StreamConfig,
+        sample_format: SampleFormat,
+        share_mode: ShareMode,
+        usage: StreamUsage,
+        data_callback: D,
+        error_callback: E,
+    ) -> Result<Self::Stream, BuildStreamError>
+    where
+        D: FnMut(&Data, &InputCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        let _ = usage;
+        self.build_input_stream_raw_with_share_mode(
+            config,
+            sample_format,
+            share_mode,
+            data_callback,
+            error_callback,
+        )
+    }
+
+    /// Create a dynamically typed output stream in the given share mode, telling the system what
+    /// it is used for.
+    ///
+    /// See [`build_input_stream_raw_with_usage`](Self::build_input_stream_raw_with_usage) for the
+    /// hosts taking the usage into account.
+    fn build_output_stream_raw_with_usage<D, E>(
+        &self,
+        config: &StreamConfig,
+        sample_format: SampleFormat,
+        share_mode: ShareMode,
+        usage: StreamUsage,
+        data_callback: D,
+        error_callback: E,
+    ) -> Result<Self::Stream, BuildStreamError>
+    where
+        D: FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        let _ = usage;
+        self.build_output_stream_raw_with_share_mode(
+            config,
+            sample_format,
+            share_mode,
+            data_callback,
+            error_callback,
+        )
+    }
+
+    /// Enables or disables direct monitoring, where the interface routes its inputs straight to
+    /// its outputs without the round trip through the computer, for zero-latency monitoring while
+    /// recording.
+    ///
+    /// - ASIO routes all inputs to the first outputs through the driver's input monitor.
+    /// - ALSA switches the "Direct Monitor" controls of the card's mixer, which only some
+    ///   interfaces have.
+    /// - CoreAudio on macOS sets the play-through of the device.
+    ///
+    /// Others return `DirectMonitoringError::NotSupported`, as do devices without the feature.
+    fn set_direct_monitoring(&self, enabled: bool) -> Result<(), DirectMonitoringError> {
+        let _ = enabled;
+        Err(DirectMonitoringError::NotSupported)
+    }
+
+    /// Switches the device to run at `sample_rate`, so that streams at that rate reach the
+    /// hardware without being resampled by the system.
+    ///
+    /// - CoreAudio on macOS sets the nominal sample rate of the device, which applies to every
+    ///   application using it.
+    /// - ALSA devices only run while a stream has them open, so ALSA makes `sample_rate` the rate
+    ///   of the default configs of this device, which streams then open the hardware at.
+    ///
+    /// Others return `SetSampleRateError::NotSupported`. WASAPI runs the device at the rate of
+    /// streams opened with `ShareMode::Exclusive` instead.
+    fn set_default_sample_rate(&self, sample_rate: SampleRate) -> Result<(), SetSampleRateError> {
+        let _ = sample_rate;
+        Err(SetSampleRateError::NotSupported)
+    }
+
+    /// The names of the channels of the device in `direction`, in channel order, such as
+    /// "Mic 1" or "Headphone L".
+    ///
+    /// - CoreAudio on macOS reads the names of the channel elements of the device.
+    /// - ASIO reads the names of the channels of the driver.
+    /// - JACK names the system ports the channels are connected to automatically.
+    ///
+    /// Channels the device leaves unnamed have empty names. Other hosts do not name channels,
+    /// and return an empty list.
+    fn channel_names(&self, direction: DeviceDirection) -> Result<Vec<String>, DeviceNameError> {
+        let _ = direction;
+        Ok(Vec::new())
+    }
 }
 
 /// A stream created from `Device`, with methods to control playback.
+///
+/// # Pausing
+///
+/// A paused stream stops calling its data callback until it is played again; a callback already
+/// running when `pause` is called still completes. Playing a playing stream and pausing a paused
+/// stream do nothing. How the device itself is stopped depends on the host:
+///
+/// - **ALSA:** the device is paused in hardware where it supports it. Otherwise it is stopped,
+///   dropping any pending frames, and prepared again on `play`.
+/// - **WASAPI:** the audio client is stopped and started again. Both are done on the stream's
+///   thread, so errors are reported to the error callback rather than returned.
+/// - **CoreAudio:** the audio unit is stopped and started again.
+/// - **AAudio:** output streams are paused, keeping their buffered frames. Input streams cannot
+///   be paused and are stopped instead.
+/// - **ASIO and JACK:** the driver keeps running; the stream skips its callback and outputs
+///   silence.
+/// - **WebAudio and Emscripten:** the audio context is suspended and resumed.
 pub trait StreamTrait {
     /// Run the stream.
     ///
@@ -199,10 +694,149 @@ pub trait StreamTrait {
     /// call `play` after creation if it is expected that the stream should run immediately.
     fn play(&self) -> Result<(), PlayStreamError>;
 
-    /// Some devices support pausing the audio stream. This can be useful for saving energy in
-    /// moments of silence.
+    /// Pause the stream. This can be useful for saving energy in moments of silence.
     ///
-    /// Note: Not all devices support suspending the stream at the hardware level. This method may
-    /// fail in these cases.
+    /// See the [trait documentation](StreamTrait#pausing) for what pausing does on each host.
     fn pause(&self) -> Result<(), PauseStreamError>;
+
+    /// Whether the stream is playing or paused.
+    ///
+    /// This reflects the last successful call to `play` or `pause`, or whether the host runs
+    /// streams upon creation if neither was called yet.
+    fn state(&self) -> StreamState;
+
+    /// Whether the stream is paused.
+    fn is_paused(&self) -> bool {
+        self.state() == StreamState::Paused
+    }
+
+    /// The time between the data callback and the device playing or capturing its first frame,
+    /// or `None` if the host does not report it.
+    ///
+    /// The latency may change while the stream runs, so apps synchronizing audio with video
+    /// should poll it. The components reported by each host are:
+    ///
+    /// - **ALSA:** the frames queued between the stream and the device, including the hardware
+    ///   FIFO, as `buffered`.
+    /// - **WASAPI:** the padding of the audio client of output streams as `buffered`, and its
+    ///   stream latency as `driver`.
+    /// - **CoreAudio on macOS:** the buffer size as `buffered`, and the latency and safety offset
+    ///   of the device as `driver` and `safety`.
+    /// - **AAudio:** the latency calculated from the stream's timestamps as `buffered`.
+    fn latency(&self) -> Option<StreamLatency> {
+        None
+    }
+
+    /// Let the audio the stream was given so far play out, then pause it.
+    ///
+    /// Blocks until the device has played everything written by the data callback before the
+    /// call. Wrap the stream in a [`DrainOnDrop`](crate::DrainOnDrop) to drain it when dropped,
+    /// rather than cutting off the audio still queued. How draining works depends on the host:
+    ///
+    /// - **ALSA:** the stream stops calling back and the device is drained.
+    /// - **AAudio:** output streams stop calling back and are stopped once their buffered
+    ///   frames have been played.
+    /// - **Others:** the stream keeps calling back for its `latency`, then pauses. Anything
+    ///   written in the meantime may be cut off. Hosts reporting no latency pause right away.
+    ///
+    /// Input streams are paused without waiting on ALSA and AAudio.
+    fn drain(&self) -> Result<(), PauseStreamError> {
+        if let Some(latency) = self.latency() {
+            std::thread::sleep(latency.total());
+        }
+        self.pause()
+    }
+
+    /// The buffer size the stream was opened with, as requested by `BufferSize::Fixed` or
+    /// negotiated for `BufferSize::Preferred`, or `None` if the host does not report it.
+    ///
+    /// The size is in the sense the host gives `BufferSize`, which for ALSA is the size of the
+    /// whole ring buffer rather than of a period. It follows changes made with `set_buffer_size`.
+    fn buffer_size(&self) -> Option<FrameCount> {
+        None
+    }
+
+    /// Change the buffer size of the stream, as set by `BufferSize::Fixed` when building it,
+    /// without tearing the stream down.
+    ///
+    /// Returns the buffer size the device settled on, which may differ from `frames` where the
+    /// host rounds it. The data callback keeps being called with buffers of the new size. Only
+    /// some hosts support this:
+    ///
+    /// - **ALSA:** the device is stopped and set up again, dropping the frames it had queued.
+    /// - **CoreAudio on macOS:** the I/O buffer size of the device is changed, which affects
+    ///   every stream using the device.
+    /// - **AAudio:** the part of the buffer of output streams used to queue frames is resized,
+    ///   lowering or raising the latency. The size of the data callback's buffers is not
+    ///   affected.
+    ///
+    /// Others return `ReconfigureStreamError::NotSupported`.
+    fn set_buffer_size(&self, frames: FrameCount) -> Result<FrameCount, ReconfigureStreamError> {
+        let _ = frames;
+        Err(ReconfigureStreamError::NotSupported)
+    }
+
+    /// The number of periods the buffer of the stream is split into, or `None` if the host does
+    /// not report it.
+    ///
+    /// The data callback handles one period at a time, so the latency of the buffer is the period
+    /// size times this count. ASIO always reports 2, for its double buffering.
+    fn periods(&self) -> Option<u32> {
+        None
+    }
+
+    /// Change the number of periods of the buffer of the stream, keeping the size of each
+    /// period, to trade latency for robustness against scheduling hiccups.
+    ///
+    /// Returns the number of periods the device settled on. Fails with
+    /// `ReconfigureStreamError::StreamConfigNotSupported` for fewer than 2 periods, or if the
+    /// device does not support the resulting buffer size. Only some hosts support this:
+    ///
+    /// - **ALSA:** the device is stopped and set up again, as with `set_buffer_size`.
+    ///
+    /// Others return `ReconfigureStreamError::NotSupported`.
+    fn set_periods(&self, periods: u32) -> Result<u32, ReconfigureStreamError> {
+        let _ = periods;
+        Err(ReconfigureStreamError::NotSupported)
+    }
+
+    /// Change the sample rate of the stream by switching the rate the device runs at.
+    ///
+    /// Players following the rate of their source material use this to keep their output
+    /// bit-perfect. The stream stops calling back while the device is switched, leaving a short
+    /// gap in the audio and dropping the frames queued so far. The error callback then receives
+    /// `StreamError::SampleRateChanged` before the first data callback at the new rate. Processing
+    /// set up through `StreamOptions` keeps assuming the rate the stream was built with.
+    ///
+    /// Fails with `ReconfigureStreamError::StreamConfigNotSupported` if the device cannot run at
+    /// `sample_rate`, in which case the stream keeps its previous rate. Only some hosts support
+    /// this:
+    ///
+    /// - **ALSA:** the device is stopped and set up again, as with `set_buffer_size`.
+    /// - **CoreAudio on macOS:** the audio unit is stopped while the nominal sample rate of the
+    ///   device is changed, which affects every stream using the device.
+    ///
+    /// Others return `ReconfigureStreamError::NotSupported`.
+    fn set_sample_rate(&self, sample_rate: SampleRate) -> Result<(), ReconfigureStreamError> {
+        let _ = sample_rate;
+        Err(ReconfigureStreamError::NotSupported)
+    }
+
+    /// Sets the volume of the stream in the system's volume mixer, from 0.0 to 1.0.
+    ///
+    /// Unlike scaling the samples, as with `StreamControls::set_volume`, this is the volume the
+    /// system mixer shows and changes for the application, such as in the Volume Mixer of
+    /// Windows. WASAPI sets the volume of the stream's audio session, shared with the other
+    /// streams of the process on the same device. Other hosts have no per-stream volume in the
+    /// system mixer and return `ReconfigureStreamError::NotSupported`.
+    fn set_mixer_volume(&self, volume: f32) -> Result<(), ReconfigureStreamError> {
+        let _ = volume;
+        Err(ReconfigureStreamError::NotSupported)
+    }
+
+    /// The volume of the stream in the system's volume mixer, from 0.0 to 1.0, which the user
+    /// may change at any time. See `set_mixer_volume`.
+    fn mixer_volume(&self) -> Result<f32, ReconfigureStreamError> {
+        Err(ReconfigureStreamError::NotSupported)
+    }
 }
@@ -0,0 +1,322 @@
+//! Keeping panics in data callbacks from unwinding into the hosts.
+//!
+//! Most hosts call the data callback from foreign code, where unwinding is undefined behaviour.
+//! The streams of the platform `Device` catch such panics and report them as
+//! `StreamError::CallbackPanicked`.
+
+use crate::{
+    ChannelCount, Data, InputCallbackInfo, OutputCallbackInfo, PlanarBuffer, PlanarBufferMut,
+    Sample, SampleFormat, StreamError,
+};
+use std::any::Any;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex};
+
+/// Wraps the callbacks of an input stream, so that a panic in the data callback is reported to
+/// the error callback. The data callback is not called again after panicking.
+pub(crate) fn input<D, E>(
+    mut data_callback: D,
+    error_callback: E,
+) -> (
+    impl FnMut(&Data, &InputCallbackInfo) + Send + 'static,
+    impl FnMut(StreamError) + Send + 'static,
+)
+where
+    D: FnMut(&Data, &InputCallbackInfo) + Send + 'static,
+    E: FnMut(StreamError) + Send + 'static,
+{
+    let mut guard = Guard::new(error_callback);
+    let error_callback = guard.error_callback();
+    let data_callback = move |data: &Data, info: &InputCallbackInfo| {
+        guard.call(|| data_callback(data, info));
+    };
+    (data_callback, error_callback)
+}
+
+/// Wraps the callbacks of an output stream of `channels` channels, so that a panic in the data
+/// callback is reported to the error callback. The buffer is silenced instead of calling the data
+/// callback again.
+pub(crate) fn output<D, E>(
+    channels: ChannelCount,
+    mut data_callback: D,
+    error_callback: E,
+) -> (
+    impl FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static,
+    impl FnMut(StreamError) + Send + 'static,
+)
+where
+    D: FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static,
+    E: FnMut(StreamError) + Send + 'static,
+{
+    let mut guard = Guard::new(error_callback);
+    let error_callback = guard.error_callback();
+    let mut silence = Silence::new(channels);
+    let data_callback = move |data: &mut Data, info: &OutputCallbackInfo| {
+        if guard.call(|| data_callback(data, info)) {
+            silence.follow(data);
+        } else {
+            silence.fill(data);
+        }
+    };
+    (data_callback, error_callback)
+}
+
+/// Wraps the callbacks of a planar input stream, like `input`.
+pub(crate) fn planar_input<T, D, E>(
+    mut data_callback: D,
+    error_callback: E,
+) -> (
+    impl FnMut(&PlanarBuffer<T>, &InputCallbackInfo) + Send + 'static,
+    impl FnMut(StreamError) + Send + 'static,
+)
+where
+    D: FnMut(&PlanarBuffer<T>, &InputCallbackInfo) + Send + 'static,
+    E: FnMut(StreamError) + Send + 'static,
+{
+    let mut guard = Guard::new(error_callback);
+    let error_callback = guard.error_callback();
+    let data_callback = move |buffer: &PlanarBuffer<T>, info: &InputCallbackInfo| {
+        guard.call(|| data_callback(buffer, info));
+    };
+    (data_callback, error_callback)
+}
+
+/// Wraps the callbacks of a planar output stream, like `output`.
+pub(crate) fn planar_output<T, D, E>(
+    mut data_callback: D,
+    error_callback: E,
+) -> (
+    impl FnMut(&mut PlanarBufferMut<T>, &OutputCallbackInfo) + Send + 'static,
+    impl FnMut(StreamError) + Send + 'static,
+)
+where
+    T: Sample,
+    D: FnMut(&mut PlanarBufferMut<T>, &OutputCallbackInfo) + Send + 'static,
+    E: FnMut(StreamError) + Send + 'static,
+{
+    let mut guard = Guard::new(error_callback);
+    let error_callback = guard.error_callback();
+    let data_callback = move |buffer: &mut PlanarBufferMut<T>, info: &OutputCallbackInfo| {
+        if !guard.call(|| data_callback(buffer, info)) {
+            for channel in buffer.iter_mut() {
+                channel.fill(Sample::from(&0.0f32));
+            }
+        }
+    };
+    (data_callback, error_callback)
+}
+
+// Calls the data callback until it panics. The error callback is shared with the host.
+struct Guard<E> {
+    error_callback: Arc<Mutex<E>>,
+    panicked: bool,
+}
+
+impl<E> Guard<E>
+where
+    E: FnMut(StreamError) + Send + 'static,
+{
+    fn new(error_callback: E) -> Self {
+        Guard {
+            error_callback: Arc::new(Mutex::new(error_callback)),
+            panicked: false,
+        }
+    }
+
+    // The error callback to pass to the host.
+    fn error_callback(&self) -> impl FnMut(StreamError) + Send + 'static {
+        let error_callback = self.error_callback.clone();
+        move |err| report(&error_callback, err)
+    }
+
+    // Calls `callback` unless it panicked before, returning whether it ran to completion.
+    #[inline]
+    fn call(&mut self, callback: impl FnOnce()) -> bool {
+        if self.panicked {
+            return false;
+        }
+        // The callback's state is never used again after a panic, so it cannot be observed
+        // broken.
+        match panic::catch_unwind(AssertUnwindSafe(callback)) {
+            Ok(()) => true,
+            Err(payload) => {
+                self.panicked = true;
+                let message = panic_message(payload.as_ref());
+                report(
+                    &self.error_callback,
+                    StreamError::CallbackPanicked { message },
+                );
+                false
+            }
+        }
+    }
+}
+
+fn report<E: FnMut(StreamError)>(error_callback: &Mutex<E>, err: StreamError) {
+    let mut error_callback = error_callback
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    (*error_callback)(err);
+}
+
+// The message passed to `panic!`, if it was a string.
+fn panic_message(payload: &(dyn Any + Send)) -> Option<String> {
+    payload
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+}
+
+// The DSD idle pattern, which averages to zero. All zero bits are full-scale negative DC.
+const DSD_SILENCE: u8 = 0x69;
+// The markers in the top byte of DoP frames, alternating from frame to frame.
+const DOP_MARKERS: [u8; 2] = [0x05, 0xfa];
+
+// Fills the buffers of an output stream with silence, which is not all zeroes for unsigned,
+// µ-law and DSD formats.
+struct Silence {
+    channels: usize,
+    // The DoP marker of the last frame written, which the next frame must not repeat for the DAC
+    // to keep decoding DSD.
+    dop_marker: u8,
+}
+
+impl Silence {
+    fn new(channels: ChannelCount) -> Self {
+        Silence {
+            channels: usize::from(channels.max(1)),
+            dop_marker: DOP_MARKERS[1],
+        }
+    }
+
+    // Remembers the DoP marker of the last frame written by the data callback.
+    #[inline]
+    fn follow(&mut self, data: &Data) {
+        if data.sample_format() == SampleFormat::Dop {
+            if let Some(last) = data.bytes().rchunks_exact(4).next() {
+                let sample = u32::from_ne_bytes([last[0], last[1], last[2], last[3]]);
+                self.dop_marker = (sample >> 24) as u8;
+            }
+        }
+    }
+
+    fn fill(&mut self, data: &mut Data) {
+        match data.sample_format() {
+            SampleFormat::DsdU8 | SampleFormat::DsdU32 => data.bytes_mut().fill(DSD_SILENCE),
+            SampleFormat::Dop => {
+                let channels = self.channels;
+                for frame in data.bytes_mut().chunks_exact_mut(4 * channels) {
+                    self.dop_marker = if self.dop_marker == DOP_MARKERS[0] {
+                        DOP_MARKERS[1]
+                    } else {
+                        DOP_MARKERS[0]
+                    };
+                    let sample = u32::from(self.dop_marker) << 24
+                        | u32::from(DSD_SILENCE) << 16
+                        | u32::from(DSD_SILENCE) << 8;
+                    for bytes in frame.chunks_exact_mut(4) {
+                        bytes.copy_from_slice(&sample.to_ne_bytes());
+                    }
+                }
+            }
+            sample_format => crate::for_each_sample_format!(sample_format => |T| {
+                if let Some(samples) = data.as_slice_mut::<T>() {
+                    samples.fill(Sample::from(&0.0f32));
+                }
+            }, _ => data.bytes_mut().fill(0)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::output;
+    use crate::{
+        Data, OutputCallbackInfo, OutputStreamTimestamp, SampleFormat, StreamError, StreamInstant,
+    };
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn reports_panic_and_silences_output() {
+        let calls = Arc::new(Mutex::new(0));
+        let errors = Arc::new(Mutex::new(vec![]));
+        let (mut data_callback, _) = {
+            let calls = calls.clone();
+            let errors = errors.clone();
+            output(
+                2,
+                move |data: &mut Data, _: &OutputCallbackInfo| {
+                    *calls.lock().unwrap() += 1;
+                    data.as_slice_mut::<u16>().unwrap().fill(1);
+                    panic!("callback failed");
+                },
+                move |err| errors.lock().unwrap().push(err),
+            )
+        };
+
+        let mut samples = [0u16; 4];
+        let mut data =
+            unsafe { Data::from_parts(samples.as_mut_ptr() as *mut (), 4, SampleFormat::U16) };
+        let instant = StreamInstant::new(0, 0);
+        let timestamp = OutputStreamTimestamp {
+            callback: instant,
+            playback: instant,
+        };
+        let info = OutputCallbackInfo::new(timestamp, 0);
+        data_callback(&mut data, &info);
+        data_callback(&mut data, &info);
+
+        assert_eq!(*calls.lock().unwrap(), 1);
+        assert_eq!(samples, [32768; 4]);
+        let errors = errors.lock().unwrap();
+        assert_eq!(errors.len(), 1);
+        match &errors[0] {
+            StreamError::CallbackPanicked { message } => {
+                assert_eq!(message.as_deref(), Some("callback failed"))
+            }
+            err => panic!("unexpected error: {}", err),
+        }
+    }
+
+    #[test]
+    fn silences_dop_and_dsd_with_idle_patterns() {
+        let mut calls = 0;
+        let (mut data_callback, _) = output(
+            2,
+            move |data: &mut Data, _: &OutputCallbackInfo| {
+                calls += 1;
+                if calls > 1 {
+                    panic!("callback failed");
+                }
+                // Two stereo frames, the last one marked 0x05.
+                let frames = [0xfa00_0000u32, 0xfa00_0000, 0x0500_0000, 0x0500_0000];
+                for (bytes, sample) in data.bytes_mut().chunks_exact_mut(4).zip(frames) {
+                    bytes.copy_from_slice(&sample.to_ne_bytes());
+                }
+            },
+            |_| (),
+        );
+        let instant = StreamInstant::new(0, 0);
+        let timestamp = OutputStreamTimestamp {
+            callback: instant,
+            playback: instant,
+        };
+        let info = OutputCallbackInfo::new(timestamp, 0);
+
+        let mut samples = [0u32; 4];
+        let mut data =
+            unsafe { Data::from_parts(samples.as_mut_ptr() as *mut (), 4, SampleFormat::Dop) };
+        data_callback(&mut data, &info);
+        data_callback(&mut data, &info);
+        assert_eq!(
+            samples,
+            [0xfa69_6900, 0xfa69_6900, 0x0569_6900, 0x0569_6900]
+        );
+
+        let mut samples = [0u8; 4];
+        let mut data =
+            unsafe { Data::from_parts(samples.as_mut_ptr() as *mut (), 4, SampleFormat::DsdU8) };
+        data_callback(&mut data, &info);
+        assert_eq!(samples, [0x69; 4]);
+    }
+}